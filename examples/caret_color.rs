@@ -0,0 +1,53 @@
+//! Caret styling example
+//!
+//! This example shows a high-contrast caret:
+//! - A bright caret color that stands out on a dark theme
+//! - A slower blink, or a steady caret when blinking is turned off
+
+use ed_egui::{EditorMode, EditorWidget};
+use eframe::egui;
+
+struct CaretColorApp {
+    editor: EditorWidget,
+}
+
+impl Default for CaretColorApp {
+    fn default() -> Self {
+        let mut editor = EditorWidget::new("caret_color")
+            .with_mode(EditorMode::Plain)
+            .with_font_size(16.0)
+            .with_caret_color(egui::Color32::from_rgb(255, 200, 0))
+            .with_caret_blink(0.8, 0.4)
+            .with_status_bar(true);
+
+        editor.set_text(
+            r#"The caret in this editor is bright yellow so it is easy to find.
+
+It stays visible for 0.8 seconds and hides for 0.4 seconds per blink.
+Use `with_caret_blink(on, 0.0)` to keep it steadily visible instead.
+"#,
+        );
+
+        Self { editor }
+    }
+}
+
+impl eframe::App for CaretColorApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.heading("Caret Styling Example");
+            ui.separator();
+
+            self.editor.show(ui);
+        });
+    }
+}
+
+fn main() -> eframe::Result<()> {
+    let native_options = eframe::NativeOptions::default();
+    eframe::run_native(
+        "Ed-Egui Caret Styling",
+        native_options,
+        Box::new(|_cc| Ok(Box::new(CaretColorApp::default()))),
+    )
+}
@@ -0,0 +1,80 @@
+//! Code editor example
+//!
+//! This example configures the editor for source code:
+//! - Rust syntax highlighting picked by file extension
+//! - A column ruler at 80 characters
+//! - Closing brackets typed on an indented line outdent it
+//! - Plain (non-modal) key bindings
+//! - A scroll area managed by the editor, so long files scroll in place
+//! - Font size and whitespace markers changed at runtime with in-place setters
+
+use ed_egui::{EditorMode, EditorWidget};
+use eframe::egui;
+
+struct CodeEditorApp {
+    editor: EditorWidget,
+    font_size: f32,
+    show_whitespace: bool,
+}
+
+impl Default for CodeEditorApp {
+    fn default() -> Self {
+        let mut editor = EditorWidget::new("code_editor")
+            .with_mode(EditorMode::Plain)
+            .with_font_size(14.0)
+            .with_ruler(Some(80))
+            .with_auto_dedent(true)
+            .with_scroll_area(true)
+            .with_status_bar(true);
+
+        editor.set_language_by_extension("rs");
+        editor.set_text(
+            r#"// Lines longer than 80 columns cross the ruler on the right.
+fn main() {
+    let message = "This string is long enough that it runs past the eighty column guide";
+    println!("{message}");
+}
+"#,
+        );
+
+        Self {
+            editor,
+            font_size: 14.0,
+            show_whitespace: false,
+        }
+    }
+}
+
+impl eframe::App for CodeEditorApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.heading("Code Editor Example");
+            ui.horizontal(|ui| {
+                if ui
+                    .add(egui::Slider::new(&mut self.font_size, 10.0..=28.0).text("Font size"))
+                    .changed()
+                {
+                    self.editor.set_font_size(self.font_size);
+                }
+                if ui
+                    .checkbox(&mut self.show_whitespace, "Show whitespace")
+                    .changed()
+                {
+                    self.editor.set_show_whitespace(self.show_whitespace);
+                }
+            });
+            ui.separator();
+
+            self.editor.show(ui);
+        });
+    }
+}
+
+fn main() -> eframe::Result<()> {
+    let native_options = eframe::NativeOptions::default();
+    eframe::run_native(
+        "Ed-Egui Code Editor",
+        native_options,
+        Box::new(|_cc| Ok(Box::new(CodeEditorApp::default()))),
+    )
+}
@@ -19,7 +19,9 @@ impl Default for EmacsEditorApp {
         let mut editor = EditorWidget::new("emacs_editor")
             .with_mode(EditorMode::Emacs) // Set to Emacs mode
             .with_font_size(16.0)
-            .with_status_bar(true);
+            .with_status_bar(true)
+            .with_trim_trailing_whitespace_on_save(true)
+            .with_save_callback(|text| println!("Saved {} bytes", text.len()));
 
         // Set initial sample text with Emacs commands cheat sheet
         editor.set_text(
@@ -41,6 +43,11 @@ This is a specialized editor that only uses Emacs keybindings.
 ### Editing
 - Standard text typing works as expected
 - Use platform standard shortcuts for copy/paste/etc.
+- Use `Ctrl+O` to open a line after the cursor, `Ctrl+J` for an indented new line
+- Use `Ctrl+K` to kill to the end of the line and `Ctrl+Y` to yank it back
+- `Alt+D` and `Alt+Backspace` kill words; consecutive kills yank back together
+- Use `Ctrl+X h` to select the whole buffer
+- Use `Ctrl+X Ctrl+S` to save (trailing whitespace is trimmed)
 
 Try it out!
 "#,
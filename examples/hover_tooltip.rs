@@ -0,0 +1,84 @@
+//! Hover tooltip example
+//!
+//! This example registers a hover provider that shows the word under the
+//! mouse pointer as a tooltip:
+//! - The provider receives the byte offset of the hovered character
+//! - A change callback keeps the provider's copy of the text up to date
+//! - Whitespace and punctuation produce no tooltip
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use ed_egui::{EditorMode, EditorWidget};
+use eframe::egui;
+
+const SAMPLE: &str = r#"Hover over any word in this text to see it in a tooltip.
+
+fn area(width: f64, height: f64) -> f64 {
+    width * height
+}
+"#;
+
+struct HoverTooltipApp {
+    editor: EditorWidget,
+}
+
+/// The identifier surrounding `offset`, if there is one
+fn word_at(text: &str, offset: usize) -> Option<String> {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    if !text[offset..].chars().next().is_some_and(is_word) {
+        return None;
+    }
+    let start = text[..offset]
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| is_word(*c))
+        .last()
+        .map_or(offset, |(i, _)| i);
+    let end = text[offset..]
+        .char_indices()
+        .find(|(_, c)| !is_word(*c))
+        .map_or(text.len(), |(i, _)| offset + i);
+    Some(text[start..end].to_string())
+}
+
+impl Default for HoverTooltipApp {
+    fn default() -> Self {
+        let text = Rc::new(RefCell::new(SAMPLE.to_string()));
+
+        let changed = Rc::clone(&text);
+        let mut editor = EditorWidget::new("hover_tooltip")
+            .with_mode(EditorMode::Plain)
+            .with_font_size(14.0)
+            .with_status_bar(true)
+            .with_change_callback(move |new_text| *changed.borrow_mut() = new_text.to_string());
+        editor.set_text(SAMPLE);
+
+        let hovered = Rc::clone(&text);
+        editor.set_hover_provider(move |offset| {
+            word_at(&hovered.borrow(), offset).map(|word| format!("Word: {word}"))
+        });
+
+        Self { editor }
+    }
+}
+
+impl eframe::App for HoverTooltipApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.heading("Hover Tooltip Example");
+            ui.separator();
+
+            self.editor.show(ui);
+        });
+    }
+}
+
+fn main() -> eframe::Result<()> {
+    let native_options = eframe::NativeOptions::default();
+    eframe::run_native(
+        "Ed-Egui Hover Tooltips",
+        native_options,
+        Box::new(|_cc| Ok(Box::new(HoverTooltipApp::default()))),
+    )
+}
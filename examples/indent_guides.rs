@@ -0,0 +1,75 @@
+//! Indentation guides example
+//!
+//! This example shows deeply nested code with indentation guides:
+//! - A faint vertical line at each indentation level
+//! - Guides that stop at each line's own depth
+//! - A custom guide color
+
+use ed_egui::{EditorMode, EditorWidget};
+use eframe::egui;
+
+struct IndentGuidesApp {
+    editor: EditorWidget,
+}
+
+impl Default for IndentGuidesApp {
+    fn default() -> Self {
+        let mut editor = EditorWidget::new("indent_guides")
+            .with_mode(EditorMode::Plain)
+            .with_font_size(14.0)
+            .with_indent_guides(true)
+            .with_indent_guide_color(egui::Color32::from_rgb(70, 70, 110))
+            .with_status_bar(true);
+
+        editor.set_language_by_extension("rs");
+        editor.set_text(
+            r#"mod parser {
+    pub fn parse(tokens: &[Token]) -> Result<Ast, Error> {
+        let mut stack = Vec::new();
+        for token in tokens {
+            match token {
+                Token::Open => stack.push(Node::default()),
+                Token::Close => {
+                    let node = stack.pop().ok_or(Error::Unbalanced)?;
+                    if let Some(parent) = stack.last_mut() {
+                        parent.children.push(node);
+                    } else {
+                        return Ok(Ast::new(node));
+                    }
+                }
+                Token::Word(word) => {
+                    if let Some(node) = stack.last_mut() {
+                        node.words.push(word.clone());
+                    }
+                }
+            }
+        }
+        Err(Error::Unbalanced)
+    }
+}
+"#,
+        );
+
+        Self { editor }
+    }
+}
+
+impl eframe::App for IndentGuidesApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.heading("Indentation Guides Example");
+            ui.separator();
+
+            self.editor.show(ui);
+        });
+    }
+}
+
+fn main() -> eframe::Result<()> {
+    let native_options = eframe::NativeOptions::default();
+    eframe::run_native(
+        "Ed-Egui Indentation Guides",
+        native_options,
+        Box::new(|_cc| Ok(Box::new(IndentGuidesApp::default()))),
+    )
+}
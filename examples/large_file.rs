@@ -0,0 +1,79 @@
+//! Large document example
+//!
+//! This example loads a 50,000 line buffer into an editor with its own
+//! scroll area and shows how long each frame takes to lay out and paint.
+//!
+//! By default the `TextEdit` underneath lays out the whole document as one
+//! galley, so the frame time grows with the document, most of all while
+//! typing. Tick "Lay out visible rows only" to hand it just the lines in view
+//! (`with_visible_rows_only`) and compare.
+
+use std::time::{Duration, Instant};
+
+use ed_egui::{EditorMode, EditorWidget};
+use eframe::egui;
+
+const LINE_COUNT: usize = 50_000;
+
+struct LargeFileApp {
+    editor: EditorWidget,
+    visible_rows_only: bool,
+    last_frame: Duration,
+}
+
+impl Default for LargeFileApp {
+    fn default() -> Self {
+        let mut editor = EditorWidget::new("large_file")
+            .with_mode(EditorMode::Plain)
+            .with_font_size(14.0)
+            .with_scroll_area(true)
+            .with_visible_rows_only(true)
+            .with_status_bar(true);
+
+        let text: String = (0..LINE_COUNT)
+            .map(|line| {
+                format!("fn line_{line}() -> usize {{ {line} * 2 }} // generated line {line}\n")
+            })
+            .collect();
+        editor.set_language_by_extension("rs");
+        editor.set_text(text);
+
+        Self {
+            editor,
+            visible_rows_only: true,
+            last_frame: Duration::ZERO,
+        }
+    }
+}
+
+impl eframe::App for LargeFileApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.heading("Large Document Example");
+            ui.label(format!(
+                "{LINE_COUNT} lines, last editor frame took {:.2} ms",
+                self.last_frame.as_secs_f64() * 1000.0
+            ));
+            if ui
+                .checkbox(&mut self.visible_rows_only, "Lay out visible rows only")
+                .changed()
+            {
+                self.editor.set_visible_rows_only(self.visible_rows_only);
+            }
+            ui.separator();
+
+            let start = Instant::now();
+            self.editor.show(ui);
+            self.last_frame = start.elapsed();
+        });
+    }
+}
+
+fn main() -> eframe::Result<()> {
+    let native_options = eframe::NativeOptions::default();
+    eframe::run_native(
+        "Ed-Egui Large Document",
+        native_options,
+        Box::new(|_cc| Ok(Box::new(LargeFileApp::default()))),
+    )
+}
@@ -0,0 +1,52 @@
+//! Large font example
+//!
+//! This example renders the editor at font size 24 so you can check that the
+//! highlighted text and the cursor stay aligned. Move the cursor through the
+//! comment and code lines below; the caret should always sit between characters.
+
+use ed_egui::EditorWidget;
+use eframe::egui;
+
+struct LargeFontApp {
+    editor: EditorWidget,
+}
+
+impl Default for LargeFontApp {
+    fn default() -> Self {
+        let mut editor = EditorWidget::new("large_font")
+            .with_font_size(24.0)
+            .with_status_bar(true);
+
+        editor.set_text(
+            r#"# Font size 24
+
+// The cursor should line up with every character here
+fn main() {
+    let answer = 42;
+}
+"#,
+        );
+
+        Self { editor }
+    }
+}
+
+impl eframe::App for LargeFontApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.heading("Large Font Example");
+            ui.separator();
+
+            self.editor.show(ui);
+        });
+    }
+}
+
+fn main() -> eframe::Result<()> {
+    let native_options = eframe::NativeOptions::default();
+    eframe::run_native(
+        "Ed-Egui Large Font",
+        native_options,
+        Box::new(|_cc| Ok(Box::new(LargeFontApp::default()))),
+    )
+}
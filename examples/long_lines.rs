@@ -0,0 +1,56 @@
+//! Horizontal scrolling example
+//!
+//! This example shows code with very long lines and word wrap off:
+//! - A horizontal scrollbar appears when a line is wider than the editor
+//! - Moving the cursor right (Ctrl+F, or End) scrolls to keep it visible
+
+use ed_egui::{EditorMode, EditorWidget};
+use eframe::egui;
+
+struct LongLinesApp {
+    editor: EditorWidget,
+}
+
+impl Default for LongLinesApp {
+    fn default() -> Self {
+        let mut editor = EditorWidget::new("long_lines")
+            .with_mode(EditorMode::Emacs)
+            .with_font_size(14.0)
+            .with_horizontal_scroll(true)
+            .with_scroll_area(true)
+            .with_status_bar(true);
+
+        editor.set_language_by_extension("rs");
+        editor.set_text(
+            r#"// Lines in this file are much wider than the window; scroll sideways or move the cursor to follow them.
+fn main() {
+    let words = ["alpha", "beta", "gamma", "delta", "epsilon", "zeta", "eta", "theta", "iota", "kappa", "lambda", "mu", "nu", "xi", "omicron", "pi", "rho", "sigma", "tau", "upsilon", "phi", "chi", "psi", "omega"];
+    let total: usize = words.iter().map(|word| word.len()).filter(|len| *len > 2).map(|len| len * 2).sum::<usize>() + words.iter().filter(|word| word.starts_with('e')).count();
+    println!("{} words with {} weighted letters, the longest being {:?}", words.len(), total, words.iter().max_by_key(|word| word.len()));
+}
+"#,
+        );
+
+        Self { editor }
+    }
+}
+
+impl eframe::App for LongLinesApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.heading("Horizontal Scrolling Example");
+            ui.separator();
+
+            self.editor.show(ui);
+        });
+    }
+}
+
+fn main() -> eframe::Result<()> {
+    let native_options = eframe::NativeOptions::default();
+    eframe::run_native(
+        "Ed-Egui Horizontal Scrolling",
+        native_options,
+        Box::new(|_cc| Ok(Box::new(LongLinesApp::default()))),
+    )
+}
@@ -0,0 +1,72 @@
+//! Markdown preview example
+//!
+//! This example shows a markdown editor next to a live preview:
+//! - The editor highlights the markdown source
+//! - The preview renders headings, emphasis, lists, links, and code blocks
+//!   with `render_markdown_preview`
+
+use ed_egui::EditorWidget;
+use eframe::egui;
+
+struct MarkdownPreviewApp {
+    editor: EditorWidget,
+}
+
+impl Default for MarkdownPreviewApp {
+    fn default() -> Self {
+        let mut editor = EditorWidget::new("markdown_preview");
+        editor.set_language_by_extension("md");
+        editor.set_text(
+            r#"# Markdown Preview
+
+Edit the text on the left and the preview on the right follows.
+Text can be **bold**, *italic*, or `inline code`, and links like
+[egui](https://github.com/emilk/egui) can be clicked.
+
+## Lists
+
+- Bullets
+  - Nested bullets
+1. Numbered items
+2. In order
+
+## Code
+
+```rust
+fn main() {
+    println!("Hello from a code block");
+}
+```
+"#,
+        );
+
+        Self { editor }
+    }
+}
+
+impl eframe::App for MarkdownPreviewApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.heading("Markdown Preview Example");
+            ui.separator();
+
+            ui.columns(2, |columns| {
+                self.editor.show(&mut columns[0]);
+                egui::ScrollArea::vertical()
+                    .id_salt("markdown_preview_scroll")
+                    .show(&mut columns[1], |ui| {
+                        self.editor.render_markdown_preview(ui)
+                    });
+            });
+        });
+    }
+}
+
+fn main() -> eframe::Result<()> {
+    let native_options = eframe::NativeOptions::default();
+    eframe::run_native(
+        "Ed-Egui Markdown Preview",
+        native_options,
+        Box::new(|_cc| Ok(Box::new(MarkdownPreviewApp::default()))),
+    )
+}
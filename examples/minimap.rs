@@ -0,0 +1,62 @@
+//! Minimap example
+//!
+//! This example shows a generated document of a few thousand lines with the
+//! minimap turned on. The strip on the right draws every line as a bar and
+//! marks the part currently in view; click or drag on it to scroll.
+
+use ed_egui::{EditorMode, EditorWidget};
+use eframe::egui;
+
+const FUNCTION_COUNT: usize = 400;
+
+struct MinimapApp {
+    editor: EditorWidget,
+}
+
+impl Default for MinimapApp {
+    fn default() -> Self {
+        let mut editor = EditorWidget::new("minimap")
+            .with_mode(EditorMode::Plain)
+            .with_font_size(14.0)
+            .with_scroll_area(true)
+            .with_minimap(true)
+            .with_status_bar(true);
+
+        let text: String = (0..FUNCTION_COUNT)
+            .map(|n| {
+                format!(
+                    "/// Generated function number {n}\n\
+                     fn function_{n}(input: &[u32]) -> u32 {{\n    \
+                         let total: u32 = input.iter().sum();\n    \
+                         total * {n}\n\
+                     }}\n\n"
+                )
+            })
+            .collect();
+        editor.set_language_by_extension("rs");
+        editor.set_text(text);
+
+        Self { editor }
+    }
+}
+
+impl eframe::App for MinimapApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.heading("Minimap Example");
+            ui.label("Click or drag on the strip at the right to scroll");
+            ui.separator();
+
+            self.editor.show(ui);
+        });
+    }
+}
+
+fn main() -> eframe::Result<()> {
+    let native_options = eframe::NativeOptions::default();
+    eframe::run_native(
+        "Ed-Egui Minimap",
+        native_options,
+        Box::new(|_cc| Ok(Box::new(MinimapApp::default()))),
+    )
+}
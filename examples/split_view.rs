@@ -0,0 +1,60 @@
+//! Split view example
+//!
+//! This example shows two editors side by side on one shared buffer:
+//! - Edits in either editor appear in the other
+//! - Each editor keeps its own cursor and key bindings (Emacs on the left,
+//!   Vim on the right)
+
+use ed_egui::editor::shared_buffer::SharedBuffer;
+use ed_egui::{EditorMode, EditorWidget, VimMode};
+use eframe::egui;
+
+struct SplitViewApp {
+    left: EditorWidget,
+    right: EditorWidget,
+}
+
+impl Default for SplitViewApp {
+    fn default() -> Self {
+        let shared = SharedBuffer::new(
+            r#"# Split View
+
+Type in either editor - the other one follows.
+
+The left editor uses Emacs bindings and the right one uses Vim bindings,
+but both edit the same text.
+"#,
+        );
+
+        let left = EditorWidget::new("split_left")
+            .with_mode(EditorMode::Emacs)
+            .with_shared_buffer(shared.clone());
+        let mut right = EditorWidget::new("split_right").with_shared_buffer(shared);
+        right.set_mode(EditorMode::Vim(VimMode::Normal));
+
+        Self { left, right }
+    }
+}
+
+impl eframe::App for SplitViewApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.heading("Split View Example");
+            ui.separator();
+
+            ui.columns(2, |columns| {
+                self.left.show(&mut columns[0]);
+                self.right.show(&mut columns[1]);
+            });
+        });
+    }
+}
+
+fn main() -> eframe::Result<()> {
+    let native_options = eframe::NativeOptions::default();
+    eframe::run_native(
+        "Ed-Egui Split View",
+        native_options,
+        Box::new(|_cc| Ok(Box::new(SplitViewApp::default()))),
+    )
+}
@@ -19,7 +19,9 @@ impl Default for VimEditorApp {
         let mut editor = EditorWidget::new("vim_editor")
             .with_mode(EditorMode::Vim(VimMode::Normal)) // Start in Normal mode
             .with_font_size(16.0)
-            .with_status_bar(true);
+            .with_status_bar(true)
+            .with_save_callback(|text| println!("Saved {} bytes", text.len()))
+            .with_quit_callback(|dirty| println!("Quit requested (unsaved changes: {dirty})"));
 
         // Set initial sample text with Vim commands cheat sheet
         editor.set_text(
@@ -33,12 +35,15 @@ This is a specialized editor that only uses Vim keybindings.
 - Press `i` to enter Insert mode
 - Press `Escape` to return to Normal mode
 - Press `v` to enter Visual mode
+- Press `o`/`O` to open a line below/above and start inserting
+- Press `dd` to delete the current line
 
 ### Normal Mode Navigation
 - Use `h/j/k/l` for left/down/up/right movement
 - Use `w/b` to move forward/backward by word
 - Use `0` to move to start of line, `$` to move to end
-- Use `g` to move to document start, `G` to move to end
+- Use `gg` to move to document start, `G` to move to end
+- Use `zt`, `zz`, or `zb` to scroll the cursor line to the top, middle, or bottom
 
 ### Visual Mode
 - All navigation keys will extend selection
@@ -47,6 +52,13 @@ This is a specialized editor that only uses Vim keybindings.
 - Press `c` to cut selection and enter insert mode
 - Press `p` to replace selection with clipboard contents
 
+### Command Line
+- Press `:` to type a command, `Enter` to run it, `Escape` to cancel
+- `:w` saves, `:q`/`:q!` quits, `:wq` does both
+- `:12` jumps to line 12
+- `:s/old/new/` replaces on the current line (add `g` for every match)
+- `:%s/old/new/g` replaces in the whole file, `:2,5s/old/new/` in lines 2-5
+
 Try it out! Press `i` to start typing.
 "#,
         );
@@ -74,6 +86,9 @@ impl eframe::App for VimEditorApp {
                         EditorMode::Vim(VimMode::Visual) => {
                             ui.label("VISUAL MODE");
                         }
+                        EditorMode::Vim(VimMode::CommandLine) => {
+                            ui.label("COMMAND MODE");
+                        }
                         _ => {
                             ui.label("UNKNOWN MODE");
                         }
@@ -0,0 +1,58 @@
+//! `ui.add` example
+//!
+//! This example adds editors with `ui.add` like any other egui widget:
+//! - A one-line label and editor laid out side by side with `ui.horizontal`
+//! - A fixed-size editor placed with `ui.add_sized`
+
+use ed_egui::{EditorMode, EditorWidget};
+use eframe::egui;
+
+struct WidgetAddApp {
+    title: EditorWidget,
+    body: EditorWidget,
+}
+
+impl Default for WidgetAddApp {
+    fn default() -> Self {
+        let mut title = EditorWidget::new("widget_add_title").with_mode(EditorMode::Plain);
+        title.set_text("Untitled note");
+
+        let mut body = EditorWidget::new("widget_add_body").with_status_bar(true);
+        body.set_text(
+            "This editor was added with ui.add_sized, so it keeps a fixed size\n\
+             while the window is resized.\n",
+        );
+
+        Self { title, body }
+    }
+}
+
+impl eframe::App for WidgetAddApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.heading("ui.add Example");
+            ui.separator();
+
+            ui.horizontal(|ui| {
+                ui.label("Title:");
+                ui.add(&mut self.title);
+            });
+
+            ui.separator();
+
+            let response = ui.add_sized([480.0, 320.0], &mut self.body);
+            if response.changed() {
+                ui.label("The body changed this frame");
+            }
+        });
+    }
+}
+
+fn main() -> eframe::Result<()> {
+    let native_options = eframe::NativeOptions::default();
+    eframe::run_native(
+        "ui.add Ed-Egui Example",
+        native_options,
+        Box::new(|_cc| Ok(Box::new(WidgetAddApp::default()))),
+    )
+}
@@ -0,0 +1,57 @@
+//! Wrapped line indentation example
+//!
+//! This example wraps long lines at the editor width:
+//! - Rows a wrapped line continues on line up with the line's indentation
+//! - Resize the window to see bullets rewrap under their marker
+
+use ed_egui::editor::commands::WrapIndent;
+use ed_egui::{EditorMode, EditorWidget};
+use eframe::egui;
+
+struct WrapIndentApp {
+    editor: EditorWidget,
+}
+
+impl Default for WrapIndentApp {
+    fn default() -> Self {
+        let mut editor = EditorWidget::new("wrap_indent")
+            .with_mode(EditorMode::Plain)
+            .with_font_size(16.0)
+            .with_word_wrap(true)
+            .with_line_wrapping_indent(WrapIndent::MatchLine)
+            .with_status_bar(true);
+
+        editor.set_text(
+            r#"Release notes
+
+  - Wrapped lines keep their indentation, so a long bullet like this one stays visually inside its list instead of snapping back to the left edge on every row it continues on.
+      - Nested bullets wrap under their own, deeper indentation, which keeps the structure of an outline readable even when its items run long.
+  - Short bullets are unaffected.
+
+A paragraph without indentation wraps at the left edge as usual, just like it would in any prose editor that soft-wraps its text.
+"#,
+        );
+
+        Self { editor }
+    }
+}
+
+impl eframe::App for WrapIndentApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.heading("Wrapped Line Indentation Example");
+            ui.separator();
+
+            self.editor.show(ui);
+        });
+    }
+}
+
+fn main() -> eframe::Result<()> {
+    let native_options = eframe::NativeOptions::default();
+    eframe::run_native(
+        "Ed-Egui Wrapped Line Indentation",
+        native_options,
+        Box::new(|_cc| Ok(Box::new(WrapIndentApp::default()))),
+    )
+}
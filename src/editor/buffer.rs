@@ -1,14 +1,149 @@
+use std::ops::{Range, RangeInclusive};
+
+use egui::text::CCursorRange;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::editor::commands::CursorMovement;
+
+/// Characters besides letters and digits that count as part of a word by default
+pub const DEFAULT_WORD_CHARS: &str = "_";
+
+/// Kinds of characters that make up words for word-wise editing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CharClass {
+    /// Letters, digits, and the configured word characters (or any non-whitespace for a WORD)
+    Word,
+    /// Any other non-whitespace character
+    Punctuation,
+    Whitespace,
+}
+
+impl CharClass {
+    /// Classify `c`; with `big_word` all non-whitespace is one class, like Vim's WORD
+    ///
+    /// `word_chars` lists the non-alphanumeric characters that belong to words.
+    fn of(c: char, big_word: bool, word_chars: &str) -> Self {
+        if c.is_whitespace() {
+            Self::Whitespace
+        } else if big_word || c.is_alphanumeric() || word_chars.contains(c) {
+            Self::Word
+        } else {
+            Self::Punctuation
+        }
+    }
+}
+
+/// Whether `c` is a strong right-to-left character: Hebrew, Arabic and related
+/// scripts, or a right-to-left mark
+fn is_rtl(c: char) -> bool {
+    matches!(
+        u32::from(c),
+        0x0590..=0x08FF | 0x200F | 0xFB1D..=0xFDFF | 0xFE70..=0xFEFF | 0x10800..=0x10FFF
+            | 0x1E800..=0x1EFFF
+    )
+}
+
+/// One change to the text: `removed` at byte `range` was replaced by `inserted`
+///
+/// `range` refers to the text before the change, so replaying a buffer's edits
+/// in order on a copy of its old text reproduces the new text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BufferEdit {
+    pub range: Range<usize>,
+    pub inserted: String,
+    pub removed: String,
+}
+
+impl BufferEdit {
+    /// The smallest edit that turns `old` into `new`, or `None` if they are equal
+    pub fn between(old: &str, new: &str) -> Option<Self> {
+        if old == new {
+            return None;
+        }
+        let mut prefix = old
+            .bytes()
+            .zip(new.bytes())
+            .take_while(|(a, b)| a == b)
+            .count();
+        while !old.is_char_boundary(prefix) || !new.is_char_boundary(prefix) {
+            prefix -= 1;
+        }
+        let max_suffix = old.len().min(new.len()) - prefix;
+        let mut suffix = old
+            .bytes()
+            .rev()
+            .zip(new.bytes().rev())
+            .take(max_suffix)
+            .take_while(|(a, b)| a == b)
+            .count();
+        while !old.is_char_boundary(old.len() - suffix) || !new.is_char_boundary(new.len() - suffix)
+        {
+            suffix -= 1;
+        }
+
+        let range = prefix..old.len() - suffix;
+        Some(Self {
+            inserted: new[prefix..new.len() - suffix].to_string(),
+            removed: old[range.clone()].to_string(),
+            range,
+        })
+    }
+}
+
+/// The text, cursor, and selection of a buffer at one moment (see `TextBuffer::snapshot`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BufferSnapshot {
+    text: String,
+    cursor_pos: usize,
+    selection: Option<Range<usize>>,
+    goal_column: Option<usize>,
+}
+
+impl BufferSnapshot {
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// The cursor position (byte offset) when the snapshot was taken
+    pub const fn cursor_position(&self) -> usize {
+        self.cursor_pos
+    }
+
+    /// The selected byte range when the snapshot was taken
+    pub fn selection(&self) -> Option<Range<usize>> {
+        self.selection.clone()
+    }
+}
+
 /// Core text buffer implementation with cursor
-#[derive(Default)]
 pub struct TextBuffer {
     /// The text content of the buffer
     text: String,
-    /// The current cursor position in the text
-    cursor_pos: usize, // Character index
+    /// The current cursor position in the text (byte offset on a char boundary)
+    cursor_pos: usize,
+    /// Selected byte range, replaced by the next insertion; never empty, and
+    /// not adjusted for edits made through `text_mut`
+    selection: Option<Range<usize>>,
     /// The current line positions (cached for efficiency)
     line_positions: Vec<usize>,
     /// Whether the line positions need to be recalculated
     needs_line_update: bool,
+    /// Column (in characters) that vertical motion tries to return to
+    goal_column: Option<usize>,
+    /// Counter bumped by every edit made through the buffer's own methods
+    revision: u64,
+    /// Non-alphanumeric characters that count as part of a word
+    word_chars: String,
+    /// Whether edits are recorded for `take_edits`
+    record_edits: bool,
+    /// Edits recorded since the last `take_edits`, oldest first
+    edits: Vec<BufferEdit>,
+}
+
+impl Default for TextBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl TextBuffer {
@@ -16,8 +151,14 @@ impl TextBuffer {
         Self {
             text: String::new(),
             cursor_pos: 0,
+            selection: None,
             line_positions: vec![0],
             needs_line_update: false,
+            goal_column: None,
+            revision: 0,
+            word_chars: DEFAULT_WORD_CHARS.to_string(),
+            record_edits: false,
+            edits: Vec::new(),
         }
     }
 
@@ -26,51 +167,709 @@ impl TextBuffer {
         &self.text
     }
 
+    pub const fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    /// Number of characters in the text (not bytes; counting takes a pass over the text)
+    pub fn len_chars(&self) -> usize {
+        self.text.chars().count()
+    }
+
+    /// Non-alphanumeric characters that count as part of a word (`_` by default)
+    pub fn word_chars(&self) -> &str {
+        &self.word_chars
+    }
+
+    /// Set which non-alphanumeric characters belong to words for word motions and deletes
+    ///
+    /// Letters and digits always do. `"_-"` suits CSS, while `""` makes `_` a boundary.
+    pub fn set_word_chars(&mut self, chars: &str) {
+        chars.clone_into(&mut self.word_chars);
+    }
+
+    /// Counter that changes whenever the buffer's editing methods modify the text
+    ///
+    /// Edits made through `text_mut` are only counted once `mark_edited` is called.
+    pub const fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Whether edits are being recorded for `take_edits`
+    pub const fn records_edits(&self) -> bool {
+        self.record_edits
+    }
+
+    /// Start or stop recording a `BufferEdit` for every change (off by default)
+    ///
+    /// Turning recording off drops any edits not yet taken.
+    pub fn set_record_edits(&mut self, record: bool) {
+        self.record_edits = record;
+        if !record {
+            self.edits.clear();
+        }
+    }
+
+    /// Take the edits recorded since the last call, oldest first
+    pub fn take_edits(&mut self) -> Vec<BufferEdit> {
+        std::mem::take(&mut self.edits)
+    }
+
+    /// Record the change from `old` to the current text, made outside the
+    /// buffer's own methods (e.g. through `text_mut`)
+    pub fn record_external_edit(&mut self, old: &str) {
+        if self.record_edits {
+            self.edits.extend(BufferEdit::between(old, &self.text));
+        }
+    }
+
+    /// Count a change made through `text_mut` in the revision
+    pub const fn mark_edited(&mut self) {
+        self.revision += 1;
+    }
+
+    /// Apply a batch of edits whose ranges all refer to the current text, in one pass
+    ///
+    /// Only each edit's `range` and `inserted` are used; `removed` is taken from
+    /// the text. Edits may come in any order but must not overlap. The cursor
+    /// keeps its place in the surrounding text, moving to the end of a
+    /// replacement that covered it. When edits are recorded, they are recorded
+    /// last to first so that replaying them in order stays correct. Returns
+    /// `false`, leaving the text alone, if any range overlaps another, runs past
+    /// the text, or splits a character.
+    pub fn apply_edits(&mut self, mut edits: Vec<BufferEdit>) -> bool {
+        edits.sort_by_key(|edit| (edit.range.start, edit.range.end));
+        let mut previous_end = 0;
+        for edit in &edits {
+            let range = &edit.range;
+            if range.start < previous_end
+                || range.start > range.end
+                || range.end > self.text.len()
+                || !self.text.is_char_boundary(range.start)
+                || !self.text.is_char_boundary(range.end)
+            {
+                return false;
+            }
+            previous_end = range.end;
+        }
+        if edits.is_empty() {
+            return true;
+        }
+
+        let mut text = String::with_capacity(self.text.len());
+        let mut copied = 0;
+        let cursor = self.cursor();
+        let mut cursor_pos = cursor;
+        for edit in &mut edits {
+            let range = edit.range.clone();
+            text.push_str(&self.text[copied..range.start]);
+            text.push_str(&edit.inserted);
+            copied = range.end;
+            if range.end <= cursor {
+                cursor_pos = cursor_pos + edit.inserted.len() - range.len();
+            } else if range.start < cursor {
+                cursor_pos = cursor_pos + edit.inserted.len() - (cursor - range.start);
+            }
+            edit.removed = self.text[range].to_string();
+        }
+        text.push_str(&self.text[copied..]);
+
+        if self.record_edits {
+            self.edits.extend(edits.into_iter().rev());
+        }
+        self.text = text;
+        self.cursor_pos = cursor_pos;
+        self.selection = None;
+        self.goal_column = None;
+        self.needs_line_update = true;
+        self.revision += 1;
+        true
+    }
+
+    /// Replace `range` with `inserted`, recording the edit and bumping the revision
+    ///
+    /// Every edit method goes through here. The cursor is left to the caller.
+    fn edit(&mut self, range: Range<usize>, inserted: &str) {
+        if self.record_edits {
+            self.edits.push(BufferEdit {
+                range: range.clone(),
+                inserted: inserted.to_string(),
+                removed: self.text[range.clone()].to_string(),
+            });
+        }
+        self.text.replace_range(range, inserted);
+        self.selection = None;
+        self.needs_line_update = true;
+        self.revision += 1;
+    }
+
+    /// Replace the whole text, recording only the part that changed
+    fn replace_text(&mut self, text: String) {
+        let old = std::mem::replace(&mut self.text, text);
+        self.selection = None;
+        if self.record_edits {
+            self.edits.extend(BufferEdit::between(&old, &self.text));
+        }
+        self.needs_line_update = true;
+        self.revision += 1;
+    }
+
+    /// Capture the text, cursor, and selection for a later `restore`
+    pub fn snapshot(&self) -> BufferSnapshot {
+        BufferSnapshot {
+            text: self.text.clone(),
+            cursor_pos: self.cursor(),
+            selection: self.selection.clone(),
+            goal_column: self.goal_column,
+        }
+    }
+
+    /// Go back to the state captured by `snapshot`
+    ///
+    /// The text change is an edit like any other: it bumps the revision and is
+    /// recorded (as the smallest changed range) when edits are being recorded.
+    pub fn restore(&mut self, snapshot: BufferSnapshot) {
+        if snapshot.text != self.text {
+            self.replace_text(snapshot.text);
+            self.update_line_positions();
+        }
+        self.cursor_pos = self.floor_char_boundary(snapshot.cursor_pos);
+        self.goal_column = snapshot.goal_column;
+        self.set_selection(snapshot.selection);
+    }
+
+    /// Mutable access to the text (the line cache is invalidated since the caller may edit it)
     pub const fn text_mut(&mut self) -> &mut String {
+        self.needs_line_update = true;
         &mut self.text
     }
 
+    /// Load a new document: the cursor goes back to the start and the selection is cleared
     pub fn set_text(&mut self, text: String) {
-        self.text = text;
-        self.cursor_pos = self.cursor_pos.min(self.text.len());
-        self.needs_line_update = true;
+        self.replace_text(text);
+        self.cursor_pos = 0;
+        self.goal_column = None;
+        // Recompute eagerly so line queries never see the old document's layout
+        self.update_line_positions();
     }
 
-    pub const fn cursor_position(&self) -> usize {
-        self.cursor_pos
+    /// The cursor position (byte offset), clamped like `cursor`
+    pub fn cursor_position(&self) -> usize {
+        self.cursor()
     }
 
+    /// The cursor position, clamped to the text and moved back to a char boundary
+    ///
+    /// `text_mut` can shorten the text under the cursor, so everything that
+    /// slices the text at the cursor reads it through here.
+    fn cursor(&self) -> usize {
+        self.floor_char_boundary(self.cursor_pos)
+    }
+
+    /// Move the cursor to `position`, forgetting the goal column if the cursor moved
     pub fn set_cursor_position(&mut self, position: usize) {
-        self.cursor_pos = position.min(self.text.len());
+        let position = self.floor_char_boundary(position);
+        if position != self.cursor() {
+            self.goal_column = None;
+        }
+        self.cursor_pos = position;
+    }
+
+    /// The selected byte range, if any text is selected
+    pub fn selection(&self) -> Option<Range<usize>> {
+        self.selection.clone()
+    }
+
+    /// Select a byte range (clamped to the text and to char boundaries), or clear the selection
+    ///
+    /// The selection is dropped by any edit; an empty range selects nothing.
+    pub fn set_selection(&mut self, range: Option<Range<usize>>) {
+        self.selection = range.and_then(|range| {
+            let start = self.floor_char_boundary(range.start.min(self.text.len()));
+            let end = self.floor_char_boundary(range.end.min(self.text.len()));
+            (start < end).then_some(start..end)
+        });
+    }
+
+    /// Take the cursor and selection from a `TextEdit` cursor range
+    ///
+    /// The range's char indices are converted to byte offsets; the cursor goes
+    /// to its moving (primary) end.
+    pub fn set_selection_from_cursor_range(&mut self, range: &CCursorRange) {
+        let [start, end] = range.sorted().map(|cursor| self.char_to_byte(cursor.index));
+        self.set_cursor_position(self.char_to_byte(range.primary.index));
+        self.set_selection(Some(start..end));
+    }
+
+    /// The selected text, if any
+    pub fn selected_text(&self) -> Option<&str> {
+        self.text.get(self.selection.clone()?)
+    }
+
+    /// The text in a byte range, clamped to the text and moved back to char
+    /// boundaries so it never panics (a reversed range gives `""`)
+    pub fn text_for_range(&self, range: Range<usize>) -> &str {
+        let start = self.floor_char_boundary(range.start);
+        let end = self.floor_char_boundary(range.end);
+        self.text.get(start..end).unwrap_or_default()
+    }
+
+    /// Delete the selected text, leaving the cursor where it started
+    ///
+    /// Returns whether there was a selection to delete.
+    pub fn delete_selection(&mut self) -> bool {
+        match self.selection.take() {
+            Some(range) => {
+                self.delete_range(range);
+                true
+            }
+            None => false,
+        }
     }
 
-    // Insert a character at the current cursor position
+    /// Insert a character at the cursor, replacing the selection if there is one
     pub fn insert_char(&mut self, c: char) {
-        self.text.insert(self.cursor_pos, c);
-        self.cursor_pos += 1;
-        self.needs_line_update = true;
+        self.delete_selection();
+        let pos = self.cursor();
+        self.edit(pos..pos, c.encode_utf8(&mut [0; 4]));
+        self.cursor_pos = pos + c.len_utf8();
+    }
+
+    /// Replace the character under the cursor with `c`, leaving the cursor after it
+    ///
+    /// At the end of a line there is nothing to replace, and a newline never
+    /// replaces anything, so `c` is inserted. With a selection, `c` replaces the
+    /// selection instead. Returns the text that was overwritten (empty when `c`
+    /// was inserted).
+    pub fn replace_char(&mut self, c: char) -> String {
+        if self.delete_selection() {
+            self.insert_char(c);
+            return String::new();
+        }
+        let start = self.cursor();
+        let end = match self.text[start..].graphemes(true).next() {
+            Some(grapheme) if c != '\n' && !grapheme.starts_with(['\n', '\r']) => {
+                start + grapheme.len()
+            }
+            _ => start,
+        };
+        let replaced = self.text[start..end].to_string();
+        self.edit(start..end, c.encode_utf8(&mut [0; 4]));
+        self.cursor_pos = start + c.len_utf8();
+        self.goal_column = None;
+        replaced
+    }
+
+    /// Put `original` back in place of the character before the cursor and
+    /// move the cursor before it, undoing a `replace_char` (Vim Replace-mode Backspace)
+    pub fn restore_replaced_char(&mut self, original: &str) {
+        let end = self.cursor();
+        let Some(typed) = self.char_before(end) else {
+            return;
+        };
+        let start = end - typed.len_utf8();
+        self.edit(start..end, original);
+        self.cursor_pos = start;
+        self.goal_column = None;
     }
 
     // Delete the character before the cursor
     pub fn delete_char(&mut self) {
-        if self.cursor_pos > 0 {
-            self.cursor_pos -= 1;
-            self.text.remove(self.cursor_pos);
-            self.needs_line_update = true;
+        let end = self.cursor();
+        if let Some(c) = self.char_before(end) {
+            self.cursor_pos = end - c.len_utf8();
+            self.edit(self.cursor_pos..end, "");
         }
     }
 
     // Delete the character under the cursor
     pub fn delete_char_forward(&mut self) {
-        if self.cursor_pos < self.text.len() {
-            self.text.remove(self.cursor_pos);
-            self.needs_line_update = true;
+        let start = self.cursor();
+        if let Some(c) = self.char_at(start) {
+            self.cursor_pos = start;
+            self.edit(start..start + c.len_utf8(), "");
+        }
+    }
+
+    /// Delete the word before the cursor, along with any whitespace between them
+    pub fn delete_word_backward(&mut self) {
+        self.kill_word_backward();
+    }
+
+    /// Delete the word before the cursor like `delete_word_backward` and return it
+    pub fn kill_word_backward(&mut self) -> String {
+        let end = self.cursor();
+        let start = self.prev_word_start(end, false);
+        if start == end {
+            return String::new();
+        }
+
+        let killed = self.text[start..end].to_string();
+        self.edit(start..end, "");
+        self.cursor_pos = start;
+        self.goal_column = None;
+        killed
+    }
+
+    /// Insert `open` and `close` at the cursor, leaving the cursor between them
+    pub fn insert_pair(&mut self, open: char, close: char) {
+        self.insert_char(open);
+        let pos = self.cursor();
+        self.edit(pos..pos, close.encode_utf8(&mut [0; 4]));
+    }
+
+    /// The characters just before and just after the cursor
+    pub fn chars_around_cursor(&self) -> (Option<char>, Option<char>) {
+        let pos = self.cursor();
+        (
+            self.text[..pos].chars().next_back(),
+            self.text[pos..].chars().next(),
+        )
+    }
+
+    /// Delete from the start of the cursor's line up to the cursor (readline `C-u`)
+    pub fn delete_to_line_start(&mut self) {
+        self.kill_to_line_start();
+    }
+
+    /// Delete like `delete_to_line_start` and return the deleted text
+    pub fn kill_to_line_start(&mut self) -> String {
+        let end = self.cursor();
+        let line = self.current_line();
+        let start = self.line_positions[line];
+        if start == end {
+            return String::new();
+        }
+
+        let killed = self.text[start..end].to_string();
+        self.edit(start..end, "");
+        self.cursor_pos = start;
+        self.goal_column = None;
+        killed
+    }
+
+    /// Delete the word after the cursor, along with any whitespace between them
+    pub fn delete_word_forward(&mut self) {
+        self.kill_word_forward();
+    }
+
+    /// Delete the word after the cursor like `delete_word_forward` and return it
+    pub fn kill_word_forward(&mut self) -> String {
+        let start = self.cursor();
+        let end = self.word_end(start, false);
+        if start == end {
+            return String::new();
+        }
+        self.cursor_pos = start;
+
+        let killed = self.text[start..end].to_string();
+        self.edit(start..end, "");
+        self.goal_column = None;
+        killed
+    }
+
+    /// Whether the text at `pos` runs right to left
+    ///
+    /// Judged by the nearest strongly directional character on the line (a
+    /// letter or a direction mark), looking after `pos` first and then before
+    /// it. A line without letters counts as left to right.
+    pub fn is_rtl_at(&self, pos: usize) -> bool {
+        let pos = self.floor_char_boundary(pos);
+        let strong = |c: &char| is_rtl(*c) || c.is_alphabetic() || *c == '\u{200E}';
+        let on_line = |c: &char| *c != '\n';
+        self.text[pos..]
+            .chars()
+            .take_while(on_line)
+            .find(strong)
+            .or_else(|| {
+                self.text[..pos]
+                    .chars()
+                    .rev()
+                    .take_while(on_line)
+                    .find(strong)
+            })
+            .is_some_and(is_rtl)
+    }
+
+    /// The run of same-class characters (word, punctuation, or whitespace) around `pos`
+    ///
+    /// At the end of the text the run before `pos` is used. With `big_word`, any
+    /// non-whitespace characters form a single word, like Vim's WORD.
+    pub fn word_range_at(&self, pos: usize, big_word: bool) -> Range<usize> {
+        let pos = self.floor_char_boundary(pos);
+        let anchor = match self.char_at(pos) {
+            Some(c) => Some((pos, c)),
+            None => self.text[..pos].char_indices().next_back(),
+        };
+        let Some((anchor, c)) = anchor else {
+            return pos..pos;
+        };
+        let class = CharClass::of(c, big_word, &self.word_chars);
+
+        let start = self.text[..anchor]
+            .char_indices()
+            .rev()
+            .take_while(|(_, c)| CharClass::of(*c, big_word, &self.word_chars) == class)
+            .last()
+            .map_or(anchor, |(i, _)| i);
+        let end = self.text[anchor..]
+            .char_indices()
+            .find(|(_, c)| CharClass::of(*c, big_word, &self.word_chars) != class)
+            .map_or(self.text.len(), |(i, _)| anchor + i);
+        start..end
+    }
+
+    /// Start of the next word after `pos` (Vim's `w`/`W`), or the text length
+    pub fn next_word_start(&self, pos: usize, big_word: bool) -> usize {
+        let pos = self.floor_char_boundary(pos);
+        let mut chars = self.text[pos..].char_indices().peekable();
+        if let Some(&(_, c)) = chars.peek() {
+            let class = CharClass::of(c, big_word, &self.word_chars);
+            if class != CharClass::Whitespace {
+                while chars
+                    .next_if(|(_, c)| CharClass::of(*c, big_word, &self.word_chars) == class)
+                    .is_some()
+                {}
+            }
+        }
+        while chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+        chars.peek().map_or(self.text.len(), |(i, _)| pos + i)
+    }
+
+    /// Start of the word before `pos` (Vim's `b`/`B`), or 0
+    pub fn prev_word_start(&self, pos: usize, big_word: bool) -> usize {
+        let pos = self.floor_char_boundary(pos);
+        let mut chars = self.text[..pos].char_indices().rev().peekable();
+        while chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+        if let Some(&(_, c)) = chars.peek() {
+            let class = CharClass::of(c, big_word, &self.word_chars);
+            while chars
+                .next_if(|(_, c)| CharClass::of(*c, big_word, &self.word_chars) == class)
+                .is_some()
+            {}
+        }
+        chars.peek().map_or(0, |(i, c)| i + c.len_utf8())
+    }
+
+    /// End (exclusive) of the word at or after `pos`, skipping whitespace first
+    pub fn word_end(&self, pos: usize, big_word: bool) -> usize {
+        let pos = self.floor_char_boundary(pos);
+        let mut chars = self.text[pos..].char_indices().peekable();
+        while chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+        if let Some(&(_, c)) = chars.peek() {
+            let class = CharClass::of(c, big_word, &self.word_chars);
+            while chars
+                .next_if(|(_, c)| CharClass::of(*c, big_word, &self.word_chars) == class)
+                .is_some()
+            {}
+        }
+        chars.peek().map_or(self.text.len(), |(i, _)| pos + i)
+    }
+
+    /// Start of the word (letters, digits, and word characters) that ends at `pos`
+    ///
+    /// Returns `pos` itself when the character before it isn't part of a word.
+    pub fn word_prefix_start(&self, pos: usize) -> usize {
+        let pos = self.floor_char_boundary(pos);
+        self.text[..pos]
+            .char_indices()
+            .rev()
+            .take_while(|(_, c)| CharClass::of(*c, false, &self.word_chars) == CharClass::Word)
+            .last()
+            .map_or(pos, |(i, _)| i)
+    }
+
+    /// Replace the partial word before the cursor with `text`, leaving the cursor after it
+    ///
+    /// This is how an accepted completion is inserted: typing `pri` and
+    /// accepting `println!` leaves `println!` rather than `priprintln!`.
+    pub fn replace_word_prefix(&mut self, text: &str) {
+        let end = self.cursor();
+        let start = self.word_prefix_start(end);
+        self.edit(start..end, text);
+        self.cursor_pos = start + text.len();
+        self.goal_column = None;
+    }
+
+    /// The column vertical motion is trying to return to, if any
+    pub const fn goal_column(&self) -> Option<usize> {
+        self.goal_column
+    }
+
+    /// Set the column vertical motion should try to return to
+    pub const fn set_goal_column(&mut self, column: usize) {
+        self.goal_column = Some(column);
+    }
+
+    /// Forget the goal column so the next vertical motion uses the current column
+    pub const fn clear_goal_column(&mut self) {
+        self.goal_column = None;
+    }
+
+    /// Move the cursor left by one user-perceived character (grapheme cluster)
+    ///
+    /// An emoji sequence or a letter with combining accents is a single step.
+    pub fn move_cursor_left(&mut self) {
+        let pos = self.cursor();
+        self.cursor_pos = self.text[..pos]
+            .grapheme_indices(true)
+            .next_back()
+            .map_or(pos, |(start, _)| start);
+        self.goal_column = Some(self.current_column());
+    }
+
+    /// Move the cursor right by one user-perceived character (grapheme cluster)
+    pub fn move_cursor_right(&mut self) {
+        let pos = self.cursor();
+        self.cursor_pos = pos + self.text[pos..].graphemes(true).next().map_or(0, str::len);
+        self.goal_column = Some(self.current_column());
+    }
+
+    /// Move the cursor up one line, keeping the goal column where the line allows
+    pub fn move_cursor_up(&mut self) {
+        self.move_cursor_up_by(1);
+    }
+
+    /// Move the cursor down one line, keeping the goal column where the line allows
+    ///
+    /// On the last line (see `text_line_count`) the cursor stays where it is.
+    pub fn move_cursor_down(&mut self) {
+        self.move_cursor_down_by(1);
+    }
+
+    /// Move the cursor up `count` lines, stopping at the first line
+    pub fn move_cursor_up_by(&mut self, count: usize) {
+        let line = self.current_line();
+        if count > 0 && line > 0 {
+            self.move_vertically_to(line.saturating_sub(count));
+        }
+    }
+
+    /// Move the cursor down `count` lines, stopping at the last line (see `text_line_count`)
+    pub fn move_cursor_down_by(&mut self, count: usize) {
+        let line = self.current_line();
+        let last = self.text_line_count().saturating_sub(1);
+        if count > 0 && line < last {
+            self.move_vertically_to((line + count).min(last));
+        }
+    }
+
+    /// Apply a cursor movement to the buffer, dropping any selection
+    pub fn move_cursor(&mut self, movement: CursorMovement) {
+        self.selection = None;
+        match movement {
+            CursorMovement::Left => self.move_cursor_left(),
+            CursorMovement::Right => self.move_cursor_right(),
+            CursorMovement::Up => self.move_cursor_up(),
+            CursorMovement::Down => self.move_cursor_down(),
+            CursorMovement::LineStart => {
+                let line = self.current_line();
+                self.cursor_pos = self.line_positions[line];
+                self.goal_column = Some(0);
+            }
+            CursorMovement::LineEnd => {
+                let line = self.current_line();
+                self.cursor_pos = self.line_end(line);
+                self.goal_column = Some(self.current_column());
+            }
+            CursorMovement::DocumentStart => {
+                self.cursor_pos = 0;
+                self.goal_column = None;
+            }
+            CursorMovement::DocumentEnd => {
+                self.cursor_pos = self.text.len();
+                self.goal_column = None;
+            }
+            CursorMovement::WordLeft => {
+                self.cursor_pos = self.prev_word_start(self.cursor(), false);
+                self.goal_column = None;
+            }
+            CursorMovement::WordRight => {
+                self.cursor_pos = self.next_word_start(self.cursor(), false);
+                self.goal_column = None;
+            }
+            CursorMovement::WordEnd => {
+                self.cursor_pos = self.word_end(self.cursor(), false);
+                self.goal_column = None;
+            }
+            CursorMovement::MatchingBracket => {
+                if let Some(pos) = self.find_matching_bracket(self.cursor()) {
+                    self.cursor_pos = pos;
+                    self.goal_column = None;
+                }
+            }
+            CursorMovement::ParagraphBackward => {
+                self.cursor_pos = self.paragraph_start();
+                self.goal_column = None;
+            }
+            CursorMovement::ParagraphForward => {
+                self.cursor_pos = self.paragraph_end();
+                self.goal_column = None;
+            }
+        }
+    }
+
+    /// Start of the nearest blank line above the cursor's paragraph, or of the text (Vim `{`)
+    fn paragraph_start(&mut self) -> usize {
+        let mut line = self.current_line();
+        while line > 0 && self.is_line_blank(line) {
+            line -= 1;
+        }
+        while line > 0 && !self.is_line_blank(line) {
+            line -= 1;
+        }
+        self.line_positions[line]
+    }
+
+    /// Start of the nearest blank line below the cursor's paragraph, or the end of the text (Vim `}`)
+    fn paragraph_end(&mut self) -> usize {
+        let last = self.text_line_count() - 1;
+        let mut line = self.current_line();
+        while line <= last && self.is_line_blank(line) {
+            line += 1;
+        }
+        while line <= last && !self.is_line_blank(line) {
+            line += 1;
         }
+        if line > last {
+            self.line_end(last)
+        } else {
+            self.line_positions[line]
+        }
+    }
+
+    /// Move to `line`, landing on the goal column or the line end if it is shorter
+    fn move_vertically_to(&mut self, line: usize) {
+        let goal = match self.goal_column {
+            Some(column) => column,
+            None => self.current_column(),
+        };
+        self.goal_column = Some(goal);
+
+        self.cursor_pos = self.offset_for_column(line, goal);
+    }
+
+    /// Move the cursor to a (line, column) position, both 0-based
+    ///
+    /// The column counts characters, like `current_column`. A line past the end
+    /// goes to the last line (see `line_count`) and a column past the end of the
+    /// line goes to its end.
+    pub fn move_cursor_to(&mut self, line: usize, column: usize) {
+        self.update_line_positions();
+        let line = line.min(self.line_positions.len() - 1);
+        self.cursor_pos = self.offset_for_column(line, column);
+        self.goal_column = None;
     }
 
-    // NOTE: All cursor movement functionality has been removed and is now
-    // handled directly by the TextEdit widget. The cursor_pos field in this
-    // struct is only updated from the TextEdit widget's cursor position.
+    /// Byte offset of a character column on a line, clamped to the line end
+    fn offset_for_column(&mut self, line: usize, column: usize) -> usize {
+        let start = self.line_positions[line];
+        let end = self.line_end(line);
+        self.text[start..end]
+            .char_indices()
+            .nth(column)
+            .map_or(end, |(offset, _)| start + offset)
+    }
 
     // Insert a newline at the cursor position
     pub fn insert_newline(&mut self) {
@@ -78,54 +877,1523 @@ impl TextBuffer {
         self.needs_line_update = true;
     }
 
-    /// Calculate positions of all line starts
-    fn update_line_positions(&mut self) {
-        if !self.needs_line_update {
-            return;
+    /// Convert a byte offset to a char index (offsets inside a char round down)
+    pub fn byte_to_char(&self, byte: usize) -> usize {
+        let byte = self.floor_char_boundary(byte);
+        self.text[..byte].chars().count()
+    }
+
+    /// Convert a char index to a byte offset (indices past the end map to the text length)
+    pub fn char_to_byte(&self, char_idx: usize) -> usize {
+        self.text
+            .char_indices()
+            .nth(char_idx)
+            .map_or(self.text.len(), |(byte, _)| byte)
+    }
+
+    /// The character starting at byte offset `pos`, or `None` at the end of the text
+    ///
+    /// An offset inside a multibyte character refers to that character.
+    pub fn char_at(&self, pos: usize) -> Option<char> {
+        self.text[self.floor_char_boundary(pos)..].chars().next()
+    }
+
+    /// The character ending at byte offset `pos`, or `None` at the start of the text
+    ///
+    /// An offset inside a multibyte character refers to the one before it.
+    pub fn char_before(&self, pos: usize) -> Option<char> {
+        self.text[..self.floor_char_boundary(pos)]
+            .chars()
+            .next_back()
+    }
+
+    /// The closest char boundary at or before `pos`, clamped to the text length
+    fn floor_char_boundary(&self, pos: usize) -> usize {
+        let mut pos = pos.min(self.text.len());
+        while !self.text.is_char_boundary(pos) {
+            pos -= 1;
         }
+        pos
+    }
 
-        self.line_positions.clear();
-        self.line_positions.push(0); // First line always starts at position 0
+    /// The cursor's line with a trailing newline, as a line-wise copy would take it
+    pub fn current_line_text(&mut self) -> String {
+        let line = self.current_line();
+        let range = self.line_range(line);
+        let mut text = self.text[range].to_string();
+        text.push('\n');
+        text
+    }
 
-        for (i, c) in self.text.char_indices() {
-            if c == '\n' {
-                self.line_positions.push(i + 1); // Line starts after the newline
+    /// Delete the cursor's line along with its newline and return it (like `current_line_text`)
+    ///
+    /// The cursor moves to the start of the line that takes its place.
+    pub fn delete_line(&mut self) -> String {
+        let line = self.current_line();
+        let killed = self.current_line_text();
+        let start = self.line_positions[line];
+        let range = match self.line_positions.get(line + 1) {
+            Some(&next_start) => start..next_start,
+            // The last line takes the newline before it instead
+            None if line > 0 => {
+                let newline = self.line_end(line - 1);
+                let newline = if self.text[..newline].ends_with('\r') {
+                    newline - 1
+                } else {
+                    newline
+                };
+                newline..self.text.len()
             }
+            None => start..self.text.len(),
+        };
+        if range.is_empty() {
+            return String::new();
         }
 
-        self.needs_line_update = false;
+        let start = range.start;
+        self.edit(range, "");
+        let line = self.line_at(start);
+        self.cursor_pos = self.line_range(line).start;
+        self.goal_column = None;
+        killed
     }
 
-    /// Get the current line number (0-based)
-    pub fn current_line(&mut self) -> usize {
-        self.update_line_positions();
-        let pos = self.cursor_pos;
+    /// Delete the text in a byte range, leaving the cursor where it started
+    pub fn delete_range(&mut self, range: Range<usize>) {
+        let start = self.floor_char_boundary(range.start.min(self.text.len()));
+        let end = self.floor_char_boundary(range.end.min(self.text.len()));
+        if start >= end {
+            return;
+        }
+        self.edit(start..end, "");
+        self.cursor_pos = start;
+        self.goal_column = None;
+    }
 
-        // Find the last line start position that's less than or equal to cursor_pos
-        match self.line_positions.binary_search(&pos) {
-            Ok(exact_match) => exact_match, // cursor is exactly at line start
-            Err(insertion_point) => insertion_point - 1, // cursor is in the middle of a line
+    /// Insert `text` at the cursor, leaving the cursor after it
+    ///
+    /// A selection is replaced by `text`.
+    pub fn insert_str(&mut self, text: &str) {
+        self.delete_selection();
+        if text.is_empty() {
+            return;
         }
+        let pos = self.cursor();
+        self.edit(pos..pos, text);
+        self.cursor_pos = pos + text.len();
+        self.goal_column = None;
     }
 
-    /// Get the current column (0-based)
-    pub fn current_column(&mut self) -> usize {
-        self.update_line_positions();
+    /// Delete from the cursor to the end of its line and return the deleted text
+    ///
+    /// At the end of a line the newline itself is deleted instead, so repeated
+    /// calls remove whole lines, like Emacs `C-k`.
+    pub fn kill_line(&mut self) -> String {
+        let pos = self.cursor();
         let line = self.current_line();
-        let line_start = self.line_positions[line];
-        self.cursor_pos - line_start
+        let line_end = self.line_end(line);
+        // Keep a CRLF line ending together
+        let content_end = if line_end > pos && self.text[..line_end].ends_with('\r') {
+            line_end - 1
+        } else {
+            line_end
+        };
+        let end = if content_end == pos {
+            // Only the line ending is left (or nothing, on the last line)
+            (line_end + 1).min(self.text.len())
+        } else {
+            content_end
+        };
+        if end == pos {
+            return String::new();
+        }
+
+        let killed = self.text[pos..end].to_string();
+        self.cursor_pos = pos;
+        self.edit(pos..end, "");
+        self.goal_column = None;
+        killed
     }
 
-    /// Get the number of lines in the buffer
-    pub fn line_count(&mut self) -> usize {
-        self.update_line_positions();
-        self.line_positions.len()
+    /// Insert a newline after the cursor, leaving the cursor where it is (Emacs `C-o`)
+    pub fn open_line(&mut self) {
+        let pos = self.cursor();
+        self.cursor_pos = pos;
+        self.edit(pos..pos, "\n");
+        self.goal_column = None;
     }
 
-    // Line and column information functions are still useful for status bar display
-    // but no longer directly manipulate the cursor position
+    /// Insert a newline and indent the new line like the current one (Emacs `C-j`)
+    ///
+    /// When the cursor is inside the indentation, only the indentation before it
+    /// is repeated, so the text after the cursor doesn't gain extra indent.
+    pub fn insert_newline_and_indent(&mut self) {
+        let line = self.current_line();
+        let line_start = self.line_positions[line];
+        let mut indent = self.line_indentation(line);
+        let pos = self.cursor();
+        indent.truncate(pos - line_start);
+
+        self.edit(pos..pos, &format!("\n{indent}"));
+        self.cursor_pos = pos + 1 + indent.len();
+        self.goal_column = None;
+    }
 
-    // NOTE: All cursor movement functionality has been removed and is now
-    // handled directly by the TextEdit widget. The cursor_pos field in this
-    // struct is only updated from the TextEdit widget's cursor position.
+    /// Open a new line below the current one, copying its indentation
+    ///
+    /// The cursor is placed at the end of the indentation on the new line.
+    pub fn open_line_below(&mut self) {
+        let line = self.current_line();
+        let end = self.line_end(line);
+        let indent = self.line_indentation(line);
+
+        self.edit(end..end, &format!("\n{indent}"));
+        self.cursor_pos = end + 1 + indent.len();
+    }
+
+    /// Open a new line above the current one, copying its indentation
+    ///
+    /// The cursor is placed at the end of the indentation on the new line.
+    pub fn open_line_above(&mut self) {
+        let line = self.current_line();
+        let start = self.line_positions[line];
+        let indent = self.line_indentation(line);
+
+        self.edit(start..start, &format!("{indent}\n"));
+        self.cursor_pos = start + indent.len();
+    }
+
+    /// Insert whole `lines` (as held by a linewise register) below the current line
+    ///
+    /// The cursor is placed at the start of the first inserted line.
+    pub fn paste_lines_below(&mut self, lines: &str) {
+        let line = self.current_line();
+        let end = self.line_end(line);
+        let lines = lines.strip_suffix('\n').unwrap_or(lines);
+
+        self.edit(end..end, &format!("\n{lines}"));
+        self.cursor_pos = end + 1;
+        self.goal_column = None;
+    }
+
+    /// Insert whole `lines` (as held by a linewise register) above the current line
+    ///
+    /// The cursor is placed at the start of the first inserted line.
+    pub fn paste_lines_above(&mut self, lines: &str) {
+        let line = self.current_line();
+        let start = self.line_positions[line];
+        let lines = lines.strip_suffix('\n').unwrap_or(lines);
+
+        self.edit(start..start, &format!("{lines}\n"));
+        self.cursor_pos = start;
+        self.goal_column = None;
+    }
+
+    /// Byte offset of the end of a line (before its newline, if any)
+    fn line_end(&mut self, line: usize) -> usize {
+        self.update_line_positions();
+        self.line_positions
+            .get(line + 1)
+            .map_or(self.text.len(), |next_start| next_start - 1)
+    }
+
+    /// The leading whitespace of a line
+    fn line_indentation(&mut self, line: usize) -> String {
+        let start = self.line_positions[line];
+        let end = self.line_end(line);
+        self.text[start..end]
+            .chars()
+            .take_while(|c| *c == ' ' || *c == '\t')
+            .collect()
+    }
+
+    /// Replace `pattern` with `replacement`, literally, on each of `lines` (0-based)
+    ///
+    /// Only the first match on a line is replaced unless `global` is set. Lines
+    /// past the end of the buffer are ignored. All replacements form a single
+    /// edit, and the cursor moves to the start of the last changed line as in Vim.
+    /// Returns the number of lines changed.
+    pub fn substitute(
+        &mut self,
+        lines: RangeInclusive<usize>,
+        pattern: &str,
+        replacement: &str,
+        global: bool,
+    ) -> usize {
+        self.update_line_positions();
+        let first = *lines.start();
+        let last = (*lines.end()).min(self.line_positions.len() - 1);
+        if pattern.is_empty() || first > last {
+            return 0;
+        }
+
+        let start = self.line_positions[first];
+        let end = self.line_end(last);
+        let mut replaced = String::with_capacity(end - start);
+        let mut changed = 0;
+        let mut last_changed_start = start;
+
+        for line in self.text[start..end].split_inclusive('\n') {
+            if line.contains(pattern) {
+                changed += 1;
+                last_changed_start = start + replaced.len();
+                if global {
+                    replaced.push_str(&line.replace(pattern, replacement));
+                } else {
+                    replaced.push_str(&line.replacen(pattern, replacement, 1));
+                }
+            } else {
+                replaced.push_str(line);
+            }
+        }
+
+        if changed > 0 {
+            self.edit(start..end, &replaced);
+            self.cursor_pos = last_changed_start;
+            self.goal_column = None;
+        }
+        changed
+    }
+
+    /// Remove trailing spaces and tabs from every line as a single edit,
+    /// optionally making sure non-empty text ends with a newline
+    ///
+    /// The cursor stays on its line, clamped to the line's new end. Returns
+    /// whether anything changed.
+    pub fn trim_trailing_whitespace(&mut self, ensure_final_newline: bool) -> bool {
+        let mut cleaned = String::with_capacity(self.text.len() + 1);
+        let cursor = self.cursor();
+        let mut cursor_pos = cursor;
+        let mut old_line_start = 0;
+        for line in self.text.split_inclusive('\n') {
+            let content = line
+                .strip_suffix('\n')
+                .map_or(line, |rest| rest.strip_suffix('\r').unwrap_or(rest));
+            let trimmed = content.trim_end_matches([' ', '\t']);
+            if (old_line_start..=old_line_start + content.len()).contains(&cursor) {
+                cursor_pos = cleaned.len() + (cursor - old_line_start).min(trimmed.len());
+            }
+            cleaned.push_str(trimmed);
+            cleaned.push_str(&line[content.len()..]);
+            old_line_start += line.len();
+        }
+        if ensure_final_newline && !cleaned.is_empty() && !cleaned.ends_with('\n') {
+            cleaned.push('\n');
+        }
+
+        if cleaned == self.text {
+            return false;
+        }
+        self.replace_text(cleaned);
+        self.cursor_pos = cursor_pos;
+        self.goal_column = None;
+        true
+    }
+
+    /// Whether everything between the start of the cursor's line and the cursor is
+    /// indentation (and there is at least some)
+    pub fn cursor_in_indentation(&mut self) -> bool {
+        let line = self.current_line();
+        let before = &self.text[self.line_positions[line]..self.cursor()];
+        !before.is_empty() && before.chars().all(|c| c == ' ' || c == '\t')
+    }
+
+    /// Whether only spaces and tabs come before the cursor on its line
+    ///
+    /// Unlike `cursor_in_indentation`, this is also true at the line start.
+    pub fn cursor_before_text(&mut self) -> bool {
+        let line = self.current_line();
+        self.text[self.line_positions[line]..self.cursor()]
+            .chars()
+            .all(|c| c == ' ' || c == '\t')
+    }
+
+    /// Add `indent` after the leading whitespace of the cursor's line
+    ///
+    /// The cursor ends up after the new indentation, at the first non-blank
+    /// character. Unlike `indent_lines`, blank lines are indented too.
+    pub fn indent_current_line(&mut self, indent: &str) {
+        let line = self.current_line();
+        let start = self.line_positions[line];
+        let indentation = self.line_indentation(line);
+        self.cursor_pos = start + indentation.len();
+        self.selection = None;
+        self.insert_str(indent);
+    }
+
+    /// Remove one level of indentation (a tab, or up to `tab_width` spaces) from a line
+    ///
+    /// The cursor keeps its place in the text. Returns whether anything was removed.
+    pub fn outdent_line(&mut self, line: usize, tab_width: usize) -> bool {
+        self.outdent_lines(line..=line, tab_width)
+    }
+
+    /// Remove one level of indentation from each of `lines` (0-based) as a single edit
+    ///
+    /// Lines past the end of the buffer are ignored and the cursor keeps its
+    /// place in the text. Returns whether anything was removed.
+    pub fn outdent_lines(&mut self, lines: RangeInclusive<usize>, tab_width: usize) -> bool {
+        self.shift_lines(lines, |line| {
+            let removed = if line.starts_with('\t') {
+                1
+            } else {
+                line.bytes()
+                    .take(tab_width)
+                    .take_while(|b| *b == b' ')
+                    .count()
+            };
+            line[removed..].to_string()
+        })
+    }
+
+    /// Prefix each non-empty line in `lines` (0-based) with `indent` as a single edit
+    ///
+    /// Lines past the end of the buffer are ignored and the cursor keeps its
+    /// place in the text. Returns whether anything was added.
+    pub fn indent_lines(&mut self, lines: RangeInclusive<usize>, indent: &str) -> bool {
+        self.shift_lines(lines, |line| {
+            if line.trim_end_matches(['\n', '\r']).is_empty() {
+                line.to_string()
+            } else {
+                format!("{indent}{line}")
+            }
+        })
+    }
+
+    /// Insert a copy of the cursor's line, or of every line the selection
+    /// touches, below the original as a single edit
+    ///
+    /// The cursor and any selection move to the same place in the copy.
+    pub fn duplicate_lines(&mut self) {
+        let lines = match self.selection.clone() {
+            Some(range) => self.lines_in_range(range),
+            None => {
+                let line = self.current_line();
+                line..=line
+            }
+        };
+        let (first, last) = (*lines.start(), *lines.end());
+
+        let block = self.line_positions[first]..self.line_end(last);
+        let copy = format!("\n{}", &self.text[block.clone()]);
+        let selection = self
+            .selection
+            .clone()
+            .map(|range| range.start + copy.len()..range.end + copy.len());
+        self.edit(block.end..block.end, &copy);
+        self.cursor_pos = self.cursor() + copy.len();
+        self.selection = selection;
+        self.goal_column = None;
+    }
+
+    /// Swap `lines` (0-based) with the line above or below them as a single edit
+    ///
+    /// The cursor and any selection move with the lines. Nothing happens when
+    /// the block already touches the first or last line (a trailing newline
+    /// doesn't start a line). Returns whether the lines moved.
+    pub fn move_lines(&mut self, lines: RangeInclusive<usize>, down: bool) -> bool {
+        let last_line = self.text_line_count() - 1;
+        let (first, last) = (*lines.start(), (*lines.end()).min(last_line));
+        if first > last || (down && last == last_line) || (!down && first == 0) {
+            return false;
+        }
+
+        let block = self.line_positions[first]..self.line_end(last);
+        let other = self.line_range(if down { last + 1 } else { first - 1 });
+        let (range, moved, shift) = if down {
+            let moved = format!(
+                "{}\n{}",
+                &self.text[other.clone()],
+                &self.text[block.clone()]
+            );
+            (block.start..other.end, moved, other.len() as isize + 1)
+        } else {
+            let moved = format!(
+                "{}\n{}",
+                &self.text[block.clone()],
+                &self.text[other.clone()]
+            );
+            (other.start..block.end, moved, -(other.len() as isize + 1))
+        };
+        // A selection may end at the start of the line after the block
+        let shifted = |pos: usize| {
+            if (block.start..=block.end + 1).contains(&pos) {
+                pos.saturating_add_signed(shift)
+            } else {
+                pos
+            }
+        };
+
+        let cursor_pos = shifted(self.cursor());
+        let selection = self
+            .selection
+            .clone()
+            .map(|selection| shifted(selection.start)..shifted(selection.end));
+        self.edit(range, &moved);
+        self.cursor_pos = cursor_pos;
+        self.selection = selection;
+        self.goal_column = None;
+        true
+    }
+
+    /// Comment out `lines` (0-based) with `prefix`, or uncomment them if every
+    /// non-blank one is already commented, as a single edit
+    ///
+    /// The prefix and a space go after the indentation the lines share, so the
+    /// block keeps its shape; uncommenting also removes one space after the
+    /// prefix. Blank lines are left alone. Returns whether anything changed.
+    pub fn toggle_line_comments(&mut self, lines: RangeInclusive<usize>, prefix: &str) -> bool {
+        let first = *lines.start();
+        let last = (*lines.end()).min(self.line_count() - 1);
+        let code_lines: Vec<String> = (first..=last)
+            .filter_map(|line| self.line(line).map(str::to_string))
+            .filter(|text| !text.trim().is_empty())
+            .collect();
+        if prefix.is_empty() || code_lines.is_empty() {
+            return false;
+        }
+        let indent_len = |line: &str| line.len() - line.trim_start_matches([' ', '\t']).len();
+        let commented = code_lines
+            .iter()
+            .all(|text| text.trim_start_matches([' ', '\t']).starts_with(prefix));
+        let column = code_lines
+            .iter()
+            .map(|text| indent_len(text))
+            .min()
+            .unwrap_or(0);
+
+        self.shift_lines(first..=last, |line| {
+            if line.trim().is_empty() {
+                return line.to_string();
+            }
+            if commented {
+                let indent = indent_len(line);
+                let rest = &line[indent + prefix.len()..];
+                let rest = rest.strip_prefix(' ').unwrap_or(rest);
+                format!("{}{rest}", &line[..indent])
+            } else {
+                format!("{}{prefix} {}", &line[..column], &line[column..])
+            }
+        })
+    }
+
+    /// Rewrite each of `lines` with `shift`, which may only add or remove leading text
+    fn shift_lines(
+        &mut self,
+        lines: RangeInclusive<usize>,
+        shift: impl Fn(&str) -> String,
+    ) -> bool {
+        self.update_line_positions();
+        let first = *lines.start();
+        let last = (*lines.end()).min(self.line_positions.len() - 1);
+        if first > last {
+            return false;
+        }
+
+        let start = self.line_positions[first];
+        let end = self.line_end(last);
+        let mut shifted = String::with_capacity(end - start);
+        let cursor = self.cursor();
+        let mut cursor_pos = cursor;
+        let mut old_line_start = start;
+        for line in self.text[start..end].split_inclusive('\n') {
+            let new_line_start = start + shifted.len();
+            let new_line = shift(line);
+            let old_line_end = old_line_start + line.len();
+            if (old_line_start..old_line_end).contains(&cursor)
+                || cursor == end && old_line_end == end
+            {
+                // Text only changes at the line start, so the cursor keeps its
+                // distance from the line end unless its character was removed
+                let from_end = old_line_end - cursor;
+                cursor_pos = (new_line_start + new_line.len())
+                    .saturating_sub(from_end)
+                    .max(new_line_start);
+            }
+            shifted.push_str(&new_line);
+            old_line_start = old_line_end;
+        }
+
+        if shifted == self.text[start..end] {
+            return false;
+        }
+        if cursor > end {
+            cursor_pos = cursor + shifted.len() - (end - start);
+        }
+        self.edit(start..end, &shifted);
+        self.cursor_pos = cursor_pos;
+        self.goal_column = None;
+        true
+    }
+
+    /// Calculate positions of all line starts
+    fn update_line_positions(&mut self) {
+        if !self.needs_line_update {
+            return;
+        }
+
+        self.line_positions.clear();
+        self.line_positions.push(0); // First line always starts at position 0
+
+        for (i, c) in self.text.char_indices() {
+            if c == '\n' {
+                self.line_positions.push(i + 1); // Line starts after the newline
+            }
+        }
+
+        self.needs_line_update = false;
+    }
+
+    /// The line (0-based) containing byte offset `pos`
+    pub fn line_at(&mut self, pos: usize) -> usize {
+        self.update_line_positions();
+        self.line_positions.partition_point(|start| *start <= pos) - 1
+    }
+
+    /// Byte range of a line (0-based), excluding its newline
+    pub fn line_range(&mut self, line: usize) -> Range<usize> {
+        self.update_line_positions();
+        let line = line.min(self.line_positions.len() - 1);
+        self.line_positions[line]..self.line_end(line)
+    }
+
+    /// Byte range of a line (0-based), including its newline
+    pub fn line_byte_range(&mut self, line: usize) -> Range<usize> {
+        let range = self.line_range(line);
+        let end = if range.end < self.text.len() {
+            range.end + 1
+        } else {
+            range.end
+        };
+        range.start..end
+    }
+
+    /// The lines (0-based, inclusive) that a byte range touches
+    ///
+    /// A non-empty range ending at the start of a line doesn't include that
+    /// line, so a selection of whole lines covers just those lines. An empty
+    /// range gives the line it is on.
+    pub fn lines_in_range(&mut self, range: Range<usize>) -> RangeInclusive<usize> {
+        let first = self.line_at(range.start);
+        let mut last = self.line_at(range.end.max(range.start));
+        if last > first && self.line_positions[last] == range.end {
+            last -= 1;
+        }
+        first..=last
+    }
+
+    /// Byte ranges of every match of `pattern`, in order and without overlaps
+    ///
+    /// Without `case_sensitive`, characters are compared by their lowercase
+    /// forms. An empty pattern matches nothing.
+    pub fn find_all(&self, pattern: &str, case_sensitive: bool) -> Vec<Range<usize>> {
+        find_matches(&self.text, pattern, case_sensitive)
+    }
+
+    /// Byte ranges of every non-empty match of `regex`, in order and without overlaps
+    #[cfg(feature = "regex")]
+    pub fn find_all_regex(&self, regex: &regex::Regex) -> Vec<Range<usize>> {
+        regex
+            .find_iter(&self.text)
+            .map(|found| found.range())
+            .filter(|range| !range.is_empty())
+            .collect()
+    }
+
+    /// Byte offset of the bracket matching the one at `pos`
+    ///
+    /// When `pos` isn't on a bracket, the first bracket after it on the same
+    /// line is used, as Vim's `%` does. Nesting is followed across lines, and
+    /// brackets in string and character literals only pair with brackets in
+    /// the same literal. Returns `None` without a bracket or a partner.
+    pub fn find_matching_bracket(&self, pos: usize) -> Option<usize> {
+        matching_bracket(&self.text, pos)
+    }
+
+    /// Get the current line number (0-based)
+    pub fn current_line(&mut self) -> usize {
+        self.update_line_positions();
+        let pos = self.cursor();
+
+        // Find the last line start position that's less than or equal to the cursor
+        match self.line_positions.binary_search(&pos) {
+            Ok(exact_match) => exact_match, // cursor is exactly at line start
+            Err(insertion_point) => insertion_point - 1, // cursor is in the middle of a line
+        }
+    }
+
+    /// Get the current column (0-based)
+    pub fn current_column(&mut self) -> usize {
+        self.cursor_line_col().1
+    }
+
+    /// Get the current line and column (both 0-based) in one pass
+    pub fn cursor_line_col(&mut self) -> (usize, usize) {
+        let line = self.current_line();
+        let line_start = self.line_positions[line];
+        let cursor = self.cursor();
+        let column = self.text[line_start..]
+            .char_indices()
+            .take_while(|(offset, _)| line_start + offset < cursor)
+            .count();
+        (line, column)
+    }
+
+    /// Get the number of lines in the buffer
+    ///
+    /// A trailing newline is followed by an implicit empty line, which counts:
+    /// `"a\nb\n"` has three lines, as the `TextEdit` shows it, `"a\nb"` has two,
+    /// and empty text has one. `line`, `line_range`, and `move_cursor_to` use the
+    /// same numbering.
+    pub fn line_count(&mut self) -> usize {
+        self.update_line_positions();
+        self.line_positions.len()
+    }
+
+    /// The text of a line (0-based) without its newline, or `None` past the last line
+    pub fn line(&mut self, line: usize) -> Option<&str> {
+        if line >= self.line_count() {
+            return None;
+        }
+        let range = self.line_range(line);
+        Some(&self.text[range])
+    }
+
+    /// Whether a line (0-based) is empty or only whitespace; lines past the end aren't
+    pub fn is_line_blank(&mut self, line: usize) -> bool {
+        self.line(line).is_some_and(|text| text.trim().is_empty())
+    }
+
+    /// How many space and tab characters a line (0-based) starts with
+    ///
+    /// Each tab counts as one; lines past the end have no indentation.
+    pub fn line_indent(&mut self, line: usize) -> usize {
+        self.line(line).map_or(0, |text| {
+            text.chars().take_while(|c| *c == ' ' || *c == '\t').count()
+        })
+    }
+
+    /// Get the number of lines the way Vim counts them
+    ///
+    /// A trailing newline ends the last line rather than starting a new one, so
+    /// `"a\nb\n"` has two lines. Empty text still has one line.
+    pub fn text_line_count(&mut self) -> usize {
+        let count = self.line_count();
+        if count > 1 && self.text.ends_with('\n') {
+            count - 1
+        } else {
+            count
+        }
+    }
+}
+
+/// Byte ranges of every match of `pattern` in `text` (see `TextBuffer::find_all`)
+pub(crate) fn find_matches(text: &str, pattern: &str, case_sensitive: bool) -> Vec<Range<usize>> {
+    if pattern.is_empty() {
+        return Vec::new();
+    }
+    if case_sensitive {
+        return text
+            .match_indices(pattern)
+            .map(|(start, found)| start..start + found.len())
+            .collect();
+    }
+
+    let mut matches = Vec::new();
+    let mut start = 0;
+    while let Some(c) = text[start..].chars().next() {
+        match match_len_ignoring_case(&text[start..], pattern) {
+            Some(len) => {
+                matches.push(start..start + len);
+                start += len;
+            }
+            None => start += c.len_utf8(),
+        }
+    }
+    matches
+}
+
+/// Length in bytes of `text`'s prefix matching `pattern` ignoring case, if it matches
+fn match_len_ignoring_case(text: &str, pattern: &str) -> Option<usize> {
+    let mut chars = text.char_indices();
+    for expected in pattern.chars() {
+        let (_, c) = chars.next()?;
+        if !c.to_lowercase().eq(expected.to_lowercase()) {
+            return None;
+        }
+    }
+    Some(chars.next().map_or(text.len(), |(offset, _)| offset))
+}
+
+/// Opening and closing brackets matched by `%`
+const BRACKET_PAIRS: [(char, char); 3] = [('(', ')'), ('[', ']'), ('{', '}')];
+
+/// The pair a bracket belongs to and whether it opens it
+fn bracket_pair(c: char) -> Option<(char, char, bool)> {
+    BRACKET_PAIRS
+        .iter()
+        .find_map(|&(open, close)| (c == open || c == close).then_some((open, close, c == open)))
+}
+
+/// Whether `c` is one of the brackets `%` matches
+pub(crate) fn is_bracket(c: char) -> bool {
+    bracket_pair(c).is_some()
+}
+
+/// Byte offset of the bracket matching the one at or after `pos` (see `TextBuffer::find_matching_bracket`)
+pub(crate) fn matching_bracket(text: &str, pos: usize) -> Option<usize> {
+    let pos = pos.min(text.len());
+    if !text.is_char_boundary(pos) {
+        return None;
+    }
+    let start = text[pos..]
+        .char_indices()
+        .take_while(|(_, c)| *c != '\n')
+        .find(|&(_, c)| is_bracket(c))
+        .map(|(offset, _)| pos + offset)?;
+
+    let brackets = brackets_with_literals(text);
+    let index = brackets.iter().position(|bracket| bracket.0 == start)?;
+    let (_, c, literal) = brackets[index];
+    let (open, close, opening) = bracket_pair(c)?;
+    let same_pair = |&&(_, other, other_literal): &&(usize, char, Option<usize>)| {
+        other_literal == literal && (other == open || other == close)
+    };
+
+    let mut depth = 0usize;
+    let mut step = |&(offset, other, _): &(usize, char, Option<usize>)| {
+        if other == c {
+            depth += 1;
+        } else {
+            depth -= 1;
+        }
+        (depth == 0).then_some(offset)
+    };
+    if opening {
+        brackets[index..]
+            .iter()
+            .filter(same_pair)
+            .find_map(&mut step)
+    } else {
+        brackets[..=index]
+            .iter()
+            .rev()
+            .filter(same_pair)
+            .find_map(&mut step)
+    }
+}
+
+/// Every bracket in `text` with the literal it is in, numbered in order
+///
+/// Double-quoted strings may escape quotes with a backslash and end at the
+/// line end; a character literal is a single (or escaped) character in single
+/// quotes, so lifetimes and apostrophes don't start one.
+fn brackets_with_literals(text: &str) -> Vec<(usize, char, Option<usize>)> {
+    let mut brackets = Vec::new();
+    let mut literal = None;
+    let mut literals = 0;
+    let mut escaped = false;
+    // End of the character literal being scanned, if any
+    let mut char_literal_end = 0;
+    for (offset, c) in text.char_indices() {
+        if offset < char_literal_end {
+            if is_bracket(c) {
+                brackets.push((offset, c, Some(literals - 1)));
+            }
+            continue;
+        }
+        match c {
+            '\n' => literal = None,
+            '\\' if literal.is_some() && !escaped => {
+                escaped = true;
+                continue;
+            }
+            '"' if !escaped => {
+                literal = match literal {
+                    Some(_) => None,
+                    None => {
+                        literals += 1;
+                        Some(literals - 1)
+                    }
+                };
+            }
+            '\'' if literal.is_none() => {
+                if let Some(len) = char_literal_len(&text[offset..]) {
+                    char_literal_end = offset + len;
+                    literals += 1;
+                }
+            }
+            c if is_bracket(c) => brackets.push((offset, c, literal)),
+            _ => {}
+        }
+        escaped = false;
+    }
+    brackets
+}
+
+/// Length in bytes of the character literal (`'x'` or `'\n'`) `text` starts with
+fn char_literal_len(text: &str) -> Option<usize> {
+    let mut chars = text.char_indices().skip(1);
+    let (_, first) = chars.next()?;
+    if first == '\\' {
+        chars.next()?;
+    } else if first == '\'' || first == '\n' {
+        return None;
+    }
+    match chars.next()? {
+        (offset, '\'') => Some(offset + 1),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use egui::text::CCursor;
+
+    /// A buffer holding `text` with the cursor at byte `cursor`
+    fn buffer(text: &str, cursor: usize) -> TextBuffer {
+        let mut buffer = TextBuffer::new();
+        buffer.set_text(text.to_string());
+        buffer.set_cursor_position(cursor);
+        buffer
+    }
+
+    #[test]
+    fn insert_pair_leaves_the_cursor_between_the_halves() {
+        let mut buffer = buffer("ab", 1);
+        buffer.insert_pair('(', ')');
+        assert_eq!(buffer.text(), "a()b");
+        assert_eq!(buffer.cursor_position(), 2);
+        assert_eq!(buffer.chars_around_cursor(), (Some('('), Some(')')));
+    }
+
+    #[test]
+    fn chars_around_cursor_clamps_a_cursor_past_the_text() {
+        let mut buffer = buffer("héllo", 6);
+        buffer.text_mut().truncate(3);
+        assert_eq!(buffer.chars_around_cursor(), (Some('é'), None));
+        buffer.text_mut().clear();
+        assert_eq!(buffer.chars_around_cursor(), (None, None));
+    }
+
+    #[test]
+    fn editing_after_text_mut_shrinks_the_text_clamps_the_cursor() {
+        let edits: [fn(&mut TextBuffer); 9] = [
+            TextBuffer::move_cursor_left,
+            TextBuffer::move_cursor_right,
+            TextBuffer::delete_char,
+            TextBuffer::delete_char_forward,
+            TextBuffer::insert_newline_and_indent,
+            |buffer| {
+                buffer.kill_to_line_start();
+            },
+            |buffer| {
+                buffer.replace_char('x');
+            },
+            |buffer| {
+                buffer.cursor_in_indentation();
+            },
+            |buffer| {
+                buffer.cursor_before_text();
+            },
+        ];
+        for edit in edits {
+            // Past the end of the text, and inside a multibyte character
+            for (text, cursor, shrunk) in [("héllo wörld", 12, "hé"), ("abcd", 1, "é")] {
+                let mut buffer = buffer(text, cursor);
+                *buffer.text_mut() = shrunk.to_string();
+                edit(&mut buffer);
+                let cursor = buffer.cursor_position();
+                assert!(buffer.text().is_char_boundary(cursor));
+            }
+        }
+    }
+
+    #[test]
+    fn word_deletion_stops_at_punctuation_and_takes_whitespace_along() {
+        let mut backward = buffer("self.items[idx] = 1;", 15);
+        backward.delete_word_backward();
+        assert_eq!(backward.text(), "self.items[idx = 1;");
+        backward.delete_word_backward();
+        assert_eq!(backward.text(), "self.items[ = 1;");
+        backward.delete_word_backward();
+        assert_eq!(backward.text(), "self.items = 1;");
+        assert_eq!(backward.cursor_position(), 10);
+
+        let mut forward = buffer("call(a,  b)", 6);
+        forward.delete_word_forward();
+        assert_eq!(forward.text(), "call(a  b)");
+        forward.delete_word_forward();
+        assert_eq!(forward.text(), "call(a)");
+        assert_eq!(forward.cursor_position(), 6);
+
+        let mut at_end = buffer("end", 3);
+        at_end.delete_word_forward();
+        assert_eq!(at_end.text(), "end");
+    }
+
+    #[test]
+    fn word_range_at_covers_identifiers_punctuation_runs_and_whitespace() {
+        // "é" starts at byte 17 and "日" at 19
+        let line = buffer("let x_1 = a->b;  é日", 0);
+        assert_eq!(line.word_range_at(0, false), 0..3);
+        assert_eq!(line.word_range_at(5, false), 4..7);
+        assert_eq!(line.word_range_at(3, false), 3..4);
+        assert_eq!(line.word_range_at(12, false), 11..13);
+        assert_eq!(line.word_range_at(12, true), 10..15);
+        assert_eq!(line.word_range_at(15, false), 15..17);
+        // Inside a multibyte character, and at the end of the text
+        assert_eq!(line.word_range_at(18, false), 17..22);
+        assert_eq!(line.word_range_at(22, false), 17..22);
+        assert_eq!(buffer("", 0).word_range_at(0, false), 0..0);
+    }
+
+    #[test]
+    fn word_motions_distinguish_words_from_big_words() {
+        let buffer = buffer("foo.bar(baz)  qux", 0);
+        assert_eq!(buffer.next_word_start(0, false), 3);
+        assert_eq!(buffer.next_word_start(3, false), 4);
+        assert_eq!(buffer.next_word_start(0, true), 14);
+        assert_eq!(buffer.next_word_start(14, false), 17);
+
+        assert_eq!(buffer.prev_word_start(14, false), 11);
+        assert_eq!(buffer.prev_word_start(14, true), 0);
+        assert_eq!(buffer.prev_word_start(5, false), 4);
+        assert_eq!(buffer.prev_word_start(0, false), 0);
+
+        assert_eq!(buffer.word_end(0, false), 3);
+        assert_eq!(buffer.word_end(0, true), 12);
+        assert_eq!(buffer.word_end(12, false), 17);
+        assert_eq!(buffer.word_end(17, false), 17);
+    }
+
+    #[test]
+    fn replace_word_prefix_swaps_the_partial_word_before_the_cursor() {
+        let mut typed = buffer("let x = pri(1);", 11);
+        typed.replace_word_prefix("println!");
+        assert_eq!(typed.text(), "let x = println!(1);");
+        assert_eq!(typed.cursor_position(), 16);
+
+        // With nothing typed yet the completion is simply inserted
+        let mut empty = buffer("a.", 2);
+        empty.replace_word_prefix("len()");
+        assert_eq!(empty.text(), "a.len()");
+        assert_eq!(empty.cursor_position(), 7);
+    }
+
+    #[test]
+    fn word_chars_decide_where_foo_bar_baz_splits() {
+        let mut words = buffer("foo-bar_baz x", 0);
+        assert_eq!(words.word_range_at(0, false), 0..3);
+        assert_eq!(words.word_range_at(5, false), 4..11);
+        assert_eq!(words.next_word_start(0, false), 3);
+
+        words.set_word_chars("-_");
+        assert_eq!(words.word_range_at(0, false), 0..11);
+        assert_eq!(words.next_word_start(0, false), 12);
+
+        // Without `_`, the underscore is punctuation too
+        words.set_word_chars("");
+        assert_eq!(words.word_range_at(5, false), 4..7);
+        assert_eq!(words.prev_word_start(11, false), 8);
+    }
+
+    #[test]
+    fn edits_are_recorded_with_the_range_they_replaced() {
+        let mut recorded = buffer("héllo", 3);
+        recorded.set_record_edits(true);
+        recorded.insert_char('!');
+        recorded.delete_char();
+        recorded.delete_char();
+        assert_eq!(
+            recorded.take_edits(),
+            [
+                BufferEdit {
+                    range: 3..3,
+                    inserted: "!".to_string(),
+                    removed: String::new(),
+                },
+                BufferEdit {
+                    range: 3..4,
+                    inserted: String::new(),
+                    removed: "!".to_string(),
+                },
+                BufferEdit {
+                    range: 1..3,
+                    inserted: String::new(),
+                    removed: "é".to_string(),
+                },
+            ]
+        );
+        assert!(recorded.take_edits().is_empty());
+
+        // Nothing is recorded until asked for
+        let mut unrecorded = buffer("abc", 0);
+        unrecorded.insert_char('x');
+        assert!(unrecorded.take_edits().is_empty());
+    }
+
+    #[test]
+    fn between_finds_the_smallest_edit_on_char_boundaries() {
+        assert_eq!(BufferEdit::between("same", "same"), None);
+        assert_eq!(
+            BufferEdit::between("aéb", "aèb"),
+            Some(BufferEdit {
+                range: 1..3,
+                inserted: "è".to_string(),
+                removed: "é".to_string(),
+            })
+        );
+        assert_eq!(
+            BufferEdit::between("aaa", "aaaa"),
+            Some(BufferEdit {
+                range: 3..3,
+                inserted: "a".to_string(),
+                removed: String::new(),
+            })
+        );
+    }
+
+    #[test]
+    fn left_and_right_step_over_whole_grapheme_clusters() {
+        // The family emoji is 18 bytes joined by ZWJs; "e\u{301}" is e + a combining accent
+        let family = "👨\u{200D}👩\u{200D}👧";
+        let mut graphemes = buffer(&format!("a{family}e\u{301}b"), 1);
+        graphemes.move_cursor_right();
+        assert_eq!(graphemes.cursor_position(), 19);
+        graphemes.move_cursor_right();
+        assert_eq!(graphemes.cursor_position(), 22);
+        graphemes.move_cursor_left();
+        graphemes.move_cursor_left();
+        assert_eq!(graphemes.cursor_position(), 1);
+        graphemes.move_cursor_left();
+        graphemes.move_cursor_left();
+        assert_eq!(graphemes.cursor_position(), 0);
+    }
+
+    #[test]
+    fn rtl_runs_are_found_by_their_nearest_letter() {
+        // "שלום" takes bytes 4..12; the second line starts at 17 and its RLM
+        // mark at 20
+        let mixed = buffer("say שלום now\n12 \u{200F}!", 0);
+        assert!(!mixed.is_rtl_at(0));
+        assert!(mixed.is_rtl_at(4));
+        assert!(mixed.is_rtl_at(7));
+        // A space looks ahead first, to the next strong character
+        assert!(mixed.is_rtl_at(3));
+        assert!(!mixed.is_rtl_at(12));
+        assert!(!mixed.is_rtl_at(14));
+        // Digits aren't strong; the mark makes the second line right to left
+        assert!(mixed.is_rtl_at(17));
+        assert!(mixed.is_rtl_at(20));
+        assert!(!buffer("12 !", 0).is_rtl_at(1));
+    }
+
+    #[test]
+    fn char_at_and_char_before_handle_the_ends_and_multibyte_text() {
+        // 'é' takes bytes 1..3 and '🦀' 3..7
+        let chars = buffer("aé🦀", 0);
+        assert_eq!(chars.char_at(0), Some('a'));
+        assert_eq!(chars.char_before(0), None);
+        assert_eq!(chars.char_at(1), Some('é'));
+        assert_eq!(chars.char_before(3), Some('é'));
+        assert_eq!(chars.char_at(3), Some('🦀'));
+        assert_eq!(chars.char_before(7), Some('🦀'));
+        assert_eq!(chars.char_at(7), None);
+
+        // Inside a character, and past the end
+        assert_eq!(chars.char_at(2), Some('é'));
+        assert_eq!(chars.char_before(5), Some('é'));
+        assert_eq!(chars.char_at(99), None);
+        assert_eq!(chars.char_before(99), Some('🦀'));
+
+        let empty = buffer("", 0);
+        assert_eq!((empty.char_at(0), empty.char_before(0)), (None, None));
+    }
+
+    #[test]
+    fn moving_down_stops_on_the_last_line_with_or_without_a_trailing_newline() {
+        for text in ["one\ntwo", "one\ntwo\n"] {
+            let mut lines = buffer(text, 2);
+            assert_eq!(lines.text_line_count(), 2, "{text:?}");
+            lines.move_cursor_down();
+            assert_eq!(lines.cursor_line_col(), (1, 2), "{text:?}");
+            lines.move_cursor_down();
+            lines.move_cursor_down_by(5);
+            assert_eq!(lines.cursor_line_col(), (1, 2), "{text:?}");
+        }
+        assert_eq!(buffer("", 0).text_line_count(), 1);
+        assert_eq!(buffer("\n", 0).text_line_count(), 1);
+    }
+
+    #[test]
+    fn line_count_includes_the_empty_line_after_a_trailing_newline() {
+        let mut two = buffer("a\nb", 0);
+        assert_eq!(two.line_count(), 2);
+        assert_eq!(two.line(1), Some("b"));
+        assert_eq!(two.line(2), None);
+
+        let mut three = buffer("a\nb\n", 0);
+        assert_eq!(three.line_count(), 3);
+        assert_eq!(three.line(2), Some(""));
+        assert_eq!(three.line(3), None);
+        three.move_cursor_to(9, 9);
+        assert_eq!(
+            (three.cursor_position(), three.cursor_line_col()),
+            (4, (2, 0))
+        );
+
+        let mut empty = buffer("", 0);
+        assert_eq!(empty.line_count(), 1);
+        assert_eq!(empty.line(0), Some(""));
+        empty.move_cursor_to(3, 3);
+        assert_eq!(empty.cursor_line_col(), (0, 0));
+    }
+
+    #[test]
+    fn cursor_line_col_matches_current_line_and_current_column() {
+        let text = "first\nsé🦀nd\n\nlast";
+        for cursor in [0, 3, 5, 6, 9, 13, 15, 16, 17, 21] {
+            let mut lines = buffer(text, cursor);
+            let each = (lines.current_line(), lines.current_column());
+            assert_eq!(lines.cursor_line_col(), each, "cursor at {cursor}");
+        }
+        assert_eq!(buffer(text, 13).cursor_line_col(), (1, 3));
+        assert_eq!(buffer(text, 21).cursor_line_col(), (3, 4));
+    }
+
+    #[test]
+    fn inserting_replaces_the_selection_and_clears_it() {
+        let mut chars = buffer("héllo wörld", 0);
+        chars.set_selection(Some(1..6));
+        chars.insert_char('x');
+        assert_eq!(chars.text(), "hx wörld");
+        assert_eq!((chars.cursor_position(), chars.selection()), (2, None));
+
+        let mut strs = buffer("héllo wörld", 0);
+        strs.set_selection(Some(7..13));
+        strs.insert_str("🦀🦀");
+        assert_eq!(strs.text(), "héllo 🦀🦀");
+        assert_eq!((strs.cursor_position(), strs.selection()), (15, None));
+
+        let mut empty = buffer("abc", 0);
+        empty.set_selection(Some(1..2));
+        empty.insert_str("");
+        assert_eq!((empty.text(), empty.selection()), ("ac", None));
+    }
+
+    #[test]
+    fn matching_brackets_follow_nesting_and_skip_literals() {
+        let code = buffer("fn f(a: [u8; 2]) {\n    g(\"(\", ')');\n}", 0);
+        for (from, to) in [(4, 15), (8, 14), (17, 36), (24, 33)] {
+            assert_eq!(code.find_matching_bracket(from), Some(to), "from {from}");
+            assert_eq!(code.find_matching_bracket(to), Some(from), "from {to}");
+        }
+        // Brackets in literals only pair inside the same literal
+        assert_eq!(code.find_matching_bracket(26), None);
+        assert_eq!(code.find_matching_bracket(31), None);
+        // Off a bracket, the next one on the line is used
+        assert_eq!(code.find_matching_bracket(0), Some(15));
+        assert_eq!(code.find_matching_bracket(18), None);
+
+        let unbalanced = buffer("((a) ]", 0);
+        assert_eq!(unbalanced.find_matching_bracket(0), None);
+        assert_eq!(unbalanced.find_matching_bracket(1), Some(3));
+        assert_eq!(unbalanced.find_matching_bracket(5), None);
+        assert_eq!(unbalanced.find_matching_bracket(99), None);
+    }
+
+    #[test]
+    fn restore_returns_to_the_snapshot_text_cursor_and_selection() {
+        let mut lines = buffer("first line\nsecond", 3);
+        lines.set_selection(Some(0..5));
+        let snapshot = lines.snapshot();
+        let copy = snapshot.clone();
+
+        lines.set_selection(None);
+        lines.move_cursor_to(1, 2);
+        lines.insert_str("XYZ ");
+        lines.delete_line();
+        assert_ne!(lines.text(), snapshot.text());
+
+        lines.set_record_edits(true);
+        lines.restore(snapshot);
+        assert_eq!(lines.text(), "first line\nsecond");
+        assert_eq!(lines.cursor_position(), 3);
+        assert_eq!(lines.selection(), Some(0..5));
+        assert_eq!(lines.line(1), Some("second"));
+        assert_eq!(lines.take_edits().len(), 1);
+
+        // Restoring the same state again changes nothing
+        lines.restore(copy);
+        assert!(lines.take_edits().is_empty());
+        assert_eq!(lines.snapshot().cursor_position(), 3);
+    }
+
+    #[test]
+    fn move_lines_swaps_with_the_neighbour_and_stops_at_the_edges() {
+        let mut lines = buffer("a\nbb\nccc\n", 4);
+        assert!(lines.move_lines(1..=1, false));
+        assert_eq!(lines.text(), "bb\na\nccc\n");
+        assert_eq!(lines.cursor_line_col(), (0, 2));
+
+        assert!(!lines.move_lines(0..=0, false));
+        assert_eq!(lines.text(), "bb\na\nccc\n");
+
+        lines.set_selection(Some(0..4));
+        assert!(lines.move_lines(0..=1, true));
+        assert_eq!(lines.text(), "ccc\nbb\na\n");
+        assert_eq!(lines.selection(), Some(4..8));
+
+        // The trailing newline doesn't start a line to move past
+        assert!(!lines.move_lines(1..=2, true));
+        assert_eq!(lines.text(), "ccc\nbb\na\n");
+    }
+
+    #[test]
+    fn duplicate_lines_copies_the_line_or_block_below_and_follows_it() {
+        let mut lines = buffer("one\ntwo", 6);
+        lines.duplicate_lines();
+        assert_eq!(lines.text(), "one\ntwo\ntwo");
+        assert_eq!(lines.cursor_line_col(), (2, 2));
+
+        let mut block = buffer("a\nbc\nd\n", 0);
+        block.set_selection(Some(1..4));
+        block.duplicate_lines();
+        assert_eq!(block.text(), "a\nbc\na\nbc\nd\n");
+        assert_eq!(block.selection(), Some(6..9));
+    }
+
+    #[test]
+    fn cursor_ranges_in_chars_become_byte_offsets() {
+        // "añ🦀b": chars at bytes 0, 1, 3, and 7
+        let mut chars = buffer("añ🦀b", 0);
+        chars.set_selection_from_cursor_range(&CCursorRange::two(CCursor::new(3), CCursor::new(1)));
+        assert_eq!(chars.selection(), Some(1..7));
+        assert_eq!(chars.cursor_position(), 1);
+
+        chars.set_selection_from_cursor_range(&CCursorRange::one(CCursor::new(4)));
+        assert_eq!((chars.selection(), chars.cursor_position()), (None, 8));
+
+        // Indices past the end are clamped to the text
+        chars
+            .set_selection_from_cursor_range(&CCursorRange::two(CCursor::new(2), CCursor::new(99)));
+        assert_eq!(
+            (chars.selection(), chars.cursor_position()),
+            (Some(3..8), 8)
+        );
+    }
+
+    #[test]
+    fn blank_lines_and_indentation_widths() {
+        let mut lines = buffer("fn f() {\n\n  \t \n\t  body\n    }\r\n", 0);
+        let blank = (0..6)
+            .map(|line| lines.is_line_blank(line))
+            .collect::<Vec<_>>();
+        assert_eq!(blank, [false, true, true, false, false, true]);
+        let indent = (0..6)
+            .map(|line| lines.line_indent(line))
+            .collect::<Vec<_>>();
+        assert_eq!(indent, [0, 0, 4, 3, 4, 0]);
+
+        // Lines past the end are neither blank nor indented
+        assert!(!lines.is_line_blank(6));
+        assert_eq!(lines.line_indent(6), 0);
+    }
+
+    #[test]
+    fn lines_in_range_covers_partial_first_and_last_lines() {
+        // Lines start at bytes 0, 4, 8, and 14
+        let mut lines = buffer("one\ntwo\nthree\nfour", 0);
+        assert_eq!(lines.lines_in_range(1..10), 0..=2);
+        assert_eq!(lines.lines_in_range(2..4), 0..=0);
+        assert_eq!(lines.lines_in_range(4..14), 1..=2);
+        assert_eq!(lines.lines_in_range(4..15), 1..=3);
+        assert_eq!(lines.lines_in_range(14..14), 3..=3);
+        assert_eq!(lines.lines_in_range(0..18), 0..=3);
+
+        assert_eq!(lines.line_range(1), 4..7);
+        assert_eq!(lines.line_byte_range(1), 4..8);
+        assert_eq!(lines.line_byte_range(3), 14..18);
+    }
+
+    #[test]
+    fn toggling_comments_on_a_mixed_block_comments_every_line() {
+        let mut block = buffer("    a();\n\n    // b();\n      c();", 0);
+        assert!(block.toggle_line_comments(0..=3, "//"));
+        assert_eq!(block.text(), "    // a();\n\n    // // b();\n    //   c();");
+        assert!(block.toggle_line_comments(0..=3, "//"));
+        assert_eq!(block.text(), "    a();\n\n    // b();\n      c();");
+
+        let mut single = buffer("x = 1\n  # y = 2", 0);
+        assert!(single.toggle_line_comments(1..=1, "#"));
+        assert_eq!(single.text(), "x = 1\n  y = 2");
+        assert!(single.toggle_line_comments(1..=1, "#"));
+        assert_eq!(single.text(), "x = 1\n  # y = 2");
+
+        assert!(!single.toggle_line_comments(0..=0, ""));
+    }
+
+    #[test]
+    fn apply_edits_matches_applying_the_edits_one_at_a_time() {
+        let original = "let a = 1; let b = 2; let c = 3;";
+        let edit = |range: Range<usize>, inserted: &str| BufferEdit {
+            range,
+            inserted: inserted.to_string(),
+            removed: String::new(),
+        };
+        // Out of order, and growing, shrinking, and deleting text
+        let edits = vec![edit(15..16, "β"), edit(4..5, "alpha"), edit(26..27, "")];
+
+        let mut sequential = original.to_string();
+        let mut by_offset = edits.clone();
+        by_offset.sort_by_key(|edit| std::cmp::Reverse(edit.range.start));
+        for edit in &by_offset {
+            sequential.replace_range(edit.range.clone(), &edit.inserted);
+        }
+
+        let mut batch = buffer(original, 19);
+        batch.set_record_edits(true);
+        assert!(batch.apply_edits(edits));
+        assert_eq!(batch.text(), sequential);
+        assert_eq!(batch.text(), "let alpha = 1; let β = 2; let  = 3;");
+        // The cursor stays before "2"
+        assert_eq!(&batch.text()[batch.cursor_position()..][..1], "2");
+        assert_eq!(batch.line_count(), 1);
+
+        // The recorded edits replay in order on the old text
+        let mut replayed = original.to_string();
+        for edit in batch.take_edits() {
+            replayed.replace_range(edit.range, &edit.inserted);
+        }
+        assert_eq!(replayed, sequential);
+
+        // Overlapping edits are refused and change nothing
+        assert!(!batch.apply_edits(vec![edit(0..5, "x"), edit(3..6, "y")]));
+        assert_eq!(batch.text(), sequential);
+    }
+
+    #[test]
+    fn set_text_resets_the_cursor_and_clears_the_selection() {
+        let mut buffer = buffer("a much longer document than the next one", 30);
+        buffer.set_selection(Some(10..30));
+        buffer.set_text("日本語".to_string());
+        assert_eq!(buffer.cursor_position(), 0);
+        assert_eq!(buffer.selection(), None);
+        buffer.insert_char('x');
+        assert_eq!(buffer.text(), "x日本語");
+    }
+
+    #[test]
+    fn len_chars_and_is_empty_count_characters_not_bytes() {
+        let mut buffer = buffer("", 0);
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.len_chars(), 0);
+
+        buffer.set_text("héllo 日本".to_string());
+        assert!(!buffer.is_empty());
+        assert_eq!(buffer.len_chars(), 8);
+        assert_eq!(buffer.text().len(), 13);
+    }
+
+    #[test]
+    fn open_line_below_the_last_line_copies_its_indentation() {
+        let mut buffer = buffer("fn main() {\n    body", 14);
+        buffer.open_line_below();
+        assert_eq!(buffer.text(), "fn main() {\n    body\n    ");
+        assert_eq!(buffer.cursor_position(), buffer.text().len());
+    }
+
+    #[test]
+    fn open_line_above_the_first_line_copies_its_indentation() {
+        let mut indented = buffer("\tfirst\nsecond", 3);
+        indented.open_line_above();
+        assert_eq!(indented.text(), "\t\n\tfirst\nsecond");
+        assert_eq!(indented.cursor_position(), 1);
+
+        let mut unindented = buffer("only", 2);
+        unindented.open_line_above();
+        assert_eq!(unindented.text(), "\nonly");
+        assert_eq!(unindented.cursor_position(), 0);
+    }
+
+    #[test]
+    fn vertical_motion_returns_to_the_goal_column_after_a_short_line() {
+        let mut buffer = buffer("long line\nab\nlong line two", 7);
+        buffer.move_cursor_down();
+        assert_eq!(buffer.cursor_line_col(), (1, 2));
+        assert_eq!(buffer.goal_column(), Some(7));
+        buffer.move_cursor_down();
+        assert_eq!(buffer.cursor_line_col(), (2, 7));
+        buffer.move_cursor_up();
+        buffer.move_cursor_up();
+        assert_eq!(buffer.cursor_line_col(), (0, 7));
+
+        // A horizontal move sets a new goal; clearing it uses the current column
+        buffer.move_cursor_left();
+        assert_eq!(buffer.goal_column(), Some(6));
+        buffer.set_goal_column(1);
+        buffer.move_cursor_down();
+        assert_eq!(buffer.cursor_line_col(), (1, 1));
+        buffer.clear_goal_column();
+        buffer.move_cursor_down();
+        assert_eq!(buffer.cursor_line_col(), (2, 1));
+    }
+
+    #[test]
+    fn move_cursor_to_clamps_the_column_and_line() {
+        let mut buffer = buffer("first line\nab\nhé llo", 0);
+        buffer.move_cursor_to(1, 1);
+        assert_eq!(buffer.cursor_position(), 12);
+
+        // Past the end of a short line
+        buffer.move_cursor_to(1, 40);
+        assert_eq!(buffer.cursor_position(), 13);
+        assert_eq!(buffer.cursor_line_col(), (1, 2));
+
+        // Columns count characters, not bytes
+        buffer.move_cursor_to(2, 3);
+        assert_eq!(buffer.cursor_position(), 18);
+        assert_eq!(buffer.char_at(buffer.cursor_position()), Some('l'));
+
+        // Past the last line
+        buffer.move_cursor_to(9, 0);
+        assert_eq!(buffer.cursor_line_col(), (2, 0));
+    }
+
+    #[test]
+    fn byte_and_char_indices_convert_over_multibyte_text() {
+        // 'é' is 2 bytes, '日' 3, and '🦀' 4
+        let buffer = buffer("aé日🦀b", 0);
+        let pairs = [(0, 0), (1, 1), (3, 2), (6, 3), (10, 4), (11, 5)];
+        for (byte, char_idx) in pairs {
+            assert_eq!(buffer.byte_to_char(byte), char_idx);
+            assert_eq!(buffer.char_to_byte(char_idx), byte);
+        }
+
+        // Inside a character counts from its start; past the end clamps
+        assert_eq!(buffer.byte_to_char(2), 1);
+        assert_eq!(buffer.byte_to_char(8), 3);
+        assert_eq!(buffer.byte_to_char(99), 5);
+        assert_eq!(buffer.char_to_byte(99), 11);
+    }
+
+    #[test]
+    fn substitute_replaces_first_or_all_matches_on_each_line() {
+        let mut buffer = buffer("a a\na a\na a", 0);
+        assert_eq!(buffer.substitute(1..=1, "a", "b", false), 1);
+        assert_eq!(buffer.text(), "a a\nb a\na a");
+
+        assert_eq!(buffer.substitute(1..=1, "a", "c", true), 1);
+        assert_eq!(buffer.text(), "a a\nb c\na a");
+
+        // Lines past the end are ignored; unchanged lines don't count
+        assert_eq!(buffer.substitute(0..=9, "a", "dé", true), 2);
+        assert_eq!(buffer.text(), "dé dé\nb c\ndé dé");
+        assert_eq!(buffer.cursor_line_col(), (2, 0));
+
+        assert_eq!(buffer.substitute(0..=2, "zzz", "y", true), 0);
+        assert_eq!(buffer.substitute(0..=2, "", "y", true), 0);
+    }
 }
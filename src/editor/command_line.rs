@@ -0,0 +1,302 @@
+//! Parsing for Vim's `:` command line
+//!
+//! The Vim handler collects the text typed after `:` and the editor parses it
+//! into an [`ExCommand`] when Enter is pressed.
+
+use std::fmt;
+
+/// A command entered on the Vim command line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExCommand {
+    /// `:w` - save the buffer
+    Write,
+    /// `:q` or `:q!` - ask the host to close the editor
+    Quit { force: bool },
+    /// `:wq` or `:x` - save, then close
+    WriteQuit,
+    /// `:<number>` - go to a line (1-based, as typed)
+    GoToLine(usize),
+    /// `:[range]s/pattern/replacement/[g]` - literal substitution
+    Substitute {
+        range: LineRange,
+        pattern: String,
+        replacement: String,
+        global: bool,
+    },
+}
+
+/// The lines a command applies to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineRange {
+    /// No range given - the line the cursor is on
+    Current,
+    /// `%` - every line in the buffer
+    All,
+    /// `first,last` - an inclusive span of lines (1-based, as typed)
+    Lines(usize, usize),
+}
+
+/// Why a command line could not be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// Nothing but whitespace was entered
+    Empty,
+    /// The command name isn't one the editor knows
+    UnknownCommand(String),
+    /// A substitution was missing its pattern or had unknown flags
+    InvalidSubstitute,
+    /// A line range was malformed or backwards
+    InvalidRange,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "Empty command"),
+            Self::UnknownCommand(name) => write!(f, "Not an editor command: {name}"),
+            Self::InvalidSubstitute => write!(f, "Invalid substitute command"),
+            Self::InvalidRange => write!(f, "Invalid range"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Parse the text typed after `:` (without the colon itself)
+pub fn parse(line: &str) -> Result<ExCommand, ParseError> {
+    let line = line.trim();
+    if line.is_empty() {
+        return Err(ParseError::Empty);
+    }
+
+    if line.chars().all(|c| c.is_ascii_digit()) {
+        // Numbers too large for usize still mean "the last line"
+        return Ok(ExCommand::GoToLine(line.parse().unwrap_or(usize::MAX)));
+    }
+
+    let (range, command) = parse_range(line)?;
+    if let Some(rest) = command.strip_prefix('s') {
+        if rest
+            .chars()
+            .next()
+            .is_some_and(|c| !c.is_alphanumeric() && !c.is_whitespace() && c != '\\')
+        {
+            return parse_substitute(range, rest);
+        }
+    }
+    if range != LineRange::Current {
+        return Err(ParseError::UnknownCommand(line.to_string()));
+    }
+
+    match line {
+        "w" | "write" => Ok(ExCommand::Write),
+        "q" | "quit" => Ok(ExCommand::Quit { force: false }),
+        "q!" | "quit!" => Ok(ExCommand::Quit { force: true }),
+        "wq" | "x" => Ok(ExCommand::WriteQuit),
+        _ => Err(ParseError::UnknownCommand(line.to_string())),
+    }
+}
+
+/// Split a leading `%` or `first,last` range off a command
+fn parse_range(line: &str) -> Result<(LineRange, &str), ParseError> {
+    if let Some(rest) = line.strip_prefix('%') {
+        return Ok((LineRange::All, rest));
+    }
+
+    let digits = line
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(line.len());
+    if digits == 0 {
+        return Ok((LineRange::Current, line));
+    }
+    let Some(rest) = line[digits..].strip_prefix(',') else {
+        return Ok((LineRange::Current, line));
+    };
+    let last_digits = rest
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(rest.len());
+    if last_digits == 0 {
+        return Err(ParseError::InvalidRange);
+    }
+
+    let first = line[..digits].parse().unwrap_or(usize::MAX);
+    let last = rest[..last_digits].parse().unwrap_or(usize::MAX);
+    if first == 0 || last < first {
+        return Err(ParseError::InvalidRange);
+    }
+    Ok((LineRange::Lines(first, last), &rest[last_digits..]))
+}
+
+/// Parse `/pattern/replacement/flags`, where `/` is any delimiter character
+///
+/// A backslash escapes the delimiter (`\/`) or itself (`\\`); other escapes are
+/// kept as typed.
+fn parse_substitute(range: LineRange, text: &str) -> Result<ExCommand, ParseError> {
+    let mut chars = text.chars();
+    let delimiter = chars.next().ok_or(ParseError::InvalidSubstitute)?;
+
+    let mut parts = vec![String::new()];
+    while let Some(c) = chars.next() {
+        // The flags after the third delimiter are taken as typed
+        let in_flags = parts.len() == 3;
+        let part = parts.last_mut().expect("parts is never empty");
+        match c {
+            '\\' if !in_flags => match chars.next() {
+                Some(next) if next == delimiter || next == '\\' => part.push(next),
+                Some(next) => {
+                    part.push('\\');
+                    part.push(next);
+                }
+                None => part.push('\\'),
+            },
+            c if c == delimiter && !in_flags => parts.push(String::new()),
+            c => part.push(c),
+        }
+    }
+
+    let mut parts = parts.into_iter();
+    let pattern = parts.next().unwrap_or_default();
+    let replacement = parts.next().unwrap_or_default();
+    let global = match parts.next().unwrap_or_default().as_str() {
+        "" => false,
+        "g" => true,
+        _ => return Err(ParseError::InvalidSubstitute),
+    };
+
+    if pattern.is_empty() {
+        return Err(ParseError::InvalidSubstitute);
+    }
+
+    Ok(ExCommand::Substitute {
+        range,
+        pattern,
+        replacement,
+        global,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn substitute(range: LineRange, pattern: &str, replacement: &str, global: bool) -> ExCommand {
+        ExCommand::Substitute {
+            range,
+            pattern: pattern.to_string(),
+            replacement: replacement.to_string(),
+            global,
+        }
+    }
+
+    #[test]
+    fn parses_write_and_quit_commands() {
+        assert_eq!(parse("w"), Ok(ExCommand::Write));
+        assert_eq!(parse(" write "), Ok(ExCommand::Write));
+        assert_eq!(parse("q"), Ok(ExCommand::Quit { force: false }));
+        assert_eq!(parse("q!"), Ok(ExCommand::Quit { force: true }));
+        assert_eq!(parse("wq"), Ok(ExCommand::WriteQuit));
+        assert_eq!(parse("x"), Ok(ExCommand::WriteQuit));
+    }
+
+    #[test]
+    fn parses_line_numbers_including_zero_and_overflow() {
+        assert_eq!(parse("12"), Ok(ExCommand::GoToLine(12)));
+        assert_eq!(parse("0"), Ok(ExCommand::GoToLine(0)));
+        assert_eq!(
+            parse("999999999999999999999"),
+            Ok(ExCommand::GoToLine(usize::MAX))
+        );
+    }
+
+    #[test]
+    fn parses_substitution_on_the_current_line() {
+        assert_eq!(
+            parse("s/a/b/"),
+            Ok(substitute(LineRange::Current, "a", "b", false))
+        );
+        assert_eq!(
+            parse("s/a/b"),
+            Ok(substitute(LineRange::Current, "a", "b", false))
+        );
+        assert_eq!(
+            parse("s/a//"),
+            Ok(substitute(LineRange::Current, "a", "", false))
+        );
+        assert_eq!(
+            parse("s#a#b#g"),
+            Ok(substitute(LineRange::Current, "a", "b", true))
+        );
+    }
+
+    #[test]
+    fn parses_whole_file_substitution_and_its_flags() {
+        assert_eq!(
+            parse("%s/foo/bar/g"),
+            Ok(substitute(LineRange::All, "foo", "bar", true))
+        );
+        assert_eq!(
+            parse("%s/foo/bar/"),
+            Ok(substitute(LineRange::All, "foo", "bar", false))
+        );
+        assert_eq!(parse("%s/foo/bar/x"), Err(ParseError::InvalidSubstitute));
+        assert_eq!(parse("%s/foo/bar/gg"), Err(ParseError::InvalidSubstitute));
+        assert_eq!(parse("s//bar/"), Err(ParseError::InvalidSubstitute));
+    }
+
+    #[test]
+    fn escaped_delimiters_are_part_of_the_pattern() {
+        assert_eq!(
+            parse(r"s/a\/b/c/"),
+            Ok(substitute(LineRange::Current, "a/b", "c", false))
+        );
+        assert_eq!(
+            parse(r"s/a/c\/d/"),
+            Ok(substitute(LineRange::Current, "a", "c/d", false))
+        );
+        assert_eq!(
+            parse(r"s/a\\/b/"),
+            Ok(substitute(LineRange::Current, r"a\", "b", false))
+        );
+        assert_eq!(
+            parse(r"s/a\nb/c/"),
+            Ok(substitute(LineRange::Current, r"a\nb", "c", false))
+        );
+    }
+
+    #[test]
+    fn parses_line_ranges() {
+        assert_eq!(
+            parse("1,3s/a/b/"),
+            Ok(substitute(LineRange::Lines(1, 3), "a", "b", false))
+        );
+        assert_eq!(parse("3,1s/a/b/"), Err(ParseError::InvalidRange));
+        assert_eq!(parse("0,2s/a/b/"), Err(ParseError::InvalidRange));
+        assert_eq!(parse("1,s/a/b/"), Err(ParseError::InvalidRange));
+    }
+
+    #[test]
+    fn rejects_unknown_commands_with_or_without_a_range() {
+        assert_eq!(
+            parse("1,2w"),
+            Err(ParseError::UnknownCommand("1,2w".to_string()))
+        );
+        assert_eq!(
+            parse("%q"),
+            Err(ParseError::UnknownCommand("%q".to_string()))
+        );
+        assert_eq!(
+            parse("edit"),
+            Err(ParseError::UnknownCommand("edit".to_string()))
+        );
+        assert_eq!(
+            parse("sort"),
+            Err(ParseError::UnknownCommand("sort".to_string()))
+        );
+    }
+
+    #[test]
+    fn rejects_an_empty_line() {
+        assert_eq!(parse(""), Err(ParseError::Empty));
+        assert_eq!(parse("   "), Err(ParseError::Empty));
+    }
+}
@@ -7,6 +7,14 @@ pub enum CursorMovement {
     Down,
     WordLeft,
     WordRight,
+    /// To the end of the word at or after the cursor (Emacs `M-f`)
+    WordEnd,
+    /// To the bracket matching the one at or after the cursor on its line (Vim `%`)
+    MatchingBracket,
+    /// To the blank line before the cursor's paragraph, or the start of the text (Vim `{`)
+    ParagraphBackward,
+    /// To the blank line after the cursor's paragraph, or the end of the text (Vim `}`)
+    ParagraphForward,
     LineStart,
     LineEnd,
     DocumentStart,
@@ -14,54 +22,178 @@ pub enum CursorMovement {
 }
 
 /// Represents commands that can be executed on the text buffer
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum EditorCommand {
     // Character operations
     InsertChar(char),
+    /// Replace the character under the cursor, or insert at a line end (overtype)
+    ReplaceChar(char),
+    /// Put back the text the last Vim Replace-mode keystroke overwrote, or move left
+    RestoreReplacedChar,
     DeleteChar,
     DeleteCharForward,
 
     // Cursor movement
     MoveCursor(CursorMovement),
+    /// Move the cursor and the view up by the number of visible lines
+    PageUp,
+    /// Move the cursor and the view down by the number of visible lines
+    PageDown,
+    /// Record the cursor position in the jumplist before a jump motion
+    PushJump,
+    /// Go back to the previous jumplist position (Vim `Ctrl+O`)
+    JumpBack,
+    /// Go forward again in the jumplist (Vim `Ctrl+I`)
+    JumpForward,
 
     // Text operations
     DeleteLine,
+    /// Delete from the start of the word before the cursor up to the cursor
+    ///
+    /// Like Ctrl+Backspace, this takes the word *before* the cursor rather than
+    /// the whole word under it; `TextBuffer::word_range_at` gives the latter.
     DeleteWord,
+    /// Delete from the cursor to the end of the word after it
+    DeleteWordForward,
+    /// Delete from the start of the line up to the cursor
+    DeleteToLineStart,
+    /// Select the whole text
+    SelectAll,
+    /// Copy the selection, or the cursor's whole line without one, to the register and clipboard
     Copy,
+    /// Like `Copy`, then delete what was copied
     Cut,
+    /// Insert the register at the cursor, replacing the selection if there is one;
+    /// otherwise whole lines go below the cursor's line (Vim `p`)
     Paste,
+    /// Like `Paste`, but whole lines go above the cursor's line (Vim `P`)
+    PasteAbove,
+    /// Delete to the end of the line (or the newline at a line end) into the kill ring
+    KillLine,
+    /// Delete from the cursor to the end of the next word into the kill ring
+    KillWord,
+    /// Delete from the start of the previous word to the cursor into the kill ring
+    KillWordBackward,
+    /// Delete from the start of the line up to the cursor into the kill ring
+    KillToLineStart,
+    /// Delete the selection into the kill ring, or the previous word without one
+    KillRegion,
+    /// Make the next kill add to the newest kill ring entry instead of starting a new one
+    AppendNextKill,
+    /// Insert the newest kill ring entry at the cursor
+    Yank,
     NewLine,
+    /// Insert a newline and indent the new line like the current one
+    NewLineAndIndent,
+    /// Insert a newline after the cursor without moving the cursor
+    OpenLine,
+    /// Open a line below the cursor's line with its indentation (Vim `o`)
+    OpenLineBelow,
+    /// Open a line above the cursor's line with its indentation (Vim `O`)
+    OpenLineAbove,
+    /// Add one level of indentation to the cursor's line, leaving the cursor after it
+    IndentLine,
+    /// Remove one level of indentation from the cursor's line
+    OutdentLine,
+    /// Indent every non-empty line in an inclusive (0-based) line range by one level
+    IndentLines {
+        first: usize,
+        last: usize,
+    },
+    /// Remove one level of indentation from every line in an inclusive line range
+    OutdentLines {
+        first: usize,
+        last: usize,
+    },
+    /// Copy the cursor's line, or the selected lines, below themselves and move onto the copy
+    DuplicateLine,
+    /// Comment out the cursor's line or the selected lines, or uncomment them if all are commented
+    ToggleLineComment,
+    /// Swap an inclusive line range with the line above it, moving the cursor along
+    MoveLinesUp {
+        first: usize,
+        last: usize,
+    },
+    /// Swap an inclusive line range with the line below it, moving the cursor along
+    MoveLinesDown {
+        first: usize,
+        last: usize,
+    },
+    /// Insert an accepted completion in place of the partial word before the cursor
+    InsertCompletion(String),
+    /// Insert an opening and a closing character with the cursor between them
+    InsertPair(char, char),
 
     // Custom commands
     Custom(String),
 
     // Mode switching
     ChangeMode(EditorMode),
+
+    /// Run a line typed on the Vim command line (the text after `:`)
+    RunCommandLine(String),
+
+    /// Hand the text to the host's save callback
+    Save,
+
+    /// Ask the host to close the editor, unsaved changes or not (Emacs `C-x C-c`)
+    Quit,
+
+    /// Scroll so the cursor's line sits at a position in the viewport
+    ScrollCursorLine(ScrollPosition),
 }
 
-/// Editor mode (Vim or Emacs)
+/// Where to put the cursor's line in the viewport (Vim `zt`, `zz`, `zb`)
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScrollPosition {
+    Top,
+    Center,
+    Bottom,
+}
+
+/// Editor mode (Vim, Emacs, or plain `TextEdit` behavior)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
 pub enum EditorMode {
     Vim(VimMode),
+    #[default]
     Emacs,
-}
-
-impl Default for EditorMode {
-    fn default() -> Self {
-        Self::Emacs
-    }
+    /// No modal key bindings - input goes straight to the `TextEdit`
+    Plain,
 }
 
 /// Vim editor modes
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
 pub enum VimMode {
+    #[default]
     Normal,
     Insert,
     Visual,
+    /// Typed characters overwrite the text under the cursor (`R`)
+    Replace,
+    /// Typing an ex command after `:`
+    CommandLine,
+}
+
+/// Shape of the text cursor drawn by the editor
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CursorShape {
+    /// Thin vertical bar between characters (the `TextEdit` default)
+    #[default]
+    Bar,
+    /// Full character cell over the character at the cursor
+    Block,
+    /// Thin line under the character at the cursor
+    Underline,
 }
 
-impl Default for VimMode {
-    fn default() -> Self {
-        Self::Normal
-    }
+/// How far rows after the first of a soft-wrapped line are indented
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WrapIndent {
+    /// Continuation rows start at the left edge
+    #[default]
+    None,
+    /// Continuation rows line up with the line's own indentation
+    MatchLine,
+    /// Continuation rows are indented by a fixed number of columns
+    Columns(usize),
 }
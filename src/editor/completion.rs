@@ -0,0 +1,45 @@
+//! Completion items supplied by the host for the autocomplete popup
+//!
+//! The host decides what to offer (e.g. from a language server) and hands the
+//! items to `EditorWidget::set_completions`; the widget draws the popup near the
+//! cursor and inserts the accepted item in place of the word being typed.
+
+/// A single entry in the completion popup
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompletionItem {
+    /// Text shown in the popup
+    pub label: String,
+    /// Text inserted when the item is accepted, if different from the label
+    pub insert_text: Option<String>,
+    /// Extra information shown next to the label (e.g. a type signature)
+    pub detail: Option<String>,
+}
+
+impl CompletionItem {
+    pub fn new(label: impl Into<String>) -> Self {
+        Self {
+            label: label.into(),
+            insert_text: None,
+            detail: None,
+        }
+    }
+
+    /// Insert `text` instead of the label when accepted
+    #[must_use]
+    pub fn with_insert_text(mut self, text: impl Into<String>) -> Self {
+        self.insert_text = Some(text.into());
+        self
+    }
+
+    /// Show `detail` next to the label
+    #[must_use]
+    pub fn with_detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+
+    /// The text inserted when this item is accepted
+    pub fn text(&self) -> &str {
+        self.insert_text.as_deref().unwrap_or(&self.label)
+    }
+}
@@ -0,0 +1,123 @@
+//! An opt-in log of what the editor did with its input
+//!
+//! With `EditorWidget::with_debug_log(true)` the editor records each key press
+//! and typed text it sees, how its key handler read them, the commands it
+//! runs, and its mode changes, oldest first. Only the most recent entries are
+//! kept, so the log can stay on in a dev overlay to answer "why did that key
+//! do this" without a debug build.
+
+use std::collections::VecDeque;
+use std::fmt;
+
+use egui::{Key, Modifiers};
+
+use crate::editor::commands::{EditorCommand, EditorMode};
+
+/// How many entries the log keeps before dropping the oldest
+pub const DEFAULT_DEBUG_LOG_CAPACITY: usize = 200;
+
+/// One thing the editor saw or did
+#[derive(Debug, Clone, PartialEq)]
+pub enum DebugLogEntry {
+    /// A key pressed while the editor had focus
+    Key { key: Key, modifiers: Modifiers },
+    /// Text typed while the editor had focus
+    Text(String),
+    /// A key handler's note on how it read a key
+    Handler {
+        handler: &'static str,
+        message: String,
+    },
+    /// A command the editor executed
+    Command(EditorCommand),
+    /// The mode at the end of a frame differed from the one before
+    ModeChange { from: EditorMode, to: EditorMode },
+}
+
+impl fmt::Display for DebugLogEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Key { key, modifiers } if modifiers.is_none() => write!(f, "key {key:?}"),
+            Self::Key { key, modifiers } => write!(f, "key {modifiers:?}+{key:?}"),
+            Self::Text(text) => write!(f, "text {text:?}"),
+            Self::Handler { handler, message } => write!(f, "{handler}: {message}"),
+            Self::Command(command) => write!(f, "command {command:?}"),
+            Self::ModeChange { from, to } => write!(f, "mode {from:?} -> {to:?}"),
+        }
+    }
+}
+
+/// A bounded list of log entries, oldest first
+#[derive(Debug, Clone, PartialEq)]
+pub struct DebugLog {
+    entries: VecDeque<DebugLogEntry>,
+    capacity: usize,
+}
+
+impl Default for DebugLog {
+    fn default() -> Self {
+        Self::new(DEFAULT_DEBUG_LOG_CAPACITY)
+    }
+}
+
+impl DebugLog {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Add an entry, dropping the oldest one when the log is full
+    pub fn push(&mut self, entry: DebugLogEntry) {
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    /// All entries, oldest first
+    pub fn entries(&self) -> impl DoubleEndedIterator<Item = &DebugLogEntry> {
+        self.entries.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_full_log_drops_its_oldest_entries() {
+        let mut log = DebugLog::new(2);
+        for text in ["a", "b", "c"] {
+            log.push(DebugLogEntry::Text(text.to_string()));
+        }
+        let entries: Vec<String> = log.entries().map(ToString::to_string).collect();
+        assert_eq!(entries, ["text \"b\"", "text \"c\""]);
+
+        // A log always keeps at least the latest entry
+        let key = DebugLogEntry::Key {
+            key: Key::A,
+            modifiers: Modifiers::CTRL,
+        };
+        let mut tiny = DebugLog::new(0);
+        tiny.push(DebugLogEntry::Command(EditorCommand::SelectAll));
+        tiny.push(key.clone());
+        assert_eq!(tiny.entries().collect::<Vec<_>>(), [&key]);
+
+        tiny.clear();
+        assert!(tiny.is_empty());
+    }
+}
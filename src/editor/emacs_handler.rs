@@ -1,17 +1,39 @@
-use crate::editor::commands::EditorCommand;
-use crate::editor::keyhandler::KeyHandler;
+use crate::editor::commands::{CursorMovement, EditorCommand};
+use crate::editor::keyhandler::{
+    key_event_indices, KeyHandler, PendingKey, DEFAULT_SEQUENCE_TIMEOUT,
+};
 use egui::{Context, Event, InputState, Key, Modifiers};
 
 /// Implements Emacs key handling for the editor
-
-#[derive(Default)]
 pub struct EmacsKeyHandler {
-    /// Debug printing enabled/disabled
+    /// Whether `debug_log` keeps its messages
     debug: bool,
+    /// Messages explaining how keys were read, for the editor's debug log
+    debug_messages: Vec<String>,
     /// Commands that need to be executed
-    /// NOTE: This field is no longer used as we now generate TextEdit-compatible events directly
-    /// Kept for backward compatibility, but may be removed in the future
+    ///
+    /// Most bindings synthesize `TextEdit`-compatible events instead; only
+    /// commands with no key equivalent (like `C-x C-s` or the kills) are queued here.
     pub commands: Vec<EditorCommand>,
+    /// A `C-x` prefix waiting for the rest of its sequence
+    pending: Option<PendingKey>,
+    /// Seconds a pending prefix waits before it is discarded
+    sequence_timeout: f64,
+    /// Whether the last key pressed was a kill, so the next kill appends to it
+    last_command_was_kill: bool,
+}
+
+impl Default for EmacsKeyHandler {
+    fn default() -> Self {
+        Self {
+            debug: false,
+            debug_messages: Vec::new(),
+            commands: Vec::new(),
+            pending: None,
+            sequence_timeout: DEFAULT_SEQUENCE_TIMEOUT,
+            last_command_was_kill: false,
+        }
+    }
 }
 
 impl EmacsKeyHandler {
@@ -19,19 +41,97 @@ impl EmacsKeyHandler {
         Self::default()
     }
 
+    /// Set how long (in seconds) a `C-x` prefix waits for the next key
+    #[must_use]
+    pub const fn with_sequence_timeout(mut self, seconds: f64) -> Self {
+        self.sequence_timeout = seconds;
+        self
+    }
+
     #[must_use]
     pub const fn with_debug(mut self, debug: bool) -> Self {
         self.debug = debug;
         self
     }
 
-    /// Enable or disable debug logging
-    fn debug_log(&self, message: &str) {
+    /// Keep or stop keeping messages about how keys were read
+    pub fn set_debug(&mut self, debug: bool) {
+        self.debug = debug;
+        if !debug {
+            self.debug_messages.clear();
+        }
+    }
+
+    /// Take the messages kept since the last call, oldest first
+    pub fn take_debug_messages(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.debug_messages)
+    }
+
+    /// Keep `message` for the editor's debug log, if debugging is on
+    fn debug_log(&mut self, message: &str) {
         if self.debug {
-            println!("[EmacsKeyHandler] {message}");
+            self.debug_messages.push(message.to_string());
         }
     }
 
+    /// Handle `C-x` and the key that follows it
+    ///
+    /// Returns `Some` with the events to remove when the input belonged to a
+    /// prefix sequence, and `None` when it should be handled normally.
+    fn handle_prefix(&mut self, input: &mut InputState) -> Option<Vec<usize>> {
+        if self
+            .pending
+            .is_some_and(|pending| pending.is_expired(input.time, self.sequence_timeout))
+        {
+            self.debug_log("C-x prefix timed out - discarding");
+            self.pending = None;
+        }
+
+        if self.pending.is_none() {
+            if input.modifiers.ctrl && input.key_pressed(Key::X) {
+                self.debug_log("Ctrl+X pressed - waiting for the rest of the sequence");
+                self.pending = Some(PendingKey::new(Key::X, input.time));
+                return Some(key_event_indices(&input.events, Key::X));
+            }
+            return None;
+        }
+
+        // Wait for a key that isn't just a modifier being held down
+        let key = *input
+            .keys_down
+            .iter()
+            .find(|key| input.key_pressed(**key))?;
+        self.pending = None;
+        let events_to_remove = key_event_indices(&input.events, key);
+
+        match key {
+            Key::H => {
+                self.debug_log("C-x h pressed - selecting all");
+                self.commands.push(EditorCommand::SelectAll);
+            }
+            Key::S if input.modifiers.ctrl => {
+                self.debug_log("C-x C-s pressed - saving");
+                self.commands.push(EditorCommand::Save);
+            }
+            Key::C if input.modifiers.ctrl => {
+                self.debug_log("C-x C-c pressed - quitting");
+                self.commands.push(EditorCommand::Quit);
+            }
+            _ => self.debug_log("Undefined C-x sequence - ignoring"),
+        }
+
+        Some(events_to_remove)
+    }
+
+    /// Queue a kill, appending it to the previous kill when they were consecutive
+    fn push_kill(&mut self, command: EditorCommand, previous_was_kill: bool) {
+        if previous_was_kill {
+            self.commands.push(EditorCommand::AppendNextKill);
+        }
+        self.commands.push(command);
+        self.last_command_was_kill = true;
+    }
+
     /// Clear any commands that have been queued up
     pub fn clear_commands(&mut self) {
         self.commands.clear();
@@ -43,40 +143,40 @@ impl KeyHandler for EmacsKeyHandler {
         // Clear any previous commands
         self.commands.clear();
 
+        // Kills only accumulate while nothing else is pressed in between
+        let previous_was_kill = std::mem::take(&mut self.last_command_was_kill);
+        if !input.events.iter().any(breaks_kill_sequence) {
+            self.last_command_was_kill = previous_was_kill;
+            return Vec::new();
+        }
+
+        if let Some(events_to_remove) = self.handle_prefix(input) {
+            return events_to_remove;
+        }
+
         // Emacs uses control and alt key combinations for most commands
         // We'll replace them with TextEdit-compatible events
         let mut events_to_remove = Vec::new();
 
         // Process CTRL key combinations
         if input.modifiers.ctrl {
-            // Basic movement - map to arrow keys
+            // Character movement goes through the buffer so emoji and combining
+            // marks are stepped over as one character; lines map to arrow keys
             if input.key_pressed(Key::F) {
-                self.debug_log("Ctrl+F pressed - mapping to Right arrow");
-                events_to_remove.extend(0..input.events.len());
-
-                input.events.push(Event::Key {
-                    key: Key::ArrowRight,
-                    physical_key: Some(Key::ArrowRight),
-                    pressed: true,
-                    repeat: false,
-                    modifiers: Modifiers::default(),
-                });
+                self.debug_log("Ctrl+F pressed - moving right");
+                events_to_remove.extend(key_event_indices(&input.events, Key::F));
+                self.commands
+                    .push(EditorCommand::MoveCursor(CursorMovement::Right));
             }
             if input.key_pressed(Key::B) {
-                self.debug_log("Ctrl+B pressed - mapping to Left arrow");
-                events_to_remove.extend(0..input.events.len());
-
-                input.events.push(Event::Key {
-                    key: Key::ArrowLeft,
-                    physical_key: Some(Key::ArrowLeft),
-                    pressed: true,
-                    repeat: false,
-                    modifiers: Modifiers::default(),
-                });
+                self.debug_log("Ctrl+B pressed - moving left");
+                events_to_remove.extend(key_event_indices(&input.events, Key::B));
+                self.commands
+                    .push(EditorCommand::MoveCursor(CursorMovement::Left));
             }
             if input.key_pressed(Key::P) {
                 self.debug_log("Ctrl+P pressed - mapping to Up arrow");
-                events_to_remove.extend(0..input.events.len());
+                events_to_remove.extend(key_event_indices(&input.events, Key::P));
 
                 input.events.push(Event::Key {
                     key: Key::ArrowUp,
@@ -88,7 +188,7 @@ impl KeyHandler for EmacsKeyHandler {
             }
             if input.key_pressed(Key::N) {
                 self.debug_log("Ctrl+N pressed - mapping to Down arrow");
-                events_to_remove.extend(0..input.events.len());
+                events_to_remove.extend(key_event_indices(&input.events, Key::N));
 
                 input.events.push(Event::Key {
                     key: Key::ArrowDown,
@@ -100,35 +200,66 @@ impl KeyHandler for EmacsKeyHandler {
             }
 
             // Line movement - map to Home/End keys
+            // Line start/end go through the buffer, so the synthesized keys can't
+            // be mistaken for Ctrl+Home/End below; C-a is never select-all here
             if input.key_pressed(Key::A) {
-                self.debug_log("Ctrl+A pressed - mapping to Home key");
-                events_to_remove.extend(0..input.events.len());
-
-                input.events.push(Event::Key {
-                    key: Key::Home,
-                    physical_key: Some(Key::Home),
-                    pressed: true,
-                    repeat: false,
-                    modifiers: Modifiers::default(),
-                });
+                self.debug_log("Ctrl+A pressed - moving to line start");
+                events_to_remove.extend(key_event_indices(&input.events, Key::A));
+                self.commands
+                    .push(EditorCommand::MoveCursor(CursorMovement::LineStart));
             }
             if input.key_pressed(Key::E) {
-                self.debug_log("Ctrl+E pressed - mapping to End key");
-                events_to_remove.extend(0..input.events.len());
+                self.debug_log("Ctrl+E pressed - moving to line end");
+                events_to_remove.extend(key_event_indices(&input.events, Key::E));
+                self.commands
+                    .push(EditorCommand::MoveCursor(CursorMovement::LineEnd));
+            }
 
-                input.events.push(Event::Key {
-                    key: Key::End,
-                    physical_key: Some(Key::End),
-                    pressed: true,
-                    repeat: false,
-                    modifiers: Modifiers::default(),
-                });
+            // Deletion and the kill ring
+            if input.key_pressed(Key::D) {
+                self.debug_log("Ctrl+D pressed - deleting character forward");
+                events_to_remove.extend(key_event_indices(&input.events, Key::D));
+                self.commands.push(EditorCommand::DeleteCharForward);
+            }
+            if input.key_pressed(Key::K) {
+                self.debug_log("Ctrl+K pressed - killing to end of line");
+                events_to_remove.extend(key_event_indices(&input.events, Key::K));
+                self.push_kill(EditorCommand::KillLine, previous_was_kill);
+            }
+            // The selection is the region; without one C-w kills the previous
+            // word and C-u kills back to the line start, as in readline
+            if input.key_pressed(Key::W) {
+                self.debug_log("Ctrl+W pressed - killing region or previous word");
+                events_to_remove.extend(key_event_indices(&input.events, Key::W));
+                self.push_kill(EditorCommand::KillRegion, previous_was_kill);
+            }
+            if input.key_pressed(Key::U) {
+                self.debug_log("Ctrl+U pressed - killing to line start");
+                events_to_remove.extend(key_event_indices(&input.events, Key::U));
+                self.push_kill(EditorCommand::KillToLineStart, previous_was_kill);
+            }
+            if input.key_pressed(Key::Y) {
+                self.debug_log("Ctrl+Y pressed - yanking");
+                events_to_remove.extend(key_event_indices(&input.events, Key::Y));
+                self.commands.push(EditorCommand::Yank);
+            }
+
+            // Line editing that goes through the buffer
+            if input.key_pressed(Key::O) {
+                self.debug_log("Ctrl+O pressed - opening line");
+                events_to_remove.extend(key_event_indices(&input.events, Key::O));
+                self.commands.push(EditorCommand::OpenLine);
+            }
+            if input.key_pressed(Key::J) {
+                self.debug_log("Ctrl+J pressed - newline and indent");
+                events_to_remove.extend(key_event_indices(&input.events, Key::J));
+                self.commands.push(EditorCommand::NewLineAndIndent);
             }
 
             // Document movement - map to Ctrl+Home/Ctrl+End
             if input.key_pressed(Key::Home) {
                 self.debug_log("Ctrl+Home pressed - document start");
-                events_to_remove.extend(0..input.events.len());
+                events_to_remove.extend(key_event_indices(&input.events, Key::Home));
 
                 // Keep the Ctrl modifier
                 input.events.push(Event::Key {
@@ -141,7 +272,7 @@ impl KeyHandler for EmacsKeyHandler {
             }
             if input.key_pressed(Key::End) {
                 self.debug_log("Ctrl+End pressed - document end");
-                events_to_remove.extend(0..input.events.len());
+                events_to_remove.extend(key_event_indices(&input.events, Key::End));
 
                 // Keep the Ctrl modifier
                 input.events.push(Event::Key {
@@ -156,44 +287,24 @@ impl KeyHandler for EmacsKeyHandler {
 
         // Process ALT (Meta) key combinations
         if input.modifiers.alt {
-            // Word movement - map to Ctrl+Left/Ctrl+Right (TextEdit standard)
+            // Word movement goes through the buffer so it follows the editor's word characters
             if input.key_pressed(Key::F) {
-                self.debug_log("Alt+F pressed - mapping to Ctrl+Right");
-                events_to_remove.extend(0..input.events.len());
-
-                let mut mods = Modifiers::default();
-                let var_name = true;
-                mods.ctrl = var_name;
-
-                input.events.push(Event::Key {
-                    key: Key::ArrowRight,
-                    physical_key: Some(Key::ArrowRight),
-                    pressed: true,
-                    repeat: false,
-                    modifiers: mods,
-                });
+                self.debug_log("Alt+F pressed - moving to the end of the word");
+                events_to_remove.extend(key_event_indices(&input.events, Key::F));
+                self.commands
+                    .push(EditorCommand::MoveCursor(CursorMovement::WordEnd));
             }
             if input.key_pressed(Key::B) {
-                self.debug_log("Alt+B pressed - mapping to Ctrl+Left");
-                events_to_remove.extend(0..input.events.len());
-
-                let mut mods = Modifiers::default();
-                let var_name = true;
-                mods.ctrl = var_name;
-
-                input.events.push(Event::Key {
-                    key: Key::ArrowLeft,
-                    physical_key: Some(Key::ArrowLeft),
-                    pressed: true,
-                    repeat: false,
-                    modifiers: mods,
-                });
+                self.debug_log("Alt+B pressed - moving to the start of the word");
+                events_to_remove.extend(key_event_indices(&input.events, Key::B));
+                self.commands
+                    .push(EditorCommand::MoveCursor(CursorMovement::WordLeft));
             }
 
             // Document movement
             if input.key_pressed(Key::Comma) && input.modifiers.shift {
                 self.debug_log("Alt+< pressed - mapping to Ctrl+Home");
-                events_to_remove.extend(0..input.events.len());
+                events_to_remove.extend(key_event_indices(&input.events, Key::Comma));
 
                 let mut mods = Modifiers::default();
                 let var_name = true;
@@ -209,7 +320,7 @@ impl KeyHandler for EmacsKeyHandler {
             }
             if input.key_pressed(Key::Period) && input.modifiers.shift {
                 self.debug_log("Alt+> pressed - mapping to Ctrl+End");
-                events_to_remove.extend(0..input.events.len());
+                events_to_remove.extend(key_event_indices(&input.events, Key::Period));
 
                 let mut mods = Modifiers::default();
                 let var_name = true;
@@ -225,10 +336,50 @@ impl KeyHandler for EmacsKeyHandler {
             }
         }
 
+        // Word kills: M-d and M-Backspace (C-Backspace and C-Delete work too)
+        if input.modifiers.alt && !input.modifiers.ctrl && input.key_pressed(Key::D) {
+            self.debug_log("Alt+D pressed - killing word forward");
+            events_to_remove.extend(key_event_indices(&input.events, Key::D));
+            self.push_kill(EditorCommand::KillWord, previous_was_kill);
+        }
+        if (input.modifiers.alt || input.modifiers.ctrl) && !input.modifiers.shift {
+            if input.key_pressed(Key::Backspace) {
+                self.debug_log("Alt+Backspace pressed - killing word backward");
+                events_to_remove.extend(key_event_indices(&input.events, Key::Backspace));
+                self.push_kill(EditorCommand::KillWordBackward, previous_was_kill);
+            }
+            if input.key_pressed(Key::Delete) {
+                self.debug_log("Alt+Delete pressed - killing word forward");
+                events_to_remove.extend(key_event_indices(&input.events, Key::Delete));
+                self.push_kill(EditorCommand::KillWord, previous_was_kill);
+            }
+        }
+
         events_to_remove
     }
 
     fn name(&self) -> &'static str {
         "emacs"
     }
+
+    fn reset(&mut self) {
+        self.pending = None;
+        self.last_command_was_kill = false;
+        self.commands.clear();
+    }
+
+    fn take_commands(&mut self) -> Vec<EditorCommand> {
+        std::mem::take(&mut self.commands)
+    }
+}
+
+/// Whether an input event is a key press, text, or click that ends a run of kills
+fn breaks_kill_sequence(event: &Event) -> bool {
+    matches!(
+        event,
+        Event::Key { pressed: true, .. }
+            | Event::Text(_)
+            | Event::Paste(_)
+            | Event::PointerButton { pressed: true, .. }
+    )
 }
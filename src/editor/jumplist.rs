@@ -0,0 +1,91 @@
+//! The Vim jumplist
+//!
+//! Motions that can move the cursor far away (`G`, `gg`, `%`, `{`, `}`,
+//! `:<line>`) record the position they leave, and `Ctrl+O` / `Ctrl+I` walk
+//! back and forward through those positions. Simple motions (`h`, `j`, `k`,
+//! `l`, word motions) are not jumps. Positions are byte offsets into the text and are
+//! clamped to the text length when jumped to.
+
+/// How many positions the jumplist keeps before dropping the oldest
+pub const DEFAULT_JUMPLIST_CAPACITY: usize = 100;
+
+/// A bounded list of cursor positions left by jumps, oldest first
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JumpList {
+    entries: Vec<usize>,
+    /// Where `Ctrl+O` / `Ctrl+I` currently are; `entries.len()` when not walking
+    index: usize,
+    capacity: usize,
+}
+
+impl Default for JumpList {
+    fn default() -> Self {
+        Self::new(DEFAULT_JUMPLIST_CAPACITY)
+    }
+}
+
+impl JumpList {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: Vec::new(),
+            index: 0,
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Record the position a jump leaves, dropping the oldest one when full
+    ///
+    /// An earlier entry for the same position is removed, and walking restarts
+    /// from the newest entry.
+    pub fn push(&mut self, pos: usize) {
+        self.entries.retain(|&entry| entry != pos);
+        self.entries.push(pos);
+        if self.entries.len() > self.capacity {
+            let excess = self.entries.len() - self.capacity;
+            self.entries.drain(..excess);
+        }
+        self.index = self.entries.len();
+    }
+
+    /// The position `Ctrl+O` jumps to from `current`
+    ///
+    /// The first step back records `current` so `Ctrl+I` can return to it.
+    pub fn back(&mut self, current: usize) -> Option<usize> {
+        if self.index == self.entries.len() {
+            self.push(current);
+            self.index = self.entries.len() - 1;
+        }
+        if self.index == 0 {
+            return None;
+        }
+        self.index -= 1;
+        Some(self.entries[self.index])
+    }
+
+    /// The position `Ctrl+I` jumps to after going back with `Ctrl+O`
+    pub fn forward(&mut self) -> Option<usize> {
+        if self.index + 1 >= self.entries.len() {
+            return None;
+        }
+        self.index += 1;
+        Some(self.entries[self.index])
+    }
+
+    /// All positions, oldest first
+    pub fn entries(&self) -> &[usize] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.index = 0;
+    }
+}
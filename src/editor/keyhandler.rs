@@ -1,4 +1,43 @@
-use egui::{Context, InputState};
+use crate::editor::commands::EditorCommand;
+use egui::{Context, Event, InputState, Key};
+
+/// Default time (in seconds) a multi-key sequence waits for its next key
+pub const DEFAULT_SEQUENCE_TIMEOUT: f64 = 1.0;
+
+/// The first key of a multi-key sequence (e.g. the `d` of `dd`, or `C-x`)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PendingKey {
+    /// The key that started the sequence
+    pub key: Key,
+    /// Input time (seconds) at which the key was pressed
+    pub since: f64,
+    /// Whether Shift was held, telling `Z` from `z`
+    pub shift: bool,
+}
+
+impl PendingKey {
+    pub const fn new(key: Key, since: f64) -> Self {
+        Self {
+            key,
+            since,
+            shift: false,
+        }
+    }
+
+    /// A pending key pressed with Shift (e.g. the first `Z` of `ZZ`)
+    pub const fn shifted(key: Key, since: f64) -> Self {
+        Self {
+            key,
+            since,
+            shift: true,
+        }
+    }
+
+    /// Whether more than `timeout` seconds have passed since the key was pressed
+    pub fn is_expired(&self, now: f64, timeout: f64) -> bool {
+        now - self.since > timeout
+    }
+}
 
 /// A trait for key handlers that can intercept and process keyboard events
 pub trait KeyHandler {
@@ -8,4 +47,50 @@ pub trait KeyHandler {
 
     /// Get the name of the key handler
     fn name(&self) -> &'static str;
+
+    /// Drop any half-typed sequence (a pending prefix, operator, or command line)
+    ///
+    /// Called when the editor switches modes, so a sequence started in one mode
+    /// can't complete in another.
+    fn reset(&mut self) {}
+
+    /// Take the buffer commands queued by the last `process_input` call
+    ///
+    /// Handlers that only synthesize `TextEdit` events can rely on the default.
+    fn take_commands(&mut self) -> Vec<EditorCommand> {
+        Vec::new()
+    }
+}
+
+/// Indices of the events produced by presses of `key`: its `Key` events and the text each press typed
+///
+/// Handlers remove only these when they act on a key, so the rest of the
+/// frame's input (e.g. a shortcut the host app handles) is left alone.
+pub(crate) fn key_event_indices(events: &[Event], key: Key) -> Vec<usize> {
+    let mut indices = Vec::new();
+    for (i, event) in events.iter().enumerate() {
+        if let Event::Key {
+            key: k, pressed, ..
+        } = event
+        {
+            if *k != key {
+                continue;
+            }
+            indices.push(i);
+            if *pressed && matches!(events.get(i + 1), Some(Event::Text(_))) {
+                indices.push(i + 1);
+            }
+        }
+    }
+    indices
+}
+
+/// Indices of the keyboard events (`Key` and `Text`) in a frame's input
+pub(crate) fn keyboard_event_indices(events: &[Event]) -> Vec<usize> {
+    events
+        .iter()
+        .enumerate()
+        .filter(|(_, event)| matches!(event, Event::Key { .. } | Event::Text(_)))
+        .map(|(i, _)| i)
+        .collect()
 }
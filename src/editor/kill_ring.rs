@@ -0,0 +1,85 @@
+//! The Emacs kill ring
+//!
+//! Text removed with kill commands (`C-k`, `M-d`, `M-Backspace`) is pushed onto
+//! the ring, newest first, and `C-y` yanks the newest entry back into the
+//! buffer. Consecutive kills accumulate into a single entry, as in Emacs. The
+//! ring is separate from the system clipboard.
+
+/// How many kills the ring keeps before dropping the oldest
+pub const DEFAULT_KILL_RING_CAPACITY: usize = 60;
+
+/// A bounded list of killed text, newest first
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KillRing {
+    entries: Vec<String>,
+    capacity: usize,
+}
+
+impl Default for KillRing {
+    fn default() -> Self {
+        Self::new(DEFAULT_KILL_RING_CAPACITY)
+    }
+}
+
+impl KillRing {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: Vec::new(),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Add a new entry, dropping the oldest one when the ring is full
+    ///
+    /// Empty text is ignored.
+    pub fn push(&mut self, text: impl Into<String>) {
+        let text = text.into();
+        if text.is_empty() {
+            return;
+        }
+        self.entries.insert(0, text);
+        self.entries.truncate(self.capacity);
+    }
+
+    /// Add `text` to the end of the newest entry (a forward kill following a kill)
+    ///
+    /// Starts a new entry if the ring is empty.
+    pub fn append(&mut self, text: &str) {
+        match self.entries.first_mut() {
+            Some(latest) => latest.push_str(text),
+            None => self.push(text),
+        }
+    }
+
+    /// Add `text` to the start of the newest entry (a backward kill following a kill)
+    ///
+    /// Starts a new entry if the ring is empty.
+    pub fn prepend(&mut self, text: &str) {
+        match self.entries.first_mut() {
+            Some(latest) => latest.insert_str(0, text),
+            None => self.push(text),
+        }
+    }
+
+    /// The text `C-y` inserts: the newest entry
+    pub fn yank(&self) -> Option<&str> {
+        self.entries.first().map(String::as_str)
+    }
+
+    /// All entries, newest first
+    pub fn entries(&self) -> &[String] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
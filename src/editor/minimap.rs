@@ -0,0 +1,64 @@
+//! Layout arithmetic for the minimap, the document overview strip beside the text
+//!
+//! The strip shows each line as a bar as long as the line, scaled so the whole
+//! document fits the strip's height. When there are more lines than the strip
+//! has room for, neighbouring lines share a bar drawn as long as the longest.
+
+/// Width of the minimap strip in points
+pub const MINIMAP_WIDTH: f32 = 80.0;
+/// Tallest a line's bar gets, so short documents aren't stretched
+pub const MAX_ROW_HEIGHT: f32 = 3.0;
+/// Line length (in characters) drawn across the full strip width
+pub const FULL_WIDTH_COLUMNS: usize = 120;
+
+/// Lengths of the bars drawn for `line_lengths` in a strip of `height` points
+///
+/// Returns the bar lengths and the height of each bar. Bars are at least one
+/// point tall, so long documents get fewer bars than lines.
+pub fn rows(line_lengths: &[usize], height: f32) -> (Vec<usize>, f32) {
+    if line_lengths.is_empty() || height <= 0.0 {
+        return (Vec::new(), 0.0);
+    }
+
+    let row_height = (height / line_lengths.len() as f32).min(MAX_ROW_HEIGHT);
+    if row_height >= 1.0 {
+        return (line_lengths.to_vec(), row_height);
+    }
+
+    let row_count = (height.floor() as usize).max(1);
+    let rows = (0..row_count)
+        .map(|row| {
+            let first = row * line_lengths.len() / row_count;
+            let last = ((row + 1) * line_lengths.len() / row_count).max(first + 1);
+            line_lengths[first..last].iter().copied().max().unwrap_or(0)
+        })
+        .collect();
+    (rows, height / row_count as f32)
+}
+
+/// The line shown at `y` points below the top of the strip
+///
+/// `row_height` and `row_count` are as returned by [`rows`]; positions past
+/// the last bar give the last line.
+pub fn line_at(y: f32, row_height: f32, row_count: usize, line_count: usize) -> usize {
+    if row_count == 0 || row_height <= 0.0 {
+        return 0;
+    }
+    let row = ((y.max(0.0) / row_height) as usize).min(row_count - 1);
+    (row * line_count / row_count).min(line_count.saturating_sub(1))
+}
+
+/// Top and bottom (in points below the top of the strip) of the bars for a line range
+pub fn span_for_lines(
+    first: usize,
+    last: usize,
+    row_height: f32,
+    row_count: usize,
+    line_count: usize,
+) -> (f32, f32) {
+    if line_count == 0 {
+        return (0.0, 0.0);
+    }
+    let scale = row_height * row_count as f32 / line_count as f32;
+    (first as f32 * scale, (last + 1) as f32 * scale)
+}
@@ -1,19 +1,87 @@
 pub mod buffer;
+pub mod command_line;
 pub mod commands;
+pub mod completion;
+pub mod debug_log;
 pub mod emacs_handler;
+pub mod jumplist;
 pub mod keyhandler;
+pub mod kill_ring;
+pub mod minimap;
+pub mod plain_handler;
+pub mod shared_buffer;
+pub mod viewport;
 pub mod vim_handler;
 
-use egui::{Color32, Context, Event, Key, Response, RichText, TextEdit, Ui};
+use std::collections::HashMap;
+use std::ops::{Range, RangeInclusive};
+use std::sync::Arc;
 
-use crate::syntax::HighlightOptions;
+use egui::{
+    text::{CCursor, CCursorRange, LayoutJob},
+    text_edit::TextEditOutput,
+    Color32, Context, Event, EventFilter, FontId, Galley, Id, ImeEvent, Key, Margin, Rect,
+    Response, RichText, ScrollArea, Sense, Stroke, TextEdit, Ui, UiBuilder, Vec2, Widget,
+};
 
-use self::buffer::TextBuffer as BufferImpl;
-use self::commands::{EditorMode, VimMode};
+use crate::syntax::comments::CommentRegistry;
+use crate::syntax::registry::HighlighterRegistry;
+use crate::syntax::{markdown, HighlightOptions, HighlightTheme, SyntaxHighlighter};
+
+use self::buffer::{
+    find_matches, is_bracket, matching_bracket, BufferEdit, BufferSnapshot,
+    TextBuffer as BufferImpl,
+};
+use self::command_line::{ExCommand, LineRange, ParseError};
+use self::commands::{
+    CursorMovement, CursorShape, EditorCommand, EditorMode, ScrollPosition, VimMode, WrapIndent,
+};
+use self::completion::CompletionItem;
+use self::debug_log::{DebugLog, DebugLogEntry};
 use self::emacs_handler::EmacsKeyHandler;
+use self::jumplist::JumpList;
 use self::keyhandler::KeyHandler;
+use self::kill_ring::KillRing;
+use self::plain_handler::PlainKeyHandler;
+use self::shared_buffer::SharedBuffer;
 use self::vim_handler::VimKeyHandler;
 
+/// Callback invoked with the new text after the buffer changes
+pub type ChangeCallback = Box<dyn FnMut(&str)>;
+
+/// Callback invoked with the text to save (Vim `:w`)
+pub type SaveCallback = Box<dyn FnMut(&str)>;
+
+/// Callback invoked when the user asks to close the editor (Vim `:q`, Emacs
+/// `C-x C-c`), with whether the text has unsaved changes
+pub type QuitCallback = Box<dyn FnMut(bool)>;
+
+/// Host function returning hover text for the byte offset under the pointer
+pub type HoverProvider = Box<dyn Fn(usize) -> Option<String>>;
+
+/// Callback invoked with each command the editor executes
+pub type CommandObserver = Box<dyn FnMut(&EditorCommand)>;
+
+/// Host action run for an `EditorCommand::Custom` with a registered name
+pub type CustomCommand = Box<dyn FnMut(&mut EditorWidget)>;
+
+/// Highlighted text from the last layout and the inputs it was made from
+struct HighlightCache {
+    /// Buffer revision of the highlighted text
+    revision: u64,
+    theme_version: u64,
+    font_size: f32,
+    job: LayoutJob,
+}
+
+/// Text held by the `Copy` and `Cut` commands for `Paste`
+#[derive(Default)]
+struct Register {
+    text: String,
+    /// Whether the text is whole lines, pasted below (or above) the cursor's line
+    linewise: bool,
+}
+
 /// The main editor widget that implements a simple code editor
 pub struct EditorWidget {
     /// The unique ID for the editor instance
@@ -22,6 +90,8 @@ pub struct EditorWidget {
     buffer: BufferImpl,
     /// The current mode of the editor (Vim or Emacs)
     current_mode: EditorMode,
+    /// Mode `clear` switches to, if set with `with_initial_mode_for_new_buffers`
+    new_buffer_mode: Option<EditorMode>,
     /// The font size for the editor
     font_size: f32,
     /// Whether to show the status bar at the bottom
@@ -32,20 +102,368 @@ pub struct EditorWidget {
     vim_handler: VimKeyHandler,
     /// Emacs key handler for emacs mode
     emacs_handler: EmacsKeyHandler,
+    /// Pass-through key handler for plain mode
+    plain_handler: PlainKeyHandler,
+    /// Cursor shape to draw for each mode (modes not listed use a bar)
+    cursor_shapes: HashMap<EditorMode, CursorShape>,
+    /// Whether to draw the per-mode cursor shape instead of the default caret
+    custom_cursor: bool,
+    /// Caret color, or the theme's text cursor color
+    caret_color: Option<Color32>,
+    /// Seconds the caret is shown and hidden per blink, or the theme's blink
+    caret_blink: Option<(f32, f32)>,
+    /// Host-provided background colors keyed by line index (0-based)
+    line_backgrounds: HashMap<usize, Color32>,
+    /// Syntax highlighter used by the layouter (falls back to `basic_highlight`)
+    highlighter: Option<Box<dyn SyntaxHighlighter>>,
+    /// Whether the text goes through the editor's layouter at all
+    highlight_enabled: bool,
+    /// Theme set with `set_theme`, used when there is no highlighter
+    theme: HighlightTheme,
+    /// Bumped whenever the highlighter or its theme changes
+    theme_version: u64,
+    /// Highlighting from the last layout, reused while its inputs are unchanged
+    highlight_cache: Option<HighlightCache>,
+    /// Registry used to pick a highlighter by language or file extension
+    highlighter_registry: HighlighterRegistry,
+    /// Name of the language selected through the registry, if any
+    language: Option<String>,
+    /// Comment syntax per language, used when toggling comments
+    comment_registry: CommentRegistry,
+    /// Column at which to draw a vertical ruler, if any
+    ruler: Option<usize>,
+    /// Color of the column ruler
+    ruler_color: Color32,
+    /// Whether to draw markers for spaces, tabs, and line ends
+    show_whitespace: bool,
+    /// Color of the whitespace markers
+    whitespace_color: Color32,
+    /// Number of columns a tab character spans
+    tab_width: usize,
+    /// Whether left/right motions follow the text's logical order rather than
+    /// the on-screen direction of right-to-left runs
+    logical_motion: bool,
+    /// Whether long lines wrap at the editor width instead of running past it
+    word_wrap: bool,
+    /// Indentation of the rows a wrapped line continues on
+    wrap_indent: WrapIndent,
+    /// Whether typing a closing bracket in indentation removes one indentation level
+    auto_dedent: bool,
+    /// Whether Tab in a line's indentation indents the line instead of inserting at the cursor
+    smart_tab: bool,
+    /// Whether the bracket at the cursor and its partner get a background
+    bracket_highlight: bool,
+    /// Whether typing an opening bracket or quote also inserts its closer
+    auto_close_pairs: bool,
+    /// Whether typed characters replace the one under the cursor (toggled with Insert)
+    overtype: bool,
+    /// Whether to draw vertical guides at each indentation level
+    indent_guides: bool,
+    /// Color of the indentation guides
+    indent_guide_color: Color32,
+    /// Called with the new text whenever the buffer changes
+    change_callback: Option<ChangeCallback>,
+    /// Called with every command before it is applied to the buffer
+    command_observer: Option<CommandObserver>,
+    /// Recent keystrokes, commands, and mode changes, when logging is on
+    debug_log: Option<DebugLog>,
+    /// Host actions for `EditorCommand::Custom`, keyed by command name
+    custom_commands: HashMap<String, CustomCommand>,
+    /// Called with the text when the user saves from the command line
+    save_callback: Option<SaveCallback>,
+    /// Called when the user quits from the command line
+    quit_callback: Option<QuitCallback>,
+    /// Result or error message of the last command-line command
+    command_message: Option<String>,
+    /// Text removed by kill commands, for yanking back
+    kill_ring: KillRing,
+    /// Whether the next kill adds to the newest kill ring entry
+    append_next_kill: bool,
+    /// Text overwritten in Vim Replace mode, newest last, for Backspace to put back
+    replaced_text: Vec<String>,
+    /// Cursor positions left by jump motions, for `Ctrl+O` / `Ctrl+I`
+    jumplist: JumpList,
+    /// Text copied or cut by commands, or last pasted from the system clipboard
+    register: Register,
+    /// Text to put on the system clipboard next frame
+    pending_clipboard: Option<String>,
+    /// Text shared with other editors, if this one is attached to any
+    shared_buffer: Option<SharedBuffer>,
+    /// Version of the shared text this editor last showed or published
+    shared_version: u64,
+    /// Whether the buffer changed since it was loaded or last marked clean
+    dirty: bool,
+    /// Buffer revision last reported through `notify_changed`
+    notified_revision: u64,
+    /// Strip trailing whitespace from every line before saving
+    trim_trailing_whitespace_on_save: bool,
+    /// Make sure the text ends with a newline before saving
+    final_newline_on_save: bool,
+    /// Whether the buffer cursor must be pushed into the `TextEdit` next frame
+    cursor_sync_pending: bool,
+    /// Selection (secondary, primary char indices) to restore with the next cursor sync
+    pending_selection: Option<(usize, usize)>,
+    /// Where the Vim visual selection started (byte offset), kept here rather
+    /// than in the handler so buffer-driven motions extend the selection
+    visual_anchor: Option<usize>,
+    /// Whether to scroll a programmatic selection into view next frame
+    reveal_selection: bool,
+    /// Where to scroll the cursor's line once the text has been laid out
+    pending_scroll: Option<ScrollPosition>,
+    /// Lines to scroll the view by (negative is up) after a page motion
+    pending_page_scroll: Option<isize>,
+    /// Whether the text is shown inside a `ScrollArea` managed by the editor
+    scroll_area: bool,
+    /// Whether long lines scroll sideways in a horizontal `ScrollArea` (without word wrap)
+    horizontal_scroll: bool,
+    /// Whether a document overview strip is drawn beside the managed scroll area
+    minimap: bool,
+    /// Whether the managed scroll area lays out only the lines in view
+    visible_rows_only: bool,
+    /// Lines of context kept above and below the cursor when it moves (Vim `scrolloff`)
+    scrolloff: usize,
+    /// Where the cursor was when the scroll margin was last checked
+    scrolloff_cursor: Option<usize>,
+    /// How far the text was scrolled past the top of the viewport last frame
+    scroll_offset: f32,
+    /// Scroll offset requested through `set_scroll_offset`, applied next frame
+    pending_scroll_offset: Option<f32>,
+    /// Inclusive range of lines visible during the last frame
+    visible_lines: (usize, usize),
+    /// The mode at the end of the last frame (`None` before the first)
+    shown_mode: Option<EditorMode>,
+    /// Whether the mode changed during the last frame
+    mode_changed: bool,
+    /// Pattern whose matches are highlighted, and whether case matters
+    search_highlight: Option<(String, bool)>,
+    /// Byte range of uncommitted IME composition text in the buffer
+    ime_preedit: Option<Range<usize>>,
+    /// Completions offered by the host, shown in a popup at the cursor
+    completions: Vec<CompletionItem>,
+    /// Index of the highlighted completion
+    completion_selected: usize,
+    /// Supplies the tooltip shown while the pointer rests over the text
+    hover_provider: Option<HoverProvider>,
+}
+
+/// Default cursor shapes: block in Vim normal/visual, bar everywhere else
+fn default_cursor_shapes() -> HashMap<EditorMode, CursorShape> {
+    HashMap::from([
+        (EditorMode::Vim(VimMode::Normal), CursorShape::Block),
+        (EditorMode::Vim(VimMode::Visual), CursorShape::Block),
+    ])
+}
+
+/// Columns spanned by a line's leading whitespace, with tab stops every `tab_width` columns
+///
+/// This matches how `apply_whitespace` renders tabs, so guides line up with the text.
+fn indent_columns(line: &str, tab_width: usize) -> usize {
+    let tab_width = tab_width.max(1);
+    line.chars()
+        .take_while(|c| matches!(c, ' ' | '\t'))
+        .fold(0, |column, c| {
+            if c == '\t' {
+                column + tab_width - column % tab_width
+            } else {
+                column + 1
+            }
+        })
+}
+
+/// Points to indent each line's wrapped continuation rows by, one entry per line
+///
+/// Indents are capped at half the wrap width so some text always fits.
+fn wrap_indents(
+    text: &str,
+    indent: WrapIndent,
+    tab_width: usize,
+    space_width: f32,
+    wrap_width: f32,
+) -> Vec<f32> {
+    let max = (wrap_width / 2.0).max(0.0);
+    text.split('\n')
+        .map(|line| {
+            let columns = match indent {
+                WrapIndent::None => 0,
+                WrapIndent::MatchLine => indent_columns(line, tab_width),
+                WrapIndent::Columns(columns) => columns,
+            };
+            (columns as f32 * space_width).min(max)
+        })
+        .collect()
+}
+
+/// Shift the rows a line wraps onto right by that line's entry in `indents`
+fn indent_wrapped_rows(galley: &mut Galley, indents: &[f32]) {
+    let mut line = 0;
+    let mut continuation = false;
+    for row in &mut galley.rows {
+        let dx = indents.get(line).copied().unwrap_or_default();
+        if continuation && dx > 0.0 {
+            let delta = Vec2::new(dx, 0.0);
+            for glyph in &mut row.glyphs {
+                glyph.pos.x += dx;
+            }
+            row.rect = row.rect.translate(delta);
+            row.visuals.mesh.translate(delta);
+            row.visuals.mesh_bounds = row.visuals.mesh_bounds.translate(delta);
+            galley.rect = galley.rect.union(row.rect);
+            galley.mesh_bounds = galley.mesh_bounds.union(row.visuals.mesh_bounds);
+        }
+
+        continuation = !row.ends_with_newline;
+        if row.ends_with_newline {
+            line += 1;
+        }
+    }
+}
+
+/// Char ranges of the bracket at or just before `cursor` (a char index) and its partner
+fn bracket_pair_backgrounds(
+    text: &str,
+    cursor: usize,
+    color: Color32,
+) -> Vec<(Range<usize>, Color32)> {
+    let mut chars = text.char_indices().skip(cursor.saturating_sub(1));
+    let (before, at) = if cursor == 0 {
+        (None, chars.next())
+    } else {
+        (chars.next(), chars.next())
+    };
+    let Some(offset) = at
+        .filter(|&(_, c)| is_bracket(c))
+        .or(before.filter(|&(_, c)| is_bracket(c)))
+        .map(|(offset, _)| offset)
+    else {
+        return Vec::new();
+    };
+    let Some(partner) = matching_bracket(text, offset) else {
+        return Vec::new();
+    };
+
+    [offset, partner]
+        .into_iter()
+        .map(|offset| {
+            let index = text[..offset].chars().count();
+            (index..index + 1, color)
+        })
+        .collect()
+}
+
+/// Char ranges of the matches of `pattern` in `text` and their backgrounds
+///
+/// The first match touching `cursor` (a char index) gets the `current` color.
+fn search_match_backgrounds(
+    text: &str,
+    pattern: &str,
+    case_sensitive: bool,
+    cursor: usize,
+    (color, current): (Color32, Color32),
+) -> Vec<(Range<usize>, Color32)> {
+    let mut chars = 0;
+    let mut counted = 0;
+    let mut current_found = false;
+    find_matches(text, pattern, case_sensitive)
+        .into_iter()
+        .map(|range| {
+            chars += text[counted..range.start].chars().count();
+            let start = chars;
+            chars += text[range.clone()].chars().count();
+            counted = range.end;
+
+            let is_current = !current_found && (start..=chars).contains(&cursor);
+            current_found |= is_current;
+            (start..chars, if is_current { current } else { color })
+        })
+        .collect()
+}
+
+/// Whether typed text is a single closing bracket that ends a block
+fn is_block_closer(text: &str) -> bool {
+    matches!(text, "}" | ")" | "]")
+}
+
+/// Pairs closed automatically with `with_auto_close_pairs`
+const AUTO_CLOSE_PAIRS: [(char, char); 4] = [('(', ')'), ('[', ']'), ('{', '}'), ('"', '"')];
+
+/// The commands for a keystroke that opens, closes, or deletes an auto-closed pair
+///
+/// `before` and `after` are the characters around the cursor. Returns `None`
+/// when the event should be handled as usual.
+fn pair_command(
+    event: &Event,
+    (before, after): (Option<char>, Option<char>),
+) -> Option<Vec<EditorCommand>> {
+    match event {
+        // Backspace inside an empty pair removes both halves
+        Event::Key {
+            key: Key::Backspace,
+            pressed: true,
+            modifiers,
+            ..
+        } if modifiers.is_none() => {
+            let (before, after) = (before?, after?);
+            AUTO_CLOSE_PAIRS
+                .contains(&(before, after))
+                .then(|| vec![EditorCommand::DeleteChar, EditorCommand::DeleteCharForward])
+        }
+        Event::Text(text) => {
+            let mut chars = text.chars();
+            let (c, None) = (chars.next()?, chars.next()) else {
+                return None;
+            };
+            // Typing a closer in front of the same closer steps over it
+            if after == Some(c) && AUTO_CLOSE_PAIRS.iter().any(|&(_, close)| close == c) {
+                return Some(vec![EditorCommand::MoveCursor(CursorMovement::Right)]);
+            }
+            let &(open, close) = AUTO_CLOSE_PAIRS.iter().find(|&&(open, _)| open == c)?;
+            // A quote next to a word is more likely an apostrophe or a closing quote
+            let is_word = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric() || c == '_');
+            if open == close && (is_word(before) || is_word(after)) {
+                return None;
+            }
+            Some(vec![EditorCommand::InsertPair(open, close)])
+        }
+        _ => None,
+    }
+}
+
+/// The word-delete command for a Ctrl+Backspace or Ctrl+Delete press (Alt on macOS),
+/// or for the readline keys Ctrl+W (previous word) and Ctrl+U (to the line start)
+fn word_delete_command(event: &Event) -> Option<EditorCommand> {
+    let Event::Key {
+        key,
+        pressed: true,
+        modifiers,
+        ..
+    } = event
+    else {
+        return None;
+    };
+    if !(modifiers.ctrl || modifiers.alt) || modifiers.mac_cmd || modifiers.shift {
+        return None;
+    }
+
+    match key {
+        Key::Backspace => Some(EditorCommand::DeleteWord),
+        Key::Delete => Some(EditorCommand::DeleteWordForward),
+        Key::W if modifiers.ctrl && !modifiers.alt => Some(EditorCommand::DeleteWord),
+        Key::U if modifiers.ctrl && !modifiers.alt => Some(EditorCommand::DeleteToLineStart),
+        _ => None,
+    }
 }
 
 impl Default for EditorWidget {
     fn default() -> Self {
-        Self {
-            id: String::new(),
-            buffer: BufferImpl::default(),
-            current_mode: EditorMode::Emacs, // Default to Emacs mode
-            font_size: 14.0,
-            show_status: true,
-            last_cursor_pos: 0,
-            vim_handler: VimKeyHandler::new().with_debug(true),
-            emacs_handler: EmacsKeyHandler::new().with_debug(true),
-        }
+        Self::new(String::new())
+    }
+}
+
+/// Lets the editor be added like any other widget: `ui.add(&mut editor)`,
+/// or `ui.add_sized(size, &mut editor)` to give it a fixed size
+impl Widget for &mut EditorWidget {
+    fn ui(self, ui: &mut Ui) -> Response {
+        self.show(ui)
     }
 }
 
@@ -55,17 +473,99 @@ impl EditorWidget {
             id: id.into(),
             buffer: BufferImpl::default(),
             current_mode: EditorMode::Emacs, // Default to Emacs mode
+            shown_mode: None,
+            mode_changed: false,
+            ime_preedit: None,
+            search_highlight: None,
+            new_buffer_mode: None,
             font_size: 14.0,
             show_status: true,
             last_cursor_pos: 0,
-            vim_handler: VimKeyHandler::new().with_debug(true),
-            emacs_handler: EmacsKeyHandler::new().with_debug(true),
+            vim_handler: VimKeyHandler::new(),
+            emacs_handler: EmacsKeyHandler::new(),
+            plain_handler: PlainKeyHandler::new(),
+            cursor_shapes: default_cursor_shapes(),
+            custom_cursor: true,
+            caret_color: None,
+            caret_blink: None,
+            line_backgrounds: HashMap::new(),
+            highlighter: None,
+            highlight_enabled: true,
+            theme: HighlightTheme::default(),
+            theme_version: 0,
+            highlight_cache: None,
+            highlighter_registry: HighlighterRegistry::default(),
+            language: None,
+            comment_registry: CommentRegistry::default(),
+            ruler: None,
+            ruler_color: Color32::from_gray(70),
+            show_whitespace: false,
+            whitespace_color: Color32::from_gray(90),
+            tab_width: 4,
+            logical_motion: true,
+            word_wrap: false,
+            wrap_indent: WrapIndent::None,
+            auto_dedent: false,
+            smart_tab: false,
+            bracket_highlight: false,
+            auto_close_pairs: false,
+            overtype: false,
+            indent_guides: false,
+            indent_guide_color: Color32::from_gray(60),
+            change_callback: None,
+            command_observer: None,
+            debug_log: None,
+            custom_commands: HashMap::new(),
+            save_callback: None,
+            quit_callback: None,
+            command_message: None,
+            kill_ring: KillRing::default(),
+            append_next_kill: false,
+            replaced_text: Vec::new(),
+            jumplist: JumpList::default(),
+            register: Register::default(),
+            pending_clipboard: None,
+            shared_buffer: None,
+            shared_version: 0,
+            dirty: false,
+            notified_revision: 0,
+            trim_trailing_whitespace_on_save: false,
+            final_newline_on_save: false,
+            cursor_sync_pending: false,
+            pending_selection: None,
+            visual_anchor: None,
+            reveal_selection: false,
+            pending_scroll: None,
+            pending_page_scroll: None,
+            scroll_area: false,
+            horizontal_scroll: false,
+            minimap: false,
+            visible_rows_only: false,
+            scrolloff: 0,
+            scrolloff_cursor: None,
+            scroll_offset: 0.0,
+            pending_scroll_offset: None,
+            visible_lines: (0, 0),
+            completions: Vec::new(),
+            completion_selected: 0,
+            hover_provider: None,
         }
     }
 
     #[must_use]
-    pub const fn with_mode(mut self, mode: EditorMode) -> Self {
-        self.current_mode = mode;
+    pub fn with_mode(mut self, mode: EditorMode) -> Self {
+        self.set_mode(mode);
+        self
+    }
+
+    /// Start in `mode` and return to it whenever the editor is cleared for a new document
+    ///
+    /// For example `EditorMode::Vim(VimMode::Insert)` lets a "new note" start
+    /// typing right away. Without this, `clear` switches to `EditorMode::default()`.
+    #[must_use]
+    pub fn with_initial_mode_for_new_buffers(mut self, mode: EditorMode) -> Self {
+        self.new_buffer_mode = Some(mode);
+        self.set_mode(mode);
         self
     }
 
@@ -81,278 +581,5094 @@ impl EditorWidget {
         self
     }
 
-    pub fn text(&self) -> &str {
-        self.buffer.text()
+    /// Use a custom syntax highlighter (e.g. markdown, diff, or a language highlighter)
+    #[must_use]
+    pub fn with_syntax_highlighter(
+        mut self,
+        highlighter: impl SyntaxHighlighter + 'static,
+    ) -> Self {
+        self.highlighter = Some(Box::new(highlighter));
+        self.language = None;
+        self.theme_version += 1;
+        self
     }
 
-    pub const fn text_mut(&mut self) -> &mut String {
-        self.buffer.text_mut()
+    /// Turn off the editor's layouter so the `TextEdit` draws plain text itself
+    ///
+    /// A fast path for huge plain-text files. Everything the layouter adds goes
+    /// with it: syntax colors, line backgrounds, search and bracket highlights,
+    /// and the word wrap settings (the `TextEdit` always wraps). On by default.
+    #[must_use]
+    pub const fn with_highlight_enabled(mut self, enabled: bool) -> Self {
+        self.highlight_enabled = enabled;
+        self
     }
 
-    pub fn set_text(&mut self, text: impl Into<String>) {
-        self.buffer.set_text(text.into());
+    /// Pick the highlighter registered for a file extension (e.g. `"rs"`)
+    ///
+    /// Unknown extensions fall back to `basic_highlight`. Returns whether a
+    /// registered highlighter was found.
+    pub fn set_language_by_extension(&mut self, extension: &str) -> bool {
+        let language = self
+            .highlighter_registry
+            .language_for_extension(extension)
+            .map(str::to_owned);
+        self.highlighter = language
+            .as_deref()
+            .and_then(|language| self.highlighter_registry.create(language));
+        self.language = language;
+        self.theme_version += 1;
+        self.highlighter.is_some()
     }
 
-    pub const fn mode(&self) -> &EditorMode {
-        &self.current_mode
+    /// The language selected through `set_language_by_extension`, if any
+    pub fn language(&self) -> Option<&str> {
+        self.language.as_deref()
     }
 
-    pub const fn set_mode(&mut self, mode: EditorMode) {
-        self.current_mode = mode;
-
-        // Update the vim handler mode if needed
-        if let EditorMode::Vim(vim_mode) = mode {
-            self.vim_handler.set_mode(vim_mode);
-        }
+    /// Mutable access to the highlighter registry, for registering custom languages
+    pub const fn highlighter_registry_mut(&mut self) -> &mut HighlighterRegistry {
+        &mut self.highlighter_registry
     }
 
-    /// The key method for the editor widget - this function:
-    /// 1. Intercepts keyboard events that would normally go to the `TextEdit`
-    /// 2. Processes vim commands directly on the buffer
-    /// 3. Prevents unwanted characters from being inserted in normal mode
-    #[allow(clippy::too_many_lines)]
-    pub fn show(&mut self, ui: &mut Ui) -> Response {
-        // 1. Process key events BEFORE we create the TextEdit widget
-        self.process_input_before_ui(ui.ctx());
+    /// Mutable access to the comment syntax registry, for adding or changing
+    /// how a language comments out lines
+    pub const fn comment_registry_mut(&mut self) -> &mut CommentRegistry {
+        &mut self.comment_registry
+    }
 
-        // 2. Show mode indicator at the top of the editor
-        match self.current_mode {
-            EditorMode::Vim(VimMode::Normal) => {
-                ui.label(
-                    RichText::new("-- VIM: NORMAL MODE --")
-                        .strong()
-                        .monospace()
-                        .color(Color32::GREEN),
-                );
-            }
-            EditorMode::Vim(VimMode::Insert) => {
-                ui.label(
-                    RichText::new("-- VIM: INSERT MODE --")
-                        .strong()
-                        .monospace()
-                        .color(Color32::YELLOW),
-                );
-            }
-            EditorMode::Vim(VimMode::Visual) => {
-                ui.label(
-                    RichText::new("-- VIM: VISUAL MODE --")
-                        .strong()
-                        .monospace()
-                        .color(Color32::GOLD),
-                );
-            }
-            EditorMode::Emacs => {
-                ui.label(
-                    RichText::new("-- EMACS MODE --")
-                        .strong()
-                        .monospace()
-                        .color(Color32::LIGHT_BLUE),
-                );
-            }
-        }
+    /// Draw a vertical guide at the given column (e.g. `Some(80)`), or `None` for no ruler
+    #[must_use]
+    pub const fn with_ruler(mut self, column: Option<usize>) -> Self {
+        self.ruler = column;
+        self
+    }
 
-        // 3. Create a layouter for basic syntax highlighting
-        let font_size = self.font_size;
-        let mut layouter = move |ui: &Ui, text: &str, _wrap_width: f32| {
-            let mut options = HighlightOptions {
-                font_size: 14.0,
-                text_color: Color32::from_rgb(220, 223, 228),
-                keyword_color: Color32::from_rgb(198, 120, 221),
-                comment_color: Color32::from_rgb(92, 99, 112),
-                heading_color: Color32::from_rgb(229, 192, 123),
-            };
-            options.font_size = font_size;
+    /// Set the color of the column ruler
+    #[must_use]
+    pub const fn with_ruler_color(mut self, color: Color32) -> Self {
+        self.ruler_color = color;
+        self
+    }
 
-            let layout_job = crate::syntax::basic_highlight(text, &options);
-            ui.fonts(|fonts| fonts.layout_job(layout_job))
-        };
+    /// Show spaces, tabs, and line ends as faint markers (the buffer text is unchanged)
+    #[must_use]
+    pub const fn with_show_whitespace(mut self, show: bool) -> Self {
+        self.show_whitespace = show;
+        self
+    }
 
-        // 4. Create a TextEdit widget for all modes - unified approach
-        // Create the TextEdit widget
-        let id = format!("{}_edit", self.id);
-        let mut text_edit = TextEdit::multiline(self.buffer.text_mut())
-            .id_source(id)
-            .font(egui::TextStyle::Monospace)
-            .desired_width(f32::INFINITY)
-            .layouter(&mut layouter);
+    /// Set the color of the whitespace markers
+    #[must_use]
+    pub const fn with_whitespace_color(mut self, color: Color32) -> Self {
+        self.whitespace_color = color;
+        self
+    }
 
-        // Add styling based on mode
-        text_edit = match self.current_mode {
-            EditorMode::Vim(VimMode::Normal) => {
-                text_edit.hint_text("Normal mode: press 'i' to edit, 'v' for visual mode")
-            }
-            EditorMode::Vim(VimMode::Insert) => {
-                text_edit.hint_text("Insert mode: press Escape to exit")
-            }
-            EditorMode::Vim(VimMode::Visual) => {
-                text_edit.hint_text("Visual mode: use movement keys to select, 'y' to copy, 'x/d' to cut, 'c' to change, 'p' to replace")
-            }
-            EditorMode::Emacs => text_edit.hint_text("Emacs mode"),
-        };
+    /// Set how many columns a tab character spans
+    #[must_use]
+    pub const fn with_tab_width(mut self, width: usize) -> Self {
+        self.tab_width = width;
+        self
+    }
 
-        // 5. Add the text edit to the UI and get the output
-        // We now use .show instead of ui.add to get access to cursor_range
-        let output = text_edit.show(ui);
-        let response = output.response;
+    /// Set which characters besides letters and digits belong to words
+    ///
+    /// Word motions (`w`/`b`, `M-f`/`M-b`), word deletes and kills, and
+    /// completion all use this. The default is `"_"`; `"_-"` suits CSS, and
+    /// `""` makes `_` a word boundary.
+    #[must_use]
+    pub fn with_word_chars(mut self, chars: &str) -> Self {
+        self.buffer.set_word_chars(chars);
+        self
+    }
 
-        // 6. Sync our buffer's cursor position with TextEdit's cursor position
-        if let Some(cursor_range) = output.cursor_range {
-            // Get the character range from the cursor
-            let range = cursor_range.as_sorted_char_range();
-            // Use the end position of the selection as our cursor
-            let cursor_pos = range.end;
-            // Update our buffer's cursor position to match TextEdit's
-            self.buffer.set_cursor_position(cursor_pos);
-            println!("DEBUG: Updated buffer cursor position to {}", cursor_pos);
-        }
+    /// Move the cursor for Vim normal-mode motions (`0`, `$`, `gg`, `G`)
+    /// through the buffer instead of synthesized arrow, Home, and End keys
+    ///
+    /// `h`, `l`, `j`, and `k` always go through the buffer, so they step over
+    /// whole grapheme clusters (emoji, combining accents) and keep their column
+    /// when they can't move past the first or last line.
+    ///
+    /// The motions then behave the same on every platform and don't depend on
+    /// how the `TextEdit` interprets key combinations. Off by default for now.
+    #[must_use]
+    pub fn with_vim_direct_motions(mut self, direct: bool) -> Self {
+        self.vim_handler.set_direct_motions(direct);
+        self
+    }
 
-        // 7. In vim normal or visual mode, ensure that the editor retains focus
-        if matches!(
-            self.current_mode,
-            EditorMode::Vim(VimMode::Normal | VimMode::Visual)
-        ) && !response.has_focus()
-        {
-            response.request_focus();
-        }
+    /// Leave Vim insert mode by typing `sequence` quickly, e.g. `"jk"` or `"jj"`
+    ///
+    /// See `VimKeyHandler::with_insert_escape_sequence`; off by default.
+    #[must_use]
+    pub fn with_vim_insert_escape_sequence(mut self, sequence: &str) -> Self {
+        self.vim_handler.set_insert_escape_sequence(sequence);
+        self
+    }
 
-        // 7. Show status bar if enabled
-        if self.show_status {
-            ui.horizontal(|ui| {
-                // Show current mode
-                let (mode_text, mode_color) = match self.current_mode {
-                    EditorMode::Vim(VimMode::Normal) => ("VIM: NORMAL", Color32::GREEN),
-                    EditorMode::Vim(VimMode::Insert) => ("VIM: INSERT", Color32::YELLOW),
-                    EditorMode::Vim(VimMode::Visual) => ("VIM: VISUAL", Color32::GOLD),
-                    EditorMode::Emacs => ("EMACS", Color32::LIGHT_BLUE),
-                };
+    /// Choose whether left/right motions (`h`/`l`, `C-f`/`C-b`) move in logical
+    /// order (the default) or visually
+    ///
+    /// In logical order "right" always moves towards the end of the text. With
+    /// visual motion, "right" inside a right-to-left run (Hebrew, Arabic) moves
+    /// towards its start, the way the run reads when the text is drawn with bidi
+    /// reordering. egui lays text out in logical order, so keep the default
+    /// unless the text is shown reordered elsewhere.
+    #[must_use]
+    pub const fn with_logical_motion(mut self, logical: bool) -> Self {
+        self.logical_motion = logical;
+        self
+    }
 
-                ui.label(
-                    RichText::new(mode_text)
-                        .monospace()
-                        .strong()
-                        .color(mode_color),
-                );
+    /// Wrap long lines at the editor width (the buffer text is unchanged)
+    #[must_use]
+    pub const fn with_word_wrap(mut self, wrap: bool) -> Self {
+        self.word_wrap = wrap;
+        self
+    }
 
-                // Show cursor position
-                let cursor_pos = self.buffer.cursor_position();
-                let line = self.buffer.current_line();
-                let column = self.buffer.current_column();
-                ui.label(
-                    RichText::new(format!(
-                        "Pos: {} (L:{}, C:{})",
-                        cursor_pos,
-                        line + 1,
-                        column + 1
-                    ))
-                    .monospace(),
-                );
+    /// Indent the continuation rows of wrapped lines (needs `with_word_wrap`)
+    #[must_use]
+    pub const fn with_line_wrapping_indent(mut self, indent: WrapIndent) -> Self {
+        self.wrap_indent = indent;
+        self
+    }
 
-                // Add a spacer to push the right-side content
-                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                    ui.label(
-                        RichText::new(format!("Chars: {}", self.buffer.text().len())).monospace(),
-                    );
-                });
-            });
-        }
+    /// Outdent the line by one level when `}`, `)`, or `]` is typed with only
+    /// indentation before the cursor
+    #[must_use]
+    pub const fn with_auto_dedent(mut self, enabled: bool) -> Self {
+        self.auto_dedent = enabled;
+        self
+    }
 
-        response
+    /// Make Tab indent the line when the cursor is at or before its first
+    /// non-blank character, moving the cursor past the new indentation
+    ///
+    /// Elsewhere in the line Tab still inserts a tab at the cursor.
+    #[must_use]
+    pub const fn with_smart_tab(mut self, enabled: bool) -> Self {
+        self.smart_tab = enabled;
+        self
     }
 
-    /// Intercept and process keyboard input before the UI is created
-    fn process_input_before_ui(&mut self, ctx: &Context) {
-        // We need to manipulate the input events to handle our custom key bindings
-        ctx.input_mut(|input| {
-            // Enhanced debug print of all input events
-            if !input.events.is_empty() {
-                println!("Input events BEFORE processing: {:?}", input.events);
-            }
+    /// Close brackets and double quotes as they are typed
+    ///
+    /// Typing `(`, `[`, `{`, or `"` inserts the closer too, with the cursor in
+    /// between; typing the closer right before itself steps over it, and
+    /// Backspace between an empty pair deletes both characters. Quotes are
+    /// only paired away from words.
+    #[must_use]
+    pub const fn with_auto_close_pairs(mut self, enabled: bool) -> Self {
+        self.auto_close_pairs = enabled;
+        self
+    }
 
-            // Debug print of input keys with more detail
-            if !input.keys_down.is_empty() {
-                println!(
-                    "Keys down BEFORE processing: {:?}, modifiers: {:?}",
-                    input.keys_down, input.modifiers
-                );
+    /// Highlight the bracket at (or just before) the cursor together with its partner
+    ///
+    /// The color is the highlighter theme's `matching_bracket`.
+    #[must_use]
+    pub const fn with_bracket_highlight(mut self, enabled: bool) -> Self {
+        self.bracket_highlight = enabled;
+        self
+    }
 
-                // This area was used for debugging problematic keys
-                // Now we have a robust solution that handles both Key and Text events
+    /// Draw faint vertical guides at each indentation level (every tab width)
+    #[must_use]
+    pub const fn with_indent_guides(mut self, show: bool) -> Self {
+        self.indent_guides = show;
+        self
+    }
 
-                // Check specifically for Ctrl+Arrow key combinations
-                if input.modifiers.ctrl {
-                    for key in &input.keys_down {
-                        if matches!(key, Key::ArrowLeft | Key::ArrowRight) {
-                            println!("DEBUG: Detected Ctrl+Arrow key: {:?}", key);
+    /// Set the color of the indentation guides
+    #[must_use]
+    pub const fn with_indent_guide_color(mut self, color: Color32) -> Self {
+        self.indent_guide_color = color;
+        self
+    }
 
-                            if input.key_pressed(*key) {
-                                println!("DEBUG: Ctrl+Arrow key was just pressed");
+    /// Set the cursor shape drawn while the editor is in `mode`
+    #[must_use]
+    pub fn with_cursor_shape(mut self, mode: EditorMode, shape: CursorShape) -> Self {
+        self.cursor_shapes.insert(mode, shape);
+        self
+    }
 
-                                // Let TextEdit handle Ctrl+Arrow keys for word movement
-                                println!("DEBUG: Letting TextEdit handle Ctrl+Arrow keys natively");
-                            }
-                        }
-                    }
-                }
-            }
+    /// Enable or disable per-mode cursor shapes (disabled falls back to the default caret)
+    #[must_use]
+    pub const fn with_custom_cursor(mut self, enabled: bool) -> Self {
+        self.custom_cursor = enabled;
+        self
+    }
+
+    /// Draw the caret in `color` instead of the theme's text cursor color
+    ///
+    /// Useful for a high-contrast caret; applies to every cursor shape.
+    #[must_use]
+    pub const fn with_caret_color(mut self, color: Color32) -> Self {
+        self.caret_color = Some(color);
+        self
+    }
+
+    /// Blink the bar caret, showing it for `on` and hiding it for `off` seconds
+    ///
+    /// Pass `0.0` for `off` to keep the caret steadily visible.
+    #[must_use]
+    pub const fn with_caret_blink(mut self, on: f32, off: f32) -> Self {
+        self.caret_blink = Some((on, off));
+        self
+    }
+
+    /// Show the text in a vertical `ScrollArea` managed by the editor
+    ///
+    /// The area fills the available height, leaving room for the status bar.
+    /// Leave this off when the host already places the editor in its own
+    /// `ScrollArea`.
+    #[must_use]
+    pub const fn with_scroll_area(mut self, enabled: bool) -> Self {
+        self.scroll_area = enabled;
+        self
+    }
+
+    /// Scroll long lines sideways with a horizontal scrollbar, keeping the cursor in view
+    ///
+    /// Has no effect while word wrap is on, since lines then fit the width.
+    #[must_use]
+    pub const fn with_horizontal_scroll(mut self, enabled: bool) -> Self {
+        self.horizontal_scroll = enabled;
+        self
+    }
+
+    /// Draw a minimap beside the text: one bar per line, as long as the line
+    ///
+    /// The visible part of the document is marked, and clicking or dragging on
+    /// the strip scrolls there. Needs the editor's own scroll area
+    /// (`with_scroll_area`).
+    #[must_use]
+    pub const fn with_minimap(mut self, enabled: bool) -> Self {
+        self.minimap = enabled;
+        self
+    }
+
+    /// Lay out only the lines in view instead of the whole document
+    ///
+    /// A fast path for huge files: the managed scroll area (`with_scroll_area`)
+    /// shows the lines in view through `ScrollArea::show_viewport`, so a frame
+    /// costs the same at line 10 as at line 50,000. Lines don't wrap and each
+    /// frame's lines are highlighted on their own, so constructs that start
+    /// above the view (block comments, code fences) aren't seen. Line
+    /// backgrounds, search and bracket highlights, the minimap, completions,
+    /// and the painted overlays (ruler, indent guides, newline markers) are
+    /// left out. Off by default.
+    #[must_use]
+    pub const fn with_visible_rows_only(mut self, enabled: bool) -> Self {
+        self.visible_rows_only = enabled;
+        self
+    }
+
+    /// Keep `lines` lines visible above and below the cursor (Vim `scrolloff`)
+    ///
+    /// When the cursor moves closer than that to the top or bottom of the
+    /// viewport, the view scrolls ahead of it. Works with the managed scroll
+    /// area (`with_scroll_area`) or the host's `ScrollArea`. Off (0) by default.
+    #[must_use]
+    pub const fn with_scrolloff(mut self, lines: usize) -> Self {
+        self.scrolloff = lines;
+        self
+    }
+
+    // In-place setters, for changing the configuration while the editor is shown
+
+    pub const fn set_font_size(&mut self, size: f32) {
+        self.font_size = size;
+    }
+
+    pub const fn set_status_bar(&mut self, show: bool) {
+        self.show_status = show;
+    }
+
+    /// Replace the syntax highlighter (e.g. when the user picks another language)
+    pub fn set_syntax_highlighter(&mut self, highlighter: impl SyntaxHighlighter + 'static) {
+        self.highlighter = Some(Box::new(highlighter));
+        self.language = None;
+        self.theme_version += 1;
+    }
+
+    pub const fn set_highlight_enabled(&mut self, enabled: bool) {
+        self.highlight_enabled = enabled;
+    }
+
+    /// Remove the syntax highlighter, falling back to `basic_highlight`
+    pub fn clear_syntax_highlighter(&mut self) {
+        self.highlighter = None;
+        self.language = None;
+        self.theme_version += 1;
+    }
+
+    /// Use a new theme, re-highlighting on the next frame
+    ///
+    /// The active syntax highlighter gets the theme; without one, `basic_highlight`
+    /// and the search and bracket highlights use it.
+    pub fn set_theme(&mut self, theme: HighlightTheme) {
+        if let Some(highlighter) = self.highlighter.as_deref_mut() {
+            highlighter.set_theme(theme.clone());
+        }
+        self.theme = theme;
+        self.theme_version += 1;
+    }
+
+    /// Draw the text as rendered markdown (e.g. in a preview pane next to the editor)
+    ///
+    /// Uses the active highlighter's theme (or the one set with `set_theme`) and
+    /// the editor's font size.
+    pub fn render_markdown_preview(&self, ui: &mut Ui) {
+        let theme = self
+            .highlighter
+            .as_deref()
+            .map_or(&self.theme, |highlighter| highlighter.theme());
+        markdown::render_markdown_preview(ui, self.buffer.text(), theme, self.font_size);
+    }
+
+    /// The active syntax highlighter, e.g. for changing its settings in place
+    ///
+    /// The cached highlighting is dropped, since anything may change through it.
+    pub fn syntax_highlighter_mut(&mut self) -> Option<&mut (dyn SyntaxHighlighter + 'static)> {
+        self.theme_version += 1;
+        self.highlighter.as_deref_mut()
+    }
+
+    pub const fn set_ruler(&mut self, column: Option<usize>) {
+        self.ruler = column;
+    }
+
+    pub const fn set_show_whitespace(&mut self, show: bool) {
+        self.show_whitespace = show;
+    }
+
+    pub const fn set_tab_width(&mut self, width: usize) {
+        self.tab_width = width;
+    }
+
+    pub fn set_word_chars(&mut self, chars: &str) {
+        self.buffer.set_word_chars(chars);
+    }
+
+    pub fn set_vim_direct_motions(&mut self, direct: bool) {
+        self.vim_handler.set_direct_motions(direct);
+    }
+
+    pub fn set_vim_insert_escape_sequence(&mut self, sequence: &str) {
+        self.vim_handler.set_insert_escape_sequence(sequence);
+    }
+
+    pub const fn set_horizontal_scroll(&mut self, enabled: bool) {
+        self.horizontal_scroll = enabled;
+    }
+
+    pub const fn set_minimap(&mut self, enabled: bool) {
+        self.minimap = enabled;
+    }
+
+    pub const fn set_visible_rows_only(&mut self, enabled: bool) {
+        self.visible_rows_only = enabled;
+    }
+
+    pub const fn set_scrolloff(&mut self, lines: usize) {
+        self.scrolloff = lines;
+    }
+
+    pub const fn set_logical_motion(&mut self, logical: bool) {
+        self.logical_motion = logical;
+    }
+
+    pub const fn set_word_wrap(&mut self, wrap: bool) {
+        self.word_wrap = wrap;
+    }
+
+    pub const fn set_line_wrapping_indent(&mut self, indent: WrapIndent) {
+        self.wrap_indent = indent;
+    }
+
+    pub const fn set_auto_dedent(&mut self, enabled: bool) {
+        self.auto_dedent = enabled;
+    }
+
+    pub const fn set_smart_tab(&mut self, enabled: bool) {
+        self.smart_tab = enabled;
+    }
+
+    pub const fn set_bracket_highlight(&mut self, enabled: bool) {
+        self.bracket_highlight = enabled;
+    }
+
+    pub const fn set_auto_close_pairs(&mut self, enabled: bool) {
+        self.auto_close_pairs = enabled;
+    }
+
+    /// Whether typing replaces the character under the cursor instead of inserting
+    ///
+    /// The Insert key toggles this in Emacs, plain, and Vim insert mode.
+    pub const fn overtype(&self) -> bool {
+        self.overtype
+    }
+
+    pub const fn set_overtype(&mut self, overtype: bool) {
+        self.overtype = overtype;
+    }
+
+    pub const fn set_indent_guides(&mut self, show: bool) {
+        self.indent_guides = show;
+    }
+
+    pub fn set_cursor_shape(&mut self, mode: EditorMode, shape: CursorShape) {
+        self.cursor_shapes.insert(mode, shape);
+    }
+
+    pub const fn set_custom_cursor(&mut self, enabled: bool) {
+        self.custom_cursor = enabled;
+    }
+
+    /// The cursor shape used for the current mode
+    pub fn cursor_shape(&self) -> CursorShape {
+        if !self.custom_cursor {
+            return CursorShape::Bar;
+        }
+        self.cursor_shapes
+            .get(&self.current_mode)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Tint the background of a line (0-based), e.g. for diff or search context
+    pub fn set_line_background(&mut self, line: usize, color: Color32) {
+        self.line_backgrounds.insert(line, color);
+    }
+
+    /// Remove the custom background of a single line
+    pub fn clear_line_background(&mut self, line: usize) {
+        self.line_backgrounds.remove(&line);
+    }
+
+    /// Remove all custom line backgrounds
+    pub fn clear_line_backgrounds(&mut self) {
+        self.line_backgrounds.clear();
+    }
+
+    pub fn text(&self) -> &str {
+        self.buffer.text()
+    }
+
+    /// Whether the editor holds no text (e.g. to disable a Save button)
+    pub const fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    /// Number of characters in the text, which differs from `text().len()` for non-ASCII text
+    pub fn len_chars(&self) -> usize {
+        self.buffer.len_chars()
+    }
+
+    /// Mutable access to the text; it is highlighted afresh on the next frame
+    pub fn text_mut(&mut self) -> &mut String {
+        self.highlight_cache = None;
+        self.buffer.text_mut()
+    }
+
+    /// Number of lines, counting the empty line after a trailing newline
+    ///
+    /// This is the numbering `line` and `move_cursor_to` use, so a gutter drawn
+    /// from it lines up with the text.
+    pub fn line_count(&mut self) -> usize {
+        self.buffer.line_count()
+    }
+
+    /// The text of a 0-based line without its newline, or `None` past the last line
+    pub fn line(&mut self, line: usize) -> Option<&str> {
+        self.buffer.line(line)
+    }
+
+    /// Replace the text, moving the cursor to the start of the document
+    ///
+    /// This is treated as loading a document, so the editor is marked clean.
+    pub fn set_text(&mut self, text: impl Into<String>) {
+        self.buffer.set_text(text.into());
+        self.cursor_sync_pending = true;
+        self.dirty = false;
+        self.publish_shared_text();
+    }
+
+    /// Capture the text, cursor, and selection, e.g. as a checkpoint to return to
+    pub fn snapshot(&self) -> BufferSnapshot {
+        self.buffer.snapshot()
+    }
+
+    /// Go back to a state captured by `snapshot`
+    ///
+    /// A text change counts as an edit: the change callback fires and the
+    /// editor becomes dirty.
+    pub fn restore(&mut self, snapshot: BufferSnapshot) {
+        let revision = self.buffer.revision();
+        self.buffer.restore(snapshot);
+        if self.buffer.revision() != revision {
+            self.notify_changed();
+        }
+
+        self.show_buffer_selection();
+        self.cursor_sync_pending = true;
+    }
+
+    /// Copy the cursor's line, or every line the selection touches, below the
+    /// original and move the cursor (and selection) onto the copy
+    ///
+    /// Bound to Ctrl+Shift+D (Cmd+Shift+D on macOS) in the modes that edit text.
+    pub fn duplicate_line(&mut self) {
+        self.apply_command(EditorCommand::DuplicateLine);
+    }
+
+    /// Comment out the cursor's line, or every line the selection touches, as
+    /// one undo step; if they are all commented already, uncomment them instead
+    ///
+    /// The comment prefix comes from the comment registry for the current
+    /// language (`//` when none is selected); nothing happens for a language
+    /// without line comments. Bound to Ctrl+/ (Cmd+/ on macOS) in the modes
+    /// that edit text, and to `gcc` (or `gc` over a selection) in Vim.
+    pub fn toggle_line_comment(&mut self) {
+        self.apply_command(EditorCommand::ToggleLineComment);
+    }
+
+    /// Reset to an empty, clean document (e.g. for a "New file" action)
+    ///
+    /// Clears the text, moves the cursor to the start, drops any half-typed key
+    /// sequence, switches to the mode set with `with_initial_mode_for_new_buffers`
+    /// (`EditorMode::default()` if none was set), and fires the change callback once.
+    pub fn clear(&mut self) {
+        self.set_text(String::new());
+        self.set_mode(self.new_buffer_mode.unwrap_or_default());
+        if let Some(callback) = self.change_callback.as_mut() {
+            callback(self.buffer.text());
+        }
+    }
+
+    /// Show and edit `shared` instead of a private document
+    ///
+    /// The editor takes the shared text right away. Its edits are published to
+    /// `shared` as they happen, and edits made through other handles appear on
+    /// its next `show`. Cursor, selection, and mode stay per editor. Edits made
+    /// directly through `text_mut` are not published.
+    #[must_use]
+    pub fn with_shared_buffer(mut self, shared: SharedBuffer) -> Self {
+        self.set_shared_buffer(shared);
+        self
+    }
+
+    /// Attach to `shared`, replacing the text with the shared text
+    pub fn set_shared_buffer(&mut self, shared: SharedBuffer) {
+        self.buffer.set_text(shared.text());
+        self.shared_version = shared.version();
+        self.shared_buffer = Some(shared);
+        self.cursor_sync_pending = true;
+    }
+
+    /// Stop sharing; the editor keeps a private copy of the current text
+    pub fn detach_shared_buffer(&mut self) {
+        self.shared_buffer = None;
+    }
+
+    /// The shared text this editor is attached to, if any
+    pub const fn shared_buffer(&self) -> Option<&SharedBuffer> {
+        self.shared_buffer.as_ref()
+    }
+
+    /// Record a `BufferEdit` for every change to the text, for `take_edits`
+    ///
+    /// This is the hook for collaborative editing or custom undo: each edit
+    /// says exactly which bytes were replaced by what, including text typed
+    /// into the `TextEdit` and text replaced with `set_text`.
+    #[must_use]
+    pub fn with_edit_recording(mut self, record: bool) -> Self {
+        self.buffer.set_record_edits(record);
+        self
+    }
+
+    /// Call `callback` with the new text whenever the buffer changes
+    #[must_use]
+    pub fn with_change_callback(mut self, callback: impl FnMut(&str) + 'static) -> Self {
+        self.change_callback = Some(Box::new(callback));
+        self
+    }
+
+    /// Call `observer` with every command the editor executes
+    ///
+    /// Commands are reported in order, just before they are applied.
+    #[must_use]
+    pub fn with_command_observer(mut self, observer: impl FnMut(&EditorCommand) + 'static) -> Self {
+        self.command_observer = Some(Box::new(observer));
+        self
+    }
+
+    /// Keep a log of the last keystrokes, commands, and mode changes (off by default)
+    ///
+    /// Read it with `debug_log`, e.g. to show in a dev overlay. The log holds
+    /// the most recent `DEFAULT_DEBUG_LOG_CAPACITY` entries.
+    #[must_use]
+    pub fn with_debug_log(mut self, enabled: bool) -> Self {
+        self.set_debug_log(enabled);
+        self
+    }
+
+    /// Turn the debug log on or off; turning it off drops the entries
+    ///
+    /// While it is on, the Vim and Emacs handlers also note how they read each key.
+    pub fn set_debug_log(&mut self, enabled: bool) {
+        self.vim_handler.set_debug(enabled);
+        self.emacs_handler.set_debug(enabled);
+        if !enabled {
+            self.debug_log = None;
+        } else if self.debug_log.is_none() {
+            self.debug_log = Some(DebugLog::default());
+        }
+    }
+
+    /// The debug log, if it is on
+    pub const fn debug_log(&self) -> Option<&DebugLog> {
+        self.debug_log.as_ref()
+    }
+
+    /// Move the key handlers' notes into the debug log
+    fn log_handler_messages(&mut self) {
+        let Some(log) = self.debug_log.as_mut() else {
+            return;
+        };
+        let vim = self.vim_handler.take_debug_messages();
+        let emacs = self.emacs_handler.take_debug_messages();
+        let messages = vim
+            .into_iter()
+            .map(|message| (self.vim_handler.name(), message))
+            .chain(
+                emacs
+                    .into_iter()
+                    .map(|message| (self.emacs_handler.name(), message)),
+            );
+        for (handler, message) in messages {
+            log.push(DebugLogEntry::Handler { handler, message });
+        }
+    }
+
+    /// Run `action` whenever `EditorCommand::Custom(name)` is executed
+    #[must_use]
+    pub fn with_custom_command(
+        mut self,
+        name: impl Into<String>,
+        action: impl FnMut(&mut Self) + 'static,
+    ) -> Self {
+        self.register_custom_command(name, action);
+        self
+    }
+
+    /// Run `action` whenever `EditorCommand::Custom(name)` is executed
+    ///
+    /// Registering a name again replaces its action. The action gets the editor,
+    /// so it can edit the text or run further commands with `apply_command`.
+    pub fn register_custom_command(
+        &mut self,
+        name: impl Into<String>,
+        action: impl FnMut(&mut Self) + 'static,
+    ) {
+        self.custom_commands.insert(name.into(), Box::new(action));
+    }
+
+    /// Call `callback` with the text when the user saves with `:w`, `:wq` or `C-x C-s`
+    ///
+    /// The editor is marked clean after the callback returns.
+    #[must_use]
+    pub fn with_save_callback(mut self, callback: impl FnMut(&str) + 'static) -> Self {
+        self.save_callback = Some(Box::new(callback));
+        self
+    }
+
+    /// Strip trailing spaces and tabs from every line when saving
+    ///
+    /// The cleanup is applied to the buffer as a single edit just before the
+    /// save callback runs, so the callback sees the cleaned text.
+    #[must_use]
+    pub const fn with_trim_trailing_whitespace_on_save(mut self, trim: bool) -> Self {
+        self.trim_trailing_whitespace_on_save = trim;
+        self
+    }
+
+    /// Make sure the text ends with a newline when saving
+    ///
+    /// Only applies together with `with_trim_trailing_whitespace_on_save`.
+    #[must_use]
+    pub const fn with_final_newline_on_save(mut self, ensure: bool) -> Self {
+        self.final_newline_on_save = ensure;
+        self
+    }
+
+    /// Call `callback` when the user closes the editor with `:q`, `:q!`, `:wq`, or `C-x C-c`
+    ///
+    /// The callback gets whether the text has unsaved changes, so the host can
+    /// decide whether to close (or offer to save first). Every quit command
+    /// fires it, including `:q` with unsaved changes and `:wq` whose save failed.
+    #[must_use]
+    pub fn with_quit_callback(mut self, callback: impl FnMut(bool) + 'static) -> Self {
+        self.quit_callback = Some(Box::new(callback));
+        self
+    }
+
+    /// Offer `items` in a completion popup at the cursor
+    ///
+    /// Up/Down pick an item and Enter or Tab inserts it in place of the word
+    /// being typed; Escape dismisses the popup. An empty list hides it.
+    pub fn set_completions(&mut self, items: Vec<CompletionItem>) {
+        self.completions = items;
+        self.completion_selected = 0;
+    }
+
+    /// Hide the completion popup
+    pub fn clear_completions(&mut self) {
+        self.completions.clear();
+        self.completion_selected = 0;
+    }
+
+    /// The completions currently offered
+    pub fn completions(&self) -> &[CompletionItem] {
+        &self.completions
+    }
+
+    /// Show a tooltip with the text `provider` returns for the byte offset under the pointer
+    ///
+    /// The provider is only asked while the pointer is over a character, and
+    /// no tooltip is shown when it returns `None`.
+    pub fn set_hover_provider(&mut self, provider: impl Fn(usize) -> Option<String> + 'static) {
+        self.hover_provider = Some(Box::new(provider));
+    }
+
+    /// Stop showing hover tooltips
+    pub fn clear_hover_provider(&mut self) {
+        self.hover_provider = None;
+    }
+
+    /// Whether the text changed since it was loaded or last marked clean
+    pub const fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Mark the current text as saved
+    pub const fn mark_clean(&mut self) {
+        self.dirty = false;
+    }
+
+    /// Record a buffer change and notify the change callback
+    fn notify_changed(&mut self) {
+        self.dirty = true;
+        self.notified_revision = self.buffer.revision();
+        self.publish_shared_text();
+        if let Some(callback) = self.change_callback.as_mut() {
+            callback(self.buffer.text());
+        }
+    }
+
+    /// Send the buffer text to the shared buffer, if attached
+    fn publish_shared_text(&mut self) {
+        if let Some(shared) = &self.shared_buffer {
+            self.shared_version = shared.publish(self.buffer.text());
+        }
+    }
+
+    /// Take the shared text if another editor changed it since this one last looked
+    fn pull_shared_text(&mut self) {
+        let Some(shared) = &self.shared_buffer else {
+            return;
+        };
+        if shared.version() == self.shared_version {
+            return;
+        }
+        self.shared_version = shared.version();
+        // Another view edited the text; keep this view's cursor where it was
+        let cursor = self.buffer.cursor_position();
+        self.buffer.set_text(shared.text());
+        self.buffer.set_cursor_position(cursor);
+        self.cursor_sync_pending = true;
+        self.dirty = true;
+        if let Some(callback) = self.change_callback.as_mut() {
+            callback(self.buffer.text());
+        }
+    }
+
+    /// Move the cursor to a 0-based (line, column), clamped to the document
+    pub fn move_cursor_to(&mut self, line: usize, column: usize) {
+        self.buffer.move_cursor_to(line, column);
+        self.cursor_sync_pending = true;
+    }
+
+    /// Run a command as if a key binding had produced it (e.g. from a toolbar button)
+    ///
+    /// The command observer sees it, the change callback fires if the text
+    /// changed, and the `TextEdit` cursor follows on the next `show`. The
+    /// change is picked up by the `TextEdit` undo history like any other edit.
+    pub fn apply_command(&mut self, command: EditorCommand) {
+        self.run_commands(vec![command]);
+    }
+
+    /// Select the whole text
+    pub fn select_all(&mut self) {
+        self.select_range(0, self.buffer.text().len());
+    }
+
+    /// Select the text between two byte offsets, scrolling the selection into view
+    ///
+    /// `start` is the anchor and `end` is where the cursor goes, so `end` may
+    /// come first to select backwards. Offsets are clamped to the text. The
+    /// selection is drawn even while the editor doesn't have focus.
+    pub fn select_range(&mut self, start: usize, end: usize) {
+        let start = self
+            .buffer
+            .byte_to_char(start.min(self.buffer.text().len()));
+        let end = self.buffer.byte_to_char(end.min(self.buffer.text().len()));
+        let [start, end] = [start, end].map(|index| self.buffer.char_to_byte(index));
+
+        self.buffer.set_cursor_position(end);
+        self.pending_selection = Some((
+            self.buffer.byte_to_char(start),
+            self.buffer.byte_to_char(end),
+        ));
+        self.buffer
+            .set_selection(Some(start.min(end)..start.max(end)));
+        self.cursor_sync_pending = true;
+        self.reveal_selection = true;
+    }
+
+    /// The selected byte range, if any text is selected
+    ///
+    /// Commands that insert text (e.g. `InsertChar` or `Paste` through
+    /// `apply_command`) replace the selection.
+    pub fn selection(&self) -> Option<Range<usize>> {
+        self.buffer.selection()
+    }
+
+    /// The selected text, if any
+    pub fn selected_text(&self) -> Option<&str> {
+        self.buffer.selected_text()
+    }
+
+    /// The text in a byte range, clamped to the text and to char boundaries
+    ///
+    /// Safer than slicing `text()` with offsets that may split a character.
+    pub fn text_for_range(&self, range: Range<usize>) -> &str {
+        self.buffer.text_for_range(range)
+    }
+
+    /// Copy the selected text to the system clipboard (on the next `show`) and
+    /// to the register `Paste` uses
+    ///
+    /// Returns `false` when nothing is selected.
+    pub fn copy_selection_to_clipboard(&mut self) -> bool {
+        let Some(text) = self.buffer.selected_text().map(str::to_string) else {
+            return false;
+        };
+        self.set_register(text, false);
+        true
+    }
+
+    /// Byte ranges of every match of `pattern` in the text, in order and without overlaps
+    ///
+    /// For building a find UI; pass a range to `select_range` to show a match.
+    pub fn search(&self, pattern: &str, case_sensitive: bool) -> Vec<Range<usize>> {
+        self.buffer.find_all(pattern, case_sensitive)
+    }
+
+    /// Highlight every match of `pattern`, with a stronger color for the match at the cursor
+    ///
+    /// The colors are the highlighter theme's `search_match` and
+    /// `current_match`. An empty pattern clears the highlighting.
+    pub fn set_search_highlight(&mut self, pattern: impl Into<String>, case_sensitive: bool) {
+        let pattern = pattern.into();
+        self.search_highlight = (!pattern.is_empty()).then_some((pattern, case_sensitive));
+    }
+
+    pub fn clear_search_highlight(&mut self) {
+        self.search_highlight = None;
+    }
+
+    /// The pattern whose matches are highlighted, if any
+    pub fn search_highlight(&self) -> Option<&str> {
+        self.search_highlight
+            .as_ref()
+            .map(|(pattern, _)| pattern.as_str())
+    }
+
+    /// Byte ranges of every non-empty match of a regular expression in the text
+    #[cfg(feature = "regex")]
+    pub fn search_regex(&self, pattern: &str) -> Result<Vec<Range<usize>>, regex::Error> {
+        let regex = regex::Regex::new(pattern)?;
+        Ok(self.buffer.find_all_regex(&regex))
+    }
+
+    /// Start or stop recording edits; stopping drops any not yet taken
+    pub fn set_edit_recording(&mut self, record: bool) {
+        self.buffer.set_record_edits(record);
+    }
+
+    /// Take the edits made since the last call, oldest first (needs `with_edit_recording`)
+    pub fn take_edits(&mut self) -> Vec<BufferEdit> {
+        self.buffer.take_edits()
+    }
+
+    /// Apply a batch of non-overlapping edits whose ranges refer to the current
+    /// text, as one change (e.g. a replace-all or edits from a peer)
+    ///
+    /// Returns `false`, changing nothing, if the edits overlap or don't fit the
+    /// text. See `TextBuffer::apply_edits`.
+    pub fn apply_edits(&mut self, edits: Vec<BufferEdit>) -> bool {
+        let revision = self.buffer.revision();
+        if !self.buffer.apply_edits(edits) {
+            return false;
+        }
+        if self.buffer.revision() != revision {
+            self.notify_changed();
+            self.cursor_sync_pending = true;
+        }
+        true
+    }
+
+    /// Text removed with kill commands (Emacs `C-k`), newest first
+    pub const fn kill_ring(&self) -> &KillRing {
+        &self.kill_ring
+    }
+
+    /// Cursor positions left by jump motions (Vim `G`, `gg`, `%`, `{`, `}`), oldest first
+    pub const fn jumplist(&self) -> &JumpList {
+        &self.jumplist
+    }
+
+    /// How far (in points) the text was scrolled past the top of the viewport last frame
+    pub const fn scroll_offset(&self) -> f32 {
+        self.scroll_offset
+    }
+
+    /// Scroll the text so `offset` points of it are above the viewport
+    ///
+    /// Applied on the next `show`, and clamped by the `ScrollArea` to the text
+    /// height. Without a managed scroll area this scrolls the host's enclosing
+    /// `ScrollArea`, if any.
+    pub const fn set_scroll_offset(&mut self, offset: f32) {
+        self.pending_scroll_offset = Some(offset.max(0.0));
+    }
+
+    /// Inclusive `(first_line, last_line)` range visible during the last frame
+    pub const fn visible_line_range(&self) -> (usize, usize) {
+        self.visible_lines
+    }
+
+    pub const fn mode(&self) -> &EditorMode {
+        &self.current_mode
+    }
+
+    /// Whether the mode changed during the last frame (e.g. `i` or Escape in Vim)
+    ///
+    /// Check it after `show` to update host UI on the frame of the switch;
+    /// `mode` gives the new mode. A `set_mode` call between frames is reported
+    /// after the next `show`.
+    pub const fn mode_changed(&self) -> bool {
+        self.mode_changed
+    }
+
+    /// Switch modes, dropping any key sequence the handlers were in the middle of
+    pub fn set_mode(&mut self, mode: EditorMode) {
+        let switched = mode != self.current_mode;
+        self.current_mode = mode;
+        self.vim_handler.reset();
+        self.emacs_handler.reset();
+        self.plain_handler.reset();
+
+        // Update the vim handler mode if needed
+        if let EditorMode::Vim(vim_mode) = mode {
+            self.vim_handler.set_mode(vim_mode);
+        }
+        self.update_visual_anchor();
+        if switched {
+            self.clamp_cursor_on_mode_switch();
+        }
+    }
+
+    /// The key method for the editor widget - this function:
+    /// 1. Intercepts keyboard events that would normally go to the `TextEdit`
+    /// 2. Processes vim commands directly on the buffer
+    /// 3. Prevents unwanted characters from being inserted in normal mode
+    #[allow(clippy::too_many_lines)]
+    pub fn show(&mut self, ui: &mut Ui) -> Response {
+        // Pick up edits made through other views of a shared buffer
+        self.pull_shared_text();
+
+        // 1. Process key events BEFORE we create the TextEdit widget
+        self.process_input_before_ui(ui.ctx());
+        if let Some(text) = self.pending_clipboard.take() {
+            ui.ctx().copy_text(text);
+        }
+        // Leaving a mode that inserts text cancels any composition in progress
+        if !self.edits_text() {
+            self.cancel_ime_composition();
+        }
+        // A cursor moved by commands isn't revealed by the TextEdit itself
+        let reveal_cursor = self.cursor_sync_pending;
+        if self.cursor_sync_pending {
+            self.sync_cursor_to_text_edit(ui.ctx());
+            self.cursor_sync_pending = false;
+        }
+
+        // 2. Show mode indicator at the top of the editor
+        match self.current_mode {
+            EditorMode::Vim(VimMode::Normal) => {
+                ui.label(
+                    RichText::new("-- VIM: NORMAL MODE --")
+                        .strong()
+                        .monospace()
+                        .color(Color32::GREEN),
+                );
+            }
+            EditorMode::Vim(VimMode::Insert) => {
+                ui.label(
+                    RichText::new("-- VIM: INSERT MODE --")
+                        .strong()
+                        .monospace()
+                        .color(Color32::YELLOW),
+                );
+            }
+            EditorMode::Vim(VimMode::Replace) => {
+                ui.label(
+                    RichText::new("-- VIM: REPLACE MODE --")
+                        .strong()
+                        .monospace()
+                        .color(Color32::LIGHT_RED),
+                );
+            }
+            EditorMode::Vim(VimMode::Visual) => {
+                ui.label(
+                    RichText::new("-- VIM: VISUAL MODE --")
+                        .strong()
+                        .monospace()
+                        .color(Color32::GOLD),
+                );
+            }
+            EditorMode::Vim(VimMode::CommandLine) => {
+                ui.label(
+                    RichText::new("-- VIM: COMMAND MODE --")
+                        .strong()
+                        .monospace()
+                        .color(Color32::LIGHT_GREEN),
+                );
+            }
+            EditorMode::Emacs => {
+                ui.label(
+                    RichText::new("-- EMACS MODE --")
+                        .strong()
+                        .monospace()
+                        .color(Color32::LIGHT_BLUE),
+                );
+            }
+            // Plain mode behaves like an ordinary text area - no indicator line
+            EditorMode::Plain => {}
+        }
+
+        // 3. Create a layouter for syntax highlighting, telling the highlighter
+        // the font size and which lines were on screen last frame
+        if let Some(highlighter) = self.highlighter.as_deref_mut() {
+            let (first, last) = self.visible_lines;
+            highlighter.set_font_size(self.font_size);
+            highlighter.set_visible_lines(first..last + 1);
+        }
+        let cursor_shape = self.cursor_shape();
+        let id = self.text_edit_id();
+        let scroll_area_id = Id::new(format!("{}_scroll", self.id));
+        // Leave room below a managed scroll area for the command line and status bar
+        let footer_rows = usize::from(self.show_status)
+            + usize::from(
+                self.current_mode == EditorMode::Vim(VimMode::CommandLine)
+                    || self.command_message.is_some(),
+            );
+        let footer_height =
+            footer_rows as f32 * (ui.spacing().interact_size.y + ui.spacing().item_spacing.y);
+        let tab_indents = self.smart_tab && self.edits_text();
+        let text_before_edit = self.text_before_edit(ui.ctx());
+        if self.visible_rows_only && self.scroll_area {
+            let max_height = (ui.available_height() - footer_height).max(0.0);
+            let response =
+                self.show_visible_rows(ui, max_height, tab_indents, cursor_shape, reveal_cursor);
+            return self.finish_show(ui, response, tab_indents, text_before_edit.as_deref(), None);
+        }
+        let highlight_enabled = self.highlight_enabled;
+        let font_size = self.font_size;
+        let line_backgrounds = &self.line_backgrounds;
+        let highlighter = self.highlighter.as_deref();
+        let highlight_cache = &mut self.highlight_cache;
+        let (revision, theme_version) = (self.buffer.revision(), self.theme_version);
+        // The TextEdit lays out again only after changing the text itself, which
+        // the buffer's revision doesn't count until after `show`
+        let mut first_layout = true;
+        let (show_whitespace, whitespace_color, tab_width) =
+            (self.show_whitespace, self.whitespace_color, self.tab_width);
+        let (word_wrap, wrap_indent) = (self.word_wrap, self.wrap_indent);
+        let search_highlight = self.search_highlight.as_ref();
+        let theme = highlighter.map_or(&self.theme, |highlighter| highlighter.theme());
+        let (search_colors, bracket_color) = (
+            (theme.search_match, theme.current_match),
+            theme.matching_bracket,
+        );
+        let fallback_options = HighlightOptions::from_theme(theme, font_size);
+        let bracket_highlight = self.bracket_highlight;
+        let cursor_char = self.buffer.byte_to_char(self.buffer.cursor_position());
+        let mut layouter = move |ui: &Ui, text: &str, wrap_width: f32| {
+            // Highlighting can be expensive, so only redo it when the text, the
+            // highlighter or its theme, or the font size changed
+            let cached = highlight_cache.as_ref().filter(|cache| {
+                first_layout
+                    && cache.revision == revision
+                    && cache.theme_version == theme_version
+                    && cache.font_size == font_size
+            });
+            let mut layout_job = if let Some(cache) = cached {
+                cache.job.clone()
+            } else {
+                let job = highlighter.map_or_else(
+                    || crate::syntax::basic_highlight(text, &fallback_options),
+                    |highlighter| highlighter.highlight(ui.ctx(), text),
+                );
+                if first_layout {
+                    *highlight_cache = Some(HighlightCache {
+                        revision,
+                        theme_version,
+                        font_size,
+                        job: job.clone(),
+                    });
+                }
+                job
+            };
+            first_layout = false;
+            let space_width =
+                ui.fonts(|fonts| fonts.glyph_width(&FontId::monospace(font_size), ' '));
+            crate::syntax::apply_whitespace(
+                &mut layout_job,
+                show_whitespace,
+                whitespace_color,
+                tab_width,
+                space_width,
+            );
+            crate::syntax::apply_line_backgrounds(&mut layout_job, line_backgrounds);
+            if let Some((pattern, case_sensitive)) = search_highlight {
+                let matches = search_match_backgrounds(
+                    text,
+                    pattern,
+                    *case_sensitive,
+                    cursor_char,
+                    search_colors,
+                );
+                crate::syntax::apply_range_backgrounds(&mut layout_job, &matches);
+            }
+            if bracket_highlight {
+                let brackets = bracket_pair_backgrounds(text, cursor_char, bracket_color);
+                crate::syntax::apply_range_backgrounds(&mut layout_job, &brackets);
+            }
+            if !word_wrap {
+                return ui.fonts(|fonts| fonts.layout_job(layout_job));
+            }
+
+            // Continuation rows are shifted right after wrapping, so wrap
+            // narrower by the widest indent to keep them inside the editor
+            let indents = wrap_indents(text, wrap_indent, tab_width, space_width, wrap_width);
+            let widest = indents.iter().copied().fold(0.0, f32::max);
+            layout_job.wrap.max_width = wrap_width - widest;
+            let mut galley = ui.fonts(|fonts| fonts.layout_job(layout_job));
+            if widest > 0.0 {
+                indent_wrapped_rows(Arc::make_mut(&mut galley), &indents);
+            }
+            galley
+        };
+
+        let reveal_selection = std::mem::take(&mut self.reveal_selection)
+            .then(|| self.buffer.selection())
+            .flatten()
+            .map(|range| {
+                (
+                    self.buffer.byte_to_char(range.start),
+                    self.buffer.byte_to_char(range.end),
+                )
+            });
+
+        // 4. Create a TextEdit widget for all modes - unified approach
+        let mut text_edit = TextEdit::multiline(self.buffer.text_mut())
+            .id(id)
+            // Use the same font size as the highlighter so the galley and edit metrics agree
+            .font(FontId::monospace(self.font_size))
+            .desired_width(f32::INFINITY)
+            // With smart tab, Tab types into the editor instead of moving focus
+            .lock_focus(tab_indents);
+        if highlight_enabled {
+            text_edit = text_edit.layouter(&mut layouter);
+        }
+
+        // Add styling based on mode
+        text_edit = match self.current_mode {
+            EditorMode::Vim(VimMode::Normal) => {
+                text_edit.hint_text("Normal mode: press 'i' to edit, 'v' for visual mode")
+            }
+            EditorMode::Vim(VimMode::Insert) => {
+                text_edit.hint_text("Insert mode: press Escape to exit")
+            }
+            EditorMode::Vim(VimMode::Replace) => {
+                text_edit.hint_text("Replace mode: typing overwrites, Backspace restores, Escape to exit")
+            }
+            EditorMode::Vim(VimMode::Visual) => {
+                text_edit.hint_text("Visual mode: use movement keys to select, 'y' to copy, 'x/d' to cut, 'c' to change, 'p' to replace")
+            }
+            EditorMode::Vim(VimMode::CommandLine) => {
+                text_edit.hint_text("Command mode: press Enter to run, Escape to cancel")
+            }
+            EditorMode::Emacs => text_edit.hint_text("Emacs mode"),
+            EditorMode::Plain => text_edit,
+        };
+
+        // 5. Add the text edit to the UI and get the output
+        // We now use .show instead of ui.add to get access to cursor_range.
+        // For non-bar cursor shapes we hide the TextEdit's own caret and paint ours.
+        let caret_color = self
+            .caret_color
+            .unwrap_or(ui.visuals().text_cursor.stroke.color);
+        let caret_blink = self.caret_blink;
+        let horizontal_scroll = self.horizontal_scroll && !self.word_wrap;
+        let show_text_edit = |ui: &mut Ui| {
+            ui.scope(|ui| {
+                let text_cursor = &mut ui.visuals_mut().text_cursor;
+                text_cursor.stroke.color = caret_color;
+                if let Some((on, off)) = caret_blink {
+                    text_cursor.blink = off > 0.0;
+                    text_cursor.on_duration = on;
+                    text_cursor.off_duration = off;
+                }
+                if cursor_shape != CursorShape::Bar {
+                    ui.visuals_mut().text_cursor.stroke = Stroke::NONE;
+                }
+                let output = text_edit.show(ui);
+                // Inside the scroll area, so it scrolls the cursor's column into view
+                if reveal_cursor && horizontal_scroll && output.response.has_focus() {
+                    if let Some(cursor_range) = output.cursor_range {
+                        let cursor = output.galley.pos_from_cursor(&cursor_range.primary);
+                        ui.scroll_to_rect(cursor.translate(output.galley_pos.to_vec2()), None);
+                    }
+                }
+                // Scroll a programmatic selection into view, focused or not
+                if let Some((start, end)) = reveal_selection {
+                    let rect = output
+                        .galley
+                        .pos_from_ccursor(CCursor::new(start))
+                        .union(output.galley.pos_from_ccursor(CCursor::new(end)));
+                    ui.scroll_to_rect(rect.translate(output.galley_pos.to_vec2()), None);
+                }
+                output
+            })
+            .inner
+        };
+        let (mut output, viewport, managed_offset) = if self.scroll_area || horizontal_scroll {
+            let mut scroll_area = ScrollArea::new([horizontal_scroll, self.scroll_area])
+                .id_salt(scroll_area_id)
+                // Without vertical scrolling the area is as tall as the text
+                .auto_shrink([false, !self.scroll_area]);
+            if self.scroll_area {
+                scroll_area =
+                    scroll_area.max_height((ui.available_height() - footer_height).max(0.0));
+                if let Some(offset) = self.pending_scroll_offset.take() {
+                    scroll_area = scroll_area.vertical_scroll_offset(offset);
+                }
+            }
+            let scrolled = if self.minimap && self.scroll_area {
+                // Leave room on the right for the minimap
+                ui.scope(|ui| {
+                    ui.set_max_width((ui.available_width() - minimap::MINIMAP_WIDTH).max(0.0));
+                    scroll_area.show(ui, show_text_edit)
+                })
+                .inner
+            } else {
+                scroll_area.show(ui, show_text_edit)
+            };
+            (
+                scrolled.inner,
+                scrolled.inner_rect.intersect(ui.clip_rect()),
+                self.scroll_area.then_some(scrolled.state.offset.y),
+            )
+        } else {
+            (show_text_edit(ui), ui.clip_rect(), None)
+        };
+        // Everything painted over the text below is clipped to the visible part
+        output.text_clip_rect = output.text_clip_rect.intersect(viewport);
+
+        if cursor_shape != CursorShape::Bar && output.response.has_focus() {
+            Self::paint_cursor(ui, &output, cursor_shape, caret_color, font_size);
+        }
+        self.update_selection(&output);
+        self.paint_unfocused_selection(ui, &output);
+        if self.edits_text() {
+            self.update_ime_preedit(ui, &output);
+        } else if output.response.has_focus() {
+            // Without IME output the platform closes its composition window
+            ui.ctx().output_mut(|output| output.ime = None);
+        }
+
+        self.update_scroll(ui, &output, viewport, managed_offset);
+        self.update_visible_lines(ui, &output);
+        if let Some(offset) = managed_offset.filter(|_| self.minimap) {
+            self.show_minimap(ui, &output, viewport, offset);
+        }
+        self.paint_ruler(ui, &output);
+        self.paint_newline_markers(ui, &output);
+        self.paint_indent_guides(ui, &output);
+
+        let clicked_completion = self.show_completions(ui, &output);
+        self.show_hover(&output);
+
+        self.finish_show(
+            ui,
+            output.response,
+            tab_indents,
+            text_before_edit.as_deref(),
+            clicked_completion,
+        )
+    }
+
+    /// The rest of `show` once the `TextEdit` is on screen: focus, recording
+    /// its edits, the command line, and the status bar
+    fn finish_show(
+        &mut self,
+        ui: &mut Ui,
+        response: Response,
+        tab_indents: bool,
+        text_before_edit: Option<&str>,
+        clicked_completion: Option<usize>,
+    ) -> Response {
+        let id = self.text_edit_id();
+        // egui drops focus on Escape before any widget sees it; keep it when the
+        // editor uses Escape itself (leaving Vim modes, closing completions)
+        let keeps_escape =
+            matches!(self.current_mode, EditorMode::Vim(_)) || !self.completions.is_empty();
+        if response.has_focus() && (keeps_escape || tab_indents) {
+            let filter = EventFilter {
+                horizontal_arrows: true,
+                vertical_arrows: true,
+                tab: tab_indents,
+                escape: keeps_escape,
+            };
+            ui.memory_mut(|memory| memory.set_focus_lock_filter(id, filter));
+        }
+        if response.changed() {
+            if let Some(before) = text_before_edit {
+                self.buffer.record_external_edit(before);
+            }
+            self.buffer.mark_edited();
+            self.notify_changed();
+        }
+
+        // 6. In vim normal, visual, or command-line mode, ensure that the editor retains focus
+        if matches!(
+            self.current_mode,
+            EditorMode::Vim(VimMode::Normal | VimMode::Visual | VimMode::CommandLine)
+        ) && !response.has_focus()
+        {
+            response.request_focus();
+        }
+
+        // Show the command being typed after ':', or the last command's message
+        if self.current_mode == EditorMode::Vim(VimMode::CommandLine) {
+            self.command_message = None;
+            let command_line = self.vim_handler.command_line().unwrap_or_default();
+            ui.label(RichText::new(format!(":{command_line}")).monospace());
+        } else if let Some(message) = &self.command_message {
+            ui.label(RichText::new(message).monospace().color(Color32::LIGHT_RED));
+        }
+
+        // Insert a completion picked with the mouse (after the cursor sync above,
+        // so the TextEdit's old cursor doesn't overwrite the new one)
+        if let Some(index) = clicked_completion {
+            let revision = self.buffer.revision();
+            self.accept_completion(index);
+            if self.buffer.revision() != revision {
+                self.notify_changed();
+            }
+            self.cursor_sync_pending = true;
+            response.request_focus();
+        }
+
+        // 7. Show status bar if enabled
+        if self.show_status {
+            ui.horizontal(|ui| {
+                // Show current mode
+                let (mode_text, mode_color) = match self.current_mode {
+                    EditorMode::Vim(VimMode::Normal) => ("VIM: NORMAL", Color32::GREEN),
+                    EditorMode::Vim(VimMode::Insert) => ("VIM: INSERT", Color32::YELLOW),
+                    EditorMode::Vim(VimMode::Replace) => ("VIM: REPLACE", Color32::LIGHT_RED),
+                    EditorMode::Vim(VimMode::Visual) => ("VIM: VISUAL", Color32::GOLD),
+                    EditorMode::Vim(VimMode::CommandLine) => ("VIM: COMMAND", Color32::LIGHT_GREEN),
+                    EditorMode::Emacs => ("EMACS", Color32::LIGHT_BLUE),
+                    EditorMode::Plain => ("PLAIN", Color32::LIGHT_GRAY),
+                };
+
+                ui.label(
+                    RichText::new(mode_text)
+                        .monospace()
+                        .strong()
+                        .color(mode_color),
+                );
+                if self.edits_text() {
+                    let typing = if self.overtype { "OVR" } else { "INS" };
+                    ui.label(RichText::new(typing).monospace());
+                }
+
+                // Show cursor position
+                let cursor_pos = self.buffer.cursor_position();
+                let (line, column) = self.buffer.cursor_line_col();
+                ui.label(
+                    RichText::new(format!(
+                        "Pos: {} (L:{}, C:{})",
+                        cursor_pos,
+                        line + 1,
+                        column + 1
+                    ))
+                    .monospace(),
+                );
+
+                // Add a spacer to push the right-side content
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    ui.label(
+                        RichText::new(format!("Chars: {}", self.buffer.len_chars())).monospace(),
+                    );
+                });
+            });
+        }
+
+        self.mode_changed = self
+            .shown_mode
+            .is_some_and(|mode| mode != self.current_mode);
+        if let (Some(log), Some(from)) = (self.debug_log.as_mut(), self.shown_mode) {
+            if self.mode_changed {
+                log.push(DebugLogEntry::ModeChange {
+                    from,
+                    to: self.current_mode,
+                });
+            }
+        }
+        self.shown_mode = Some(self.current_mode);
+
+        response
+    }
+
+    /// Show the lines in view, for `with_visible_rows_only`
+    ///
+    /// The `TextEdit` edits a copy of the visible lines, plus one line above
+    /// and below so the arrow keys can leave the view, placed where those lines
+    /// sit in the whole document; its edits are spliced back into the buffer.
+    /// Between frames its stored cursor is kept in document char indices, as
+    /// `sync_cursor_to_text_edit` writes it.
+    fn show_visible_rows(
+        &mut self,
+        ui: &mut Ui,
+        max_height: f32,
+        tab_indents: bool,
+        cursor_shape: CursorShape,
+        reveal_cursor: bool,
+    ) -> Response {
+        let id = self.text_edit_id();
+        let scroll_area_id = Id::new(format!("{}_scroll", self.id));
+        let font_id = FontId::monospace(self.font_size);
+        let row_height = ui.fonts(|fonts| fonts.row_height(&font_id));
+        let margin = Margin::symmetric(4, 2);
+        let line_count = self.buffer.line_count();
+        let line_top = |line: usize| margin.topf() + line as f32 * row_height;
+
+        // The TextEdit can't reach lines it doesn't have, so bring the cursor's
+        // line into view before typing at it or after a command moved it
+        let typing = ui.memory(|memory| memory.has_focus(id))
+            && ui.input(|input| {
+                input.events.iter().any(|event| {
+                    matches!(
+                        event,
+                        Event::Text(_) | Event::Paste(_) | Event::Key { pressed: true, .. }
+                    )
+                })
+            });
+        let cursor_top = line_top(self.buffer.current_line());
+        let mut offset = self.pending_scroll_offset.take();
+        if let Some(position) = self.pending_scroll.take() {
+            offset = Some(viewport::scroll_offset_for_row(
+                cursor_top, row_height, max_height, position,
+            ));
+        }
+        if let Some(lines) = self.pending_page_scroll.take() {
+            offset = Some((self.scroll_offset + lines as f32 * row_height).max(0.0));
+        }
+        if offset.is_none() && (reveal_cursor || typing) {
+            offset = viewport::scroll_offset_with_margin(
+                cursor_top,
+                row_height,
+                max_height,
+                self.scroll_offset,
+                self.scrolloff as f32 * row_height,
+            );
+        }
+        self.reveal_selection = false;
+
+        let mut scroll_area = ScrollArea::both()
+            .id_salt(scroll_area_id)
+            .auto_shrink([false, false])
+            .max_height(max_height);
+        if let Some(offset) = offset {
+            scroll_area = scroll_area.vertical_scroll_offset(offset);
+        }
+
+        let buffer = &mut self.buffer;
+        let highlighter = self.highlighter.as_deref();
+        let theme = highlighter.map_or(&self.theme, |highlighter| highlighter.theme());
+        let fallback_options = HighlightOptions::from_theme(theme, self.font_size);
+        let highlight_enabled = self.highlight_enabled;
+        let (show_whitespace, whitespace_color, tab_width) =
+            (self.show_whitespace, self.whitespace_color, self.tab_width);
+        let caret_color = self
+            .caret_color
+            .unwrap_or(ui.visuals().text_cursor.stroke.color);
+        let caret_blink = self.caret_blink;
+        let scrolled = scroll_area.show_viewport(ui, |ui, viewport| {
+            let top = ui.max_rect().top();
+            ui.set_height(line_top(line_count) + margin.bottomf());
+
+            let (first, last) = viewport::visible_line_range(
+                viewport.min.y - margin.topf(),
+                viewport.height(),
+                row_height,
+                line_count,
+            );
+            let (first, last) = (first.saturating_sub(1), (last + 1).min(line_count - 1));
+            let window_range = buffer.line_range(first).start..buffer.line_range(last).end;
+            let char_start = buffer.byte_to_char(window_range.start);
+            let mut window = buffer.text()[window_range.clone()].to_string();
+            let char_end = char_start + window.chars().count();
+
+            let mut state = TextEdit::load_state(ui.ctx(), id).unwrap_or_default();
+            let doc_range = state.cursor.char_range().unwrap_or_else(|| {
+                CCursorRange::one(CCursor::new(buffer.byte_to_char(buffer.cursor_position())))
+            });
+            let in_window = |cursor: CCursor| (char_start..=char_end).contains(&cursor.index);
+            let window_cursor = (in_window(doc_range.primary) && in_window(doc_range.secondary))
+                .then(|| {
+                    let mut range = doc_range;
+                    range.primary.index -= char_start;
+                    range.secondary.index -= char_start;
+                    range
+                });
+            state.cursor.set_char_range(window_cursor);
+            TextEdit::store_state(ui.ctx(), id, state);
+
+            let mut layouter = |ui: &Ui, text: &str, _wrap_width: f32| {
+                let mut job = if highlight_enabled {
+                    highlighter.map_or_else(
+                        || crate::syntax::basic_highlight(text, &fallback_options),
+                        |highlighter| highlighter.highlight(ui.ctx(), text),
+                    )
+                } else {
+                    LayoutJob::simple(
+                        text.to_owned(),
+                        font_id.clone(),
+                        ui.visuals().text_color(),
+                        f32::INFINITY,
+                    )
+                };
+                let space_width = ui.fonts(|fonts| fonts.glyph_width(&font_id, ' '));
+                crate::syntax::apply_whitespace(
+                    &mut job,
+                    show_whitespace,
+                    whitespace_color,
+                    tab_width,
+                    space_width,
+                );
+                // One row per line keeps every line where `line_top` puts it
+                job.wrap.max_width = f32::INFINITY;
+                ui.fonts(|fonts| fonts.layout_job(job))
+            };
+
+            let window_top = top + line_top(first) - margin.topf();
+            let window_bottom = top + line_top(last + 1) + margin.bottomf();
+            let rect = Rect::from_x_y_ranges(ui.max_rect().x_range(), window_top..=window_bottom);
+            let output = ui
+                .scope_builder(UiBuilder::new().max_rect(rect), |ui| {
+                    let text_cursor = &mut ui.visuals_mut().text_cursor;
+                    text_cursor.stroke.color = caret_color;
+                    if let Some((on, off)) = caret_blink {
+                        text_cursor.blink = off > 0.0;
+                        text_cursor.on_duration = on;
+                        text_cursor.off_duration = off;
+                    }
+                    if cursor_shape != CursorShape::Bar {
+                        ui.visuals_mut().text_cursor.stroke = Stroke::NONE;
+                    }
+                    TextEdit::multiline(&mut window)
+                        .id(id)
+                        .font(font_id.clone())
+                        .margin(margin)
+                        .desired_width(f32::INFINITY)
+                        .desired_rows(1)
+                        .lock_focus(tab_indents)
+                        .layouter(&mut layouter)
+                        .show(ui)
+                })
+                .inner;
+
+            let changed = output.response.changed();
+            if changed {
+                buffer.text_mut().replace_range(window_range, &window);
+            }
+            // Without a cursor in the window the TextEdit makes one up at its
+            // end; only a click or drag there moves the buffer's cursor
+            let window_after = output.cursor_range.map(|range| range.as_ccursor_range());
+            let moved = changed
+                || (window_after != window_cursor
+                    && (window_cursor.is_some() || output.response.is_pointer_button_down_on()));
+            let doc_after = if moved {
+                window_after.map(|mut range| {
+                    range.primary.index += char_start;
+                    range.secondary.index += char_start;
+                    range
+                })
+            } else {
+                Some(doc_range)
+            };
+            if let Some(range) = doc_after.filter(|_| moved) {
+                buffer.set_selection_from_cursor_range(&range);
+            }
+            let mut state = output.state.clone();
+            state.cursor.set_char_range(doc_after);
+            TextEdit::store_state(ui.ctx(), id, state);
+            (output, moved)
+        });
+
+        let (mut output, moved) = scrolled.inner;
+        let viewport_height = scrolled.inner_rect.height();
+        self.scroll_offset = scrolled.state.offset.y;
+        self.visible_lines = viewport::visible_line_range(
+            self.scroll_offset - margin.topf(),
+            viewport_height,
+            row_height,
+            line_count,
+        );
+        output.text_clip_rect = output
+            .text_clip_rect
+            .intersect(scrolled.inner_rect.intersect(ui.clip_rect()));
+        if cursor_shape != CursorShape::Bar && output.response.has_focus() {
+            Self::paint_cursor(ui, &output, cursor_shape, caret_color, self.font_size);
+        }
+
+        // Follow a cursor the TextEdit moved onto the line above or below the view
+        if moved {
+            let cursor_top = line_top(self.buffer.current_line());
+            if let Some(target) = viewport::scroll_offset_with_margin(
+                cursor_top,
+                row_height,
+                viewport_height,
+                self.scroll_offset,
+                self.scrolloff as f32 * row_height,
+            ) {
+                self.pending_scroll_offset = Some(target);
+                ui.ctx().request_repaint();
+            }
+        }
+
+        output.response
+    }
+
+    /// Paint a block or underline cursor over the character at the cursor position
+    fn paint_cursor(
+        ui: &Ui,
+        output: &TextEditOutput,
+        shape: CursorShape,
+        color: Color32,
+        font_size: f32,
+    ) {
+        let Some(cursor_range) = output.cursor_range else {
+            return;
+        };
+
+        // Cursor rect from the galley is zero-width; widen it to one character cell
+        let rect = output
+            .galley
+            .pos_from_cursor(&cursor_range.primary)
+            .translate(output.galley_pos.to_vec2());
+        let char_width = ui.fonts(|fonts| fonts.glyph_width(&FontId::monospace(font_size), 'M'));
+
+        let cursor_rect = match shape {
+            CursorShape::Block => {
+                Rect::from_min_size(rect.min, Vec2::new(char_width, rect.height()))
+            }
+            CursorShape::Underline => Rect::from_min_size(
+                rect.left_bottom() - Vec2::new(0.0, 2.0),
+                Vec2::new(char_width, 2.0),
+            ),
+            CursorShape::Bar => return,
+        };
+
+        ui.painter()
+            .with_clip_rect(output.text_clip_rect)
+            .rect_filled(cursor_rect, 0.0, color.gamma_multiply(0.5));
+    }
+
+    /// Have the `TextEdit` show the buffer's selection, anchored at the end away from the cursor
+    fn show_buffer_selection(&mut self) {
+        let cursor = self.buffer.cursor_position();
+        self.pending_selection = self.buffer.selection().map(|range| {
+            let anchor = if range.start == cursor {
+                range.end
+            } else {
+                range.start
+            };
+            (
+                self.buffer.byte_to_char(anchor),
+                self.buffer.byte_to_char(cursor),
+            )
+        });
+    }
+
+    /// Take the cursor and selection from the `TextEdit` (only reported while it has focus)
+    fn update_selection(&mut self, output: &TextEditOutput) {
+        if let Some(cursor_range) = output.cursor_range {
+            self.buffer
+                .set_selection_from_cursor_range(&cursor_range.as_ccursor_range());
+        }
+    }
+
+    /// Highlight the selection while the `TextEdit`, which only draws it when focused, isn't
+    fn paint_unfocused_selection(&self, ui: &Ui, output: &TextEditOutput) {
+        if output.response.has_focus() {
+            return;
+        }
+        let Some(range) = self.buffer.selection() else {
+            return;
+        };
+        let [start, end] = [range.start, range.end].map(|byte| {
+            output
+                .galley
+                .from_ccursor(CCursor::new(self.buffer.byte_to_char(byte)))
+        });
+
+        let painter = ui.painter().with_clip_rect(output.text_clip_rect);
+        let fill = ui.visuals().selection.bg_fill.gamma_multiply(0.5);
+        let galley = &output.galley;
+        for row_index in start.rcursor.row..=end.rcursor.row {
+            let Some(row) = galley.rows.get(row_index) else {
+                break;
+            };
+            let left = if row_index == start.rcursor.row {
+                row.x_offset(start.rcursor.column)
+            } else {
+                row.rect.left()
+            };
+            let right = if row_index == end.rcursor.row {
+                row.x_offset(end.rcursor.column)
+            } else {
+                // Show the selected newline as a little extra width
+                row.rect.right() + 4.0
+            };
+            let rect = Rect::from_x_y_ranges(left..=right, row.rect.y_range())
+                .translate(output.galley_pos.to_vec2());
+            painter.rect_filled(rect, 0.0, fill);
+        }
+    }
+
+    /// Draw the completion popup below the cursor, returning the index of a clicked item
+    fn show_completions(&self, ui: &Ui, output: &TextEditOutput) -> Option<usize> {
+        if self.completions.is_empty() || !output.response.has_focus() {
+            return None;
+        }
+        let cursor_range = output.cursor_range?;
+        let cursor_rect = output
+            .galley
+            .pos_from_cursor(&cursor_range.primary)
+            .translate(output.galley_pos.to_vec2());
+
+        let mut clicked = None;
+        egui::Area::new(Id::new(format!("{}_completions", self.id)))
+            .order(egui::Order::Foreground)
+            .fixed_pos(cursor_rect.left_bottom())
+            .show(ui.ctx(), |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    for (index, item) in self.completions.iter().enumerate() {
+                        let text = match &item.detail {
+                            Some(detail) => format!("{}  {detail}", item.label),
+                            None => item.label.clone(),
+                        };
+                        let selected = index == self.completion_selected;
+                        if ui
+                            .selectable_label(selected, RichText::new(text).monospace())
+                            .clicked()
+                        {
+                            clicked = Some(index);
+                        }
+                    }
+                });
+            });
+        clicked
+    }
+
+    /// Ask the hover provider about the character under the pointer and show its tooltip
+    fn show_hover(&self, output: &TextEditOutput) {
+        let Some(provider) = self.hover_provider.as_ref() else {
+            return;
+        };
+        let Some(char_index) = Self::hovered_char(output) else {
+            return;
+        };
+        if let Some(text) = provider(self.buffer.char_to_byte(char_index)) {
+            output.response.clone().on_hover_text_at_pointer(text);
+        }
+    }
+
+    /// Char index of the glyph under the pointer, if the pointer is over one
+    fn hovered_char(output: &TextEditOutput) -> Option<usize> {
+        let pointer = output.response.hover_pos()?;
+        if !output.text_clip_rect.contains(pointer) {
+            return None;
+        }
+        let local = pointer - output.galley_pos.to_vec2();
+
+        let mut row_start = 0;
+        for row in &output.galley.rows {
+            if row.rect.y_range().contains(local.y) {
+                return row
+                    .glyphs
+                    .iter()
+                    .position(|glyph| {
+                        glyph.chr != '\n'
+                            && (glyph.pos.x..glyph.pos.x + glyph.advance_width).contains(&local.x)
+                    })
+                    .map(|index| row_start + index);
+            }
+            row_start += row.char_count_including_newline();
+        }
+        None
+    }
+
+    /// Insert the completion at `index` and close the popup
+    fn accept_completion(&mut self, index: usize) {
+        if let Some(item) = self.completions.get(index) {
+            let text = item.text().to_string();
+            self.clear_completions();
+            self.execute_command(EditorCommand::InsertCompletion(text));
+        }
+    }
+
+    /// Work out which lines are inside the visible part of the text area
+    ///
+    /// Uses the galley's rows, so lines soft-wrapped over several rows count once.
+    fn update_visible_lines(&mut self, ui: &Ui, output: &TextEditOutput) {
+        let visible = ui.clip_rect().intersect(output.text_clip_rect);
+        let scroll_offset = visible.top() - output.galley_pos.y;
+        let rows = output
+            .galley
+            .rows
+            .iter()
+            .map(|row| (row.rect.top(), row.rect.bottom(), row.ends_with_newline));
+
+        self.visible_lines =
+            viewport::visible_line_range_in_rows(rows, scroll_offset, visible.height());
+    }
+
+    /// Record this frame's scroll offset and carry out a pending scroll request
+    /// (`set_scroll_offset`, or a `ScrollCursorLine` command)
+    ///
+    /// `managed_offset` is the offset of the editor's own scroll area, if it has one.
+    fn update_scroll(
+        &mut self,
+        ui: &Ui,
+        output: &TextEditOutput,
+        viewport: Rect,
+        managed_offset: Option<f32>,
+    ) {
+        // Measured against the whole viewport, which may start above the text
+        let text_offset = viewport.top() - output.galley_pos.y;
+        self.scroll_offset = managed_offset.unwrap_or(text_offset.max(0.0));
+
+        let cursor_line_target = self.pending_scroll.take().and_then(|position| {
+            let cursor_range = output.cursor_range?;
+            let row = output.galley.pos_from_cursor(&cursor_range.primary);
+            Some(viewport::scroll_offset_for_row(
+                row.top(),
+                row.height(),
+                viewport.height(),
+                position,
+            ))
+        });
+        let page_target = self.pending_page_scroll.take().map(|lines| {
+            let row_height = ui.fonts(|fonts| fonts.row_height(&FontId::monospace(self.font_size)));
+            (text_offset + lines as f32 * row_height).max(0.0)
+        });
+        let cursor_line_target = cursor_line_target.or(page_target);
+
+        // Scroll ahead of a cursor that moved into the margin, leaving the view
+        // alone while it is scrolled some other way
+        let cursor = output.cursor_range.map(|range| range.primary);
+        let cursor_moved = cursor.map(|cursor| cursor.ccursor.index) != self.scrolloff_cursor;
+        self.scrolloff_cursor = cursor.map(|cursor| cursor.ccursor.index);
+        let margin_target = cursor
+            .filter(|_| self.scrolloff > 0 && cursor_moved)
+            .and_then(|cursor| {
+                let row = output.galley.pos_from_cursor(&cursor);
+                viewport::scroll_offset_with_margin(
+                    row.top(),
+                    row.height(),
+                    viewport.height(),
+                    text_offset,
+                    self.scrolloff as f32 * row.height(),
+                )
+            });
+        let cursor_line_target = cursor_line_target.or(margin_target);
+
+        if let Some(offset) = managed_offset {
+            // The managed scroll area takes the new offset when it is shown next
+            if let Some(target) = cursor_line_target {
+                self.pending_scroll_offset = Some((offset + target - text_offset).max(0.0));
+            }
+        } else if let Some(target) = cursor_line_target.or(self.pending_scroll_offset.take()) {
+            // A positive delta moves the content down, i.e. scrolls up
+            ui.scroll_with_delta(Vec2::new(0.0, text_offset - target));
+        }
+    }
+
+    /// Draw the minimap to the right of the viewport and scroll to where it is clicked
+    ///
+    /// `managed_offset` is the scroll offset of the editor's own scroll area.
+    fn show_minimap(
+        &mut self,
+        ui: &Ui,
+        output: &TextEditOutput,
+        viewport: Rect,
+        managed_offset: f32,
+    ) {
+        let rect = Rect::from_min_size(
+            egui::pos2(viewport.right(), viewport.top()),
+            Vec2::new(minimap::MINIMAP_WIDTH, viewport.height()),
+        );
+        let line_lengths: Vec<usize> = self
+            .buffer
+            .text()
+            .split('\n')
+            .map(|line| line.chars().count())
+            .collect();
+        let (rows, row_height) = minimap::rows(&line_lengths, rect.height());
+
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 0.0, ui.visuals().extreme_bg_color);
+        let (first, last) = self.visible_lines;
+        let (top, bottom) =
+            minimap::span_for_lines(first, last, row_height, rows.len(), line_lengths.len());
+        painter.rect_filled(
+            Rect::from_x_y_ranges(rect.x_range(), rect.top() + top..=rect.top() + bottom),
+            0.0,
+            ui.visuals().selection.bg_fill.gamma_multiply(0.35),
+        );
+
+        let color = ui.visuals().text_color().gamma_multiply(0.35);
+        let bar_height = if row_height >= 2.0 {
+            row_height - 1.0
+        } else {
+            row_height
+        };
+        let full_width = rect.width() - 4.0;
+        for (row, &length) in rows.iter().enumerate() {
+            if length == 0 {
+                continue;
+            }
+            let width = full_width * length.min(minimap::FULL_WIDTH_COLUMNS) as f32
+                / minimap::FULL_WIDTH_COLUMNS as f32;
+            let top = rect.top() + row as f32 * row_height;
+            painter.rect_filled(
+                Rect::from_min_size(
+                    egui::pos2(rect.left() + 2.0, top),
+                    Vec2::new(width.max(1.0), bar_height),
+                ),
+                0.0,
+                color,
+            );
+        }
+
+        let id = Id::new(format!("{}_minimap", self.id));
+        let response = ui.interact(rect, id, Sense::click_and_drag());
+        if let Some(pointer) = response.interact_pointer_pos() {
+            // Center the clicked line in the viewport
+            let line = minimap::line_at(
+                pointer.y - rect.top(),
+                row_height,
+                rows.len(),
+                line_lengths.len(),
+            );
+            let start = self.buffer.line_range(line).start;
+            let row = output
+                .galley
+                .pos_from_ccursor(CCursor::new(self.buffer.byte_to_char(start)));
+            let row_top = output.galley_pos.y + row.top() - viewport.top();
+            let target = managed_offset + row_top - (viewport.height() - row.height()) / 2.0;
+            self.pending_scroll_offset = Some(target.max(0.0));
+        }
+    }
+
+    /// Paint the column ruler over the text area
+    fn paint_ruler(&self, ui: &Ui, output: &TextEditOutput) {
+        let Some(column) = self.ruler else {
+            return;
+        };
+
+        // Measure from the monospace glyph width so the ruler follows the font size
+        let char_width =
+            ui.fonts(|fonts| fonts.glyph_width(&FontId::monospace(self.font_size), 'M'));
+        let x = output.galley_pos.x + char_width * column as f32;
+        let clip = output.text_clip_rect;
+
+        ui.painter().with_clip_rect(clip).vline(
+            x,
+            clip.y_range(),
+            Stroke::new(1.0, self.ruler_color),
+        );
+    }
+
+    /// Paint a marker at the end of every line when whitespace is shown
+    ///
+    /// Spaces and tabs are replaced in the layout job, but newlines can't be
+    /// without breaking the line layout, so their markers are painted on top.
+    fn paint_newline_markers(&self, ui: &Ui, output: &TextEditOutput) {
+        if !self.show_whitespace {
+            return;
+        }
+
+        let painter = ui.painter().with_clip_rect(output.text_clip_rect);
+        let font_id = FontId::monospace(self.font_size);
+        for row in output
+            .galley
+            .rows
+            .iter()
+            .filter(|row| row.ends_with_newline)
+        {
+            let pos = output.galley_pos + row.rect.right_top().to_vec2();
+            if !output.text_clip_rect.contains(pos) {
+                continue;
+            }
+            painter.text(
+                pos,
+                egui::Align2::LEFT_TOP,
+                crate::syntax::NEWLINE_MARKER,
+                font_id.clone(),
+                self.whitespace_color,
+            );
+        }
+    }
+
+    /// Paint a vertical guide at each indentation level of every line
+    ///
+    /// A line only gets guides up to its own indentation depth, and wrapped
+    /// lines carry their guides down through all of their rows.
+    fn paint_indent_guides(&self, ui: &Ui, output: &TextEditOutput) {
+        if !self.indent_guides || self.tab_width == 0 {
+            return;
+        }
+
+        let char_width =
+            ui.fonts(|fonts| fonts.glyph_width(&FontId::monospace(self.font_size), 'M'));
+        let painter = ui.painter().with_clip_rect(output.text_clip_rect);
+        let stroke = Stroke::new(1.0, self.indent_guide_color);
+
+        let mut rows = output.galley.rows.iter();
+        for line in self.buffer.text().split('\n') {
+            // Collect the rows this line wraps onto; the last one ends with the newline
+            let Some(first_row) = rows.next() else {
+                break;
+            };
+            let mut last_row = first_row;
+            while !last_row.ends_with_newline {
+                match rows.next() {
+                    Some(row) => last_row = row,
+                    None => break,
+                }
+            }
+
+            let levels = indent_columns(line, self.tab_width) / self.tab_width;
+            let top = output.galley_pos.y + first_row.rect.top();
+            let bottom = output.galley_pos.y + last_row.rect.bottom();
+            for level in 0..levels {
+                let x = output.galley_pos.x + char_width * (level * self.tab_width) as f32;
+                painter.vline(x, top..=bottom, stroke);
+            }
+        }
+    }
+
+    /// Intercept and process keyboard input before the UI is created
+    ///
+    /// Nothing is touched unless the editor has keyboard focus, and the handlers
+    /// only remove the events they act on, so the host's shortcuts keep working.
+    fn process_input_before_ui(&mut self, ctx: &Context) {
+        if !ctx.memory(|memory| memory.has_focus(self.text_edit_id())) {
+            return;
+        }
+        let mode_before = self.current_mode;
+        if let Some(log) = self.debug_log.as_mut() {
+            ctx.input(|input| {
+                for event in &input.events {
+                    match event {
+                        Event::Key {
+                            key,
+                            pressed: true,
+                            modifiers,
+                            ..
+                        } => log.push(DebugLogEntry::Key {
+                            key: *key,
+                            modifiers: *modifiers,
+                        }),
+                        Event::Text(text) => log.push(DebugLogEntry::Text(text.clone())),
+                        _ => {}
+                    }
+                }
+            });
+        }
+
+        // Word deletion goes through the buffer in every mode that inserts text,
+        // unless there is a selection for the TextEdit to delete instead
+        let edits_text = self.edits_text();
+        let selection = TextEdit::load_state(ctx, self.text_edit_id())
+            .and_then(|state| state.cursor.char_range());
+        let has_selection =
+            selection.is_some_and(|range| range.primary.index != range.secondary.index);
+        // Tab and Shift+Tab indent or outdent every line of a multi-line selection
+        let block_lines = selection
+            .filter(|_| edits_text)
+            .and_then(|range| self.selected_lines(range));
+        let mut block_outdent = None;
+        let mut line_move = None;
+        let mut intercepted_commands = Vec::new();
+        let mut dedent_closer =
+            self.auto_dedent && edits_text && !has_selection && self.buffer.cursor_in_indentation();
+        let smart_tab = self.smart_tab
+            && edits_text
+            && !has_selection
+            && self.completions.is_empty()
+            && self.buffer.cursor_before_text();
+        let close_pairs = self.auto_close_pairs && edits_text && !has_selection && !self.overtype;
+        let chars_around_cursor = if close_pairs {
+            self.buffer.chars_around_cursor()
+        } else {
+            (None, None)
+        };
+
+        // Keep the register in step with text pasted from the system clipboard
+        let pasted = ctx.input(|input| {
+            input.events.iter().rev().find_map(|event| match event {
+                Event::Paste(text) => Some(text.clone()),
+                _ => None,
+            })
+        });
+        if let Some(text) = pasted.filter(|text| *text != self.register.text) {
+            self.register = Register {
+                text,
+                linewise: false,
+            };
+        }
+
+        // We need to manipulate the input events to handle our custom key bindings
+        ctx.input_mut(|input| {
+            // Emacs kills words into the kill ring from its own handler instead
+            if edits_text && !has_selection && self.current_mode != EditorMode::Emacs {
+                input
+                    .events
+                    .retain(|event| match word_delete_command(event) {
+                        Some(command) => {
+                            intercepted_commands.push(command);
+                            false
+                        }
+                        None => true,
+                    });
+            }
+
+            if block_lines.is_some() {
+                input.events.retain(|event| match event {
+                    Event::Key {
+                        key: Key::Tab,
+                        pressed: true,
+                        modifiers,
+                        ..
+                    } if !(modifiers.ctrl || modifiers.alt || modifiers.command) => {
+                        block_outdent = Some(modifiers.shift);
+                        false
+                    }
+                    _ => true,
+                });
+            }
+
+            // Alt+Up and Alt+Down move the cursor's line, or every selected line,
+            // Ctrl+Shift+D duplicates them, and Ctrl+/ comments them out
+            if edits_text {
+                input.events.retain(|event| match event {
+                    Event::Key {
+                        key: Key::Slash,
+                        pressed: true,
+                        modifiers,
+                        ..
+                    } if modifiers.command && !(modifiers.shift || modifiers.alt) => {
+                        intercepted_commands.push(EditorCommand::ToggleLineComment);
+                        false
+                    }
+                    Event::Key {
+                        key: Key::D,
+                        pressed: true,
+                        modifiers,
+                        ..
+                    } if modifiers.command && modifiers.shift && !modifiers.alt => {
+                        intercepted_commands.push(EditorCommand::DuplicateLine);
+                        false
+                    }
+                    Event::Key {
+                        key: key @ (Key::ArrowUp | Key::ArrowDown),
+                        pressed: true,
+                        modifiers,
+                        ..
+                    } if modifiers.alt
+                        && !(modifiers.ctrl || modifiers.shift || modifiers.command) =>
+                    {
+                        line_move = Some(*key == Key::ArrowDown);
+                        false
+                    }
+                    _ => true,
+                });
+            }
+
+            // Tab in the indentation indents the line rather than splitting it
+            if smart_tab {
+                input.events.retain(|event| match event {
+                    Event::Key {
+                        key: Key::Tab,
+                        pressed: true,
+                        modifiers,
+                        ..
+                    } if modifiers.is_none() => {
+                        intercepted_commands.push(EditorCommand::IndentLine);
+                        false
+                    }
+                    _ => true,
+                });
+            }
+
+            // A closing bracket typed into indentation lines up with its opener
+            if dedent_closer {
+                input.events.retain(|event| match event {
+                    Event::Text(text) if dedent_closer && is_block_closer(text) => {
+                        intercepted_commands.push(EditorCommand::OutdentLine);
+                        intercepted_commands.extend(text.chars().map(EditorCommand::InsertChar));
+                        dedent_closer = false;
+                        false
+                    }
+                    _ => true,
+                });
+            }
+
+            // Brackets and quotes are typed in pairs; only a lone keystroke is
+            // handled, so text typed in the same frame keeps its order
+            let text_events = input
+                .events
+                .iter()
+                .filter(|event| matches!(event, Event::Text(_)))
+                .count();
+            if close_pairs && text_events <= 1 {
+                input
+                    .events
+                    .retain(|event| match pair_command(event, chars_around_cursor) {
+                        Some(commands) => {
+                            intercepted_commands.extend(commands);
+                            false
+                        }
+                        None => true,
+                    });
+            }
+
+            // Insert toggles overtype, where typed text replaces what's under the cursor
+            if edits_text {
+                let mut overtype = self.overtype;
+                input.events.retain(|event| match event {
+                    Event::Key {
+                        key: Key::Insert,
+                        pressed: true,
+                        modifiers,
+                        ..
+                    } if modifiers.is_none() => {
+                        overtype = !overtype;
+                        false
+                    }
+                    Event::Text(text) if overtype && !has_selection => {
+                        intercepted_commands.extend(text.chars().map(|c| match c {
+                            '\n' => EditorCommand::InsertChar(c),
+                            _ => EditorCommand::ReplaceChar(c),
+                        }));
+                        false
+                    }
+                    _ => true,
+                });
+                self.overtype = overtype;
+            }
+
+            // While the completion popup is open it owns the navigation keys
+            if edits_text && !self.completions.is_empty() {
+                let count = self.completions.len();
+                let mut selected = self.completion_selected;
+                let mut accepted = None;
+                let mut dismissed = false;
+
+                input.events.retain(|event| {
+                    let Event::Key {
+                        key,
+                        pressed: true,
+                        modifiers,
+                        ..
+                    } = event
+                    else {
+                        return true;
+                    };
+                    if !modifiers.is_none() {
+                        return true;
+                    }
+                    match key {
+                        Key::ArrowDown => selected = (selected + 1) % count,
+                        Key::ArrowUp => selected = (selected + count - 1) % count,
+                        Key::Enter | Key::Tab => accepted = Some(selected),
+                        Key::Escape => dismissed = true,
+                        _ => return true,
+                    }
+                    false
+                });
+
+                self.completion_selected = selected;
+                if let Some(item) = accepted.and_then(|index| self.completions.get(index)) {
+                    intercepted_commands
+                        .push(EditorCommand::InsertCompletion(item.text().to_string()));
+                    dismissed = true;
+                }
+                if dismissed {
+                    self.clear_completions();
+                }
+            }
 
             // Events we want to remove
             let mut events_to_remove;
 
-            // Process events based on current mode
-            match self.current_mode {
-                EditorMode::Vim(_) => {
-                    // Use the dedicated Vim key handler
-                    events_to_remove = self.vim_handler.process_input(ctx, input);
-                    println!(
-                        "DEBUG: After vim_handler.process_input - events to remove: {:?}",
-                        events_to_remove
-                    );
+            // Process events based on current mode
+            match self.current_mode {
+                EditorMode::Vim(_) => {
+                    // Use the dedicated Vim key handler
+                    events_to_remove = self.vim_handler.process_input(ctx, input);
+
+                    // Look for custom cursor movement signals
+                    let mut custom_events_to_remove = Vec::new();
+                    for (i, event) in input.events.iter().enumerate() {
+                        if let Event::Text(text) = event {
+                            if text.starts_with("__CUSTOM_CURSOR__:") {
+                                custom_events_to_remove.push(i);
+                            }
+                        }
+                    }
+                    events_to_remove.extend(custom_events_to_remove);
+
+                    // Update last cursor position for Vim normal mode after commands
+                    if matches!(self.current_mode, EditorMode::Vim(VimMode::Normal)) {
+                        self.last_cursor_pos = self.buffer.cursor_position();
+                    }
+
+                    // Sync the editor mode with the handler
+                    self.current_mode = EditorMode::Vim(self.vim_handler.mode());
+                    self.update_visual_anchor();
+                }
+                EditorMode::Emacs => {
+                    // Use the dedicated Emacs key handler
+                    // The Emacs handler now generates TextEdit-compatible events directly
+                    events_to_remove = self.emacs_handler.process_input(ctx, input);
+                }
+                EditorMode::Plain => {
+                    // Plain mode never intercepts anything
+                    events_to_remove = self.plain_handler.process_input(ctx, input);
+                }
+            }
+            self.log_handler_messages();
+
+            // IME composition only reaches the TextEdit in modes that insert text,
+            // including when this frame's keys just left such a mode
+            if !self.edits_text() {
+                events_to_remove.extend(
+                    input
+                        .events
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, event)| matches!(event, Event::Ime(_)))
+                        .map(|(i, _)| i),
+                );
+            }
+
+            // Remove events in reverse order to maintain correct indices
+            events_to_remove.sort_unstable();
+            events_to_remove.dedup();
+
+            for &index in events_to_remove.iter().rev() {
+                if index < input.events.len() {
+                    input.events.remove(index);
+                }
+            }
+        });
+
+        // The selection moves with the lines, so hand it to the buffer first
+        let mut moved_selection = None;
+        if let Some(down) = line_move {
+            let (first, last) = match &block_lines {
+                Some(lines) => (*lines.start(), *lines.end()),
+                None => {
+                    let line = self.buffer.current_line();
+                    (line, line)
+                }
+            };
+            if let Some(range) = selection.filter(|_| has_selection) {
+                let [anchor, cursor] = [range.secondary.index, range.primary.index]
+                    .map(|index| self.buffer.char_to_byte(index));
+                self.buffer.set_cursor_position(cursor);
+                self.buffer
+                    .set_selection(Some(anchor.min(cursor)..anchor.max(cursor)));
+                moved_selection = Some(anchor < cursor);
+            }
+            intercepted_commands.push(if down {
+                EditorCommand::MoveLinesDown { first, last }
+            } else {
+                EditorCommand::MoveLinesUp { first, last }
+            });
+        }
+
+        // Remember where the selection ends sit relative to their line ends, which
+        // don't move when the lines are shifted
+        let mut block_selection = None;
+        if let (Some(lines), Some(outdent), Some(range)) = (block_lines, block_outdent, selection) {
+            let (first, last) = (*lines.start(), *lines.end());
+            intercepted_commands.push(if outdent {
+                EditorCommand::OutdentLines { first, last }
+            } else {
+                EditorCommand::IndentLines { first, last }
+            });
+            block_selection = Some((
+                self.line_end_anchor(range.secondary.index),
+                self.line_end_anchor(range.primary.index),
+            ));
+        }
+
+        if self.current_mode != mode_before {
+            self.clamp_cursor_on_mode_switch();
+            self.replaced_text.clear();
+        }
+
+        // Apply any buffer commands the handler queued, then move the
+        // TextEdit's cursor to wherever the commands left the buffer cursor
+        let mut commands = intercepted_commands;
+        commands.extend(match self.current_mode {
+            EditorMode::Vim(_) => self.vim_handler.take_commands(),
+            EditorMode::Emacs => self.emacs_handler.take_commands(),
+            EditorMode::Plain => self.plain_handler.take_commands(),
+        });
+        self.run_commands(commands);
+
+        let moved_range = self.buffer.selection();
+        if let (Some(forward), Some(range)) = (moved_selection, moved_range) {
+            let (anchor, cursor) = if forward {
+                (range.start, range.end)
+            } else {
+                (range.end, range.start)
+            };
+            self.pending_selection = Some((
+                self.buffer.byte_to_char(anchor),
+                self.buffer.byte_to_char(cursor),
+            ));
+        }
+
+        if let Some((secondary, primary)) = block_selection {
+            let secondary = self.resolve_line_end_anchor(secondary);
+            let primary = self.resolve_line_end_anchor(primary);
+            self.buffer.set_cursor_position(primary);
+            self.buffer
+                .set_selection(Some(secondary.min(primary)..secondary.max(primary)));
+            self.pending_selection = Some((
+                self.buffer.byte_to_char(secondary),
+                self.buffer.byte_to_char(primary),
+            ));
+        }
+    }
+
+    /// Step back onto the last character when entering Vim normal mode at a line end
+    ///
+    /// The normal-mode cursor rests on a character, never after the last one
+    /// of a line as it can in insert mode.
+    fn clamp_cursor_on_mode_switch(&mut self) {
+        if self.current_mode != EditorMode::Vim(VimMode::Normal) {
+            return;
+        }
+        let line = self.buffer.current_line();
+        let range = self.buffer.line_range(line);
+        if !range.is_empty() && self.buffer.cursor_position() == range.end {
+            self.buffer.move_cursor_left();
+            self.cursor_sync_pending = true;
+        }
+    }
+
+    /// Whether typed text goes into the buffer in the current mode
+    const fn edits_text(&self) -> bool {
+        matches!(
+            self.current_mode,
+            EditorMode::Emacs | EditorMode::Plain | EditorMode::Vim(VimMode::Insert)
+        )
+    }
+
+    /// Track the uncommitted IME composition the `TextEdit` inserted this frame
+    ///
+    /// The `TextEdit` puts preedit text into the buffer and selects it until the
+    /// composition is committed or cleared.
+    fn update_ime_preedit(&mut self, ui: &Ui, output: &TextEditOutput) {
+        let composing = ui.input(|input| {
+            input.events.iter().rev().find_map(|event| match event {
+                Event::Ime(ImeEvent::Preedit(text)) if text == "\n" || text == "\r" => None,
+                Event::Ime(ImeEvent::Preedit(text)) => Some(!text.is_empty()),
+                Event::Ime(ImeEvent::Commit(_) | ImeEvent::Disabled) => Some(false),
+                _ => None,
+            })
+        });
+        match composing {
+            Some(true) => {
+                self.ime_preedit = output.cursor_range.map(|range| {
+                    let Range { start, end } = range.as_sorted_char_range();
+                    self.buffer.char_to_byte(start)..self.buffer.char_to_byte(end)
+                });
+            }
+            Some(false) => self.ime_preedit = None,
+            None => {}
+        }
+    }
+
+    /// Drop an uncommitted IME composition from the buffer
+    fn cancel_ime_composition(&mut self) {
+        if let Some(range) = self.ime_preedit.take() {
+            self.buffer.delete_range(range);
+            self.notify_changed();
+            self.cursor_sync_pending = true;
+        }
+    }
+
+    /// The lines covered by a selection spanning more than one line
+    ///
+    /// A selection ending at the very start of a line doesn't include that line.
+    fn selected_lines(&mut self, range: CCursorRange) -> Option<RangeInclusive<usize>> {
+        let [start, end] = range
+            .sorted()
+            .map(|cursor| self.buffer.char_to_byte(cursor.index));
+        if self.buffer.line_at(start) == self.buffer.line_at(end) {
+            return None;
+        }
+        Some(self.buffer.lines_in_range(start..end))
+    }
+
+    /// Toggle comments on the selected lines, keeping the selection where the
+    /// mode shows one (Vim normal mode drops it, as after visual `gc`)
+    fn toggle_comments(&mut self) {
+        let Some(prefix) = self.line_comment_prefix() else {
+            return;
+        };
+        let cursor = self.buffer.cursor_position();
+        let selection = self.buffer.selection();
+        let lines = match selection.clone() {
+            Some(range) => self.buffer.lines_in_range(range),
+            None => {
+                let line = self.buffer.current_line();
+                line..=line
+            }
+        };
+        let ends = selection
+            .filter(|_| self.current_mode != EditorMode::Vim(VimMode::Normal))
+            .map(|range| {
+                let anchor = if range.start == cursor {
+                    range.end
+                } else {
+                    range.start
+                };
+                let [anchor, cursor] = [anchor, cursor].map(|pos| {
+                    let index = self.buffer.byte_to_char(pos);
+                    self.line_end_anchor(index)
+                });
+                (anchor, cursor)
+            });
+
+        self.buffer.toggle_line_comments(lines, &prefix);
+        if let Some((anchor, cursor)) = ends {
+            let [anchor, cursor] = [anchor, cursor].map(|end| self.resolve_line_end_anchor(end));
+            self.buffer.set_cursor_position(cursor);
+            self.buffer
+                .set_selection(Some(anchor.min(cursor)..anchor.max(cursor)));
+        } else {
+            self.buffer.set_selection(None);
+        }
+        self.show_buffer_selection();
+    }
+
+    /// The line comment prefix for the current language from the comment
+    /// registry, `//` when no language is selected, or `None` for a language
+    /// without line comments
+    fn line_comment_prefix(&self) -> Option<String> {
+        match self.language.as_deref() {
+            Some(language) => self
+                .comment_registry
+                .line_prefix(language)
+                .map(str::to_string),
+            None => Some("//".to_string()),
+        }
+    }
+
+    /// A char index as (line, bytes before the line end)
+    fn line_end_anchor(&mut self, char_index: usize) -> (usize, usize) {
+        let byte = self.buffer.char_to_byte(char_index);
+        let line = self.buffer.line_at(byte);
+        (line, self.buffer.line_range(line).end - byte)
+    }
+
+    /// The byte offset for a `line_end_anchor`, clamped to the start of its line
+    fn resolve_line_end_anchor(&mut self, (line, from_end): (usize, usize)) -> usize {
+        let range = self.buffer.line_range(line);
+        range.end.saturating_sub(from_end).max(range.start)
+    }
+
+    /// The logical direction of a left/right motion, which flips inside
+    /// right-to-left text when motion is visual
+    fn logical_movement(&self, movement: CursorMovement) -> CursorMovement {
+        if self.logical_motion || !self.buffer.is_rtl_at(self.buffer.cursor_position()) {
+            return movement;
+        }
+        match movement {
+            CursorMovement::Left => CursorMovement::Right,
+            CursorMovement::Right => CursorMovement::Left,
+            other => other,
+        }
+    }
+
+    /// Move the cursor a page up or down and scroll the view along with it
+    ///
+    /// A page is the visible lines less one, so a line of context stays on
+    /// screen, and the cursor keeps its place in the viewport.
+    fn move_page(&mut self, down: bool) {
+        let (first, last) = self.visible_lines;
+        let page = (last - first).max(1);
+        let line = self.buffer.current_line();
+        if down {
+            self.buffer.move_cursor_down_by(page);
+        } else {
+            self.buffer.move_cursor_up_by(page);
+        }
+        let moved = self.buffer.current_line() as isize - line as isize;
+        if moved != 0 {
+            self.pending_page_scroll = Some(moved);
+        }
+    }
+
+    /// Anchor the selection at the cursor on entering Vim visual mode, and drop it on leaving
+    fn update_visual_anchor(&mut self) {
+        if self.current_mode != EditorMode::Vim(VimMode::Visual) {
+            self.visual_anchor = None;
+        } else if self.visual_anchor.is_none() {
+            self.visual_anchor = Some(self.buffer.cursor_position());
+        }
+    }
+
+    /// Put killed text on the kill ring, joining it to the previous kill if requested
+    ///
+    /// Backward kills go in front of the text they join, so the entry reads in
+    /// buffer order.
+    fn record_kill(&mut self, killed: String, backward: bool) {
+        if std::mem::take(&mut self.append_next_kill) {
+            if backward {
+                self.kill_ring.prepend(&killed);
+            } else {
+                self.kill_ring.append(&killed);
+            }
+        } else {
+            self.kill_ring.push(killed);
+        }
+    }
+
+    /// Execute commands, then report any change once and sync the `TextEdit` cursor
+    fn run_commands(&mut self, commands: Vec<EditorCommand>) {
+        if commands.is_empty() {
+            return;
+        }
+
+        let revision = self.buffer.revision();
+        for command in commands {
+            self.execute_command(command);
+        }
+        // Saving may already have reported its own cleanup edit
+        if self.buffer.revision() != revision && self.buffer.revision() != self.notified_revision {
+            self.notify_changed();
+        }
+        self.cursor_sync_pending = true;
+    }
+
+    /// Apply a single command directly to the buffer
+    fn execute_command(&mut self, command: EditorCommand) {
+        if let Some(observer) = self.command_observer.as_mut() {
+            observer(&command);
+        }
+        if let Some(log) = self.debug_log.as_mut() {
+            log.push(DebugLogEntry::Command(command.clone()));
+        }
+
+        match command {
+            EditorCommand::InsertChar(c) => self.buffer.insert_char(c),
+            EditorCommand::ReplaceChar(c) => {
+                let replaced = self.buffer.replace_char(c);
+                if self.current_mode == EditorMode::Vim(VimMode::Replace) {
+                    self.replaced_text.push(replaced);
+                }
+            }
+            // Before the first replaced character Backspace only moves left, as in Vim
+            EditorCommand::RestoreReplacedChar => match self.replaced_text.pop() {
+                Some(original) => self.buffer.restore_replaced_char(&original),
+                None => self.buffer.move_cursor_left(),
+            },
+            EditorCommand::DeleteChar => self.buffer.delete_char(),
+            EditorCommand::DeleteCharForward => self.buffer.delete_char_forward(),
+            EditorCommand::DeleteWord => self.buffer.delete_word_backward(),
+            EditorCommand::DeleteWordForward => self.buffer.delete_word_forward(),
+            EditorCommand::DeleteToLineStart => self.buffer.delete_to_line_start(),
+            EditorCommand::SelectAll => self.select_all(),
+            EditorCommand::DeleteLine => {
+                self.buffer.delete_line();
+            }
+            // Copy and cut take the selection, or the cursor's line without one
+            EditorCommand::Copy => match self.buffer.selected_text().map(str::to_string) {
+                Some(text) => self.set_register(text, false),
+                None => {
+                    let text = self.buffer.current_line_text();
+                    self.set_register(text, true);
+                }
+            },
+            EditorCommand::Cut => match self.buffer.selected_text().map(str::to_string) {
+                Some(text) => {
+                    self.buffer.delete_selection();
+                    self.set_register(text, false);
+                }
+                None => {
+                    let text = self.buffer.delete_line();
+                    self.set_register(text, true);
+                }
+            },
+            // Whole lines go on their own lines unless they replace a selection
+            EditorCommand::Paste if self.register.linewise && self.buffer.selection().is_none() => {
+                self.buffer.paste_lines_below(&self.register.text);
+            }
+            EditorCommand::PasteAbove
+                if self.register.linewise && self.buffer.selection().is_none() =>
+            {
+                self.buffer.paste_lines_above(&self.register.text);
+            }
+            EditorCommand::Paste | EditorCommand::PasteAbove => {
+                self.buffer.insert_str(&self.register.text);
+            }
+            EditorCommand::NewLine => self.buffer.insert_newline(),
+            EditorCommand::KillLine => {
+                let killed = self.buffer.kill_line();
+                self.record_kill(killed, false);
+            }
+            EditorCommand::KillWord => {
+                let killed = self.buffer.kill_word_forward();
+                self.record_kill(killed, false);
+            }
+            EditorCommand::KillWordBackward => {
+                let killed = self.buffer.kill_word_backward();
+                self.record_kill(killed, true);
+            }
+            EditorCommand::KillToLineStart => {
+                let killed = self.buffer.kill_to_line_start();
+                self.record_kill(killed, true);
+            }
+            EditorCommand::KillRegion => match self.buffer.selected_text().map(str::to_string) {
+                Some(killed) => {
+                    self.buffer.delete_selection();
+                    self.record_kill(killed, false);
+                }
+                None => {
+                    let killed = self.buffer.kill_word_backward();
+                    self.record_kill(killed, true);
+                }
+            },
+            EditorCommand::AppendNextKill => self.append_next_kill = true,
+            EditorCommand::Yank => {
+                if let Some(text) = self.kill_ring.yank() {
+                    self.buffer.insert_str(text);
+                }
+            }
+            EditorCommand::NewLineAndIndent => self.buffer.insert_newline_and_indent(),
+            EditorCommand::OpenLine => self.buffer.open_line(),
+            EditorCommand::OpenLineBelow => self.buffer.open_line_below(),
+            EditorCommand::OpenLineAbove => self.buffer.open_line_above(),
+            EditorCommand::InsertCompletion(text) => self.buffer.replace_word_prefix(&text),
+            EditorCommand::InsertPair(open, close) => self.buffer.insert_pair(open, close),
+            // Block indent uses a tab, just like pressing Tab without a selection
+            EditorCommand::IndentLines { first, last } => {
+                self.buffer.indent_lines(first..=last, "\t");
+            }
+            EditorCommand::OutdentLines { first, last } => {
+                self.buffer.outdent_lines(first..=last, self.tab_width);
+            }
+            // One level is a tab, as with block indent
+            EditorCommand::IndentLine => self.buffer.indent_current_line("\t"),
+            EditorCommand::OutdentLine => {
+                let line = self.buffer.current_line();
+                self.buffer.outdent_line(line, self.tab_width);
+            }
+            EditorCommand::DuplicateLine => {
+                self.buffer.duplicate_lines();
+                self.show_buffer_selection();
+            }
+            EditorCommand::ToggleLineComment => self.toggle_comments(),
+            EditorCommand::MoveLinesUp { first, last } => {
+                self.buffer.move_lines(first..=last, false);
+            }
+            EditorCommand::MoveLinesDown { first, last } => {
+                self.buffer.move_lines(first..=last, true);
+            }
+            EditorCommand::MoveCursor(movement) => {
+                let movement = self.logical_movement(movement);
+                self.buffer.move_cursor(movement);
+            }
+            EditorCommand::PageUp => self.move_page(false),
+            EditorCommand::PageDown => self.move_page(true),
+            EditorCommand::PushJump => self.jumplist.push(self.buffer.cursor_position()),
+            EditorCommand::JumpBack => {
+                if let Some(pos) = self.jumplist.back(self.buffer.cursor_position()) {
+                    self.buffer.set_cursor_position(pos);
+                }
+            }
+            EditorCommand::JumpForward => {
+                if let Some(pos) = self.jumplist.forward() {
+                    self.buffer.set_cursor_position(pos);
+                }
+            }
+            EditorCommand::ChangeMode(mode) => self.set_mode(mode),
+            EditorCommand::RunCommandLine(line) => self.run_command_line(&line),
+            EditorCommand::Save => self.save(),
+            EditorCommand::Quit => self.quit(),
+            EditorCommand::ScrollCursorLine(position) => self.pending_scroll = Some(position),
+            EditorCommand::Custom(name) => self.run_custom_command(&name),
+        }
+    }
+
+    /// Run the action registered for a custom command; unknown names do nothing
+    fn run_custom_command(&mut self, name: &str) {
+        // Take the action out while it runs so it can borrow the editor
+        let Some(mut action) = self.custom_commands.remove(name) else {
+            return;
+        };
+        action(self);
+        // Keep a replacement the action registered for itself
+        self.custom_commands
+            .entry(name.to_string())
+            .or_insert(action);
+    }
+
+    /// Hold text for `Paste` and put it on the system clipboard
+    ///
+    /// `linewise` text is whole lines, pasted below the cursor's line (above with `PasteAbove`).
+    fn set_register(&mut self, text: String, linewise: bool) {
+        if text.is_empty() {
+            return;
+        }
+        self.pending_clipboard = Some(text.clone());
+        self.register = Register { text, linewise };
+    }
+
+    /// Parse and run a line typed on the Vim command line
+    fn run_command_line(&mut self, line: &str) {
+        self.command_message = None;
+        let command = match command_line::parse(line) {
+            Ok(command) => command,
+            Err(ParseError::Empty) => return,
+            Err(err) => {
+                self.command_message = Some(err.to_string());
+                return;
+            }
+        };
+
+        match command {
+            ExCommand::Write => self.save(),
+            // The host decides what to do with unsaved changes, forced or not
+            ExCommand::Quit { .. } => self.quit(),
+            // A failed save still asks to quit; the dirty flag tells the host it failed
+            ExCommand::WriteQuit => {
+                self.save();
+                self.quit();
+            }
+            ExCommand::GoToLine(line) => {
+                self.jumplist.push(self.buffer.cursor_position());
+                self.buffer.move_cursor_to(line.saturating_sub(1), 0);
+            }
+            ExCommand::Substitute {
+                range,
+                pattern,
+                replacement,
+                global,
+            } => {
+                let lines = match range {
+                    LineRange::Current => {
+                        let line = self.buffer.current_line();
+                        line..=line
+                    }
+                    LineRange::All => 0..=usize::MAX,
+                    LineRange::Lines(first, last) => first - 1..=last - 1,
+                };
+                if self
+                    .buffer
+                    .substitute(lines, &pattern, &replacement, global)
+                    == 0
+                {
+                    self.command_message = Some(format!("Pattern not found: {pattern}"));
+                }
+            }
+        }
+    }
+
+    /// Hand the text to the save callback and mark the editor clean
+    fn save(&mut self) {
+        if self.save_callback.is_none() {
+            self.command_message = Some("No save handler configured".to_string());
+            return;
+        }
+        if self.trim_trailing_whitespace_on_save
+            && self
+                .buffer
+                .trim_trailing_whitespace(self.final_newline_on_save)
+        {
+            self.notify_changed();
+        }
+        if let Some(callback) = self.save_callback.as_mut() {
+            callback(self.buffer.text());
+        }
+        self.mark_clean();
+    }
+
+    /// Fire the quit callback with whether there are unsaved changes
+    fn quit(&mut self) {
+        if let Some(callback) = self.quit_callback.as_mut() {
+            callback(self.dirty);
+        }
+    }
+
+    /// A copy of the text to compare the `TextEdit`'s own edits against
+    ///
+    /// Edits the `TextEdit` makes itself are recorded by comparing the text
+    /// afterwards, so keep a copy while there is input that could change it.
+    fn text_before_edit(&self, ctx: &Context) -> Option<String> {
+        (self.buffer.records_edits()
+            && ctx.input(|input| !input.events.is_empty())
+            && ctx.memory(|memory| memory.has_focus(self.text_edit_id())))
+        .then(|| self.buffer.text().to_string())
+    }
+
+    /// The id of the inner `TextEdit`, used to read and write its state
+    fn text_edit_id(&self) -> Id {
+        Id::new(format!("{}_edit", self.id))
+    }
+
+    /// Push the buffer's cursor position (or a pending selection) into the `TextEdit`'s stored state
+    fn sync_cursor_to_text_edit(&mut self, ctx: &Context) {
+        let id = self.text_edit_id();
+        // The TextEdit works in char indices while the buffer uses byte offsets
+        let char_index = self.buffer.byte_to_char(self.buffer.cursor_position());
+
+        // A visual selection stays anchored while the cursor moves
+        let anchor = self
+            .visual_anchor
+            .map(|anchor| self.buffer.byte_to_char(anchor));
+        let range = match (self.pending_selection.take(), anchor) {
+            (Some((secondary, primary)), _) => {
+                CCursorRange::two(CCursor::new(secondary), CCursor::new(primary))
+            }
+            (None, Some(anchor)) => {
+                CCursorRange::two(CCursor::new(anchor), CCursor::new(char_index))
+            }
+            (None, None) => CCursorRange::one(CCursor::new(char_index)),
+        };
+
+        let mut state = TextEdit::load_state(ctx, id).unwrap_or_default();
+        state.cursor.set_char_range(Some(range));
+        TextEdit::store_state(ctx, id, state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syntax::languages::{LanguageHighlighter, RustTokenizer};
+    use std::cell::{Cell, RefCell};
+    use std::rc::Rc;
+
+    use egui::{vec2, CentralPanel, Modifiers, Pos2, RawInput};
+
+    /// Runs an editor headlessly, one egui frame per call, with its `TextEdit` focused
+    struct Harness {
+        ctx: Context,
+        time: f64,
+        screen: Rect,
+        /// Keys pressed in the last frame, released at the start of the next
+        held: Vec<Key>,
+    }
+
+    impl Harness {
+        fn new() -> Self {
+            Self {
+                ctx: Context::default(),
+                time: 0.0,
+                screen: Rect::from_min_size(Pos2::ZERO, vec2(800.0, 600.0)),
+                held: Vec::new(),
+            }
+        }
+
+        /// Show `editor` for one frame with `events` and held `modifiers`
+        fn frame_with(
+            &mut self,
+            editor: &mut EditorWidget,
+            events: Vec<Event>,
+            modifiers: Modifiers,
+        ) {
+            // Keys pressed in the last frame are let go first
+            let mut all_events: Vec<Event> = std::mem::take(&mut self.held)
+                .into_iter()
+                .map(|key| Event::Key {
+                    key,
+                    physical_key: None,
+                    pressed: false,
+                    repeat: false,
+                    modifiers: Modifiers::NONE,
+                })
+                .collect();
+            self.held = events
+                .iter()
+                .filter_map(|event| match event {
+                    Event::Key {
+                        key, pressed: true, ..
+                    } => Some(*key),
+                    _ => None,
+                })
+                .collect();
+            all_events.extend(events);
+
+            // Requesting focus again would reset the editor's focus lock filter
+            let id = editor.text_edit_id();
+            if self.time > 0.0 && !self.ctx.memory(|memory| memory.has_focus(id)) {
+                self.ctx.memory_mut(|memory| memory.request_focus(id));
+            }
+            self.time += 0.05;
+            let raw = RawInput {
+                time: Some(self.time),
+                screen_rect: Some(self.screen),
+                modifiers,
+                events: all_events,
+                ..RawInput::default()
+            };
+            let _ = self.ctx.run(raw, |ctx| {
+                CentralPanel::default().show(ctx, |ui| {
+                    editor.show(ui);
+                });
+            });
+        }
+
+        fn frame(&mut self, editor: &mut EditorWidget, events: Vec<Event>) {
+            self.frame_with(editor, events, Modifiers::NONE);
+        }
+
+        /// Let `seconds` pass before the next frame
+        fn wait(&mut self, seconds: f64) {
+            self.time += seconds;
+        }
+
+        /// Show an editor for the first time and focus it
+        fn start(&mut self, editor: &mut EditorWidget) {
+            self.frame(editor, Vec::new());
+            self.frame(editor, Vec::new());
+        }
+
+        /// Press `key` with `modifiers`, in a frame of its own
+        fn press(&mut self, editor: &mut EditorWidget, key: Key, modifiers: Modifiers) {
+            self.frame_with(editor, vec![key_event(key, modifiers)], modifiers);
+        }
+
+        /// Type `text` one character per frame, as key presses with their text
+        fn type_keys(&mut self, editor: &mut EditorWidget, text: &str) {
+            for c in text.chars() {
+                let (key, shift) = key_for_char(c);
+                let modifiers = if shift {
+                    Modifiers::SHIFT
+                } else {
+                    Modifiers::NONE
+                };
+                let mut events = Vec::new();
+                if let Some(key) = key {
+                    events.push(key_event(key, modifiers));
+                }
+                events.push(Event::Text(c.to_string()));
+                self.frame_with(editor, events, modifiers);
+            }
+        }
+    }
+
+    fn key_event(key: Key, modifiers: Modifiers) -> Event {
+        Event::Key {
+            key,
+            physical_key: None,
+            pressed: true,
+            repeat: false,
+            modifiers,
+        }
+    }
+
+    /// The key that types `c` on a US keyboard, and whether Shift is held
+    fn key_for_char(c: char) -> (Option<Key>, bool) {
+        let shifted = |key| (Some(key), true);
+        match c {
+            'a'..='z' | '0'..='9' => (Key::from_name(&c.to_ascii_uppercase().to_string()), false),
+            'A'..='Z' => (Key::from_name(&c.to_string()), true),
+            ' ' => (Some(Key::Space), false),
+            '/' => (Some(Key::Slash), false),
+            ':' => shifted(Key::Semicolon),
+            '$' => shifted(Key::Num4),
+            '%' => shifted(Key::Num5),
+            '{' => shifted(Key::OpenBracket),
+            '}' => shifted(Key::CloseBracket),
+            '(' => shifted(Key::Num9),
+            ')' => shifted(Key::Num0),
+            '[' => (Some(Key::OpenBracket), false),
+            ']' => (Some(Key::CloseBracket), false),
+            _ => (None, false),
+        }
+    }
+
+    #[test]
+    fn search_matches_get_backgrounds_in_char_indices() {
+        let colors = (Color32::YELLOW, Color32::RED);
+        let backgrounds = search_match_backgrounds("é foo Foo foo", "foo", false, 6, colors);
+        assert_eq!(
+            backgrounds,
+            vec![
+                (2..5, Color32::YELLOW),
+                (6..9, Color32::RED),
+                (10..13, Color32::YELLOW),
+            ]
+        );
+        let backgrounds = search_match_backgrounds("é foo Foo foo", "foo", true, 0, colors);
+        assert_eq!(
+            backgrounds,
+            vec![(2..5, Color32::YELLOW), (10..13, Color32::YELLOW)]
+        );
+    }
+
+    #[test]
+    fn layout_sections_over_a_match_carry_the_search_background() {
+        let theme = HighlightTheme::default();
+        let text = "let foo = foo();";
+        let mut job = crate::syntax::basic_highlight(text, &HighlightOptions::default());
+        let matches = search_match_backgrounds(
+            text,
+            "foo",
+            true,
+            0,
+            (theme.search_match, theme.current_match),
+        );
+        crate::syntax::apply_range_backgrounds(&mut job, &matches);
+
+        // Every section overlapping a range, with its background
+        let backgrounds_over = |range: Range<usize>| -> Vec<Color32> {
+            job.sections
+                .iter()
+                .filter(|section| {
+                    section.byte_range.start < range.end && range.start < section.byte_range.end
+                })
+                .map(|section| section.format.background)
+                .collect()
+        };
+        assert_eq!(backgrounds_over(4..7), vec![theme.search_match]);
+        assert_eq!(backgrounds_over(10..13), vec![theme.search_match]);
+        assert!(!backgrounds_over(0..3).contains(&theme.search_match));
+
+        let cursor_on_second = search_match_backgrounds(
+            text,
+            "foo",
+            true,
+            11,
+            (theme.search_match, theme.current_match),
+        );
+        assert_eq!(cursor_on_second[1].1, theme.current_match);
+    }
+
+    #[test]
+    fn delete_line_removes_the_cursors_line() {
+        let mut editor = EditorWidget::new("delete_line");
+        editor.set_text("one\ntwo\nthree");
+        editor.move_cursor_to(1, 1);
+        editor.apply_command(EditorCommand::DeleteLine);
+        assert_eq!(editor.text(), "one\nthree");
+        assert_eq!(editor.buffer.cursor_position(), 4);
+
+        editor.move_cursor_to(1, 2);
+        editor.apply_command(EditorCommand::DeleteLine);
+        assert_eq!(editor.text(), "one");
+    }
+
+    #[test]
+    fn delete_word_removes_the_word_before_the_cursor() {
+        let mut editor = EditorWidget::new("delete_word");
+        editor.set_text("let value = 1;");
+        editor.move_cursor_to(0, 9);
+        editor.apply_command(EditorCommand::DeleteWord);
+        assert_eq!(editor.text(), "let  = 1;");
+        assert_eq!(editor.buffer.cursor_position(), 4);
+    }
+
+    #[test]
+    fn ctrl_backspace_and_ctrl_delete_delete_words_in_every_insert_mode() {
+        for mode in [
+            EditorMode::Plain,
+            EditorMode::Emacs,
+            EditorMode::Vim(VimMode::Insert),
+        ] {
+            let mut harness = Harness::new();
+            let mut editor = EditorWidget::new("ctrl_word_delete").with_mode(mode);
+            editor.set_text("foo.bar(baz) qux");
+            editor.move_cursor_to(0, 7);
+            harness.start(&mut editor);
+
+            harness.press(&mut editor, Key::Backspace, Modifiers::CTRL);
+            assert_eq!(editor.text(), "foo.(baz) qux", "{mode:?}");
+            assert_eq!(editor.buffer.cursor_position(), 4, "{mode:?}");
+
+            harness.press(&mut editor, Key::Delete, Modifiers::CTRL);
+            assert_eq!(editor.text(), "foo.baz) qux", "{mode:?}");
+            harness.press(&mut editor, Key::Delete, Modifiers::CTRL);
+            assert_eq!(editor.text(), "foo.) qux", "{mode:?}");
+            assert_eq!(editor.buffer.cursor_position(), 4, "{mode:?}");
+        }
+    }
+
+    #[test]
+    fn accepting_a_completion_replaces_the_typed_prefix() {
+        let mut harness = Harness::new();
+        let mut editor = EditorWidget::new("completion").with_mode(EditorMode::Plain);
+        editor.set_text("fn main() { pr }");
+        editor.move_cursor_to(0, 14);
+        harness.start(&mut editor);
+
+        editor.set_completions(vec![
+            CompletionItem::new("print!"),
+            CompletionItem::new("println!").with_insert_text("println!(\"\")"),
+        ]);
+        harness.press(&mut editor, Key::ArrowDown, Modifiers::NONE);
+        harness.press(&mut editor, Key::Enter, Modifiers::NONE);
+        assert_eq!(editor.text(), "fn main() { println!(\"\") }");
+        assert_eq!(editor.buffer.cursor_position(), 24);
+        assert!(editor.completions().is_empty());
+    }
+
+    #[test]
+    fn a_closer_typed_into_indentation_is_dedented_when_enabled() {
+        let text = "fn f() {\n    if x {\n        y();\n        ";
+        for (enabled, expected) in [
+            (true, "fn f() {\n    if x {\n        y();\n    }"),
+            (false, "fn f() {\n    if x {\n        y();\n        }"),
+        ] {
+            let mut harness = Harness::new();
+            let mut editor = EditorWidget::new("auto_dedent")
+                .with_mode(EditorMode::Plain)
+                .with_auto_dedent(enabled);
+            editor.set_text(text);
+            editor.move_cursor_to(3, 8);
+            harness.start(&mut editor);
+
+            harness.type_keys(&mut editor, "}");
+            assert_eq!(editor.text(), expected);
+        }
+
+        // After other text on the line the closer is typed as is
+        let mut harness = Harness::new();
+        let mut editor = EditorWidget::new("auto_dedent")
+            .with_mode(EditorMode::Plain)
+            .with_auto_dedent(true);
+        editor.set_text("    a");
+        editor.move_cursor_to(0, 5);
+        harness.start(&mut editor);
+        harness.type_keys(&mut editor, "]");
+        assert_eq!(editor.text(), "    a]");
+    }
+
+    #[test]
+    fn tab_and_shift_tab_shift_every_selected_line_in_one_edit() {
+        let mut harness = Harness::new();
+        let mut editor = EditorWidget::new("block_indent")
+            .with_mode(EditorMode::Plain)
+            .with_edit_recording(true);
+        editor.set_text("one\n  two\nthree\nfour");
+        harness.start(&mut editor);
+        editor.select_range(1, 13);
+        harness.frame(&mut editor, Vec::new());
+        let _ = editor.take_edits();
+
+        harness.press(&mut editor, Key::Tab, Modifiers::NONE);
+        assert_eq!(editor.text(), "\tone\n\t  two\n\tthree\nfour");
+        assert_eq!(editor.take_edits().len(), 1);
+        assert_eq!(editor.selected_text(), Some("ne\n\t  two\n\tthr"));
+
+        harness.press(&mut editor, Key::Tab, Modifiers::SHIFT);
+        assert_eq!(editor.text(), "one\n  two\nthree\nfour");
+        assert_eq!(editor.take_edits().len(), 1);
+        assert_eq!(editor.selected_text(), Some("ne\n  two\nthr"));
+        harness.frame(&mut editor, Vec::new());
+        assert_eq!(editor.selected_text(), Some("ne\n  two\nthr"));
+    }
+
+    #[test]
+    fn editors_on_a_shared_buffer_see_each_others_edits() {
+        let shared = SharedBuffer::new("shared text");
+        let (mut left_harness, mut right_harness) = (Harness::new(), Harness::new());
+        let mut left = EditorWidget::new("left")
+            .with_mode(EditorMode::Plain)
+            .with_shared_buffer(shared.clone());
+        let mut right = EditorWidget::new("right")
+            .with_mode(EditorMode::Plain)
+            .with_shared_buffer(shared.clone());
+        left_harness.start(&mut left);
+        right.move_cursor_to(0, 11);
+        right_harness.start(&mut right);
+
+        left.move_cursor_to(0, 0);
+        left_harness.frame(&mut left, Vec::new());
+        left_harness.type_keys(&mut left, "my ");
+        assert_eq!(shared.text(), "my shared text");
+        right_harness.frame(&mut right, Vec::new());
+        assert_eq!(right.text(), "my shared text");
+        // The other view's cursor keeps its byte offset
+        assert_eq!(right.buffer.cursor_position(), 11);
+
+        right.apply_command(EditorCommand::InsertChar('!'));
+        left_harness.frame(&mut left, Vec::new());
+        assert_eq!(left.text(), "my shared t!ext");
+
+        // A plain handle changes both views
+        shared.set_text("replaced");
+        left_harness.frame(&mut left, Vec::new());
+        right_harness.frame(&mut right, Vec::new());
+        assert_eq!((left.text(), right.text()), ("replaced", "replaced"));
+    }
+
+    #[test]
+    fn vim_zt_zz_and_zb_scroll_the_cursor_line_into_place() {
+        let mut harness = Harness::new();
+        let mut editor = EditorWidget::new("scroll_cursor_line")
+            .with_mode(EditorMode::Vim(VimMode::Normal))
+            .with_scroll_area(true);
+        editor.set_text(vec!["line"; 200].join("\n"));
+        editor.move_cursor_to(100, 0);
+        harness.start(&mut editor);
+
+        let mut scroll = |keys: &str| {
+            harness.type_keys(&mut editor, keys);
+            harness.frame(&mut editor, Vec::new());
+            harness.frame(&mut editor, Vec::new());
+            editor.scroll_offset()
+        };
+        let top = scroll("zt");
+        let center = scroll("zz");
+        let bottom = scroll("zb");
+        assert!(top > 0.0);
+        assert!(bottom < center && center < top, "{bottom} {center} {top}");
+        assert!(((top - center) - (center - bottom)).abs() < 1.0);
+        assert_eq!(editor.buffer.current_line(), 100);
+    }
+
+    #[test]
+    fn a_saved_scroll_offset_restores_into_a_new_editor() {
+        let text = vec!["line"; 200].join("\n");
+        let mut harness = Harness::new();
+        let mut editor = EditorWidget::new("scroll_saved").with_scroll_area(true);
+        editor.set_text(text.clone());
+        harness.start(&mut editor);
+        editor.set_scroll_offset(300.0);
+        harness.frame(&mut editor, Vec::new());
+        harness.frame(&mut editor, Vec::new());
+        let saved = editor.scroll_offset();
+        assert!((saved - 300.0).abs() < 0.5, "{saved}");
+
+        let mut harness = Harness::new();
+        let mut restored = EditorWidget::new("scroll_restored").with_scroll_area(true);
+        restored.set_text(text);
+        restored.set_scroll_offset(saved);
+        harness.start(&mut restored);
+        harness.frame(&mut restored, Vec::new());
+        assert_eq!(restored.scroll_offset(), saved);
+
+        // Offsets past the end are clamped to the text height
+        restored.set_scroll_offset(1.0e6);
+        harness.frame(&mut restored, Vec::new());
+        harness.frame(&mut restored, Vec::new());
+        let end = restored.scroll_offset();
+        assert!(end > saved && end < 1.0e6, "{end}");
+    }
+
+    #[test]
+    fn apply_command_moves_the_cursor_and_reports_edits() {
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let changes = Rc::new(RefCell::new(Vec::new()));
+        let mut harness = Harness::new();
+        let mut editor = EditorWidget::new("apply_command")
+            .with_mode(EditorMode::Plain)
+            .with_command_observer({
+                let seen = Rc::clone(&seen);
+                move |command| seen.borrow_mut().push(command.clone())
+            })
+            .with_change_callback({
+                let changes = Rc::clone(&changes);
+                move |text| changes.borrow_mut().push(text.to_string())
+            });
+        editor.set_text("one\ntwo\nthree");
+        harness.start(&mut editor);
+
+        let document_end = EditorCommand::MoveCursor(CursorMovement::DocumentEnd);
+        editor.apply_command(document_end.clone());
+        assert_eq!(editor.buffer.cursor_position(), 13);
+        assert_eq!(*seen.borrow(), [document_end]);
+        assert!(changes.borrow().is_empty());
+
+        // The TextEdit cursor follows, so typing continues at the end
+        harness.frame(&mut editor, Vec::new());
+        harness.type_keys(&mut editor, "s");
+        assert_eq!(editor.text(), "one\ntwo\nthrees");
+
+        editor.apply_command(EditorCommand::DeleteLine);
+        assert_eq!(editor.text(), "one\ntwo");
+        assert_eq!(
+            changes.borrow().last().map(String::as_str),
+            Some("one\ntwo")
+        );
+    }
+
+    #[test]
+    fn custom_commands_run_their_registered_action() {
+        let mut editor = EditorWidget::new("custom_command").with_custom_command(
+            "exclaim",
+            |editor: &mut EditorWidget| {
+                editor.apply_command(EditorCommand::MoveCursor(CursorMovement::DocumentEnd));
+                editor.apply_command(EditorCommand::InsertChar('!'));
+            },
+        );
+        editor.set_text("hello");
+        editor.apply_command(EditorCommand::Custom("exclaim".to_string()));
+        assert_eq!(editor.text(), "hello!");
+
+        // Running it again works, and unknown names are ignored
+        editor.apply_command(EditorCommand::Custom("exclaim".to_string()));
+        editor.apply_command(EditorCommand::Custom("whisper".to_string()));
+        assert_eq!(editor.text(), "hello!!");
+
+        // Registering the name again replaces the action
+        let count = Rc::new(RefCell::new(0));
+        editor.register_custom_command("exclaim", {
+            let count = Rc::clone(&count);
+            move |_| *count.borrow_mut() += 1
+        });
+        editor.apply_command(EditorCommand::Custom("exclaim".to_string()));
+        assert_eq!(*count.borrow(), 1);
+        assert_eq!(editor.text(), "hello!!");
+    }
+
+    #[test]
+    fn vim_word_motions_follow_the_configured_word_chars() {
+        for (word_chars, after_w) in [("_", 3), ("_-", 12)] {
+            let mut harness = Harness::new();
+            let mut editor = EditorWidget::new("word_chars")
+                .with_mode(EditorMode::Vim(VimMode::Normal))
+                .with_word_chars(word_chars);
+            editor.set_text("foo-bar_baz qux");
+            harness.start(&mut editor);
+
+            harness.type_keys(&mut editor, "w");
+            assert_eq!(editor.buffer.cursor_position(), after_w, "{word_chars:?}");
+        }
+    }
+
+    #[test]
+    fn ctrl_a_selects_all_or_goes_to_the_line_start_depending_on_the_mode() {
+        // Off macOS a Ctrl press sets both `ctrl` and `command`
+        let ctrl = Modifiers::CTRL | Modifiers::COMMAND;
+        for (mode, selection, cursor) in [
+            (EditorMode::Plain, Some(0..11), 11),
+            (EditorMode::Vim(VimMode::Insert), Some(0..11), 11),
+            (EditorMode::Emacs, None, 4),
+            (EditorMode::Vim(VimMode::Normal), None, 6),
+        ] {
+            let mut harness = Harness::new();
+            let mut editor = EditorWidget::new("ctrl_a").with_mode(mode);
+            editor.set_text("one\ntwo\nsix");
+            editor.move_cursor_to(1, 2);
+            harness.start(&mut editor);
+
+            harness.press(&mut editor, Key::A, ctrl);
+            assert_eq!(editor.selection(), selection, "{mode:?}");
+            assert_eq!(editor.buffer.cursor_position(), cursor, "{mode:?}");
+            assert_eq!(editor.text(), "one\ntwo\nsix", "{mode:?}");
+        }
+
+        // Emacs selects all with C-x h instead
+        let mut harness = Harness::new();
+        let mut editor = EditorWidget::new("ctrl_a").with_mode(EditorMode::Emacs);
+        editor.set_text("one\ntwo");
+        harness.start(&mut editor);
+        harness.press(&mut editor, Key::X, ctrl);
+        harness.press(&mut editor, Key::H, Modifiers::NONE);
+        assert_eq!(editor.selected_text(), Some("one\ntwo"));
+    }
+
+    #[test]
+    fn keys_the_editor_does_not_use_reach_the_rest_of_the_app() {
+        // Runs one frame and returns the events left after the editor has shown
+        let run = |harness: &mut Harness, editor: &mut EditorWidget, events: Vec<Event>| {
+            harness.time += 0.05;
+            let raw = RawInput {
+                time: Some(harness.time),
+                screen_rect: Some(harness.screen),
+                events,
+                ..RawInput::default()
+            };
+            let mut remaining = Vec::new();
+            let _ = harness.ctx.run(raw, |ctx| {
+                CentralPanel::default().show(ctx, |ui| {
+                    editor.show(ui);
+                });
+                remaining = ctx.input(|input| input.events.clone());
+            });
+            remaining
+        };
+        let frame_events = || {
+            vec![
+                key_event(Key::H, Modifiers::NONE),
+                Event::Text("h".to_string()),
+                key_event(Key::F5, Modifiers::NONE),
+                Event::PointerMoved(Pos2::new(5.0, 5.0)),
+            ]
+        };
+
+        let mut harness = Harness::new();
+        let mut editor = EditorWidget::new("host_keys").with_mode(EditorMode::Vim(VimMode::Normal));
+        editor.set_text("abc");
+        editor.move_cursor_to(0, 2);
+        harness.start(&mut editor);
+        let remaining = run(&mut harness, &mut editor, frame_events());
+        assert_eq!(editor.buffer.cursor_position(), 1);
+        assert!(remaining.contains(&key_event(Key::F5, Modifiers::NONE)));
+        assert!(remaining.contains(&Event::PointerMoved(Pos2::new(5.0, 5.0))));
+        assert!(!remaining.contains(&Event::Text("h".to_string())));
+
+        // Without focus the editor takes nothing
+        let mut harness = Harness::new();
+        let mut editor = EditorWidget::new("host_keys").with_mode(EditorMode::Vim(VimMode::Normal));
+        editor.set_text("abc");
+        editor.move_cursor_to(0, 2);
+        let remaining = run(&mut harness, &mut editor, frame_events());
+        assert_eq!(remaining, frame_events());
+        assert_eq!(editor.buffer.cursor_position(), 2);
+    }
+
+    #[test]
+    fn direct_and_synthesized_vim_motions_land_in_the_same_place() {
+        let text = "first line\n  two three\nlast";
+        for (keys, expected) in [("0", 11), ("$", 22), ("gg", 0), ("G", 27)] {
+            let mut positions = Vec::new();
+            for direct in [false, true] {
+                let mut harness = Harness::new();
+                let mut editor = EditorWidget::new("direct_motions")
+                    .with_mode(EditorMode::Vim(VimMode::Normal))
+                    .with_vim_direct_motions(direct);
+                editor.set_text(text);
+                editor.move_cursor_to(1, 5);
+                harness.start(&mut editor);
+
+                harness.type_keys(&mut editor, keys);
+                harness.frame(&mut editor, Vec::new());
+                positions.push(editor.buffer.cursor_position());
+            }
+            assert_eq!(positions, [expected, expected], "{keys}");
+        }
+    }
+
+    #[test]
+    fn text_typed_into_the_text_edit_is_recorded_as_edits() {
+        let mut harness = Harness::new();
+        let mut editor = EditorWidget::new("typed_edits")
+            .with_mode(EditorMode::Plain)
+            .with_edit_recording(true);
+        editor.set_text("ab");
+        editor.move_cursor_to(0, 1);
+        harness.start(&mut editor);
+        let _ = editor.take_edits();
+
+        harness.type_keys(&mut editor, "x");
+        harness.press(&mut editor, Key::Delete, Modifiers::NONE);
+        let edits = editor.take_edits();
+        assert_eq!(
+            edits
+                .iter()
+                .map(|edit| (
+                    edit.range.clone(),
+                    edit.inserted.as_str(),
+                    edit.removed.as_str()
+                ))
+                .collect::<Vec<_>>(),
+            [(1..1, "x", ""), (2..3, "", "b")]
+        );
+    }
+
+    #[test]
+    fn vim_l_and_emacs_c_f_move_over_a_family_emoji_in_one_step() {
+        let text = "a👨\u{200D}👩\u{200D}👧b";
+        let mut harness = Harness::new();
+        let mut vim = EditorWidget::new("emoji_vim").with_mode(EditorMode::Vim(VimMode::Normal));
+        vim.set_text(text);
+        harness.start(&mut vim);
+        harness.type_keys(&mut vim, "l");
+        assert_eq!(vim.buffer.cursor_position(), 1);
+        harness.type_keys(&mut vim, "l");
+        assert_eq!(vim.buffer.cursor_position(), 19);
+        harness.type_keys(&mut vim, "h");
+        assert_eq!(vim.buffer.cursor_position(), 1);
+
+        let mut harness = Harness::new();
+        let mut emacs = EditorWidget::new("emoji_emacs").with_mode(EditorMode::Emacs);
+        emacs.set_text(text);
+        emacs.move_cursor_to(0, 1);
+        harness.start(&mut emacs);
+        harness.press(&mut emacs, Key::F, Modifiers::CTRL);
+        assert_eq!(emacs.buffer.cursor_position(), 19);
+        harness.press(&mut emacs, Key::B, Modifiers::CTRL);
+        assert_eq!(emacs.buffer.cursor_position(), 1);
+    }
+
+    #[test]
+    fn motion_over_a_hebrew_word_is_logical_unless_visual_is_chosen() {
+        // "שלום" takes bytes 4..12, two bytes per letter
+        let text = "say שלום now";
+        for (logical, after_right) in [(true, 8), (false, 4)] {
+            let mut editor = EditorWidget::new("hebrew").with_logical_motion(logical);
+            editor.set_text(text);
+            editor.buffer.set_cursor_position(6);
+            editor.apply_command(EditorCommand::MoveCursor(CursorMovement::Right));
+            assert_eq!(editor.buffer.cursor_position(), after_right, "{logical}");
+
+            // Outside the Hebrew word both move the same way
+            editor.buffer.set_cursor_position(1);
+            editor.apply_command(EditorCommand::MoveCursor(CursorMovement::Right));
+            assert_eq!(editor.buffer.cursor_position(), 2, "{logical}");
+        }
+
+        // Vim motions, line and column math, and deletion don't panic on it
+        let mut harness = Harness::new();
+        let mut editor = EditorWidget::new("hebrew").with_mode(EditorMode::Vim(VimMode::Normal));
+        editor.set_text(text);
+        harness.start(&mut editor);
+        harness.type_keys(&mut editor, "wlx$");
+        assert_eq!(editor.text(), "say שום now");
+        assert_eq!(editor.buffer.cursor_line_col(), (0, 11));
+    }
+
+    #[test]
+    fn insert_toggles_overtype_which_replaces_up_to_the_line_end() {
+        for mode in [
+            EditorMode::Plain,
+            EditorMode::Emacs,
+            EditorMode::Vim(VimMode::Insert),
+        ] {
+            let mut harness = Harness::new();
+            let mut editor = EditorWidget::new("overtype").with_mode(mode);
+            editor.set_text("abcd\nnext");
+            editor.move_cursor_to(0, 1);
+            harness.start(&mut editor);
+
+            harness.press(&mut editor, Key::Insert, Modifiers::NONE);
+            assert!(editor.overtype(), "{mode:?}");
+            harness.type_keys(&mut editor, "XYZW");
+            // Past the line end the typing is inserted, never eating the newline
+            assert_eq!(editor.text(), "aXYZW\nnext", "{mode:?}");
+            assert_eq!(editor.buffer.cursor_position(), 5, "{mode:?}");
+
+            harness.press(&mut editor, Key::Insert, Modifiers::NONE);
+            assert!(!editor.overtype(), "{mode:?}");
+            harness.type_keys(&mut editor, "!");
+            assert_eq!(editor.text(), "aXYZW!\nnext", "{mode:?}");
+        }
+    }
+
+    #[test]
+    fn a_visual_selection_grows_over_several_frames() {
+        let mut harness = Harness::new();
+        let mut editor = EditorWidget::new("visual").with_mode(EditorMode::Vim(VimMode::Normal));
+        editor.set_text("one two\nthree");
+        editor.move_cursor_to(0, 1);
+        harness.start(&mut editor);
+
+        harness.type_keys(&mut editor, "v");
+        let mut selections = Vec::new();
+        for keys in ["l", "l", "w", "j"] {
+            harness.type_keys(&mut editor, keys);
+            // An idle frame in between must not collapse the selection
+            harness.frame(&mut editor, Vec::new());
+            selections.push(editor.selection());
+        }
+        assert_eq!(*editor.mode(), EditorMode::Vim(VimMode::Visual));
+        assert_eq!(
+            selections,
+            [Some(1..2), Some(1..3), Some(1..4), Some(1..12)]
+        );
+        assert_eq!(editor.selected_text(), Some("ne two\nthre"));
+    }
+
+    #[test]
+    fn mode_changed_is_set_only_on_the_frame_of_the_switch() {
+        let mut harness = Harness::new();
+        let mut editor =
+            EditorWidget::new("mode_changed").with_mode(EditorMode::Vim(VimMode::Normal));
+        harness.start(&mut editor);
+        assert!(!editor.mode_changed());
+
+        harness.type_keys(&mut editor, "l");
+        assert!(!editor.mode_changed());
+        harness.type_keys(&mut editor, "i");
+        assert!(editor.mode_changed());
+        assert_eq!(*editor.mode(), EditorMode::Vim(VimMode::Insert));
+        harness.frame(&mut editor, Vec::new());
+        assert!(!editor.mode_changed());
+
+        harness.press(&mut editor, Key::Escape, Modifiers::NONE);
+        assert!(editor.mode_changed());
+
+        // A switch between frames shows up after the next one
+        editor.set_mode(EditorMode::Emacs);
+        harness.frame(&mut editor, Vec::new());
+        assert!(editor.mode_changed());
+        harness.frame(&mut editor, Vec::new());
+        assert!(!editor.mode_changed());
+    }
+
+    #[test]
+    fn ime_commits_insert_text_in_insert_mode_only() {
+        let ime = |event| Event::Ime(event);
+        for (mode, expected) in [
+            (EditorMode::Vim(VimMode::Insert), "a你好b"),
+            (EditorMode::Plain, "a你好b"),
+            (EditorMode::Vim(VimMode::Normal), "ab"),
+        ] {
+            let mut harness = Harness::new();
+            let mut editor = EditorWidget::new("ime").with_mode(mode);
+            editor.set_text("ab");
+            editor.move_cursor_to(0, 1);
+            harness.start(&mut editor);
+
+            harness.frame(&mut editor, vec![ime(ImeEvent::Enabled)]);
+            harness.frame(&mut editor, vec![ime(ImeEvent::Preedit("ni".to_string()))]);
+            harness.frame(
+                &mut editor,
+                vec![ime(ImeEvent::Preedit("nihao".to_string()))],
+            );
+            harness.frame(&mut editor, vec![ime(ImeEvent::Commit("你好".to_string()))]);
+            assert_eq!(editor.text(), expected, "{mode:?}");
+        }
+
+        // Leaving insert mode mid-composition drops the uncommitted text
+        let mut harness = Harness::new();
+        let mut editor = EditorWidget::new("ime").with_mode(EditorMode::Vim(VimMode::Insert));
+        editor.set_text("ab");
+        editor.move_cursor_to(0, 1);
+        harness.start(&mut editor);
+        harness.frame(&mut editor, vec![ime(ImeEvent::Enabled)]);
+        harness.frame(&mut editor, vec![ime(ImeEvent::Preedit("ni".to_string()))]);
+        assert_eq!(editor.text(), "anib");
+        harness.press(&mut editor, Key::Escape, Modifiers::NONE);
+        assert_eq!(editor.text(), "ab");
+        assert_eq!(*editor.mode(), EditorMode::Vim(VimMode::Normal));
+    }
+
+    #[test]
+    fn vim_j_and_down_stay_on_the_last_line_and_keep_the_column() {
+        for text in ["one\ntwo", "one\ntwo\n"] {
+            let mut harness = Harness::new();
+            let mut editor =
+                EditorWidget::new("last_line").with_mode(EditorMode::Vim(VimMode::Normal));
+            editor.set_text(text);
+            editor.move_cursor_to(0, 1);
+            harness.start(&mut editor);
+
+            harness.type_keys(&mut editor, "j");
+            assert_eq!(editor.buffer.cursor_line_col(), (1, 1), "{text:?}");
+            harness.type_keys(&mut editor, "j");
+            harness.press(&mut editor, Key::ArrowDown, Modifiers::NONE);
+            assert_eq!(editor.buffer.cursor_line_col(), (1, 1), "{text:?}");
+            assert_eq!(editor.text(), text);
+        }
+    }
+
+    #[test]
+    fn escape_from_insert_mode_steps_back_onto_the_last_character() {
+        let mut harness = Harness::new();
+        let mut editor = EditorWidget::new("escape").with_mode(EditorMode::Vim(VimMode::Normal));
+        editor.set_text("one\n\nthree");
+        harness.start(&mut editor);
 
-                    // Look for custom cursor movement signals
-                    let mut custom_events_to_remove = Vec::new();
-                    for (i, event) in input.events.iter().enumerate() {
-                        if let Event::Text(text) = event {
-                            if text.starts_with("__CUSTOM_CURSOR__:") {
-                                custom_events_to_remove.push(i);
-                            }
-                        }
-                    }
-                    events_to_remove.extend(custom_events_to_remove);
+        harness.type_keys(&mut editor, "$ix");
+        assert_eq!(editor.text(), "onex\n\nthree");
+        assert_eq!(editor.buffer.cursor_line_col(), (0, 4));
+        harness.press(&mut editor, Key::Escape, Modifiers::NONE);
+        assert_eq!(*editor.mode(), EditorMode::Vim(VimMode::Normal));
+        assert_eq!(editor.buffer.cursor_line_col(), (0, 3));
 
-                    // Update last cursor position for Vim normal mode after commands
-                    if matches!(self.current_mode, EditorMode::Vim(VimMode::Normal)) {
-                        self.last_cursor_pos = self.buffer.cursor_position();
-                    }
+        // An empty line has no character to step back onto
+        harness.type_keys(&mut editor, "ji");
+        harness.press(&mut editor, Key::Escape, Modifiers::NONE);
+        assert_eq!(editor.buffer.cursor_line_col(), (1, 0));
 
-                    // Sync the editor mode with the handler
-                    self.current_mode = EditorMode::Vim(self.vim_handler.mode());
-                }
-                EditorMode::Emacs => {
-                    // Use the dedicated Emacs key handler
-                    // The Emacs handler now generates TextEdit-compatible events directly
-                    events_to_remove = self.emacs_handler.process_input(ctx, input);
+        // In the middle of a line the cursor is already on a character
+        editor.move_cursor_to(2, 2);
+        harness.type_keys(&mut editor, "i");
+        harness.press(&mut editor, Key::Escape, Modifiers::NONE);
+        assert_eq!(editor.buffer.cursor_line_col(), (2, 2));
+    }
 
-                    // No need to process commands here anymore since the emacs_handler
-                    // is now directly generating TextEdit events just like vim_handler
-                    println!(
-                        "DEBUG: Emacs handler processed input - events to remove: {:?}",
-                        events_to_remove
-                    );
-                }
-            }
+    #[test]
+    fn search_finds_non_overlapping_matches_on_char_boundaries() {
+        let mut editor = EditorWidget::new("search");
+        editor.set_text("aaaa Straße STRASSE straße");
+
+        assert_eq!(editor.search("aa", true), vec![0..2, 2..4]);
+        assert_eq!(editor.search("straße", true), vec![21..28]);
+        assert_eq!(editor.search("STRAßE", false), vec![5..12, 21..28]);
+        assert!(editor.search("strasse", true).is_empty());
+        assert!(editor.search("xyz", false).is_empty());
+        assert!(editor.search("", false).is_empty());
+        for range in editor.search("ß", false) {
+            assert!(editor.text().is_char_boundary(range.start));
+            assert!(editor.text().is_char_boundary(range.end));
+        }
+    }
 
-            // Debug print after processing
-            println!("Input events AFTER processing: {:?}", input.events);
+    #[cfg(feature = "regex")]
+    #[test]
+    fn search_regex_finds_matches_and_reports_bad_patterns() {
+        let mut editor = EditorWidget::new("search_regex");
+        editor.set_text("fn a() {}\nfn bé() {}");
 
-            // Remove events in reverse order to maintain correct indices
-            events_to_remove.sort_unstable();
-            events_to_remove.dedup();
+        assert_eq!(editor.search_regex(r"fn \w+").unwrap(), vec![0..4, 10..16]);
+        assert!(editor.search_regex("x*").unwrap().is_empty());
+        assert!(editor.search_regex("(").is_err());
+    }
 
-            for &index in events_to_remove.iter().rev() {
-                if index < input.events.len() {
-                    input.events.remove(index);
-                }
-            }
+    #[test]
+    fn navigation_keys_move_the_cursor_in_vim_normal_and_visual_modes() {
+        let mut harness = Harness::new();
+        let mut editor = EditorWidget::new("nav_keys").with_mode(EditorMode::Vim(VimMode::Normal));
+        editor.set_text(
+            (0..200)
+                .map(|n| format!("line {n}"))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        );
+        editor.move_cursor_to(1, 2);
+        harness.start(&mut editor);
+
+        harness.press(&mut editor, Key::End, Modifiers::NONE);
+        assert_eq!(editor.buffer.cursor_line_col(), (1, 6));
+        harness.press(&mut editor, Key::Home, Modifiers::NONE);
+        assert_eq!(editor.buffer.cursor_line_col(), (1, 0));
+
+        let page = editor.visible_lines.1 - editor.visible_lines.0;
+        assert!(page > 1, "page of {page} lines");
+        harness.press(&mut editor, Key::PageDown, Modifiers::NONE);
+        assert_eq!(editor.buffer.cursor_line_col(), (1 + page, 0));
+        harness.press(&mut editor, Key::PageUp, Modifiers::NONE);
+        assert_eq!(editor.buffer.cursor_line_col(), (1, 0));
+        assert_eq!(editor.text().lines().count(), 200);
+
+        // In visual mode the keys extend the selection from where it started
+        harness.type_keys(&mut editor, "v");
+        harness.press(&mut editor, Key::End, Modifiers::NONE);
+        harness.press(&mut editor, Key::PageDown, Modifiers::NONE);
+        assert_eq!(editor.buffer.cursor_line_col(), (1 + page, 6));
+        let cursor = editor.buffer.cursor_position();
+        assert_eq!(editor.buffer.selection(), Some(7..cursor));
+    }
+
+    #[test]
+    fn arrows_home_end_and_backspace_reach_the_text_edit_in_vim_insert_mode() {
+        let mut harness = Harness::new();
+        let mut editor =
+            EditorWidget::new("insert_keys").with_mode(EditorMode::Vim(VimMode::Insert));
+        editor.set_text("one\ntwo");
+        editor.move_cursor_to(1, 2);
+        harness.start(&mut editor);
+
+        harness.press(&mut editor, Key::ArrowLeft, Modifiers::NONE);
+        assert_eq!(editor.buffer.cursor_line_col(), (1, 1));
+        harness.press(&mut editor, Key::ArrowUp, Modifiers::NONE);
+        assert_eq!(editor.buffer.cursor_line_col(), (0, 1));
+        harness.press(&mut editor, Key::End, Modifiers::NONE);
+        assert_eq!(editor.buffer.cursor_line_col(), (0, 3));
+        harness.press(&mut editor, Key::Backspace, Modifiers::NONE);
+        harness.press(&mut editor, Key::Home, Modifiers::NONE);
+        harness.press(&mut editor, Key::ArrowRight, Modifiers::NONE);
+        harness.type_keys(&mut editor, "x");
+
+        assert_eq!(editor.text(), "oxn\ntwo");
+        assert_eq!(*editor.mode(), EditorMode::Vim(VimMode::Insert));
+    }
+
+    #[test]
+    fn an_insert_escape_sequence_leaves_insert_mode_without_its_characters() {
+        let insert_editor = || {
+            let mut editor = EditorWidget::new("jk")
+                .with_mode(EditorMode::Vim(VimMode::Insert))
+                .with_vim_insert_escape_sequence("jk");
+            editor.set_text("ab");
+            editor.move_cursor_to(0, 1);
+            editor
+        };
+
+        // Typed in separate frames, the j is inserted and then taken back
+        let mut harness = Harness::new();
+        let mut editor = insert_editor();
+        harness.start(&mut editor);
+        harness.type_keys(&mut editor, "j");
+        assert_eq!(editor.text(), "ajb");
+        harness.type_keys(&mut editor, "k");
+        assert_eq!(editor.text(), "ab");
+        assert_eq!(*editor.mode(), EditorMode::Vim(VimMode::Normal));
+        assert_eq!(editor.buffer.cursor_position(), 1);
+
+        // Both in one frame, neither is inserted
+        let mut harness = Harness::new();
+        let mut editor = insert_editor();
+        harness.start(&mut editor);
+        harness.frame(
+            &mut editor,
+            vec![Event::Text("j".to_string()), Event::Text("k".to_string())],
+        );
+        assert_eq!(editor.text(), "ab");
+        assert_eq!(*editor.mode(), EditorMode::Vim(VimMode::Normal));
+
+        // Another character after j, or a k too late, is typed as usual
+        let mut harness = Harness::new();
+        let mut editor = insert_editor();
+        harness.start(&mut editor);
+        harness.type_keys(&mut editor, "jx");
+        harness.type_keys(&mut editor, "j");
+        harness.wait(5.0);
+        harness.type_keys(&mut editor, "k");
+        assert_eq!(editor.text(), "ajxjkb");
+        assert_eq!(*editor.mode(), EditorMode::Vim(VimMode::Insert));
+    }
+
+    #[test]
+    fn ctrl_w_and_ctrl_u_kill_readline_style() {
+        let ctrl = Modifiers::CTRL | Modifiers::COMMAND;
+        for mode in [EditorMode::Plain, EditorMode::Vim(VimMode::Insert)] {
+            let mut harness = Harness::new();
+            let mut editor = EditorWidget::new("readline").with_mode(mode);
+            editor.set_text("one\nfoo bar.baz");
+            editor.move_cursor_to(1, 11);
+            harness.start(&mut editor);
+
+            harness.press(&mut editor, Key::W, ctrl);
+            assert_eq!(editor.text(), "one\nfoo bar.", "{mode:?}");
+            harness.press(&mut editor, Key::U, ctrl);
+            assert_eq!(editor.text(), "one\n", "{mode:?}");
+            assert_eq!(editor.buffer.cursor_line_col(), (1, 0), "{mode:?}");
+        }
+
+        // Emacs kills onto the kill ring: C-w takes the region when there is
+        // one and the previous word otherwise
+        let mut harness = Harness::new();
+        let mut editor = EditorWidget::new("readline_emacs").with_mode(EditorMode::Emacs);
+        editor.set_text("one two three");
+        editor.move_cursor_to(0, 13);
+        harness.start(&mut editor);
+
+        harness.press(&mut editor, Key::W, ctrl);
+        assert_eq!(editor.text(), "one two ");
+        // Moving in between starts a new kill-ring entry
+        harness.press(&mut editor, Key::E, ctrl);
+        editor.select_range(0, 4);
+        harness.frame(&mut editor, Vec::new());
+        harness.press(&mut editor, Key::W, ctrl);
+        assert_eq!(editor.text(), "two ");
+        harness.press(&mut editor, Key::E, ctrl);
+        harness.press(&mut editor, Key::Y, ctrl);
+        assert_eq!(editor.text(), "two one ");
+        harness.press(&mut editor, Key::U, ctrl);
+        harness.press(&mut editor, Key::Y, ctrl);
+        assert_eq!(editor.text(), "two one ");
+    }
+
+    #[test]
+    fn smart_tab_indents_in_the_indentation_and_inserts_elsewhere() {
+        let mut harness = Harness::new();
+        let mut editor = EditorWidget::new("smart_tab")
+            .with_mode(EditorMode::Plain)
+            .with_smart_tab(true);
+        editor.set_text("x\n  foo bar");
+        editor.move_cursor_to(1, 1);
+        harness.start(&mut editor);
+
+        harness.press(&mut editor, Key::Tab, Modifiers::NONE);
+        assert_eq!(editor.text(), "x\n  \tfoo bar");
+        assert_eq!(editor.buffer.cursor_line_col(), (1, 3));
+
+        editor.move_cursor_to(1, 6);
+        harness.frame(&mut editor, Vec::new());
+        harness.press(&mut editor, Key::Tab, Modifiers::NONE);
+        assert_eq!(editor.text(), "x\n  \tfoo\t bar");
+        assert_eq!(editor.buffer.cursor_line_col(), (1, 7));
+
+        // Without smart tab, Tab moves focus on as egui's `TextEdit` does
+        let mut harness = Harness::new();
+        let mut editor = EditorWidget::new("plain_tab").with_mode(EditorMode::Plain);
+        editor.set_text("x\n  foo bar");
+        editor.move_cursor_to(1, 1);
+        harness.start(&mut editor);
+        harness.press(&mut editor, Key::Tab, Modifiers::NONE);
+        assert_eq!(editor.text(), "x\n  foo bar");
+    }
+
+    #[test]
+    fn ctrl_o_and_ctrl_i_walk_the_jumplist_but_not_simple_motions() {
+        let ctrl = Modifiers::CTRL | Modifiers::COMMAND;
+        let mut harness = Harness::new();
+        let mut editor = EditorWidget::new("jumplist").with_mode(EditorMode::Vim(VimMode::Normal));
+        editor.set_text("zero\none\ntwo\nthree\nfour");
+        editor.move_cursor_to(2, 1);
+        harness.start(&mut editor);
+
+        harness.type_keys(&mut editor, "G");
+        assert_eq!(editor.buffer.cursor_position(), 23);
+        harness.press(&mut editor, Key::O, ctrl);
+        assert_eq!(editor.buffer.cursor_line_col(), (2, 1));
+        harness.press(&mut editor, Key::I, ctrl);
+        assert_eq!(editor.buffer.cursor_position(), 23);
+
+        // h/j/k/l aren't jumps, so Ctrl+O skips back past them
+        harness.type_keys(&mut editor, "kkh");
+        harness.type_keys(&mut editor, "gg");
+        assert_eq!(editor.buffer.cursor_position(), 0);
+        harness.press(&mut editor, Key::O, ctrl);
+        assert_eq!(editor.buffer.cursor_line_col(), (2, 2));
+        harness.press(&mut editor, Key::O, ctrl);
+        assert_eq!(editor.buffer.cursor_position(), 23);
+    }
+
+    #[test]
+    fn alt_up_and_down_move_the_cursor_line() {
+        let mut harness = Harness::new();
+        let mut editor = EditorWidget::new("move_line").with_mode(EditorMode::Plain);
+        editor.set_text("one\ntwo\nthree");
+        editor.move_cursor_to(1, 1);
+        harness.start(&mut editor);
+
+        harness.press(&mut editor, Key::ArrowUp, Modifiers::ALT);
+        assert_eq!(editor.text(), "two\none\nthree");
+        assert_eq!(editor.buffer.cursor_line_col(), (0, 1));
+        harness.press(&mut editor, Key::ArrowUp, Modifiers::ALT);
+        assert_eq!(editor.text(), "two\none\nthree");
+        assert_eq!(editor.buffer.cursor_line_col(), (0, 1));
+
+        // Each move is a single edit
+        editor.set_edit_recording(true);
+        harness.press(&mut editor, Key::ArrowDown, Modifiers::ALT);
+        assert_eq!(editor.take_edits().len(), 1);
+        harness.press(&mut editor, Key::ArrowDown, Modifiers::ALT);
+        assert_eq!(editor.text(), "one\nthree\ntwo");
+        assert_eq!(editor.buffer.cursor_line_col(), (2, 1));
+    }
+
+    #[test]
+    fn ctrl_shift_d_and_vim_yyp_duplicate_the_line() {
+        let mut harness = Harness::new();
+        let mut editor = EditorWidget::new("duplicate").with_mode(EditorMode::Plain);
+        editor.set_text("first\nsecond");
+        editor.move_cursor_to(0, 2);
+        harness.start(&mut editor);
+
+        harness.press(
+            &mut editor,
+            Key::D,
+            Modifiers::CTRL | Modifiers::COMMAND | Modifiers::SHIFT,
+        );
+        assert_eq!(editor.text(), "first\nfirst\nsecond");
+        assert_eq!(editor.buffer.cursor_line_col(), (1, 2));
+
+        let mut harness = Harness::new();
+        let mut editor = EditorWidget::new("yyp").with_mode(EditorMode::Vim(VimMode::Normal));
+        editor.set_text("first\nsecond");
+        editor.move_cursor_to(0, 2);
+        harness.start(&mut editor);
+
+        harness.type_keys(&mut editor, "yyp");
+        assert_eq!(editor.text(), "first\nfirst\nsecond");
+        assert_eq!(editor.buffer.current_line(), 1);
+    }
+
+    /// Colors nothing and counts the bytes it is asked to highlight
+    struct CountingHighlighter {
+        theme: HighlightTheme,
+        bytes: Rc<Cell<usize>>,
+    }
+
+    impl SyntaxHighlighter for CountingHighlighter {
+        fn highlight(&self, _ctx: &Context, text: &str) -> LayoutJob {
+            self.bytes.set(self.bytes.get() + text.len());
+            LayoutJob::simple(
+                text.to_string(),
+                FontId::monospace(14.0),
+                self.theme.foreground,
+                f32::INFINITY,
+            )
+        }
+
+        fn set_theme(&mut self, theme: HighlightTheme) {
+            self.theme = theme;
+        }
+
+        fn theme(&self) -> &HighlightTheme {
+            &self.theme
+        }
+    }
+
+    #[test]
+    fn turning_highlighting_off_skips_the_highlighter_for_a_large_buffer() {
+        let text = "let value = 42; // a line of plain text\n".repeat(5_000);
+        let highlighted_bytes = |enabled: bool| {
+            let bytes = Rc::new(Cell::new(0));
+            let mut harness = Harness::new();
+            let mut editor = EditorWidget::new("highlight_cost")
+                .with_mode(EditorMode::Plain)
+                .with_syntax_highlighter(CountingHighlighter {
+                    theme: HighlightTheme::default(),
+                    bytes: Rc::clone(&bytes),
+                })
+                .with_highlight_enabled(enabled);
+            editor.set_text(&text);
+            harness.start(&mut editor);
+            harness.type_keys(&mut editor, "x");
+            (bytes.get(), editor.highlight_cache.is_some())
+        };
+
+        // Each edit highlights the whole document again with highlighting on
+        let (on, cached) = highlighted_bytes(true);
+        assert!(on >= 2 * text.len(), "{on} bytes highlighted");
+        assert!(cached);
+        assert_eq!(highlighted_bytes(false), (0, false));
+    }
+
+    #[test]
+    fn visible_rows_only_lays_out_and_edits_just_the_lines_in_view() {
+        let line = "let value = 42; // a line of plain text\n";
+        let text = line.repeat(10_000);
+        let bytes = Rc::new(Cell::new(0));
+        let mut harness = Harness::new();
+        let mut editor = EditorWidget::new("visible_rows")
+            .with_mode(EditorMode::Plain)
+            .with_scroll_area(true)
+            .with_visible_rows_only(true)
+            .with_syntax_highlighter(CountingHighlighter {
+                theme: HighlightTheme::default(),
+                bytes: Rc::clone(&bytes),
+            });
+        editor.set_text(&text);
+        harness.start(&mut editor);
+
+        // An edit highlights a screenful of lines rather than the document
+        bytes.set(0);
+        harness.type_keys(&mut editor, "x");
+        assert!(
+            bytes.get() < 100 * line.len(),
+            "{} bytes highlighted",
+            bytes.get()
+        );
+        assert_eq!(editor.text().len(), text.len() + 1);
+        assert!(editor.text().starts_with("xlet value"));
+
+        // Far down the document the view follows the cursor and typing lands there
+        editor.move_cursor_to(7_000, 4);
+        harness.frame(&mut editor, Vec::new());
+        let (first, last) = editor.visible_line_range();
+        assert!((first..=last).contains(&7_000), "{first}..={last}");
+        harness.type_keys(&mut editor, "y");
+        assert_eq!(
+            editor.buffer.line(7_000),
+            Some("let yvalue = 42; // a line of plain text")
+        );
+        assert_eq!(editor.text().len(), text.len() + 2);
+
+        // Moving down off the bottom line scrolls the next one into view
+        let (_, last) = editor.visible_line_range();
+        editor.move_cursor_to(last, 0);
+        harness.frame(&mut editor, Vec::new());
+        harness.press(&mut editor, Key::ArrowDown, Modifiers::NONE);
+        harness.frame(&mut editor, Vec::new());
+        assert_eq!(editor.buffer.current_line(), last + 1);
+        let (first, last_after) = editor.visible_line_range();
+        assert!(
+            (first..=last_after).contains(&(last + 1)),
+            "{first}..={last_after}"
+        );
+    }
+
+    #[test]
+    fn vim_replace_mode_overwrites_and_backspace_restores() {
+        let mut harness = Harness::new();
+        let mut editor = EditorWidget::new("replace").with_mode(EditorMode::Vim(VimMode::Normal));
+        editor.set_text("hello world\nhi");
+        harness.start(&mut editor);
+
+        harness.type_keys(&mut editor, "R");
+        assert_eq!(*editor.mode(), EditorMode::Vim(VimMode::Replace));
+        harness.type_keys(&mut editor, "HEY");
+        assert_eq!(editor.text(), "HEYlo world\nhi");
+        harness.press(&mut editor, Key::Backspace, Modifiers::NONE);
+        harness.press(&mut editor, Key::Backspace, Modifiers::NONE);
+        assert_eq!(editor.text(), "Hello world\nhi");
+        assert_eq!(editor.buffer.cursor_position(), 1);
+
+        // Past the end of the line typing inserts, and Backspace removes it again
+        editor.move_cursor_to(1, 1);
+        harness.frame(&mut editor, Vec::new());
+        harness.type_keys(&mut editor, "ey");
+        assert_eq!(editor.text(), "Hello world\nhey");
+        harness.press(&mut editor, Key::Backspace, Modifiers::NONE);
+        harness.press(&mut editor, Key::Backspace, Modifiers::NONE);
+        assert_eq!(editor.text(), "Hello world\nhi");
+
+        harness.press(&mut editor, Key::Escape, Modifiers::NONE);
+        assert_eq!(*editor.mode(), EditorMode::Vim(VimMode::Normal));
+    }
+
+    #[test]
+    fn ctrl_slash_and_vim_gcc_toggle_line_comments() {
+        let mut harness = Harness::new();
+        let mut editor = EditorWidget::new("comment").with_mode(EditorMode::Plain);
+        editor.set_text("let a = 1;\n// let b = 2;\nlet c = 3;");
+        harness.start(&mut editor);
+
+        editor.select_range(2, 16);
+        harness.frame(&mut editor, Vec::new());
+        harness.press(
+            &mut editor,
+            Key::Slash,
+            Modifiers::CTRL | Modifiers::COMMAND,
+        );
+        assert_eq!(editor.text(), "// let a = 1;\n// // let b = 2;\nlet c = 3;");
+        assert!(editor.buffer.selection().is_some());
+
+        let mut harness = Harness::new();
+        let mut editor = EditorWidget::new("gcc").with_mode(EditorMode::Vim(VimMode::Normal));
+        editor.set_text("let a = 1;\n    let b = 2;");
+        editor.move_cursor_to(1, 6);
+        harness.start(&mut editor);
+
+        harness.type_keys(&mut editor, "gcc");
+        assert_eq!(editor.text(), "let a = 1;\n    // let b = 2;");
+        harness.type_keys(&mut editor, "gcc");
+        assert_eq!(editor.text(), "let a = 1;\n    let b = 2;");
+    }
+
+    #[test]
+    fn a_copied_multibyte_selection_reaches_the_clipboard() {
+        let mut harness = Harness::new();
+        let mut editor = EditorWidget::new("copy_multibyte");
+        editor.set_text("naïve 🦀 café");
+        harness.start(&mut editor);
+
+        // Offsets inside 'ï' and the crab are clamped to their starts
+        assert_eq!(editor.text_for_range(2..9), "ïve ");
+        assert_eq!(editor.text_for_range(7..usize::MAX), "🦀 café");
+
+        assert!(!editor.copy_selection_to_clipboard());
+        editor.select_range(2, 13);
+        assert!(editor.copy_selection_to_clipboard());
+        assert_eq!(editor.text(), "naïve 🦀 café");
+
+        harness.time += 0.05;
+        let raw = RawInput {
+            time: Some(harness.time),
+            screen_rect: Some(harness.screen),
+            ..RawInput::default()
+        };
+        let output = harness.ctx.run(raw, |ctx| {
+            CentralPanel::default().show(ctx, |ui| {
+                editor.show(ui);
+            });
         });
+        assert!(output
+            .platform_output
+            .commands
+            .contains(&egui::OutputCommand::CopyText("ïve 🦀 c".to_string())));
+    }
+
+    #[test]
+    fn vim_dd_cuts_the_line_into_the_register_for_p() {
+        let mut harness = Harness::new();
+        let mut editor = EditorWidget::new("ddp").with_mode(EditorMode::Vim(VimMode::Normal));
+        editor.set_text("one\ntwo\nthree");
+        harness.start(&mut editor);
+
+        harness.type_keys(&mut editor, "ddp");
+        assert_eq!(editor.text(), "two\none\nthree");
+        assert_eq!(editor.buffer.current_line(), 1);
+
+        // The deleted line replaces an earlier yank
+        harness.type_keys(&mut editor, "ggyyjdd");
+        assert_eq!(editor.text(), "two\nthree");
+        harness.type_keys(&mut editor, "p");
+        assert_eq!(editor.text(), "two\nthree\none");
+    }
+
+    #[test]
+    fn vim_visual_operators_go_through_the_register() {
+        let mut harness = Harness::new();
+        let mut editor =
+            EditorWidget::new("visual_yank").with_mode(EditorMode::Vim(VimMode::Normal));
+        editor.set_text("alpha beta\ngamma");
+        harness.start(&mut editor);
+
+        // A linewise yank first, which the visual yank must replace
+        harness.type_keys(&mut editor, "yy");
+        harness.type_keys(&mut editor, "vlly");
+        assert_eq!(*editor.mode(), EditorMode::Vim(VimMode::Normal));
+        assert_eq!(
+            (editor.register.text.as_str(), editor.register.linewise),
+            ("al", false)
+        );
+        harness.type_keys(&mut editor, "$p");
+        assert_eq!(editor.text(), "alpha betaal\ngamma");
+
+        // d cuts the selection, and p over a selection replaces it
+        editor.move_cursor_to(1, 0);
+        harness.frame(&mut editor, Vec::new());
+        harness.type_keys(&mut editor, "vld");
+        assert_eq!(editor.text(), "alpha betaal\namma");
+        assert_eq!(editor.register.text, "g");
+        harness.type_keys(&mut editor, "vlp");
+        assert_eq!(editor.text(), "alpha betaal\ngmma");
+
+        // c cuts and starts inserting
+        harness.type_keys(&mut editor, "0vlc");
+        assert_eq!(*editor.mode(), EditorMode::Vim(VimMode::Insert));
+        harness.type_keys(&mut editor, "X");
+        assert_eq!(editor.text(), "alpha betaal\nXmma");
+    }
+
+    #[test]
+    fn copy_and_cut_take_the_line_without_a_selection() {
+        let mut editor = EditorWidget::new("line_register");
+        editor.set_text("one\ntwo\n");
+        editor.move_cursor_to(1, 1);
+        editor.apply_command(EditorCommand::Copy);
+        assert_eq!(editor.pending_clipboard.as_deref(), Some("two\n"));
+
+        editor.move_cursor_to(0, 2);
+        editor.apply_command(EditorCommand::Paste);
+        assert_eq!(editor.text(), "one\ntwo\ntwo\n");
+        assert_eq!(editor.buffer.current_line(), 1);
+
+        editor.move_cursor_to(0, 0);
+        editor.apply_command(EditorCommand::Cut);
+        assert_eq!(editor.text(), "two\ntwo\n");
+        editor.apply_command(EditorCommand::PasteAbove);
+        assert_eq!(editor.text(), "one\ntwo\ntwo\n");
+        assert_eq!(editor.buffer.cursor_position(), 0);
+    }
+
+    #[test]
+    fn select_range_selects_backwards_snaps_to_characters_and_scrolls_into_view() {
+        let mut harness = Harness::new();
+        let mut editor = EditorWidget::new("select_range").with_scroll_area(true);
+        let mut text = "héllo\nwörld\n".to_string();
+        text.push_str(&"line\n".repeat(200));
+        text.push_str("the end");
+        editor.set_text(text.clone());
+        harness.start(&mut editor);
+
+        editor.select_range(8, 1);
+        assert_eq!(editor.selected_text(), Some("éllo\nw"));
+        assert_eq!(editor.buffer.cursor_position(), 1);
+        harness.frame(&mut editor, Vec::new());
+        assert_eq!(editor.selection(), Some(1..8));
+
+        // An offset inside 'é' snaps to its start; past the end clamps
+        editor.select_range(2, usize::MAX);
+        assert_eq!(editor.selection(), Some(1..text.len()));
+
+        editor.select_range(text.len() - 3, text.len());
+        harness.frame(&mut editor, Vec::new());
+        harness.frame(&mut editor, Vec::new());
+        assert_eq!(editor.selected_text(), Some("end"));
+        assert!(editor.scroll_offset() > 0.0);
+
+        editor.select_all();
+        harness.frame(&mut editor, Vec::new());
+        assert_eq!(editor.selected_text(), Some(text.as_str()));
+    }
+
+    #[test]
+    fn copy_and_cut_take_the_selection_when_there_is_one() {
+        let mut editor = EditorWidget::new("selection_register");
+        editor.set_text("héllo wörld");
+        editor.select_range(1, 6);
+        editor.apply_command(EditorCommand::Copy);
+        assert_eq!(editor.pending_clipboard.as_deref(), Some("éllo"));
+        assert_eq!(editor.text(), "héllo wörld");
+
+        editor.select_range(7, 13);
+        editor.apply_command(EditorCommand::Cut);
+        assert_eq!(editor.text(), "héllo ");
+        assert_eq!(editor.pending_clipboard.as_deref(), Some("wörld"));
+
+        editor.move_cursor_to(0, 0);
+        editor.apply_command(EditorCommand::Paste);
+        assert_eq!(editor.text(), "wörldhéllo ");
+    }
+
+    #[test]
+    fn vim_command_line_goes_to_lines_saves_and_quits() {
+        let saved = Rc::new(RefCell::new(Vec::new()));
+        let quit = Rc::new(RefCell::new(Vec::new()));
+        let mut harness = Harness::new();
+        let mut editor = EditorWidget::new("ex")
+            .with_mode(EditorMode::Vim(VimMode::Normal))
+            .with_save_callback({
+                let saved = Rc::clone(&saved);
+                move |text| saved.borrow_mut().push(text.to_string())
+            })
+            .with_quit_callback({
+                let quit = Rc::clone(&quit);
+                move |dirty| quit.borrow_mut().push(dirty)
+            });
+        editor.set_text("one\ntwo\nthree\n");
+        harness.start(&mut editor);
+
+        harness.type_keys(&mut editor, ":3");
+        assert_eq!(*editor.mode(), EditorMode::Vim(VimMode::CommandLine));
+        harness.press(&mut editor, Key::Enter, Modifiers::NONE);
+        assert_eq!(*editor.mode(), EditorMode::Vim(VimMode::Normal));
+        assert_eq!(editor.buffer.current_line(), 2);
+
+        harness.type_keys(&mut editor, ":0");
+        harness.press(&mut editor, Key::Enter, Modifiers::NONE);
+        assert_eq!(editor.buffer.current_line(), 0);
+
+        harness.type_keys(&mut editor, ":s/one/1/");
+        harness.press(&mut editor, Key::Enter, Modifiers::NONE);
+        assert_eq!(editor.text(), "1\ntwo\nthree\n");
+
+        harness.type_keys(&mut editor, ":w");
+        harness.press(&mut editor, Key::Enter, Modifiers::NONE);
+        assert_eq!(*saved.borrow(), vec!["1\ntwo\nthree\n".to_string()]);
+
+        // Escape abandons the command line without running it
+        harness.type_keys(&mut editor, ":q");
+        harness.press(&mut editor, Key::Escape, Modifiers::NONE);
+        assert_eq!(*editor.mode(), EditorMode::Vim(VimMode::Normal));
+        assert!(quit.borrow().is_empty());
+
+        harness.type_keys(&mut editor, ":q");
+        harness.press(&mut editor, Key::Enter, Modifiers::NONE);
+        assert_eq!(*quit.borrow(), vec![false]);
+    }
+
+    #[test]
+    fn typed_brackets_are_closed_and_backspace_removes_an_empty_pair() {
+        let mut harness = Harness::new();
+        let mut editor = EditorWidget::new("pairs")
+            .with_mode(EditorMode::Plain)
+            .with_auto_close_pairs(true);
+        harness.start(&mut editor);
+
+        harness.type_keys(&mut editor, "(");
+        assert_eq!(editor.text(), "()");
+        harness.type_keys(&mut editor, ")");
+        assert_eq!(editor.text(), "()");
+        assert_eq!(editor.buffer.cursor_position(), 2);
+
+        harness.type_keys(&mut editor, "[");
+        assert_eq!(editor.text(), "()[]");
+        harness.press(&mut editor, Key::Backspace, Modifiers::NONE);
+        assert_eq!(editor.text(), "()");
+    }
+
+    #[test]
+    fn typing_after_the_text_shrinks_under_the_cursor_does_not_panic() {
+        for close_pairs in [false, true] {
+            let mut harness = Harness::new();
+            let mut editor = EditorWidget::new("shrink")
+                .with_mode(EditorMode::Plain)
+                .with_auto_close_pairs(close_pairs);
+            editor.set_text("some text");
+            harness.start(&mut editor);
+            harness.press(&mut editor, Key::End, Modifiers::NONE);
+
+            editor.text_mut().clear();
+            harness.type_keys(&mut editor, "x");
+            harness.type_keys(&mut editor, "(");
+        }
+    }
+
+    #[test]
+    fn clear_empties_the_editor_and_returns_to_the_default_mode() {
+        let changes = Rc::new(RefCell::new(Vec::new()));
+        let mut harness = Harness::new();
+        let mut editor = EditorWidget::new("clear")
+            .with_mode(EditorMode::Vim(VimMode::Normal))
+            .with_change_callback({
+                let changes = Rc::clone(&changes);
+                move |text| changes.borrow_mut().push(text.to_string())
+            });
+        editor.set_text("one\ntwo\n");
+        harness.start(&mut editor);
+        harness.type_keys(&mut editor, "jx");
+        changes.borrow_mut().clear();
+
+        editor.clear();
+        assert_eq!(editor.text(), "");
+        assert_eq!(editor.buffer.cursor_position(), 0);
+        assert!(!editor.is_dirty());
+        assert_eq!(*editor.mode(), EditorMode::default());
+        assert_eq!(*changes.borrow(), vec![String::new()]);
+    }
+
+    #[test]
+    fn clear_drops_a_half_typed_key_sequence() {
+        let mut harness = Harness::new();
+        let mut editor = EditorWidget::new("clear_pending")
+            .with_initial_mode_for_new_buffers(EditorMode::Vim(VimMode::Normal));
+        harness.start(&mut editor);
+        harness.type_keys(&mut editor, "g");
+
+        editor.clear();
+        assert_eq!(*editor.mode(), EditorMode::Vim(VimMode::Normal));
+        editor.set_text("one\ntwo\n");
+        editor.move_cursor_to(1, 0);
+        harness.type_keys(&mut editor, "g");
+        assert_eq!(editor.buffer.current_line(), 1);
+    }
+
+    #[test]
+    fn a_new_note_starts_typing_in_vim_insert_mode() {
+        let mut harness = Harness::new();
+        let mut editor = EditorWidget::new("new_note")
+            .with_initial_mode_for_new_buffers(EditorMode::Vim(VimMode::Insert));
+        assert_eq!(*editor.mode(), EditorMode::Vim(VimMode::Insert));
+        harness.start(&mut editor);
+        harness.type_keys(&mut editor, "hi");
+        assert_eq!(editor.text(), "hi");
+
+        harness.press(&mut editor, Key::Escape, Modifiers::NONE);
+        assert_eq!(*editor.mode(), EditorMode::Vim(VimMode::Normal));
+        editor.clear();
+        assert_eq!(*editor.mode(), EditorMode::Vim(VimMode::Insert));
+        harness.type_keys(&mut editor, "again");
+        assert_eq!(editor.text(), "again");
+    }
+
+    #[test]
+    fn set_mode_drops_pending_keys_in_the_handlers() {
+        let mut harness = Harness::new();
+        let mut editor = EditorWidget::new("set_mode_pending").with_mode(EditorMode::Emacs);
+        editor.set_text("one\ntwo");
+        harness.start(&mut editor);
+
+        // A C-x prefix doesn't survive a trip through another mode
+        harness.press(&mut editor, Key::X, Modifiers::CTRL);
+        editor.set_mode(EditorMode::Plain);
+        editor.set_mode(EditorMode::Emacs);
+        harness.press(&mut editor, Key::H, Modifiers::NONE);
+        assert_eq!(editor.selection(), None);
+
+        // Nor does the first `g` of `gg`
+        editor.set_mode(EditorMode::Vim(VimMode::Normal));
+        editor.move_cursor_to(1, 0);
+        harness.type_keys(&mut editor, "g");
+        editor.set_mode(EditorMode::Vim(VimMode::Normal));
+        harness.type_keys(&mut editor, "g");
+        assert_eq!(editor.buffer.current_line(), 1);
+    }
+
+    #[test]
+    fn debug_log_records_keys_handler_notes_commands_and_mode_changes() {
+        let mut harness = Harness::new();
+        let mut editor = EditorWidget::new("debug_log")
+            .with_mode(EditorMode::Vim(VimMode::Normal))
+            .with_debug_log(true);
+        editor.set_text("one\ntwo\n");
+        harness.start(&mut editor);
+        harness.type_keys(&mut editor, "jxi");
+        harness.frame(&mut editor, Vec::new());
+
+        let entries: Vec<String> = editor
+            .debug_log()
+            .unwrap()
+            .entries()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            entries,
+            vec![
+                "key J",
+                "text \"j\"",
+                "vim: 'j' key pressed - moving down",
+                "vim: Text event detected: 'j'",
+                "command MoveCursor(Down)",
+                "key X",
+                "text \"x\"",
+                "vim: 'x' key pressed - deleting character under cursor",
+                "vim: Text event detected: 'x'",
+                "command DeleteCharForward",
+                "key I",
+                "text \"i\"",
+                "vim: 'i' key pressed - entering insert mode",
+                "vim: Text event detected: 'i'",
+                "mode Vim(Normal) -> Vim(Insert)",
+            ]
+        );
+    }
+
+    #[test]
+    fn debug_log_is_off_by_default() {
+        let mut harness = Harness::new();
+        let mut editor =
+            EditorWidget::new("no_debug_log").with_mode(EditorMode::Vim(VimMode::Normal));
+        editor.set_text("one\ntwo\n");
+        harness.start(&mut editor);
+        harness.type_keys(&mut editor, "j");
+        assert!(editor.debug_log().is_none());
+        assert!(editor.vim_handler.take_debug_messages().is_empty());
+    }
+
+    /// An editor whose save and quit callbacks append to a shared list of events
+    fn editor_recording_save_and_quit(
+        id: &str,
+        mode: EditorMode,
+        events: &Rc<RefCell<Vec<String>>>,
+    ) -> EditorWidget {
+        EditorWidget::new(id)
+            .with_mode(mode)
+            .with_save_callback({
+                let events = Rc::clone(events);
+                move |text| events.borrow_mut().push(format!("save {text:?}"))
+            })
+            .with_quit_callback({
+                let events = Rc::clone(events);
+                move |dirty| events.borrow_mut().push(format!("quit dirty={dirty}"))
+            })
+    }
+
+    #[test]
+    fn trailing_whitespace_is_trimmed_only_when_saving() {
+        let mut harness = Harness::new();
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let mut editor =
+            editor_recording_save_and_quit("trim", EditorMode::Vim(VimMode::Normal), &events)
+                .with_trim_trailing_whitespace_on_save(true)
+                .with_final_newline_on_save(true);
+        editor.set_text("one  \ntwo\t\n\nthree ");
+        editor.move_cursor_to(1, 3);
+        harness.start(&mut editor);
+        harness.type_keys(&mut editor, "x");
+        assert_eq!(editor.text(), "one  \ntwo\n\nthree ");
+        assert!(events.borrow().is_empty());
+
+        harness.type_keys(&mut editor, ":w");
+        harness.press(&mut editor, Key::Enter, Modifiers::NONE);
+        assert_eq!(*events.borrow(), ["save \"one\\ntwo\\n\\nthree\\n\""]);
+        assert_eq!(editor.text(), "one\ntwo\n\nthree\n");
+        assert_eq!(editor.buffer.cursor_line_col(), (1, 2));
+        assert!(!editor.is_dirty());
+    }
+
+    #[test]
+    fn quit_commands_report_whether_there_are_unsaved_changes() {
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let mut harness = Harness::new();
+        let mut editor =
+            editor_recording_save_and_quit("quit", EditorMode::Vim(VimMode::Normal), &events);
+        editor.set_text("one\n");
+        harness.start(&mut editor);
+
+        harness.type_keys(&mut editor, ":q");
+        harness.press(&mut editor, Key::Enter, Modifiers::NONE);
+        assert_eq!(*events.borrow(), vec!["quit dirty=false"]);
+
+        events.borrow_mut().clear();
+        harness.type_keys(&mut editor, "x:q");
+        harness.press(&mut editor, Key::Enter, Modifiers::NONE);
+        assert_eq!(*events.borrow(), vec!["quit dirty=true"]);
+
+        events.borrow_mut().clear();
+        harness.type_keys(&mut editor, ":wq");
+        harness.press(&mut editor, Key::Enter, Modifiers::NONE);
+        assert_eq!(*events.borrow(), vec!["save \"ne\\n\"", "quit dirty=false"]);
+    }
+
+    #[test]
+    fn write_quit_without_a_save_handler_still_quits_dirty() {
+        let quit = Rc::new(RefCell::new(Vec::new()));
+        let mut harness = Harness::new();
+        let mut editor = EditorWidget::new("wq_unsaved")
+            .with_mode(EditorMode::Vim(VimMode::Normal))
+            .with_quit_callback({
+                let quit = Rc::clone(&quit);
+                move |dirty| quit.borrow_mut().push(dirty)
+            });
+        editor.set_text("one\n");
+        harness.start(&mut editor);
+
+        harness.type_keys(&mut editor, "x:wq");
+        harness.press(&mut editor, Key::Enter, Modifiers::NONE);
+        assert_eq!(*quit.borrow(), vec![true]);
+    }
+
+    #[test]
+    fn emacs_quit_reports_unsaved_changes() {
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let mut harness = Harness::new();
+        let mut editor = editor_recording_save_and_quit("emacs_quit", EditorMode::Emacs, &events);
+        harness.start(&mut editor);
+
+        harness.type_keys(&mut editor, "a");
+        harness.press(&mut editor, Key::X, Modifiers::CTRL);
+        harness.press(&mut editor, Key::C, Modifiers::CTRL);
+        assert_eq!(*events.borrow(), vec!["quit dirty=true"]);
+    }
+
+    #[test]
+    fn emacs_open_line_keeps_the_cursor_and_newline_and_indent_moves_it() {
+        let mut harness = Harness::new();
+        let mut editor = EditorWidget::new("emacs_open_line").with_mode(EditorMode::Emacs);
+        editor.set_text("    let a = 1;");
+        editor.move_cursor_to(0, 8);
+        harness.start(&mut editor);
+
+        harness.press(&mut editor, Key::O, Modifiers::CTRL);
+        assert_eq!(editor.text(), "    let \na = 1;");
+        assert_eq!(editor.buffer.cursor_position(), 8);
+
+        harness.press(&mut editor, Key::J, Modifiers::CTRL);
+        assert_eq!(editor.text(), "    let \n    \na = 1;");
+        assert_eq!(editor.buffer.cursor_line_col(), (1, 4));
+    }
+
+    #[test]
+    fn emacs_c_d_stops_at_the_end_and_c_k_on_an_empty_line_kills_the_newline() {
+        let mut harness = Harness::new();
+        let mut editor = EditorWidget::new("emacs_delete").with_mode(EditorMode::Emacs);
+        editor.set_text("one\n\ntwo");
+        editor.move_cursor_to(2, 3);
+        harness.start(&mut editor);
+
+        harness.press(&mut editor, Key::D, Modifiers::CTRL);
+        assert_eq!(editor.text(), "one\n\ntwo");
+        assert!(!editor.is_dirty());
+
+        editor.move_cursor_to(1, 0);
+        harness.frame(&mut editor, Vec::new());
+        harness.press(&mut editor, Key::K, Modifiers::CTRL);
+        assert_eq!(editor.text(), "one\ntwo");
+        assert_eq!(editor.buffer.cursor_position(), 4);
+        assert_eq!(editor.kill_ring().yank(), Some("\n"));
+
+        harness.press(&mut editor, Key::D, Modifiers::CTRL);
+        assert_eq!(editor.text(), "one\nwo");
+    }
+
+    #[test]
+    fn consecutive_emacs_kills_collect_in_one_ring_entry() {
+        let mut harness = Harness::new();
+        let mut editor = EditorWidget::new("emacs_kill_ring").with_mode(EditorMode::Emacs);
+        editor.set_text("alpha beta\ngamma delta");
+        harness.start(&mut editor);
+
+        harness.press(&mut editor, Key::K, Modifiers::CTRL);
+        harness.press(&mut editor, Key::K, Modifiers::CTRL);
+        assert_eq!(editor.text(), "gamma delta");
+        assert_eq!(editor.kill_ring().entries(), ["alpha beta\n"]);
+
+        // Any other command starts a new entry, and backward word kills
+        // join it in front
+        harness.press(&mut editor, Key::E, Modifiers::CTRL);
+        harness.press(&mut editor, Key::Backspace, Modifiers::ALT);
+        harness.press(&mut editor, Key::Backspace, Modifiers::ALT);
+        assert_eq!(editor.text(), "");
+        assert_eq!(editor.kill_ring().len(), 2);
+        assert_eq!(editor.kill_ring().yank(), Some("gamma delta"));
+
+        harness.press(&mut editor, Key::Y, Modifiers::CTRL);
+        assert_eq!(editor.text(), "gamma delta");
+    }
+
+    #[test]
+    fn zz_saves_then_quits_and_zq_only_quits() {
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let mut harness = Harness::new();
+        let mut editor =
+            editor_recording_save_and_quit("zz", EditorMode::Vim(VimMode::Normal), &events);
+        editor.set_text("one\n");
+        harness.start(&mut editor);
+
+        harness.type_keys(&mut editor, "xZZ");
+        assert_eq!(*events.borrow(), vec!["save \"ne\\n\"", "quit dirty=false"]);
+
+        events.borrow_mut().clear();
+        harness.type_keys(&mut editor, "xZQ");
+        assert_eq!(*events.borrow(), vec!["quit dirty=true"]);
+        assert_eq!(editor.text(), "e\n");
+    }
+
+    #[test]
+    fn vim_yy_then_p_duplicates_the_line_below_and_capital_p_above() {
+        let mut harness = Harness::new();
+        let mut editor = EditorWidget::new("yyp").with_mode(EditorMode::Vim(VimMode::Normal));
+        editor.set_text("one\ntwo");
+        harness.start(&mut editor);
+
+        harness.type_keys(&mut editor, "yyp");
+        assert_eq!(editor.text(), "one\none\ntwo");
+        assert_eq!(editor.buffer.current_line(), 1);
+
+        // The last line has no newline of its own
+        harness.type_keys(&mut editor, "jyyp");
+        assert_eq!(editor.text(), "one\none\ntwo\ntwo");
+        assert_eq!(editor.buffer.current_line(), 3);
+
+        harness.type_keys(&mut editor, "ggP");
+        assert_eq!(editor.text(), "two\none\none\ntwo\ntwo");
+        assert_eq!(editor.buffer.current_line(), 0);
+    }
+
+    /// The text and colors of the highlighting the editor last cached
+    fn cached_highlight(editor: &EditorWidget) -> (String, Vec<Color32>) {
+        let job = &editor.highlight_cache.as_ref().unwrap().job;
+        let colors = job.sections.iter().map(|section| section.format.color);
+        (job.text.clone(), colors.collect())
+    }
+
+    #[test]
+    fn changing_the_theme_after_an_edit_recolors_the_same_text() {
+        let mut harness = Harness::new();
+        let mut editor = EditorWidget::new("theme").with_mode(EditorMode::Plain);
+        harness.start(&mut editor);
+        harness.type_keys(&mut editor, "abc");
+        harness.frame(&mut editor, Vec::new());
+
+        let (text, colors) = cached_highlight(&editor);
+        assert_eq!(text, "abc");
+        assert_eq!(colors, vec![HighlightTheme::default().foreground]);
+
+        // Without a syntax highlighter the theme colors `basic_highlight`
+        editor.set_theme(HighlightTheme::default().with_foreground(Color32::RED));
+        harness.frame(&mut editor, Vec::new());
+        let (text, colors) = cached_highlight(&editor);
+        assert_eq!(text, "abc");
+        assert_eq!(colors, vec![Color32::RED]);
+    }
+
+    #[test]
+    fn highlighters_lay_out_text_at_the_editor_font_size() {
+        let mut harness = Harness::new();
+        let mut editor = EditorWidget::new("font_size")
+            .with_mode(EditorMode::Plain)
+            .with_font_size(24.0)
+            .with_syntax_highlighter(LanguageHighlighter::new("rust", RustTokenizer::default()));
+        editor.set_text("fn main() {}");
+        harness.start(&mut editor);
+
+        let sizes = |editor: &EditorWidget| {
+            let job = &editor.highlight_cache.as_ref().unwrap().job;
+            job.sections
+                .iter()
+                .map(|section| section.format.font_id.size)
+                .collect::<Vec<_>>()
+        };
+        assert!(!sizes(&editor).is_empty());
+        assert!(sizes(&editor).iter().all(|&size| size == 24.0));
+
+        editor.set_font_size(18.0);
+        harness.frame(&mut editor, Vec::new());
+        assert!(sizes(&editor).iter().all(|&size| size == 18.0));
+    }
+
+    #[test]
+    fn text_changed_outside_the_buffer_methods_is_highlighted_again() {
+        let mut harness = Harness::new();
+        let mut editor = EditorWidget::new("stale_highlight").with_mode(EditorMode::Plain);
+        editor.set_text("one");
+        harness.start(&mut editor);
+        assert_eq!(cached_highlight(&editor).0, "one");
+
+        editor.text_mut().push_str(" two");
+        harness.frame(&mut editor, Vec::new());
+        assert_eq!(cached_highlight(&editor).0, "one two");
+    }
+
+    #[test]
+    fn visible_lines_count_a_wrapped_line_once() {
+        let mut harness = Harness::new();
+        let mut editor = EditorWidget::new("wrapped_view")
+            .with_mode(EditorMode::Plain)
+            .with_scroll_area(true)
+            .with_word_wrap(true);
+        // The first line wraps over far more rows than fit in the viewport
+        editor.set_text(format!("{}\nsecond\nthird", "word ".repeat(2000)));
+        harness.start(&mut editor);
+        assert_eq!(editor.visible_line_range(), (0, 0));
+
+        editor.set_word_wrap(false);
+        harness.frame(&mut editor, Vec::new());
+        assert_eq!(editor.visible_line_range(), (0, 2));
+    }
+
+    #[test]
+    fn language_by_extension_falls_back_for_unknown_extensions() {
+        let mut editor = EditorWidget::new("language");
+        assert!(editor.set_language_by_extension("rs"));
+        assert_eq!(editor.language(), Some("rust"));
+        assert!(editor.set_language_by_extension("md"));
+        assert_eq!(editor.language(), Some("markdown"));
+
+        assert!(!editor.set_language_by_extension("py"));
+        assert_eq!(editor.language(), None);
+        assert!(editor.highlighter.is_none());
+    }
+
+    #[test]
+    fn vim_j_and_k_keep_the_goal_column_across_a_short_line() {
+        let mut harness = Harness::new();
+        let mut editor =
+            EditorWidget::new("goal_column").with_mode(EditorMode::Vim(VimMode::Normal));
+        editor.set_text("long line\nab\nlong line two");
+        editor.move_cursor_to(0, 7);
+        harness.start(&mut editor);
+
+        harness.type_keys(&mut editor, "j");
+        assert_eq!(editor.buffer.current_line(), 1);
+        harness.type_keys(&mut editor, "j");
+        assert_eq!(editor.buffer.cursor_line_col(), (2, 7));
+        harness.type_keys(&mut editor, "kk");
+        assert_eq!(editor.buffer.cursor_line_col(), (0, 7));
+    }
+
+    #[test]
+    fn showing_whitespace_leaves_the_text_alone() {
+        let mut harness = Harness::new();
+        let mut editor = EditorWidget::new("whitespace")
+            .with_mode(EditorMode::Plain)
+            .with_show_whitespace(true);
+        editor.set_text("a\tb");
+        harness.start(&mut editor);
+        harness.press(&mut editor, Key::End, Modifiers::NONE);
+        harness.type_keys(&mut editor, " c");
+        assert_eq!(editor.text(), "a\tb c");
+    }
+
+    #[test]
+    fn a_pending_key_times_out_and_the_next_key_acts_alone() {
+        let mut harness = Harness::new();
+        let mut editor = EditorWidget::new("timeout").with_mode(EditorMode::Vim(VimMode::Normal));
+        editor.set_text("one\ntwo\nthree");
+        harness.start(&mut editor);
+
+        harness.type_keys(&mut editor, "d");
+        harness.wait(keyhandler::DEFAULT_SEQUENCE_TIMEOUT + 1.0);
+        harness.type_keys(&mut editor, "j");
+        assert_eq!(editor.text(), "one\ntwo\nthree");
+        assert_eq!(editor.buffer.current_line(), 1);
+
+        // Within the timeout the sequence completes
+        harness.type_keys(&mut editor, "dd");
+        assert_eq!(editor.text(), "one\nthree");
+    }
+
+    #[test]
+    fn an_emacs_prefix_times_out() {
+        let mut harness = Harness::new();
+        let mut editor = EditorWidget::new("emacs_timeout").with_mode(EditorMode::Emacs);
+        editor.set_text("one");
+        harness.start(&mut editor);
+
+        harness.press(&mut editor, Key::X, Modifiers::CTRL);
+        harness.wait(keyhandler::DEFAULT_SEQUENCE_TIMEOUT + 1.0);
+        harness.type_keys(&mut editor, "h");
+        assert_eq!(editor.selection(), None);
+        assert_eq!(editor.text(), "hone");
+    }
+
+    #[test]
+    fn command_observer_sees_the_commands_a_key_produces() {
+        let observed = Rc::new(RefCell::new(Vec::new()));
+        let mut harness = Harness::new();
+        let mut editor = EditorWidget::new("observer")
+            .with_mode(EditorMode::Vim(VimMode::Normal))
+            .with_command_observer({
+                let observed = Rc::clone(&observed);
+                move |command| observed.borrow_mut().push(command.clone())
+            });
+        editor.set_text("abc");
+        harness.start(&mut editor);
+
+        harness.type_keys(&mut editor, "x");
+        assert_eq!(*observed.borrow(), vec![EditorCommand::DeleteCharForward]);
+        assert_eq!(editor.text(), "bc");
+
+        observed.borrow_mut().clear();
+        harness.type_keys(&mut editor, "lx");
+        editor.apply_command(EditorCommand::SelectAll);
+        assert_eq!(
+            *observed.borrow(),
+            vec![
+                EditorCommand::MoveCursor(CursorMovement::Right),
+                EditorCommand::DeleteCharForward,
+                EditorCommand::SelectAll,
+            ]
+        );
+    }
+
+    #[test]
+    fn vim_substitute_on_the_current_line_globally_and_in_the_whole_file() {
+        let mut harness = Harness::new();
+        let mut editor =
+            EditorWidget::new("substitute").with_mode(EditorMode::Vim(VimMode::Normal));
+        editor.set_text("a a\na a\na a");
+        editor.move_cursor_to(1, 0);
+        harness.start(&mut editor);
+        let run = |harness: &mut Harness, editor: &mut EditorWidget, line: &str| {
+            harness.type_keys(editor, line);
+            harness.press(editor, Key::Enter, Modifiers::NONE);
+        };
+
+        run(&mut harness, &mut editor, ":s/a/b/");
+        assert_eq!(editor.text(), "a a\nb a\na a");
+        run(&mut harness, &mut editor, ":s/a/c/g");
+        assert_eq!(editor.text(), "a a\nb c\na a");
+        run(&mut harness, &mut editor, ":%s/a/d/g");
+        assert_eq!(editor.text(), "d d\nb c\nd d");
+        assert!(editor.is_dirty());
     }
 }
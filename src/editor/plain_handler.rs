@@ -0,0 +1,58 @@
+use crate::editor::commands::EditorCommand;
+use crate::editor::keyhandler::KeyHandler;
+use egui::{Context, Event, InputState, Key};
+
+/// A pass-through key handler for plain editing mode
+///
+/// Input goes to the `TextEdit` untouched, except that Ctrl+A (Cmd+A on
+/// macOS) selects all through the editor like it does in Vim insert mode.
+#[derive(Default)]
+pub struct PlainKeyHandler {
+    /// Commands queued by the last `process_input` call
+    commands: Vec<EditorCommand>,
+}
+
+impl PlainKeyHandler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl KeyHandler for PlainKeyHandler {
+    fn process_input(&mut self, _ctx: &Context, input: &mut InputState) -> Vec<usize> {
+        self.commands.clear();
+
+        let events_to_remove: Vec<usize> = input
+            .events
+            .iter()
+            .enumerate()
+            .filter(|(_, event)| is_select_all(event))
+            .map(|(i, _)| i)
+            .collect();
+        if !events_to_remove.is_empty() {
+            self.commands.push(EditorCommand::SelectAll);
+        }
+        events_to_remove
+    }
+
+    fn name(&self) -> &'static str {
+        "plain"
+    }
+
+    fn take_commands(&mut self) -> Vec<EditorCommand> {
+        std::mem::take(&mut self.commands)
+    }
+}
+
+/// Whether an event is the platform select-all shortcut (Ctrl+A, or Cmd+A on macOS)
+pub(crate) fn is_select_all(event: &Event) -> bool {
+    matches!(
+        event,
+        Event::Key {
+            key: Key::A,
+            pressed: true,
+            modifiers,
+            ..
+        } if modifiers.command && !modifiers.shift && !modifiers.alt
+    )
+}
@@ -0,0 +1,60 @@
+//! Text shared between several editor views
+//!
+//! Each `EditorWidget` keeps its own buffer, cursor, and mode. A view attached
+//! to a [`SharedBuffer`] publishes its edits to the shared text as they happen
+//! and picks up edits made elsewhere at the start of its next `show`, so every
+//! attached view displays the same document (e.g. a split view).
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// The shared text and a counter bumped on every change
+#[derive(Debug, Default)]
+struct SharedText {
+    text: String,
+    version: u64,
+}
+
+/// A handle to text shared by several editors; clones refer to the same text
+#[derive(Debug, Clone, Default)]
+pub struct SharedBuffer {
+    inner: Rc<RefCell<SharedText>>,
+}
+
+impl SharedBuffer {
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            inner: Rc::new(RefCell::new(SharedText {
+                text: text.into(),
+                version: 0,
+            })),
+        }
+    }
+
+    /// A copy of the current text
+    pub fn text(&self) -> String {
+        self.inner.borrow().text.clone()
+    }
+
+    /// Replace the text; attached editors show it from their next frame
+    pub fn set_text(&self, text: impl Into<String>) {
+        let mut shared = self.inner.borrow_mut();
+        shared.text = text.into();
+        shared.version += 1;
+    }
+
+    /// Counter that changes whenever the text is replaced
+    pub fn version(&self) -> u64 {
+        self.inner.borrow().version
+    }
+
+    /// Publish `text` unless it is already current, returning the new version
+    pub(crate) fn publish(&self, text: &str) -> u64 {
+        let mut shared = self.inner.borrow_mut();
+        if shared.text != text {
+            text.clone_into(&mut shared.text);
+            shared.version += 1;
+        }
+        shared.version
+    }
+}
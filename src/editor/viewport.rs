@@ -0,0 +1,173 @@
+//! Viewport arithmetic shared by scrolling and viewport-limited rendering
+
+use crate::editor::commands::ScrollPosition;
+
+/// The inclusive range of lines visible in a viewport
+///
+/// Assumes every line is one row of `row_height` points (no soft wrap); with
+/// wrapped rows use `visible_line_range_in_rows`.
+/// `scroll_offset` is how far the text has been scrolled past the viewport top.
+/// Returns `(first_line, last_line)`, both clamped to the document.
+pub fn visible_line_range(
+    scroll_offset: f32,
+    viewport_height: f32,
+    row_height: f32,
+    line_count: usize,
+) -> (usize, usize) {
+    if line_count == 0 || row_height <= 0.0 {
+        return (0, 0);
+    }
+
+    let last_line = line_count - 1;
+    let first = (scroll_offset.max(0.0) / row_height).floor() as usize;
+    let last = ((scroll_offset.max(0.0) + viewport_height.max(0.0)) / row_height).ceil() as usize;
+
+    (
+        first.min(last_line),
+        last.saturating_sub(1).max(first).min(last_line),
+    )
+}
+
+/// The inclusive range of lines visible in a viewport, from laid-out rows
+///
+/// Each row is `(top, bottom, ends_line)`, measured from the top of the text,
+/// where `ends_line` is whether the row ends with its line's newline. A line
+/// soft-wrapped over several rows counts as visible if any of its rows is.
+/// Returns `(first_line, last_line)`; a viewport past the last row gives the
+/// last line.
+pub fn visible_line_range_in_rows(
+    rows: impl IntoIterator<Item = (f32, f32, bool)>,
+    scroll_offset: f32,
+    viewport_height: f32,
+) -> (usize, usize) {
+    let top = scroll_offset.max(0.0);
+    let bottom = top + viewport_height.max(0.0);
+    let mut line = 0;
+    let mut visible: Option<(usize, usize)> = None;
+    for (row_top, row_bottom, ends_line) in rows {
+        if row_top >= bottom {
+            break;
+        }
+        if row_bottom > top {
+            visible = Some((visible.map_or(line, |(first, _)| first), line));
+        }
+        if ends_line {
+            line += 1;
+        }
+    }
+    visible.unwrap_or((line, line))
+}
+
+/// The scroll offset that puts a row at `position` in the viewport
+///
+/// `row_top` is the row's distance from the top of the text. The result is
+/// never negative, so rows near the start of the document can't be moved
+/// further down than the first line allows.
+pub fn scroll_offset_for_row(
+    row_top: f32,
+    row_height: f32,
+    viewport_height: f32,
+    position: ScrollPosition,
+) -> f32 {
+    let offset = match position {
+        ScrollPosition::Top => row_top,
+        ScrollPosition::Center => row_top + (row_height - viewport_height) / 2.0,
+        ScrollPosition::Bottom => row_top + row_height - viewport_height,
+    };
+    offset.max(0.0)
+}
+
+/// The scroll offset that keeps `margin` points of context above and below a row
+///
+/// `scroll_offset` is the current offset. Returns `None` when the row and its
+/// margins already fit in the viewport. A margin too large for the viewport is
+/// shrunk so the row ends up centered.
+pub fn scroll_offset_with_margin(
+    row_top: f32,
+    row_height: f32,
+    viewport_height: f32,
+    scroll_offset: f32,
+    margin: f32,
+) -> Option<f32> {
+    let margin = margin.min((viewport_height - row_height) / 2.0).max(0.0);
+    let top = (row_top - margin).max(0.0);
+    let bottom = row_top + row_height + margin - viewport_height;
+    if scroll_offset > top {
+        Some(top)
+    } else if scroll_offset < bottom {
+        Some(bottom)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn visible_line_range_counts_whole_and_partly_visible_rows() {
+        // Rows of 10 points, scrolled 25 points into a 100 line document
+        assert_eq!(visible_line_range(25.0, 30.0, 10.0, 100), (2, 5));
+        assert_eq!(visible_line_range(0.0, 30.0, 10.0, 100), (0, 2));
+        // Clamped to the document, and to the top when scrolled above it
+        assert_eq!(visible_line_range(950.0, 100.0, 10.0, 100), (95, 99));
+        assert_eq!(visible_line_range(-5.0, 20.0, 10.0, 3), (0, 1));
+        assert_eq!(visible_line_range(0.0, 20.0, 10.0, 0), (0, 0));
+    }
+
+    #[test]
+    fn visible_line_range_in_rows_follows_wrapped_lines() {
+        // Line 0 wraps over three rows, lines 1 and 2 take one row each
+        let rows = [
+            (0.0, 10.0, false),
+            (10.0, 20.0, false),
+            (20.0, 30.0, true),
+            (30.0, 40.0, true),
+            (40.0, 50.0, false),
+        ];
+        assert_eq!(visible_line_range_in_rows(rows, 0.0, 20.0), (0, 0));
+        assert_eq!(visible_line_range_in_rows(rows, 15.0, 20.0), (0, 1));
+        assert_eq!(visible_line_range_in_rows(rows, 30.0, 100.0), (1, 2));
+        assert_eq!(visible_line_range_in_rows(rows, 60.0, 20.0), (2, 2));
+    }
+
+    #[test]
+    fn scroll_offset_for_row_puts_the_row_at_the_top_center_or_bottom() {
+        // A 20 point row 500 points down, in a 200 point viewport
+        let offset = |position| scroll_offset_for_row(500.0, 20.0, 200.0, position);
+        assert_eq!(offset(ScrollPosition::Top), 500.0);
+        assert_eq!(offset(ScrollPosition::Center), 410.0);
+        assert_eq!(offset(ScrollPosition::Bottom), 320.0);
+
+        // Rows near the start can't be moved further down than the first line
+        assert_eq!(
+            scroll_offset_for_row(40.0, 20.0, 200.0, ScrollPosition::Center),
+            0.0
+        );
+        assert_eq!(
+            scroll_offset_for_row(40.0, 20.0, 200.0, ScrollPosition::Bottom),
+            0.0
+        );
+    }
+    #[test]
+    fn scroll_offset_with_margin_scrolls_only_when_the_cursor_enters_the_margin() {
+        // Rows of 10 points in a 100 point viewport, with a three line margin
+        let offset = |row_top, scroll_offset| {
+            scroll_offset_with_margin(row_top, 10.0, 100.0, scroll_offset, 30.0)
+        };
+        assert_eq!(offset(50.0, 0.0), None);
+        assert_eq!(offset(60.0, 0.0), None);
+        assert_eq!(offset(70.0, 0.0), Some(10.0));
+        assert_eq!(offset(200.0, 120.0), Some(140.0));
+        assert_eq!(offset(150.0, 130.0), Some(120.0));
+        // Near the start the margin can't scroll above the first line
+        assert_eq!(offset(10.0, 50.0), Some(0.0));
+
+        // A margin taller than half the viewport centers the row
+        assert_eq!(
+            scroll_offset_with_margin(500.0, 10.0, 100.0, 0.0, 100.0),
+            Some(455.0)
+        );
+    }
+}
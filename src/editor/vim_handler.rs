@@ -1,13 +1,33 @@
-use crate::editor::commands::VimMode;
-use crate::editor::keyhandler::KeyHandler;
-use egui::{Context, Event, InputState, Key, Modifiers};
+use crate::editor::commands::{CursorMovement, EditorCommand, EditorMode, ScrollPosition, VimMode};
+use crate::editor::keyhandler::{
+    key_event_indices, keyboard_event_indices, KeyHandler, PendingKey, DEFAULT_SEQUENCE_TIMEOUT,
+};
+use crate::editor::plain_handler::is_select_all;
+use egui::{Context, Event, ImeEvent, InputState, Key, Modifiers};
 
 /// Implements Vim key handling for the editor
 pub struct VimKeyHandler {
     /// The current vim mode (Normal, Insert, Visual)
     mode: VimMode,
-    /// Debug printing enabled/disabled
+    /// Whether `debug_log` keeps its messages
     debug: bool,
+    /// Messages explaining how keys were read, for the editor's debug log
+    debug_messages: Vec<String>,
+    /// Buffer commands queued for the editor to execute
+    commands: Vec<EditorCommand>,
+    /// First key of a multi-key command (`gg`, `dd`) waiting for its second key
+    pending: Option<PendingKey>,
+    /// Seconds a pending key waits before it is discarded
+    sequence_timeout: f64,
+    /// Text typed after `:` in command-line mode
+    command_line: String,
+    /// Whether normal-mode motions move the buffer cursor instead of
+    /// synthesizing arrow/Home/End key events for the `TextEdit`
+    direct_motions: bool,
+    /// Two characters that leave insert mode when typed in quick succession (e.g. `jk`)
+    insert_escape: Option<[char; 2]>,
+    /// Input time at which the first character of `insert_escape` was typed
+    insert_escape_started: Option<f64>,
 }
 
 impl Default for VimKeyHandler {
@@ -15,6 +35,14 @@ impl Default for VimKeyHandler {
         Self {
             mode: VimMode::Normal,
             debug: false,
+            debug_messages: Vec::new(),
+            commands: Vec::new(),
+            pending: None,
+            sequence_timeout: DEFAULT_SEQUENCE_TIMEOUT,
+            command_line: String::new(),
+            direct_motions: false,
+            insert_escape: None,
+            insert_escape_started: None,
         }
     }
 }
@@ -30,6 +58,67 @@ impl VimKeyHandler {
         self
     }
 
+    /// Keep or stop keeping messages about how keys were read
+    pub fn set_debug(&mut self, debug: bool) {
+        self.debug = debug;
+        if !debug {
+            self.debug_messages.clear();
+        }
+    }
+
+    /// Take the messages kept since the last call, oldest first
+    pub fn take_debug_messages(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.debug_messages)
+    }
+
+    /// Set how long (in seconds) the first key of `gg`/`dd` waits for the second
+    #[must_use]
+    pub const fn with_sequence_timeout(mut self, seconds: f64) -> Self {
+        self.sequence_timeout = seconds;
+        self
+    }
+
+    /// Move the cursor in normal mode through the buffer instead of synthesized keys
+    #[must_use]
+    pub const fn with_direct_motions(mut self, direct: bool) -> Self {
+        self.direct_motions = direct;
+        self
+    }
+
+    /// Switch normal-mode motions between buffer commands and synthesized keys
+    pub const fn set_direct_motions(&mut self, direct: bool) {
+        self.direct_motions = direct;
+    }
+
+    pub const fn direct_motions(&self) -> bool {
+        self.direct_motions
+    }
+
+    /// Leave insert mode when the two characters of `sequence` (e.g. `"jk"`) are
+    /// typed within the sequence timeout
+    ///
+    /// The characters are not left in the text. Anything other than exactly two
+    /// characters turns the sequence off.
+    #[must_use]
+    pub fn with_insert_escape_sequence(mut self, sequence: &str) -> Self {
+        self.set_insert_escape_sequence(sequence);
+        self
+    }
+
+    pub fn set_insert_escape_sequence(&mut self, sequence: &str) {
+        let mut chars = sequence.chars();
+        self.insert_escape = match (chars.next(), chars.next(), chars.next()) {
+            (Some(first), Some(second), None) => Some([first, second]),
+            _ => None,
+        };
+        self.insert_escape_started = None;
+    }
+
+    /// The insert-mode escape sequence, if one is set
+    pub fn insert_escape_sequence(&self) -> Option<String> {
+        self.insert_escape.map(String::from_iter)
+    }
+
     pub const fn mode(&self) -> VimMode {
         self.mode
     }
@@ -37,12 +126,85 @@ impl VimKeyHandler {
     /// Set the current vim mode
     pub const fn set_mode(&mut self, mode: VimMode) {
         self.mode = mode;
+        self.pending = None;
+        self.insert_escape_started = None;
     }
 
-    /// Enable or disable debug logging
-    fn debug_log(&self, message: &str) {
+    /// The text typed so far after `:`, while in command-line mode
+    pub fn command_line(&self) -> Option<&str> {
+        (self.mode == VimMode::CommandLine).then_some(self.command_line.as_str())
+    }
+
+    /// Drop a pending key whose sequence was abandoned for longer than the timeout
+    fn expire_pending(&mut self, now: f64) {
+        if self
+            .pending
+            .is_some_and(|pending| pending.is_expired(now, self.sequence_timeout))
+        {
+            self.debug_log("Pending key timed out - discarding");
+            self.pending = None;
+        }
+    }
+
+    /// Record a press of `key` as part of a doubled sequence (`gg`, `dd`)
+    ///
+    /// Returns true when this press completes the sequence; otherwise `key`
+    /// becomes the pending key.
+    fn complete_sequence(&mut self, key: Key, now: f64) -> bool {
+        if self.pending.is_some_and(|pending| pending.key == key) {
+            self.pending = None;
+            true
+        } else {
+            self.pending = Some(PendingKey::new(key, now));
+            false
+        }
+    }
+
+    /// Keep `message` for the editor's debug log, if debugging is on
+    fn debug_log(&mut self, message: &str) {
         if self.debug {
-            println!("[VimKeyHandler] {message}");
+            self.debug_messages.push(message.to_string());
+        }
+    }
+
+    /// Queue a motion once per frame, whether it arrived as a key or as text
+    fn push_motion_once(&mut self, movement: CursorMovement) {
+        let command = EditorCommand::MoveCursor(movement);
+        if !self.commands.contains(&command) {
+            self.commands.push(command);
+        }
+    }
+
+    /// The paragraph motion for `{` (`[` with shift) or `}` (`]` with shift)
+    fn paragraph_motion(key: Key) -> CursorMovement {
+        if key == Key::OpenBracket {
+            CursorMovement::ParagraphBackward
+        } else {
+            CursorMovement::ParagraphForward
+        }
+    }
+
+    /// Record the cursor position in the jumplist once per frame, before a jump motion
+    fn push_jump(&mut self) {
+        if !self.commands.contains(&EditorCommand::PushJump) {
+            self.commands.push(EditorCommand::PushJump);
+        }
+    }
+
+    /// Queue a normal-mode motion
+    ///
+    /// With direct motions this is a buffer command; otherwise the `synthesized`
+    /// events are added to the input for the `TextEdit` to move its own cursor.
+    fn push_motion(
+        &mut self,
+        events: &mut Vec<Event>,
+        movement: CursorMovement,
+        synthesized: impl IntoIterator<Item = Event>,
+    ) {
+        if self.direct_motions {
+            self.commands.push(EditorCommand::MoveCursor(movement));
+        } else {
+            events.extend(synthesized);
         }
     }
 
@@ -147,14 +309,25 @@ impl VimKeyHandler {
             });
         }
 
-        println!(
-            "DEBUG: Generated {} document navigation events (selection: {})",
-            events.len(),
-            with_selection
-        );
         events
     }
 
+    /// The command for a Home, End, Page Up, or Page Down press
+    ///
+    /// Ctrl+Home and Ctrl+End (Cmd on macOS) go to the start and end of the document.
+    fn navigation_command(key: Key, modifiers: Modifiers) -> EditorCommand {
+        match key {
+            Key::Home if modifiers.command => {
+                EditorCommand::MoveCursor(CursorMovement::DocumentStart)
+            }
+            Key::End if modifiers.command => EditorCommand::MoveCursor(CursorMovement::DocumentEnd),
+            Key::End => EditorCommand::MoveCursor(CursorMovement::LineEnd),
+            Key::PageUp => EditorCommand::PageUp,
+            Key::PageDown => EditorCommand::PageDown,
+            _ => EditorCommand::MoveCursor(CursorMovement::LineStart),
+        }
+    }
+
     /// Toggle visual mode from normal mode, or go to normal mode from visual mode
     fn toggle_visual_mode(&mut self) {
         match self.mode {
@@ -166,7 +339,7 @@ impl VimKeyHandler {
                 self.debug_log("Exiting visual mode");
                 self.mode = VimMode::Normal;
             }
-            VimMode::Insert => {
+            VimMode::Insert | VimMode::Replace | VimMode::CommandLine => {
                 // Only toggle between normal and visual
                 self.debug_log("Cannot toggle visual mode from current mode");
             }
@@ -177,296 +350,338 @@ impl VimKeyHandler {
     #[allow(clippy::too_many_lines)]
     fn handle_normal_mode(&mut self, input: &mut InputState) -> Vec<usize> {
         let mut events_to_remove = Vec::new();
+        let mut g_key_handled = false;
+        let mut z_handled = false;
+        let original_len = input.events.len();
+        self.expire_pending(input.time);
+
+        // Select-all isn't bound in normal mode; keep it from the TextEdit
+        events_to_remove.extend(
+            input
+                .events
+                .iter()
+                .enumerate()
+                .filter(|(_, event)| is_select_all(event))
+                .map(|(i, _)| i),
+        );
 
         // Process keyboard events (individual keys)
         for key in &input.keys_down {
             if input.key_pressed(*key) {
+                // The key after `z` picks where the cursor's line is scrolled to;
+                // `ZZ` saves and quits like `:wq`, and `ZQ` quits like `:q!`
+                if let Some(pending) = self.pending.filter(|pending| pending.key == Key::Z) {
+                    self.pending = None;
+                    z_handled = true;
+                    events_to_remove.extend(key_event_indices(&input.events, *key));
+
+                    let command = match (pending.shift, *key, input.modifiers.shift) {
+                        (false, Key::T, _) => {
+                            Some(EditorCommand::ScrollCursorLine(ScrollPosition::Top))
+                        }
+                        (false, Key::Z, _) => {
+                            Some(EditorCommand::ScrollCursorLine(ScrollPosition::Center))
+                        }
+                        (false, Key::B, _) => {
+                            Some(EditorCommand::ScrollCursorLine(ScrollPosition::Bottom))
+                        }
+                        (true, Key::Z, true) => {
+                            Some(EditorCommand::RunCommandLine("wq".to_string()))
+                        }
+                        (true, Key::Q, true) => {
+                            Some(EditorCommand::RunCommandLine("q!".to_string()))
+                        }
+                        _ => None,
+                    };
+                    if let Some(command) = command {
+                        self.debug_log(&format!("z sequence pressed - running {command:?}"));
+                        self.commands.push(command);
+                    } else {
+                        self.debug_log("Undefined z sequence - ignoring");
+                    }
+                    break;
+                }
+
+                // `gc` waits for a second `c`, which toggles the line's comment
+                if *key == Key::C
+                    && input.modifiers.is_none()
+                    && self.pending.is_some_and(|pending| pending.key == Key::G)
+                {
+                    self.debug_log("'gc' pressed - waiting for second key");
+                    events_to_remove.extend(key_event_indices(&input.events, *key));
+                    self.pending = Some(PendingKey::new(Key::C, input.time));
+                    break;
+                }
+
+                // Any other key abandons a pending two-key sequence
+                if self.pending.is_some_and(|pending| pending.key != *key) {
+                    self.pending = None;
+                }
+
                 match *key {
+                    Key::C if self.pending.is_some() => {
+                        self.debug_log("'gcc' pressed - toggling the line comment");
+                        events_to_remove.extend(key_event_indices(&input.events, *key));
+                        self.pending = None;
+                        self.commands.push(EditorCommand::ToggleLineComment);
+                    }
+
+                    // Ctrl+O and Ctrl+I walk back and forward through the jumplist
+                    Key::O if input.modifiers.ctrl => {
+                        self.debug_log("Ctrl+O pressed - jumping back");
+                        events_to_remove.extend(key_event_indices(&input.events, *key));
+                        self.commands.push(EditorCommand::JumpBack);
+                    }
+                    Key::I if input.modifiers.ctrl => {
+                        self.debug_log("Ctrl+I pressed - jumping forward");
+                        events_to_remove.extend(key_event_indices(&input.events, *key));
+                        self.commands.push(EditorCommand::JumpForward);
+                    }
+
                     // Mode transitions
+                    Key::R if input.modifiers.shift => {
+                        self.debug_log("'R' key pressed - entering replace mode");
+                        self.mode = VimMode::Replace;
+                        events_to_remove.extend(key_event_indices(&input.events, *key));
+                        break;
+                    }
                     Key::I => {
                         self.debug_log("'i' key pressed - entering insert mode");
                         self.mode = VimMode::Insert;
-                        events_to_remove.extend(0..input.events.len());
+                        events_to_remove.extend(key_event_indices(&input.events, *key));
+                        break;
+                    }
+                    Key::O => {
+                        // Open a line below (o) or above (O) and start inserting there
+                        if input.modifiers.shift {
+                            self.debug_log("'O' key pressed - opening line above");
+                            self.commands.push(EditorCommand::OpenLineAbove);
+                        } else {
+                            self.debug_log("'o' key pressed - opening line below");
+                            self.commands.push(EditorCommand::OpenLineBelow);
+                        }
+                        self.mode = VimMode::Insert;
+                        events_to_remove.extend(key_event_indices(&input.events, *key));
                         break;
                     }
                     Key::V => {
                         self.debug_log("'v' key pressed - entering visual mode");
                         self.toggle_visual_mode();
-                        events_to_remove.extend(0..input.events.len());
+                        events_to_remove.extend(key_event_indices(&input.events, *key));
                         break;
                     }
 
-                    // Basic movement - translate to arrow keys
+                    // Basic movement goes through the buffer: h and l step over emoji
+                    // and combining marks as one character, and j and k keep the
+                    // column and stop at the first and last lines
                     Key::H => {
-                        self.debug_log("'h' key pressed - mapping to Left arrow");
-                        println!(
-                            "DEBUG(h): All input events before processing: {:?}",
-                            input.events
-                        );
-                        println!("DEBUG(h): Current modifiers: {:?}", input.modifiers);
-                        events_to_remove.extend(0..input.events.len());
-
-                        let event = Event::Key {
-                            key: Key::ArrowLeft,
-                            physical_key: Some(Key::ArrowLeft),
-                            pressed: true,
-                            repeat: false,
-                            modifiers: input.modifiers,
-                        };
-                        println!("DEBUG(h): Pushing new event: {:?}", event);
-                        input.events.push(event);
-                        println!(
-                            "DEBUG(h): All input events after processing: {:?}",
-                            input.events
-                        );
+                        self.debug_log("'h' key pressed - moving left");
+                        events_to_remove.extend(key_event_indices(&input.events, *key));
+                        self.commands
+                            .push(EditorCommand::MoveCursor(CursorMovement::Left));
                     }
                     Key::J => {
-                        self.debug_log("'j' key pressed - mapping to Down arrow");
-                        events_to_remove.extend(0..input.events.len());
-
-                        input.events.push(Event::Key {
-                            key: Key::ArrowDown,
-                            physical_key: Some(Key::ArrowDown),
-                            pressed: true,
-                            repeat: false,
-                            modifiers: input.modifiers,
-                        });
+                        self.debug_log("'j' key pressed - moving down");
+                        events_to_remove.extend(key_event_indices(&input.events, *key));
+                        self.commands
+                            .push(EditorCommand::MoveCursor(CursorMovement::Down));
+                    }
+                    // Plain Up and Down behave like k and j instead of jumping to
+                    // the ends of the first and last lines
+                    Key::ArrowDown | Key::ArrowUp if input.modifiers.is_none() => {
+                        self.debug_log(&format!("{key:?} key pressed - moving"));
+                        events_to_remove.extend(key_event_indices(&input.events, *key));
+                        let movement = if *key == Key::ArrowDown {
+                            CursorMovement::Down
+                        } else {
+                            CursorMovement::Up
+                        };
+                        self.commands.push(EditorCommand::MoveCursor(movement));
                     }
                     Key::K => {
-                        self.debug_log("'k' key pressed - mapping to Up arrow");
-                        events_to_remove.extend(0..input.events.len());
-
-                        input.events.push(Event::Key {
-                            key: Key::ArrowUp,
-                            physical_key: Some(Key::ArrowUp),
-                            pressed: true,
-                            repeat: false,
-                            modifiers: input.modifiers,
-                        });
+                        self.debug_log("'k' key pressed - moving up");
+                        events_to_remove.extend(key_event_indices(&input.events, *key));
+                        self.commands
+                            .push(EditorCommand::MoveCursor(CursorMovement::Up));
                     }
                     Key::L => {
-                        self.debug_log("'l' key pressed - mapping to Right arrow");
-                        events_to_remove.extend(0..input.events.len());
-
-                        input.events.push(Event::Key {
-                            key: Key::ArrowRight,
-                            physical_key: Some(Key::ArrowRight),
-                            pressed: true,
-                            repeat: false,
-                            modifiers: input.modifiers,
-                        });
+                        self.debug_log("'l' key pressed - moving right");
+                        events_to_remove.extend(key_event_indices(&input.events, *key));
+                        self.commands
+                            .push(EditorCommand::MoveCursor(CursorMovement::Right));
                     }
 
-                    // Word movement using custom implementation for vim-like behavior
+                    // Word movement goes through the buffer so it follows the editor's word characters
                     Key::W => {
                         // Capital W and lowercase w both move by word in the same way
-                        self.debug_log("'w/W' key pressed - mapping to vim-style word movement");
-                        println!("DEBUG: Processing W key in normal mode");
-                        events_to_remove.extend(0..input.events.len());
-
-                        // PRECISE SINGLE WORD MOVEMENT APPROACH:
-                        // The previous implementation was adding too many events at once, causing
-                        // multiple word jumps. This implementation uses a more controlled approach
-                        // that ensures we move exactly one word at a time.
-
-                        // We'll use two separate approaches and let the platform choose which works:
-                        // 1. For Windows/Linux: Use a simple Right arrow + Ctrl+Right sequence
-                        // 2. For macOS: Use a Right arrow + Alt+Right sequence
-
-                        // First, a single right arrow to handle edge cases at word boundaries
-                        input.events.push(Event::Key {
-                            key: Key::ArrowRight,
-                            physical_key: Some(Key::ArrowRight),
-                            pressed: true,
-                            repeat: false,
-                            modifiers: Modifiers::default(),
-                        });
-
-                        // Then, use Ctrl+Right for Windows/Linux word movement
-                        // We'll do this in a separate message so it doesn't combine with the previous one
-                        let mut ctrl_mods = Modifiers {
-                            alt: core::default::Default::default(),
-                            ctrl: core::default::Default::default(),
-                            shift: core::default::Default::default(),
-                            mac_cmd: core::default::Default::default(),
-                            command: core::default::Default::default(),
-                        };
-                        ctrl_mods.ctrl = true;
-                        input.events.push(Event::Key {
-                            key: Key::ArrowRight,
-                            physical_key: Some(Key::ArrowRight),
-                            pressed: true,
-                            repeat: false,
-                            modifiers: ctrl_mods,
-                        });
-
-                        // Also try Alt+Right for macOS in a separate event
-                        let mut alt_mods = Modifiers {
-                            alt: core::default::Default::default(),
-                            ctrl: core::default::Default::default(),
-                            shift: core::default::Default::default(),
-                            mac_cmd: core::default::Default::default(),
-                            command: core::default::Default::default(),
-                        };
-                        alt_mods.alt = true;
-                        input.events.push(Event::Key {
-                            key: Key::ArrowRight,
-                            physical_key: Some(Key::ArrowRight),
-                            pressed: true,
-                            repeat: false,
-                            modifiers: alt_mods,
-                        });
+                        self.debug_log("'w/W' key pressed - moving to the next word");
+                        events_to_remove.extend(key_event_indices(&input.events, *key));
+                        self.push_motion_once(CursorMovement::WordRight);
                     }
                     Key::B => {
                         // Capital B and lowercase b both move by word backward in the same way
-                        self.debug_log("'b/B' key pressed - mapping to vim-style word movement");
-                        println!("DEBUG: Processing B key in normal mode");
-                        events_to_remove.extend(0..input.events.len());
-
-                        // PRECISE SINGLE WORD MOVEMENT APPROACH:
-                        // The previous implementation was adding too many events at once, causing
-                        // multiple word jumps. This implementation uses a more controlled approach
-                        // that ensures we move exactly one word at a time.
-
-                        // We'll use two separate approaches and let the platform choose which works:
-                        // 1. For Windows/Linux: Use a simple Left arrow + Ctrl+Left sequence
-                        // 2. For macOS: Use a Left arrow + Alt+Left sequence
-
-                        // First, a single left arrow to handle edge cases at word boundaries
-                        input.events.push(Event::Key {
-                            key: Key::ArrowLeft,
-                            physical_key: Some(Key::ArrowLeft),
-                            pressed: true,
-                            repeat: false,
-                            modifiers: Modifiers::default(),
-                        });
-
-                        // Then, use Ctrl+Left for Windows/Linux word movement
-                        // We'll do this in a separate message so it doesn't combine with the previous one
-                        let mut ctrl_mods = Modifiers {
-                            alt: core::default::Default::default(),
-                            ctrl: core::default::Default::default(),
-                            shift: core::default::Default::default(),
-                            mac_cmd: core::default::Default::default(),
-                            command: core::default::Default::default(),
-                        };
-                        ctrl_mods.ctrl = true;
-                        input.events.push(Event::Key {
-                            key: Key::ArrowLeft,
-                            physical_key: Some(Key::ArrowLeft),
-                            pressed: true,
-                            repeat: false,
-                            modifiers: ctrl_mods,
-                        });
-
-                        // Also try Alt+Left for macOS in a separate event
-                        let mut alt_mods = Modifiers {
-                            alt: core::default::Default::default(),
-                            ctrl: core::default::Default::default(),
-                            shift: core::default::Default::default(),
-                            mac_cmd: core::default::Default::default(),
-                            command: core::default::Default::default(),
-                        };
-                        alt_mods.alt = true;
-                        input.events.push(Event::Key {
-                            key: Key::ArrowLeft,
-                            physical_key: Some(Key::ArrowLeft),
-                            pressed: true,
-                            repeat: false,
-                            modifiers: alt_mods,
-                        });
+                        self.debug_log("'b/B' key pressed - moving to the previous word");
+                        events_to_remove.extend(key_event_indices(&input.events, *key));
+                        self.push_motion_once(CursorMovement::WordLeft);
                     }
 
                     // Line movement - translate to Home/End keys
                     Key::Num0 => {
                         self.debug_log("'0' key pressed - mapping to Home key");
-                        events_to_remove.extend(0..input.events.len());
+                        events_to_remove.extend(key_event_indices(&input.events, *key));
 
-                        input.events.push(Event::Key {
+                        let event = Event::Key {
                             key: Key::Home,
                             physical_key: Some(Key::Home),
                             pressed: true,
                             repeat: false,
                             modifiers: input.modifiers,
-                        });
+                        };
+                        self.push_motion(&mut input.events, CursorMovement::LineStart, [event]);
                     }
                     Key::Num4 if input.modifiers.shift => {
                         self.debug_log("'$' key pressed (Shift+4) - mapping to End key");
-                        events_to_remove.extend(0..input.events.len());
+                        events_to_remove.extend(key_event_indices(&input.events, *key));
 
-                        input.events.push(Event::Key {
+                        let event = Event::Key {
                             key: Key::End,
                             physical_key: Some(Key::End),
                             pressed: true,
                             repeat: false,
                             modifiers: Modifiers::default(), // Remove shift
-                        });
+                        };
+                        self.push_motion(&mut input.events, CursorMovement::LineEnd, [event]);
+                    }
+
+                    // % jumps to the matching bracket
+                    Key::Num5 if input.modifiers.shift => {
+                        self.debug_log("'%' key pressed - moving to the matching bracket");
+                        events_to_remove.extend(key_event_indices(&input.events, *key));
+                        self.push_jump();
+                        self.push_motion_once(CursorMovement::MatchingBracket);
+                    }
+
+                    // { and } jump over paragraphs to the surrounding blank lines
+                    Key::OpenBracket | Key::CloseBracket if input.modifiers.shift => {
+                        self.debug_log(&format!("{key:?} key pressed - moving by paragraph"));
+                        events_to_remove.extend(key_event_indices(&input.events, *key));
+                        self.push_jump();
+                        self.push_motion_once(Self::paragraph_motion(*key));
+                    }
+
+                    // The navigation keys move the buffer cursor, as in insert mode
+                    Key::Home | Key::End | Key::PageUp | Key::PageDown => {
+                        self.debug_log(&format!("{key:?} key pressed - moving"));
+                        events_to_remove.extend(key_event_indices(&input.events, *key));
+                        self.commands
+                            .push(Self::navigation_command(*key, input.modifiers));
                     }
 
                     // Document movement - translate to document navigation events
                     Key::G => {
-                        events_to_remove.extend(0..input.events.len());
+                        events_to_remove.extend(key_event_indices(&input.events, *key));
+                        g_key_handled = true;
 
                         if input.modifiers.shift {
                             self.debug_log("'G' key pressed - mapping to document-end");
-                            println!("DEBUG: Processing 'G' key in normal mode");
+                            self.pending = None;
+                            self.push_jump();
 
-                            // Generate document end navigation events
                             let events = self.gen_doc_navigation_events(true, false);
-                            println!(
-                                "DEBUG: Generated {} events for document-end movement",
-                                events.len()
+                            self.push_motion(
+                                &mut input.events,
+                                CursorMovement::DocumentEnd,
+                                events,
                             );
+                        } else if self.complete_sequence(Key::G, input.time) {
+                            self.debug_log("'gg' pressed - mapping to document-start");
+                            self.push_jump();
 
-                            // Add all generated events to the input queue
-                            for event in events {
-                                println!("DEBUG: Adding document-end event: {:?}", event);
-                                input.events.push(event);
-                            }
-                        } else {
-                            self.debug_log("'g' key pressed - mapping to document-start");
-                            println!("DEBUG: Processing 'g' key in normal mode");
-
-                            // Generate document start navigation events
                             let events = self.gen_doc_navigation_events(false, false);
-                            println!(
-                                "DEBUG: Generated {} events for document-start movement",
-                                events.len()
+                            self.push_motion(
+                                &mut input.events,
+                                CursorMovement::DocumentStart,
+                                events,
                             );
+                        } else {
+                            self.debug_log("'g' key pressed - waiting for second key");
+                        }
+                    }
 
-                            // Add all generated events to the input queue
-                            for event in events {
-                                println!("DEBUG: Adding document-start event: {:?}", event);
-                                input.events.push(event);
-                            }
+                    // Cut the current line into the register with dd
+                    Key::D => {
+                        events_to_remove.extend(key_event_indices(&input.events, *key));
+
+                        if self.complete_sequence(Key::D, input.time) {
+                            self.debug_log("'dd' pressed - deleting line");
+                            self.commands.push(EditorCommand::Cut);
+                        } else {
+                            self.debug_log("'d' key pressed - waiting for second key");
                         }
                     }
 
-                    // Editing operations
-                    Key::X => {
-                        self.debug_log("'x' key pressed - mapping to Delete");
-                        events_to_remove.extend(0..input.events.len());
+                    // Yank the current line with yy
+                    Key::Y => {
+                        events_to_remove.extend(key_event_indices(&input.events, *key));
 
-                        input.events.push(Event::Key {
-                            key: Key::Delete,
-                            physical_key: Some(Key::Delete),
-                            pressed: true,
-                            repeat: false,
-                            modifiers: input.modifiers,
+                        if self.complete_sequence(Key::Y, input.time) {
+                            self.debug_log("'yy' pressed - yanking line");
+                            self.commands.push(EditorCommand::Copy);
+                        } else {
+                            self.debug_log("'y' key pressed - waiting for second key");
+                        }
+                    }
+
+                    // Paste below the cursor's line with p, above it with P
+                    Key::P => {
+                        events_to_remove.extend(key_event_indices(&input.events, *key));
+                        if input.modifiers.shift {
+                            self.debug_log("'P' key pressed - pasting above");
+                            self.commands.push(EditorCommand::PasteAbove);
+                        } else {
+                            self.debug_log("'p' key pressed - pasting below");
+                            self.commands.push(EditorCommand::Paste);
+                        }
+                    }
+
+                    // Scrolling: zt, zz, zb; ZZ and ZQ quit
+                    Key::Z => {
+                        self.debug_log("'z' key pressed - waiting for second key");
+                        events_to_remove.extend(key_event_indices(&input.events, *key));
+                        self.pending = Some(if input.modifiers.shift {
+                            PendingKey::shifted(Key::Z, input.time)
+                        } else {
+                            PendingKey::new(Key::Z, input.time)
                         });
                     }
 
+                    // Editing operations
+                    Key::X => {
+                        self.debug_log("'x' key pressed - deleting character under cursor");
+                        events_to_remove.extend(key_event_indices(&input.events, *key));
+                        self.commands.push(EditorCommand::DeleteCharForward);
+                    }
+
                     _ => {}
                 }
             }
         }
 
+        // The second key of a z sequence was consumed; don't also treat its text
+        // as a motion (e.g. the `b` of `zb`)
+        if z_handled {
+            return events_to_remove;
+        }
+
         // Handle text events in normal mode
         let mut dollar_key_pressed = false;
+        let mut percent_pressed = false;
+        let mut paragraph_motion = None;
         let mut w_key_text_pressed = false;
         let mut b_key_text_pressed = false;
         let mut g_key_text_pressed = false;
         let mut shift_g_pressed = false;
+        let mut colon_pressed = false;
 
         // First pass - detect special text characters
         for (i, event) in input.events.iter().enumerate() {
@@ -495,6 +710,19 @@ impl VimKeyHandler {
                 } else if text == "G" {
                     shift_g_pressed = true;
                     self.debug_log("'G' character detected in text event");
+                } else if text == "%" {
+                    percent_pressed = true;
+                    self.debug_log("'%' character detected in text event");
+                } else if text == "{" || text == "}" {
+                    paragraph_motion = Some(if text == "{" {
+                        CursorMovement::ParagraphBackward
+                    } else {
+                        CursorMovement::ParagraphForward
+                    });
+                    self.debug_log(&format!("'{text}' character detected in text event"));
+                } else if text == ":" {
+                    colon_pressed = true;
+                    self.debug_log("':' character detected in text event");
                 }
 
                 // In vim normal mode, suppress all text insertion
@@ -509,146 +737,52 @@ impl VimKeyHandler {
         // Check if we've seen w, b, G in text events and convert them to appropriate key events
         // This is crucial for platforms/conditions where only Text events are sent and not Key events
 
-        // Generate word motion events for 'w'
+        // Word motions for 'w' and 'b' (the key arms above may already have queued them)
         if w_key_text_pressed {
-            self.debug_log("Converting 'w' text to vim-style word movement");
-
-            // PRECISE SINGLE WORD MOVEMENT APPROACH:
-            // The previous implementation was adding too many events at once, causing
-            // multiple word jumps. This implementation uses a more controlled approach.
-
-            // First, a single right arrow to handle edge cases at word boundaries
-            input.events.push(Event::Key {
-                key: Key::ArrowRight,
-                physical_key: Some(Key::ArrowRight),
-                pressed: true,
-                repeat: false,
-                modifiers: Modifiers::default(),
-            });
-
-            // Then, use Ctrl+Right for Windows/Linux word movement
-            // We'll do this in a separate message so it doesn't combine with the previous one
-            let mut ctrl_mods = Modifiers {
-                alt: core::default::Default::default(),
-                ctrl: core::default::Default::default(),
-                shift: core::default::Default::default(),
-                mac_cmd: core::default::Default::default(),
-                command: core::default::Default::default(),
-            };
-            ctrl_mods.ctrl = true;
-            input.events.push(Event::Key {
-                key: Key::ArrowRight,
-                physical_key: Some(Key::ArrowRight),
-                pressed: true,
-                repeat: false,
-                modifiers: ctrl_mods,
-            });
-
-            // Also try Alt+Right for macOS in a separate event
-            let mut alt_mods = Modifiers {
-                alt: core::default::Default::default(),
-                ctrl: core::default::Default::default(),
-                shift: core::default::Default::default(),
-                mac_cmd: core::default::Default::default(),
-                command: core::default::Default::default(),
-            };
-            alt_mods.alt = true;
-            input.events.push(Event::Key {
-                key: Key::ArrowRight,
-                physical_key: Some(Key::ArrowRight),
-                pressed: true,
-                repeat: false,
-                modifiers: alt_mods,
-            });
-
-            println!("DEBUG: Added vim-style events for word-right movement");
+            self.debug_log("Converting 'w' text to a word motion");
+            self.push_motion_once(CursorMovement::WordRight);
         }
-
-        // Generate word motion events for 'b'
         if b_key_text_pressed {
-            self.debug_log("Converting 'b' text to vim-style word movement");
-
-            // PRECISE SINGLE WORD MOVEMENT APPROACH:
-            // The previous implementation was adding too many events at once, causing
-            // multiple word jumps. This implementation uses a more controlled approach.
-
-            // First, a single left arrow to handle edge cases at word boundaries
-            input.events.push(Event::Key {
-                key: Key::ArrowLeft,
-                physical_key: Some(Key::ArrowLeft),
-                pressed: true,
-                repeat: false,
-                modifiers: Modifiers::default(),
-            });
+            self.debug_log("Converting 'b' text to a word motion");
+            self.push_motion_once(CursorMovement::WordLeft);
+        }
 
-            // Then, use Ctrl+Left for Windows/Linux word movement
-            // We'll do this in a separate message so it doesn't combine with the previous one
-            let mut ctrl_mods = Modifiers {
-                alt: core::default::Default::default(),
-                ctrl: core::default::Default::default(),
-                shift: core::default::Default::default(),
-                mac_cmd: core::default::Default::default(),
-                command: core::default::Default::default(),
-            };
-            ctrl_mods.ctrl = true;
-            input.events.push(Event::Key {
-                key: Key::ArrowLeft,
-                physical_key: Some(Key::ArrowLeft),
-                pressed: true,
-                repeat: false,
-                modifiers: ctrl_mods,
-            });
+        if percent_pressed {
+            self.debug_log("Converting '%' text to a matching-bracket motion");
+            self.push_jump();
+            self.push_motion_once(CursorMovement::MatchingBracket);
+        }
 
-            // Also try Alt+Left for macOS in a separate event
-            let mut alt_mods = Modifiers {
-                alt: core::default::Default::default(),
-                ctrl: core::default::Default::default(),
-                shift: core::default::Default::default(),
-                mac_cmd: core::default::Default::default(),
-                command: core::default::Default::default(),
-            };
-            alt_mods.alt = true;
-            input.events.push(Event::Key {
-                key: Key::ArrowLeft,
-                physical_key: Some(Key::ArrowLeft),
-                pressed: true,
-                repeat: false,
-                modifiers: alt_mods,
-            });
+        if let Some(movement) = paragraph_motion {
+            self.debug_log("Converting a paragraph character to a paragraph motion");
+            self.push_jump();
+            self.push_motion_once(movement);
+        }
 
-            println!("DEBUG: Added vim-style events for word-left movement");
+        // ':' starts the command line; everything else this frame is dropped
+        if colon_pressed {
+            self.debug_log("Entering command-line mode");
+            self.pending = None;
+            self.command_line.clear();
+            self.mode = VimMode::CommandLine;
+            events_to_remove.extend(original_len..input.events.len());
+            return events_to_remove;
         }
 
-        // Generate document motion events for 'g'
-        if g_key_text_pressed {
-            self.debug_log("Converting 'g' text to document-start navigation events");
+        // Generate document motion events for 'gg' (only when no key event handled it)
+        if g_key_text_pressed && !g_key_handled && self.complete_sequence(Key::G, input.time) {
+            self.debug_log("Converting 'gg' text to document-start navigation events");
+            self.push_jump();
             let events = self.gen_doc_navigation_events(false, false);
-            println!(
-                "DEBUG: Generated {} events for document-start movement from text event",
-                events.len()
-            );
-
-            // Add all generated events to the input queue
-            for event in events {
-                println!("DEBUG: Adding document-start event from text: {:?}", event);
-                input.events.push(event);
-            }
+            self.push_motion(&mut input.events, CursorMovement::DocumentStart, events);
         }
 
         // Generate document motion events for 'G' (shift+g)
-        if shift_g_pressed {
+        if shift_g_pressed && !g_key_handled {
             self.debug_log("Converting 'G' text to document-end navigation events");
+            self.push_jump();
             let events = self.gen_doc_navigation_events(true, false);
-            println!(
-                "DEBUG: Generated {} events for document-end movement from text event",
-                events.len()
-            );
-
-            // Add all generated events to the input queue
-            for event in events {
-                println!("DEBUG: Adding document-end event from text: {:?}", event);
-                input.events.push(event);
-            }
+            self.push_motion(&mut input.events, CursorMovement::DocumentEnd, events);
         }
 
         // Generate line end motion for '$'
@@ -665,26 +799,213 @@ impl VimKeyHandler {
                 repeat: false,
                 modifiers: mods,
             };
-            input.events.push(event);
+            self.push_motion(&mut input.events, CursorMovement::LineEnd, [event]);
         }
 
         events_to_remove
     }
 
+    /// Handle the key events for vim command-line mode
+    ///
+    /// Typed text is collected until Enter runs it or Escape cancels it; nothing
+    /// reaches the `TextEdit` meanwhile.
+    fn handle_command_line_mode(&mut self, input: &InputState) -> Vec<usize> {
+        for event in &input.events {
+            match event {
+                Event::Key {
+                    key: Key::Escape,
+                    pressed: true,
+                    ..
+                } => {
+                    self.debug_log("Escape key pressed - cancelling command line");
+                    self.command_line.clear();
+                    self.mode = VimMode::Normal;
+                    break;
+                }
+                Event::Key {
+                    key: Key::Enter,
+                    pressed: true,
+                    ..
+                } => {
+                    let line = std::mem::take(&mut self.command_line);
+                    self.debug_log(&format!("Running command line ':{line}'"));
+                    self.commands.push(EditorCommand::RunCommandLine(line));
+                    self.mode = VimMode::Normal;
+                    break;
+                }
+                // Backspace on an empty command line leaves it, as in Vim
+                Event::Key {
+                    key: Key::Backspace,
+                    pressed: true,
+                    ..
+                } if self.command_line.is_empty() => {
+                    self.mode = VimMode::Normal;
+                    break;
+                }
+                Event::Key {
+                    key: Key::Backspace,
+                    pressed: true,
+                    ..
+                } => {
+                    self.command_line.pop();
+                }
+                Event::Text(text) | Event::Ime(ImeEvent::Commit(text)) => {
+                    self.command_line.push_str(text);
+                }
+                _ => {}
+            }
+        }
+
+        keyboard_event_indices(&input.events)
+    }
+
     /// Handle the key events for vim insert mode
+    ///
+    /// Only Escape and select-all are taken; the arrow keys, Home/End, and
+    /// Backspace reach the `TextEdit` unchanged, so they edit as in plain mode.
     fn handle_insert_mode(&mut self, input: &InputState) -> Vec<usize> {
         let mut events_to_remove = Vec::new();
 
+        // Ctrl+A (Cmd+A on macOS) selects all, as in plain mode
+        for (i, event) in input.events.iter().enumerate() {
+            if is_select_all(event) {
+                self.debug_log("Ctrl+A pressed - selecting all");
+                events_to_remove.push(i);
+                if !self.commands.contains(&EditorCommand::SelectAll) {
+                    self.commands.push(EditorCommand::SelectAll);
+                }
+            }
+        }
+
         // Check for Escape key to exit insert mode
         for key in &input.keys_down {
             if *key == Key::Escape && input.key_pressed(*key) {
                 self.debug_log("Escape key pressed - exiting insert mode");
                 self.mode = VimMode::Normal;
-                events_to_remove.extend(0..input.events.len());
+                self.insert_escape_started = None;
+                events_to_remove.extend(key_event_indices(&input.events, *key));
                 break;
             }
         }
 
+        if let Some(sequence) = self.insert_escape.filter(|_| self.mode == VimMode::Insert) {
+            events_to_remove.extend(self.match_insert_escape(input, sequence));
+        }
+
+        events_to_remove
+    }
+
+    /// Handle the key events for vim replace mode
+    ///
+    /// Typed text overwrites the characters under the cursor and Backspace puts
+    /// them back; the arrow keys reach the `TextEdit` and move as usual.
+    fn handle_replace_mode(&mut self, input: &InputState) -> Vec<usize> {
+        let mut events_to_remove = Vec::new();
+        for (i, event) in input.events.iter().enumerate() {
+            match event {
+                Event::Key {
+                    key: Key::Escape,
+                    pressed: true,
+                    ..
+                } => {
+                    self.debug_log("Escape key pressed - exiting replace mode");
+                    self.mode = VimMode::Normal;
+                    // Nothing typed after Escape this frame replaces text
+                    events_to_remove.extend(
+                        keyboard_event_indices(&input.events)
+                            .into_iter()
+                            .filter(|&index| index >= i),
+                    );
+                    break;
+                }
+                Event::Key {
+                    key: Key::Backspace,
+                    pressed: true,
+                    ..
+                } => {
+                    self.debug_log("Backspace pressed - restoring a replaced character");
+                    self.commands.push(EditorCommand::RestoreReplacedChar);
+                    events_to_remove.push(i);
+                }
+                Event::Key {
+                    key: Key::Enter,
+                    pressed: true,
+                    ..
+                } => {
+                    self.commands.push(EditorCommand::ReplaceChar('\n'));
+                    events_to_remove.push(i);
+                }
+                Event::Text(text) => {
+                    self.commands
+                        .extend(text.chars().map(EditorCommand::ReplaceChar));
+                    events_to_remove.push(i);
+                }
+                _ => {}
+            }
+        }
+        events_to_remove
+    }
+
+    /// Watch insert-mode typing for the escape sequence
+    ///
+    /// The first character is typed as usual. When the second follows within
+    /// the sequence timeout it is dropped, the first is deleted again, and the
+    /// editor goes back to normal mode; any other key keeps both characters.
+    /// Returns the events to remove.
+    fn match_insert_escape(
+        &mut self,
+        input: &InputState,
+        [first, second]: [char; 2],
+    ) -> Vec<usize> {
+        let mut events_to_remove = Vec::new();
+        if self
+            .insert_escape_started
+            .is_some_and(|since| input.time - since > self.sequence_timeout)
+        {
+            self.insert_escape_started = None;
+        }
+
+        // Index of the first character's text when both arrive in one frame
+        let mut first_this_frame = None;
+        for (i, event) in input.events.iter().enumerate() {
+            match event {
+                Event::Text(text)
+                    if self.insert_escape_started.is_some() && is_single_char(text, second) =>
+                {
+                    self.debug_log("Insert escape sequence typed - entering normal mode");
+                    self.insert_escape_started = None;
+                    match first_this_frame {
+                        Some(index) => events_to_remove.push(index),
+                        None => self.commands.push(EditorCommand::DeleteChar),
+                    }
+                    // Through a command, so the first character is deleted before
+                    // the cursor is adjusted for normal mode
+                    self.commands
+                        .push(EditorCommand::ChangeMode(EditorMode::Vim(VimMode::Normal)));
+                    // Nothing else typed this frame is inserted
+                    events_to_remove.extend(
+                        keyboard_event_indices(&input.events)
+                            .into_iter()
+                            .filter(|&index| index >= i),
+                    );
+                    break;
+                }
+                Event::Text(text) => {
+                    let started = is_single_char(text, first);
+                    self.insert_escape_started = started.then_some(input.time);
+                    first_this_frame = started.then_some(i);
+                }
+                // Named keys (arrows, Backspace, Enter) break the sequence; letter
+                // keys arrive with their text
+                Event::Key {
+                    key, pressed: true, ..
+                } if key.name().chars().count() > 1 => {
+                    self.insert_escape_started = None;
+                    first_this_frame = None;
+                }
+                _ => {}
+            }
+        }
         events_to_remove
     }
 
@@ -692,25 +1013,45 @@ impl VimKeyHandler {
     #[allow(clippy::too_many_lines)]
     fn handle_visual_mode(&mut self, input: &mut InputState) -> Vec<usize> {
         let mut events_to_remove = Vec::new();
+        self.expire_pending(input.time);
 
         // Process keyboard events (individual keys)
         for key in &input.keys_down {
             if input.key_pressed(*key) {
+                // `gc` toggles comments on the selected lines
+                if *key == Key::C
+                    && input.modifiers.is_none()
+                    && self.pending.is_some_and(|pending| pending.key == Key::G)
+                {
+                    self.debug_log("'gc' pressed in visual mode - toggling comments");
+                    events_to_remove.extend(key_event_indices(&input.events, *key));
+                    self.pending = None;
+                    self.commands.push(EditorCommand::ToggleLineComment);
+                    self.mode = VimMode::Normal;
+                    break;
+                }
+
+                // Any other key abandons a pending two-key sequence
+                if self.pending.is_some_and(|pending| pending.key != *key) {
+                    self.pending = None;
+                }
+
                 match *key {
                     // Exit visual mode with Escape
                     Key::Escape => {
                         self.debug_log("Escape key pressed - exiting visual mode");
                         self.mode = VimMode::Normal;
-                        events_to_remove.extend(0..input.events.len());
+                        events_to_remove.extend(key_event_indices(&input.events, *key));
                         break;
                     }
 
-                    // Basic movement - translate to Shift+arrow keys to create selection
+                    // h and l translate to Shift+arrow keys to extend the selection;
+                    // j and k move the buffer cursor, with the selection anchored
                     Key::H => {
                         self.debug_log(
                             "'h' key pressed in visual mode - mapping to Shift+Left arrow",
                         );
-                        events_to_remove.extend(0..input.events.len());
+                        events_to_remove.extend(key_event_indices(&input.events, *key));
 
                         let mut mods = input.modifiers;
                         mods.shift = true;
@@ -724,44 +1065,22 @@ impl VimKeyHandler {
                         });
                     }
                     Key::J => {
-                        self.debug_log(
-                            "'j' key pressed in visual mode - mapping to Shift+Down arrow",
-                        );
-                        events_to_remove.extend(0..input.events.len());
-
-                        let mut mods = input.modifiers;
-                        mods.shift = true;
-
-                        input.events.push(Event::Key {
-                            key: Key::ArrowDown,
-                            physical_key: Some(Key::ArrowDown),
-                            pressed: true,
-                            repeat: false,
-                            modifiers: mods,
-                        });
+                        self.debug_log("'j' key pressed in visual mode - extending down");
+                        events_to_remove.extend(key_event_indices(&input.events, *key));
+                        self.commands
+                            .push(EditorCommand::MoveCursor(CursorMovement::Down));
                     }
                     Key::K => {
-                        self.debug_log(
-                            "'k' key pressed in visual mode - mapping to Shift+Up arrow",
-                        );
-                        events_to_remove.extend(0..input.events.len());
-
-                        let mut mods = input.modifiers;
-                        mods.shift = true;
-
-                        input.events.push(Event::Key {
-                            key: Key::ArrowUp,
-                            physical_key: Some(Key::ArrowUp),
-                            pressed: true,
-                            repeat: false,
-                            modifiers: mods,
-                        });
+                        self.debug_log("'k' key pressed in visual mode - extending up");
+                        events_to_remove.extend(key_event_indices(&input.events, *key));
+                        self.commands
+                            .push(EditorCommand::MoveCursor(CursorMovement::Up));
                     }
                     Key::L => {
                         self.debug_log(
                             "'l' key pressed in visual mode - mapping to Shift+Right arrow",
                         );
-                        events_to_remove.extend(0..input.events.len());
+                        events_to_remove.extend(key_event_indices(&input.events, *key));
 
                         let mut mods = input.modifiers;
                         mods.shift = true;
@@ -775,31 +1094,21 @@ impl VimKeyHandler {
                         });
                     }
 
+                    // Word movement goes through the buffer; the editor keeps the
+                    // selection anchored where visual mode started
                     Key::W => {
-                        events_to_remove.extend(0..input.events.len());
-
-                        let mut mods = input.modifiers;
-                        mods.ctrl = true;
-                        input.events.push(Event::Key {
-                            key: Key::ArrowRight,
-                            physical_key: None,
-                            pressed: false,
-                            repeat: false,
-                            modifiers: mods,
-                        });
+                        self.debug_log(
+                            "'w' key pressed in visual mode - extending to the next word",
+                        );
+                        events_to_remove.extend(key_event_indices(&input.events, *key));
+                        self.push_motion_once(CursorMovement::WordRight);
                     }
-
                     Key::B => {
-                        events_to_remove.extend(0..input.events.len());
-                        let mut mods = input.modifiers;
-                        mods.ctrl = true;
-                        input.events.push(Event::Key {
-                            key: Key::ArrowLeft,
-                            physical_key: None,
-                            pressed: false,
-                            repeat: false,
-                            modifiers: mods,
-                        });
+                        self.debug_log(
+                            "'b' key pressed in visual mode - extending to the previous word",
+                        );
+                        events_to_remove.extend(key_event_indices(&input.events, *key));
+                        self.push_motion_once(CursorMovement::WordLeft);
                     }
 
                     // Line movement - translate to Shift+Home/End keys
@@ -807,7 +1116,7 @@ impl VimKeyHandler {
                         self.debug_log(
                             "'0' key pressed in visual mode - mapping to Shift+Home key",
                         );
-                        events_to_remove.extend(0..input.events.len());
+                        events_to_remove.extend(key_event_indices(&input.events, *key));
 
                         let mut mods = input.modifiers;
                         mods.shift = true;
@@ -821,7 +1130,7 @@ impl VimKeyHandler {
                         });
                     }
                     Key::Num4 if input.modifiers.shift => {
-                        events_to_remove.extend(0..input.events.len());
+                        events_to_remove.extend(key_event_indices(&input.events, *key));
 
                         let mut mods = input.modifiers;
                         mods.shift = true;
@@ -835,10 +1144,36 @@ impl VimKeyHandler {
                         });
                     }
 
+                    Key::Num5 if input.modifiers.shift => {
+                        self.debug_log(
+                            "'%' key pressed in visual mode - extending to the matching bracket",
+                        );
+                        events_to_remove.extend(key_event_indices(&input.events, *key));
+                        self.commands
+                            .push(EditorCommand::MoveCursor(CursorMovement::MatchingBracket));
+                    }
+
+                    Key::OpenBracket | Key::CloseBracket if input.modifiers.shift => {
+                        self.debug_log(&format!(
+                            "{key:?} key pressed in visual mode - extending by paragraph"
+                        ));
+                        events_to_remove.extend(key_event_indices(&input.events, *key));
+                        self.commands
+                            .push(EditorCommand::MoveCursor(Self::paragraph_motion(*key)));
+                    }
+
+                    // The navigation keys move the buffer cursor; the selection stays anchored
+                    Key::Home | Key::End | Key::PageUp | Key::PageDown => {
+                        self.debug_log(&format!("{key:?} key pressed in visual mode - extending"));
+                        events_to_remove.extend(key_event_indices(&input.events, *key));
+                        self.commands
+                            .push(Self::navigation_command(*key, input.modifiers));
+                    }
+
                     // Document movement - translate to document navigation events with selection
                     Key::G => {
                         if input.modifiers.shift {
-                            events_to_remove.extend(0..input.events.len());
+                            events_to_remove.extend(key_event_indices(&input.events, *key));
 
                             let events = self.gen_doc_navigation_events(true, true);
 
@@ -846,111 +1181,49 @@ impl VimKeyHandler {
                                 input.events.push(event);
                             }
                         } else {
-                            events_to_remove.extend(0..input.events.len());
+                            events_to_remove.extend(key_event_indices(&input.events, *key));
 
-                            let events = self.gen_doc_navigation_events(false, true);
+                            if self.complete_sequence(Key::G, input.time) {
+                                let events = self.gen_doc_navigation_events(false, true);
 
-                            for event in events {
-                                input.events.push(event);
+                                for event in events {
+                                    input.events.push(event);
+                                }
                             }
                         }
                     }
 
-                    // Delete/cut selection with x or d
+                    // Cut the selection into the register with x or d
                     Key::X | Key::D => {
-                        self.debug_log(
-                            "'x' or 'd' key pressed in visual mode - mapping to Ctrl+X (cut)",
-                        );
-                        events_to_remove.extend(0..input.events.len());
-
-                        let mut mods = input.modifiers;
-                        mods.ctrl = true;
-
-                        input.events.push(Event::Key {
-                            key: Key::X,
-                            physical_key: Some(Key::X),
-                            pressed: true,
-                            repeat: false,
-                            modifiers: mods,
-                        });
-
-                        // Exit visual mode after operation
+                        self.debug_log("'x' or 'd' key pressed in visual mode - cutting");
+                        events_to_remove.extend(key_event_indices(&input.events, *key));
+                        self.commands.push(EditorCommand::Cut);
                         self.mode = VimMode::Normal;
                     }
 
-                    // Copy selection with y (yank)
+                    // Copy the selection into the register with y (yank)
                     Key::Y => {
-                        self.debug_log("'y' key pressed in visual mode - mapping to Ctrl+C (copy)");
-                        events_to_remove.extend(0..input.events.len());
-
-                        let mut mods = input.modifiers;
-                        mods.ctrl = true;
-
-                        input.events.push(Event::Key {
-                            key: Key::C,
-                            physical_key: Some(Key::C),
-                            pressed: true,
-                            repeat: false,
-                            modifiers: mods,
-                        });
-
-                        // Exit visual mode after operation
+                        self.debug_log("'y' key pressed in visual mode - copying");
+                        events_to_remove.extend(key_event_indices(&input.events, *key));
+                        self.commands.push(EditorCommand::Copy);
                         self.mode = VimMode::Normal;
                     }
 
-                    // Change selection with c (cut + enter insert mode)
+                    // Change the selection with c: cut it and start inserting
                     Key::C => {
                         self.debug_log(
                             "'c' key pressed in visual mode - cut and enter insert mode",
                         );
-                        events_to_remove.extend(0..input.events.len());
-
-                        // First do a cut operation (Ctrl+X)
-                        let mut mods = input.modifiers;
-                        mods.ctrl = true;
-
-                        input.events.push(Event::Key {
-                            key: Key::X,
-                            physical_key: Some(Key::X),
-                            pressed: true,
-                            repeat: false,
-                            modifiers: mods,
-                        });
-
-                        // Then enter insert mode
+                        events_to_remove.extend(key_event_indices(&input.events, *key));
+                        self.commands.push(EditorCommand::Cut);
                         self.mode = VimMode::Insert;
                     }
 
-                    // Paste over selection with p
+                    // Replace the selection with the register with p
                     Key::P => {
-                        self.debug_log("'p' key pressed in visual mode - cut and paste");
-                        events_to_remove.extend(0..input.events.len());
-
-                        // First do a cut operation (Ctrl+X)
-                        let mut cut_mods = input.modifiers;
-                        cut_mods.ctrl = true;
-
-                        input.events.push(Event::Key {
-                            key: Key::X,
-                            physical_key: Some(Key::X),
-                            pressed: true,
-                            repeat: false,
-                            modifiers: cut_mods,
-                        });
-
-                        // Then do a paste operation (Ctrl+V)
-                        let mut paste_mods = input.modifiers;
-                        paste_mods.ctrl = true;
-
-                        input.events.push(Event::Key {
-                            key: Key::V,
-                            physical_key: Some(Key::V),
-                            pressed: true,
-                            repeat: false,
-                            modifiers: paste_mods,
-                        });
-
-                        // Exit visual mode after operation
+                        self.debug_log("'p' key pressed in visual mode - replacing with register");
+                        events_to_remove.extend(key_event_indices(&input.events, *key));
+                        self.commands.push(EditorCommand::Paste);
                         self.mode = VimMode::Normal;
                     }
 
@@ -977,11 +1250,30 @@ impl KeyHandler for VimKeyHandler {
         match self.mode {
             VimMode::Normal => self.handle_normal_mode(input),
             VimMode::Insert => self.handle_insert_mode(input),
+            VimMode::Replace => self.handle_replace_mode(input),
             VimMode::Visual => self.handle_visual_mode(input),
+            VimMode::CommandLine => self.handle_command_line_mode(input),
         }
     }
 
     fn name(&self) -> &'static str {
         "vim"
     }
+
+    fn reset(&mut self) {
+        self.pending = None;
+        self.insert_escape_started = None;
+        self.command_line.clear();
+        self.commands.clear();
+    }
+
+    fn take_commands(&mut self) -> Vec<EditorCommand> {
+        std::mem::take(&mut self.commands)
+    }
+}
+
+/// Whether `text` is exactly the character `c`
+fn is_single_char(text: &str, c: char) -> bool {
+    let mut chars = text.chars();
+    chars.next() == Some(c) && chars.next().is_none()
 }
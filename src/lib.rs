@@ -4,6 +4,6 @@ pub mod syntax;
 
 // Re-export the main components for easier access
 pub use editor::{
-    commands::{EditorMode, VimMode},
+    commands::{CursorShape, EditorMode, VimMode},
     EditorWidget,
 };
@@ -0,0 +1,217 @@
+use crate::syntax::{HighlightTheme, SyntaxHighlighter};
+use egui::{text::LayoutJob, Color32, Context, FontId, TextFormat};
+
+/// The 16 standard terminal colors: black, red, green, yellow, blue, magenta,
+/// cyan, and white, followed by their bright variants
+pub const ANSI_COLORS: [Color32; 16] = [
+    Color32::from_rgb(40, 44, 52),
+    Color32::from_rgb(224, 108, 117),
+    Color32::from_rgb(152, 195, 121),
+    Color32::from_rgb(229, 192, 123),
+    Color32::from_rgb(97, 175, 239),
+    Color32::from_rgb(198, 120, 221),
+    Color32::from_rgb(86, 182, 194),
+    Color32::from_rgb(171, 178, 191),
+    Color32::from_rgb(92, 99, 112),
+    Color32::from_rgb(255, 123, 134),
+    Color32::from_rgb(179, 229, 144),
+    Color32::from_rgb(255, 215, 140),
+    Color32::from_rgb(127, 198, 255),
+    Color32::from_rgb(225, 149, 246),
+    Color32::from_rgb(111, 214, 227),
+    Color32::from_rgb(255, 255, 255),
+];
+
+/// Highlighter for text containing ANSI SGR escape sequences (terminal output, logs)
+///
+/// Foreground and background colors, bright variants, bold, and resets are
+/// applied, and every escape sequence is stripped from the displayed text.
+/// Bold text is drawn in the bright variant of its color, as most terminals do.
+///
+/// Because the escape codes are removed, the laid-out text is shorter than the
+/// buffer, so this highlighter is meant for read-only display.
+pub struct AnsiHighlighter {
+    theme: HighlightTheme,
+    font_size: f32,
+}
+
+impl Default for AnsiHighlighter {
+    fn default() -> Self {
+        Self {
+            theme: HighlightTheme::default(),
+            font_size: 14.0,
+        }
+    }
+}
+
+/// Text attributes set by the SGR sequences seen so far
+#[derive(Debug, Clone, Copy, Default)]
+struct SgrState {
+    /// Index into `ANSI_COLORS` (0-7; bold brightens it), or the theme foreground
+    foreground: Option<usize>,
+    /// Index into `ANSI_COLORS`, or no background
+    background: Option<usize>,
+    bold: bool,
+}
+
+impl SgrState {
+    /// Apply the `;`-separated parameters of one `ESC [ ... m` sequence
+    fn apply(&mut self, params: &str) {
+        let mut codes = params
+            .split(';')
+            .map(|code| code.parse::<u16>().unwrap_or(0));
+        while let Some(code) = codes.next() {
+            match code {
+                0 => *self = Self::default(),
+                1 => self.bold = true,
+                22 => self.bold = false,
+                30..=37 => self.foreground = Some(usize::from(code - 30)),
+                39 => self.foreground = None,
+                40..=47 => self.background = Some(usize::from(code - 40)),
+                49 => self.background = None,
+                90..=97 => self.foreground = Some(usize::from(code - 90 + 8)),
+                100..=107 => self.background = Some(usize::from(code - 100 + 8)),
+                // 256-color and RGB colors aren't supported; skip their arguments
+                38 | 48 => match codes.next() {
+                    Some(5) => {
+                        codes.next();
+                    }
+                    Some(2) => {
+                        codes.nth(2);
+                    }
+                    _ => {}
+                },
+                _ => {}
+            }
+        }
+    }
+
+    fn format(&self, theme: &HighlightTheme, font_size: f32) -> TextFormat {
+        let color = match (self.foreground, self.bold) {
+            (Some(index), true) if index < 8 => ANSI_COLORS[index + 8],
+            (Some(index), _) => ANSI_COLORS[index],
+            (None, true) => ANSI_COLORS[15],
+            (None, false) => theme.foreground,
+        };
+        TextFormat {
+            font_id: FontId::monospace(font_size),
+            color,
+            background: self
+                .background
+                .map_or(Color32::TRANSPARENT, |index| ANSI_COLORS[index]),
+            ..Default::default()
+        }
+    }
+}
+
+impl AnsiHighlighter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl SyntaxHighlighter for AnsiHighlighter {
+    fn highlight(&self, _ctx: &Context, text: &str) -> LayoutJob {
+        let mut job = LayoutJob::default();
+        let mut state = SgrState::default();
+        let mut rest = text;
+
+        while let Some(escape) = rest.find('\x1b') {
+            if escape > 0 {
+                job.append(
+                    &rest[..escape],
+                    0.0,
+                    state.format(&self.theme, self.font_size),
+                );
+            }
+            rest = &rest[escape + 1..];
+
+            // Only CSI sequences (`ESC [ params final`) are understood; a lone ESC is dropped
+            let Some(sequence) = rest.strip_prefix('[') else {
+                continue;
+            };
+            let Some(end) = sequence.find(|c: char| ('\x40'..='\x7e').contains(&c)) else {
+                // Unterminated sequence at the end of the text
+                rest = "";
+                break;
+            };
+            if sequence[end..].starts_with('m') {
+                state.apply(&sequence[..end]);
+            }
+            rest = &sequence[end + 1..];
+        }
+
+        if !rest.is_empty() {
+            job.append(rest, 0.0, state.format(&self.theme, self.font_size));
+        }
+
+        job
+    }
+
+    fn set_theme(&mut self, theme: HighlightTheme) {
+        self.theme = theme;
+    }
+
+    fn theme(&self) -> &HighlightTheme {
+        &self.theme
+    }
+
+    fn set_font_size(&mut self, size: f32) {
+        self.font_size = size;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Each section's text with its foreground and background
+    fn spans(job: &LayoutJob) -> Vec<(&str, Color32, Color32)> {
+        job.sections
+            .iter()
+            .map(|section| {
+                (
+                    &job.text[section.byte_range.clone()],
+                    section.format.color,
+                    section.format.background,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sgr_sequences_color_their_spans_and_are_stripped() {
+        let text = "ok \x1b[31merror\x1b[0m \x1b[1;32;44mbold\x1b[39m on blue\x1b[m done";
+        let highlighter = AnsiHighlighter::new();
+        let job = highlighter.highlight(&Context::default(), text);
+        let theme = HighlightTheme::default();
+        let none = Color32::TRANSPARENT;
+
+        assert_eq!(job.text, "ok error bold on blue done");
+        assert_eq!(
+            spans(&job),
+            vec![
+                ("ok ", theme.foreground, none),
+                ("error", ANSI_COLORS[1], none),
+                (" ", theme.foreground, none),
+                ("bold", ANSI_COLORS[10], ANSI_COLORS[4]),
+                (" on blue", ANSI_COLORS[15], ANSI_COLORS[4]),
+                (" done", theme.foreground, none),
+            ]
+        );
+    }
+
+    #[test]
+    fn bright_codes_and_unknown_sequences() {
+        let text = "\x1b[93;101mhi\x1b[2Jthere\x1b[38;5;200mx\x1b[";
+        let job = AnsiHighlighter::new().highlight(&Context::default(), text);
+
+        assert_eq!(job.text, "hitherex");
+        let spans = spans(&job);
+        assert_eq!(spans[0], ("hi", ANSI_COLORS[11], ANSI_COLORS[9]));
+        // A non-SGR sequence is stripped without changing the colors
+        assert_eq!(spans[1], ("there", ANSI_COLORS[11], ANSI_COLORS[9]));
+        // A 256-color code is skipped along with its argument
+        assert_eq!(spans[2], ("x", ANSI_COLORS[11], ANSI_COLORS[9]));
+    }
+}
@@ -0,0 +1,134 @@
+use std::collections::HashMap;
+
+/// How a language writes comments
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CommentSyntax {
+    /// Prefix that comments out the rest of a line (e.g. `//`)
+    pub line: Option<String>,
+    /// Delimiters around a block comment (e.g. `/*` and `*/`)
+    pub block: Option<(String, String)>,
+}
+
+impl CommentSyntax {
+    /// Line comments only
+    pub fn line(prefix: &str) -> Self {
+        Self {
+            line: Some(prefix.to_string()),
+            block: None,
+        }
+    }
+
+    /// Block comments only
+    pub fn block(open: &str, close: &str) -> Self {
+        Self {
+            line: None,
+            block: Some((open.to_string(), close.to_string())),
+        }
+    }
+
+    /// Both line and block comments
+    pub fn line_and_block(prefix: &str, open: &str, close: &str) -> Self {
+        Self {
+            line: Some(prefix.to_string()),
+            block: Some((open.to_string(), close.to_string())),
+        }
+    }
+}
+
+/// Registry mapping language names to their comment syntax
+///
+/// Language names are the ones used by the
+/// [`HighlighterRegistry`](super::registry::HighlighterRegistry) (e.g.
+/// `"rust"`), and lookups are case-insensitive.
+#[derive(Debug, Clone)]
+pub struct CommentRegistry {
+    syntaxes: HashMap<String, CommentSyntax>,
+}
+
+impl Default for CommentRegistry {
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        for language in [
+            "rust",
+            "c",
+            "cpp",
+            "csharp",
+            "go",
+            "java",
+            "javascript",
+            "typescript",
+            "kotlin",
+            "swift",
+        ] {
+            registry.register(language, CommentSyntax::line_and_block("//", "/*", "*/"));
+        }
+        for language in ["python", "shell", "bash", "ruby", "toml", "yaml"] {
+            registry.register(language, CommentSyntax::line("#"));
+        }
+        registry.register("sql", CommentSyntax::line_and_block("--", "/*", "*/"));
+        registry.register("lua", CommentSyntax::line_and_block("--", "--[[", "]]"));
+        registry.register("css", CommentSyntax::block("/*", "*/"));
+        registry.register("html", CommentSyntax::block("<!--", "-->"));
+        registry.register("markdown", CommentSyntax::block("<!--", "-->"));
+        registry
+    }
+}
+
+impl CommentRegistry {
+    /// Create a registry pre-populated with the built-in languages
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a registry with no languages registered
+    pub fn empty() -> Self {
+        Self {
+            syntaxes: HashMap::new(),
+        }
+    }
+
+    /// Register (or replace) the comment syntax of a language
+    pub fn register(&mut self, language: &str, syntax: CommentSyntax) {
+        self.syntaxes.insert(language.to_lowercase(), syntax);
+    }
+
+    /// The comment syntax registered for a language
+    pub fn get(&self, language: &str) -> Option<&CommentSyntax> {
+        self.syntaxes.get(&language.to_lowercase())
+    }
+
+    /// The line comment prefix of a language, if it has one
+    pub fn line_prefix(&self, language: &str) -> Option<&str> {
+        self.get(language)?.line.as_deref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rust_and_python_resolve_to_their_comment_prefixes() {
+        let registry = CommentRegistry::new();
+        assert_eq!(registry.line_prefix("rust"), Some("//"));
+        assert_eq!(registry.line_prefix("Python"), Some("#"));
+        assert_eq!(
+            registry.get("rust").and_then(|syntax| syntax.block.clone()),
+            Some(("/*".to_string(), "*/".to_string()))
+        );
+        assert_eq!(registry.get("python").unwrap().block, None);
+        // Block-only and unknown languages have no line prefix
+        assert_eq!(registry.line_prefix("css"), None);
+        assert_eq!(registry.line_prefix("brainfuck"), None);
+    }
+
+    #[test]
+    fn registered_syntax_replaces_or_adds_a_language() {
+        let mut registry = CommentRegistry::empty();
+        assert_eq!(registry.line_prefix("rust"), None);
+        registry.register("Haskell", CommentSyntax::line_and_block("--", "{-", "-}"));
+        registry.register("rust", CommentSyntax::line("///"));
+        assert_eq!(registry.line_prefix("haskell"), Some("--"));
+        assert_eq!(registry.line_prefix("rust"), Some("///"));
+    }
+}
@@ -0,0 +1,112 @@
+use crate::syntax::{HighlightTheme, SyntaxHighlighter};
+use egui::{text::LayoutJob, Color32, Context, FontId, TextFormat};
+
+/// Highlighter for unified-diff output
+///
+/// Added lines are green, removed lines red, hunk headers use the theme's
+/// operator color and file headers its keyword color. Context lines are plain.
+pub struct DiffHighlighter {
+    theme: HighlightTheme,
+    font_size: f32,
+    /// Color for `+` lines
+    pub added: Color32,
+    /// Color for `-` lines
+    pub removed: Color32,
+}
+
+impl Default for DiffHighlighter {
+    fn default() -> Self {
+        Self {
+            theme: HighlightTheme::default(),
+            font_size: 14.0,
+            added: Color32::from_rgb(152, 195, 121),
+            removed: Color32::from_rgb(224, 108, 117),
+        }
+    }
+}
+
+impl DiffHighlighter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pick the color for a single diff line
+    fn line_color(&self, line: &str) -> Color32 {
+        if line.starts_with("+++") || line.starts_with("---") || line.starts_with("diff ") {
+            self.theme.keyword
+        } else if line.starts_with("@@") {
+            self.theme.operator
+        } else if line.starts_with('+') {
+            self.added
+        } else if line.starts_with('-') {
+            self.removed
+        } else {
+            self.theme.foreground
+        }
+    }
+}
+
+impl SyntaxHighlighter for DiffHighlighter {
+    fn highlight(&self, _ctx: &Context, text: &str) -> LayoutJob {
+        let mut job = LayoutJob::default();
+
+        // split_inclusive keeps the newlines so the job text matches the buffer exactly
+        for line in text.split_inclusive('\n') {
+            job.append(
+                line,
+                0.0,
+                TextFormat {
+                    font_id: FontId::monospace(self.font_size),
+                    color: self.line_color(line),
+                    ..Default::default()
+                },
+            );
+        }
+
+        job
+    }
+
+    fn set_theme(&mut self, theme: HighlightTheme) {
+        self.theme = theme;
+    }
+
+    fn theme(&self) -> &HighlightTheme {
+        &self.theme
+    }
+
+    fn set_font_size(&mut self, size: f32) {
+        self.font_size = size;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unified_diff_lines_get_their_colors() {
+        let diff = "diff --git a/f b/f\n--- a/f\n+++ b/f\n@@ -1,2 +1,2 @@\n same\n-old\n+new";
+        let highlighter = DiffHighlighter::new();
+        let job = highlighter.highlight(&Context::default(), diff);
+        let theme = HighlightTheme::default();
+
+        assert_eq!(job.text, diff);
+        let lines: Vec<(&str, Color32)> = job
+            .sections
+            .iter()
+            .map(|section| (&job.text[section.byte_range.clone()], section.format.color))
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                ("diff --git a/f b/f\n", theme.keyword),
+                ("--- a/f\n", theme.keyword),
+                ("+++ b/f\n", theme.keyword),
+                ("@@ -1,2 +1,2 @@\n", theme.operator),
+                (" same\n", theme.foreground),
+                ("-old\n", highlighter.removed),
+                ("+new", highlighter.added),
+            ]
+        );
+    }
+}
@@ -1,6 +1,8 @@
 use crate::syntax::{HighlightTheme, SyntaxHighlighter, TokenType};
-use egui::{text::LayoutJob, Context, FontId, TextFormat};
-use std::collections::HashMap;
+use egui::{
+    text::{LayoutJob, LayoutSection},
+    Context,
+};
 
 // This is a placeholder for more complex language parsers
 // In a production implementation, you'd likely use syntect or another syntax highlighting library
@@ -22,6 +24,7 @@ pub struct LanguageHighlighter {
     pub language: String,
     pub tokenizer: Box<dyn LanguageTokenizer>,
     pub theme: HighlightTheme,
+    pub font_size: f32,
 }
 
 impl LanguageHighlighter {
@@ -30,6 +33,7 @@ impl LanguageHighlighter {
             language: language.into(),
             tokenizer: Box::new(tokenizer),
             theme: HighlightTheme::default(),
+            font_size: 14.0,
         }
     }
 }
@@ -41,57 +45,32 @@ impl SyntaxHighlighter for LanguageHighlighter {
         // Tokenize the input
         let tokens = self.tokenizer.tokenize(text);
 
-        // Convert tokens to text spans
+        // Convert tokens to text spans, merging neighbors that look the same so
+        // large files don't produce a section per character
         for token in tokens {
-            let format = match token.token_type {
-                TokenType::Keyword => TextFormat {
-                    font_id: FontId::monospace(14.0),
-                    color: self.theme.keyword,
-                    ..Default::default()
-                },
-                TokenType::Function => TextFormat {
-                    font_id: FontId::monospace(14.0),
-                    color: self.theme.function,
-                    ..Default::default()
-                },
-                TokenType::Type => TextFormat {
-                    font_id: FontId::monospace(14.0),
-                    color: self.theme.type_name,
-                    ..Default::default()
-                },
-                TokenType::String => TextFormat {
-                    font_id: FontId::monospace(14.0),
-                    color: self.theme.string,
-                    ..Default::default()
-                },
-                TokenType::Number => TextFormat {
-                    font_id: FontId::monospace(14.0),
-                    color: self.theme.number,
-                    ..Default::default()
-                },
-                TokenType::Comment => TextFormat {
-                    font_id: FontId::monospace(14.0),
-                    color: self.theme.comment,
-                    ..Default::default()
-                },
-                TokenType::Operator => TextFormat {
-                    font_id: FontId::monospace(14.0),
-                    color: self.theme.operator,
-                    ..Default::default()
-                },
-                TokenType::Variable => TextFormat {
-                    font_id: FontId::monospace(14.0),
-                    color: self.theme.variable,
-                    ..Default::default()
-                },
-                _ => TextFormat {
-                    font_id: FontId::monospace(14.0),
-                    color: self.theme.foreground,
-                    ..Default::default()
-                },
-            };
+            let format = self.theme.format_for(token.token_type, self.font_size);
+            let start = job.text.len();
+            job.text.push_str(&token.text);
+            let end = job.text.len();
 
-            job.append(&token.text, 0.0, format);
+            match job.sections.last_mut() {
+                Some(section) if section.format == format => section.byte_range.end = end,
+                // Color doesn't show on whitespace, so it can join any section
+                // whose font and decorations would draw it the same way
+                Some(section)
+                    if token.text.chars().all(char::is_whitespace)
+                        && section.format.font_id == format.font_id
+                        && section.format.underline == format.underline
+                        && section.format.background == format.background =>
+                {
+                    section.byte_range.end = end;
+                }
+                _ => job.sections.push(LayoutSection {
+                    leading_space: 0.0,
+                    byte_range: start..end,
+                    format,
+                }),
+            }
         }
 
         job
@@ -104,6 +83,10 @@ impl SyntaxHighlighter for LanguageHighlighter {
     fn theme(&self) -> &HighlightTheme {
         &self.theme
     }
+
+    fn set_font_size(&mut self, size: f32) {
+        self.font_size = size;
+    }
 }
 
 /// Very basic Rust tokenizer (just a simple example, not complete)
@@ -190,7 +173,8 @@ impl LanguageTokenizer for RustTokenizer {
         let mut in_comment = false;
 
         // This is a very simplistic tokenizer and doesn't handle many edge cases
-        for c in text.chars() {
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
             if in_comment {
                 current_token.push(c);
                 if c == '\n' {
@@ -225,7 +209,10 @@ impl LanguageTokenizer for RustTokenizer {
                         TokenType::Keyword
                     } else if self.types.contains(&current_token) {
                         TokenType::Type
-                    } else if current_token.chars().all(|c| c.is_digit(10) || c == '.') {
+                    } else if current_token
+                        .chars()
+                        .all(|c| c.is_ascii_digit() || c == '.')
+                    {
                         TokenType::Number
                     } else if current_token.starts_with("fn ") || current_token.ends_with("()") {
                         TokenType::Function
@@ -243,7 +230,7 @@ impl LanguageTokenizer for RustTokenizer {
                 if c == '"' {
                     current_token.push(c);
                     in_string = true;
-                } else if c == '/' && text.chars().nth(text.find(c).unwrap() + 1) == Some('/') {
+                } else if c == '/' && chars.peek() == Some(&'/') {
                     current_token.push(c);
                     in_comment = true;
                 } else if !c.is_whitespace() {
@@ -262,11 +249,18 @@ impl LanguageTokenizer for RustTokenizer {
 
         // Don't forget the last token
         if !current_token.is_empty() {
-            let token_type = if self.keywords.contains(&current_token) {
+            let token_type = if in_comment {
+                TokenType::Comment
+            } else if in_string {
+                TokenType::String
+            } else if self.keywords.contains(&current_token) {
                 TokenType::Keyword
             } else if self.types.contains(&current_token) {
                 TokenType::Type
-            } else if current_token.chars().all(|c| c.is_digit(10) || c == '.') {
+            } else if current_token
+                .chars()
+                .all(|c| c.is_ascii_digit() || c == '.')
+            {
                 TokenType::Number
             } else if current_token.starts_with("fn ") || current_token.ends_with("()") {
                 TokenType::Function
@@ -283,3 +277,86 @@ impl LanguageTokenizer for RustTokenizer {
         tokens
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use egui::Color32;
+
+    /// The format of the section holding `needle`
+    fn format_of<'a>(job: &'a LayoutJob, needle: &str) -> &'a egui::TextFormat {
+        let start = job.text.find(needle).expect("needle in text");
+        &job.sections
+            .iter()
+            .find(|section| section.byte_range.contains(&start))
+            .expect("section over needle")
+            .format
+    }
+
+    #[test]
+    fn an_overridden_keyword_color_reaches_the_highlighted_text() {
+        let mut highlighter = LanguageHighlighter::new("rust", RustTokenizer::default());
+        highlighter.set_theme(HighlightTheme::default().with_keyword(Color32::RED));
+        let job = highlighter.highlight(&Context::default(), "let x: i32 = 1;");
+
+        assert_eq!(format_of(&job, "let").color, Color32::RED);
+        assert_eq!(
+            format_of(&job, "i32").color,
+            HighlightTheme::default().type_name
+        );
+    }
+    #[test]
+    fn keywords_use_the_bold_font_and_comments_are_italic_by_default() {
+        let bold = egui::FontFamily::Name("mono-bold".into());
+        let mut highlighter = LanguageHighlighter::new("rust", RustTokenizer::default());
+        highlighter.set_theme(HighlightTheme::default().with_bold_font(bold.clone()));
+        let job = highlighter.highlight(&Context::default(), "fn x() {} // done\n");
+
+        let keyword = format_of(&job, "fn");
+        assert_eq!(keyword.font_id.family, bold);
+        assert!(!keyword.italics);
+
+        let name = format_of(&job, "x");
+        assert_eq!(name.font_id.family, egui::FontFamily::Monospace);
+
+        let comment = format_of(&job, "// done");
+        assert!(comment.italics);
+        assert_eq!(comment.font_id.family, egui::FontFamily::Monospace);
+    }
+    #[test]
+    fn sections_cover_the_exact_text_in_few_pieces() {
+        let line = "    let total: u32 = items.iter().map(|x| x * 2).sum(); // doubled\n";
+        let text = format!("fn f() {{\n{}\r\n\t\n}}\n", line.repeat(50));
+        let highlighter = LanguageHighlighter::new("rust", RustTokenizer::default());
+        let job = highlighter.highlight(&Context::default(), &text);
+
+        assert_eq!(job.text, text);
+        let joined: String = job
+            .sections
+            .iter()
+            .map(|section| &job.text[section.byte_range.clone()])
+            .collect();
+        assert_eq!(joined, text);
+        assert!(job
+            .sections
+            .windows(2)
+            .all(|pair| pair[0].byte_range.end == pair[1].byte_range.start));
+
+        // Same-looking neighbors are merged: `().` is one operator run, and
+        // whitespace (newlines included) joins the section before it
+        assert!(job
+            .sections
+            .iter()
+            .any(|section| &job.text[section.byte_range.clone()] == "()."));
+        assert!(job
+            .sections
+            .iter()
+            .any(|section| &job.text[section.byte_range.clone()] == "() {\n    "));
+        let tokens = RustTokenizer::default().tokenize(&text).len();
+        assert!(
+            job.sections.len() * 3 < tokens * 2,
+            "{} sections for {tokens} tokens",
+            job.sections.len()
+        );
+    }
+}
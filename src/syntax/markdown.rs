@@ -1,31 +1,128 @@
-use crate::syntax::{ContentBlock, HighlightTheme, SyntaxHighlighter, TokenType};
-use egui::{text::LayoutJob, Context, FontId, TextFormat};
+use crate::syntax::{
+    append_job, heading_font_size, heading_level, ContentBlock, HighlightTheme, LineState,
+    SyntaxHighlighter,
+};
+use egui::{text::LayoutJob, Context, FontId, Frame, RichText, TextFormat, Ui};
 use std::collections::HashMap;
 
+/// Fence tags mapped to the language names highlighters are registered under
+const DEFAULT_LANGUAGE_ALIASES: &[(&str, &str)] = &[
+    ("rs", "rust"),
+    ("js", "javascript"),
+    ("ts", "typescript"),
+    ("py", "python"),
+    ("sh", "bash"),
+    ("shell", "bash"),
+    ("md", "markdown"),
+    ("yml", "yaml"),
+];
+
+/// Maps a fence tag (attributes already stripped) to a language name
+pub type LanguageResolver = Box<dyn Fn(&str) -> Option<String>>;
+
 /// Highlighter for Markdown content with embedded code blocks
 pub struct MarkdownHighlighter {
     theme: HighlightTheme,
+    font_size: f32,
     language_highlighters: HashMap<String, Box<dyn SyntaxHighlighter>>,
+    /// Language names keyed by fence tag (e.g. `rs` for `rust`)
+    language_aliases: HashMap<String, String>,
+    /// Resolves fence tags the alias map doesn't cover
+    language_resolver: Option<LanguageResolver>,
 }
 
-impl MarkdownHighlighter {
-    pub fn new() -> Self {
+impl Default for MarkdownHighlighter {
+    fn default() -> Self {
         Self {
             theme: HighlightTheme::default(),
+            font_size: 14.0,
             language_highlighters: HashMap::new(),
+            language_aliases: DEFAULT_LANGUAGE_ALIASES
+                .iter()
+                .map(|(alias, language)| ((*alias).to_string(), (*language).to_string()))
+                .collect(),
+            language_resolver: None,
+        }
+    }
+}
+
+impl MarkdownHighlighter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Highlight fences tagged `alias` with the highlighter registered for `language`
+    #[must_use]
+    pub fn with_language_alias(
+        mut self,
+        alias: impl Into<String>,
+        language: impl Into<String>,
+    ) -> Self {
+        self.language_aliases
+            .insert(alias.into().to_lowercase(), language.into().to_lowercase());
+        self
+    }
+
+    /// Resolve fence tags missing from the alias map with `resolver`
+    ///
+    /// The resolver gets the lowercased tag without fence attributes and returns
+    /// the language to use, or `None` to use the tag as it is.
+    #[must_use]
+    pub fn with_language_resolver(
+        mut self,
+        resolver: impl Fn(&str) -> Option<String> + 'static,
+    ) -> Self {
+        self.language_resolver = Some(Box::new(resolver));
+        self
+    }
+
+    /// The language whose highlighter a fence's info string selects
+    ///
+    /// Attributes after the tag are dropped (`rust,ignore` and `python {.numberLines}`
+    /// select `rust` and `python`), then aliases and the resolver are applied.
+    pub fn resolve_language(&self, info: &str) -> String {
+        let tag = info
+            .trim()
+            .split(|c: char| c == ',' || c == '{' || c.is_whitespace())
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+        if let Some(language) = self.language_aliases.get(&tag) {
+            return language.clone();
         }
+        self.language_resolver
+            .as_ref()
+            .and_then(|resolver| resolver(&tag))
+            .map_or(tag, |language| language.to_lowercase())
     }
 
-    fn parse_blocks(&self, text: &str) -> Vec<ContentBlock> {
+    /// Register a highlighter for fenced code blocks tagged with `language` or an alias of it
+    ///
+    /// Language names and fence tags are matched case-insensitively.
+    #[must_use]
+    pub fn with_language_highlighter(
+        mut self,
+        language: impl Into<String>,
+        highlighter: impl SyntaxHighlighter + 'static,
+    ) -> Self {
+        let mut highlighter: Box<dyn SyntaxHighlighter> = Box::new(highlighter);
+        highlighter.set_font_size(self.font_size);
+        self.language_highlighters
+            .insert(language.into().to_lowercase(), highlighter);
+        self
+    }
+
+    /// Split `text` into markdown and fenced code blocks covering all of it
+    fn parse_blocks(text: &str) -> Vec<ContentBlock> {
         let mut blocks = Vec::new();
         let mut current_pos = 0;
         let mut in_code_block = false;
         let mut code_block_language = None;
         let mut code_block_start = 0;
 
-        for (line_idx, line) in text.lines().enumerate() {
+        for line in text.split_inclusive('\n') {
             let line_pos = current_pos;
-            let line_len = line.len() + 1; // +1 for newline
+            let line_len = line.len(); // Including the newline, if any
 
             // Check for code block delimiter
             if line.trim().starts_with("```") {
@@ -43,16 +140,14 @@ impl MarkdownHighlighter {
                 } else {
                     // Start of code block
                     // If there's content before this code block, add it as markdown
-                    if code_block_start < line_pos && !blocks.is_empty() {
-                        let last_block_end = blocks.last().map_or(0, |b| b.end);
-                        if last_block_end < line_pos {
-                            blocks.push(ContentBlock {
-                                start: last_block_end,
-                                end: line_pos,
-                                language: Some("markdown".to_string()),
-                                is_code_block: false,
-                            });
-                        }
+                    let last_block_end = blocks.last().map_or(0, |b| b.end);
+                    if last_block_end < line_pos {
+                        blocks.push(ContentBlock {
+                            start: last_block_end,
+                            end: line_pos,
+                            language: Some("markdown".to_string()),
+                            is_code_block: false,
+                        });
                     }
 
                     // Parse language from fence
@@ -91,219 +186,177 @@ impl MarkdownHighlighter {
 
     fn highlight_markdown(&self, text: &str) -> LayoutJob {
         let mut job = LayoutJob::default();
-        let mut pos = 0;
-
-        // Simple and incomplete markdown highlighting
-        for line in text.lines() {
-            let line_start = pos;
-            let line_len = line.len();
-
-            // Headings
-            if line.starts_with('#') {
-                let mut level = 0;
-                for c in line.chars() {
-                    if c == '#' {
-                        level += 1;
-                    } else {
-                        break;
-                    }
-                }
 
-                if level > 0 && level <= 6 && (line.chars().nth(level) == Some(' ')) {
-                    // Add the heading markers
-                    job.append(
-                        &line[0..level],
-                        0.0,
-                        TextFormat {
-                            font_id: FontId::monospace(14.0),
-                            color: self.theme.heading[level.min(6) - 1],
-                            ..Default::default()
-                        },
-                    );
+        // split_inclusive keeps each line's own ending (or none) so the job
+        // text matches the buffer exactly
+        for line in text.split_inclusive('\n') {
+            let (content, ending) = split_line_ending(line);
+            self.append_markdown_line(&mut job, content);
+            self.append_line_ending(&mut job, ending);
+        }
 
-                    // Add the heading text
-                    job.append(
-                        &line[level..],
-                        0.0,
-                        TextFormat {
-                            font_id: FontId::proportional(16.0 + (6 - level) as f32),
-                            color: self.theme.heading[level.min(6) - 1],
-                            ..Default::default()
-                        },
-                    );
+        job
+    }
 
-                    job.append(
-                        "\n",
-                        0.0,
-                        TextFormat {
-                            font_id: FontId::monospace(14.0),
-                            color: self.theme.foreground,
-                            ..Default::default()
-                        },
-                    );
+    /// Append a line ending (`\n`, `\r\n`, or nothing) to `job`
+    fn append_line_ending(&self, job: &mut LayoutJob, ending: &str) {
+        if ending.is_empty() {
+            return;
+        }
+        job.append(
+            ending,
+            0.0,
+            TextFormat {
+                font_id: FontId::monospace(self.font_size),
+                color: self.theme.foreground,
+                ..Default::default()
+            },
+        );
+    }
 
-                    pos += line_len + 1;
-                    continue;
-                }
-            }
+    /// Append one line of markdown (without its newline) to `job`
+    ///
+    /// This is a simple and incomplete markdown highlighter.
+    fn append_markdown_line(&self, job: &mut LayoutJob, line: &str) {
+        // Headings
+        if let Some(level) = heading_level(line) {
+            // Add the heading markers
+            job.append(
+                &line[0..level],
+                0.0,
+                TextFormat {
+                    font_id: FontId::monospace(self.font_size),
+                    color: self.theme.heading[level - 1],
+                    ..Default::default()
+                },
+            );
 
-            // Bold/Strong (very simple implementation)
-            if line.contains("**") {
-                let parts: Vec<&str> = line.split("**").collect();
-                let mut is_bold = false;
+            // Add the heading text
+            job.append(
+                &line[level..],
+                0.0,
+                TextFormat {
+                    font_id: FontId::proportional(heading_font_size(self.font_size, level)),
+                    color: self.theme.heading[level - 1],
+                    ..Default::default()
+                },
+            );
 
-                for (i, part) in parts.iter().enumerate() {
-                    let format = if is_bold {
-                        TextFormat {
-                            font_id: FontId::monospace(14.0),
-                            color: self.theme.strong,
-                            italics: false,
-                            ..Default::default()
-                        }
-                    } else {
-                        TextFormat {
-                            font_id: FontId::monospace(14.0),
-                            color: self.theme.foreground,
-                            ..Default::default()
-                        }
-                    };
+            return;
+        }
 
-                    job.append(part, 0.0, format);
-
-                    // Add the delimiter except for the last part
-                    if i < parts.len() - 1 {
-                        if !is_bold {
-                            // Opening delimiter
-                            job.append(
-                                "**",
-                                0.0,
-                                TextFormat {
-                                    font_id: FontId::monospace(14.0),
-                                    color: self.theme.operator,
-                                    ..Default::default()
-                                },
-                            );
-                        } else {
-                            // Closing delimiter
-                            job.append(
-                                "**",
-                                0.0,
-                                TextFormat {
-                                    font_id: FontId::monospace(14.0),
-                                    color: self.theme.operator,
-                                    ..Default::default()
-                                },
-                            );
-                        }
-                        is_bold = !is_bold;
-                    }
-                }
+        // Bold/Strong (very simple implementation)
+        if line.contains("**") {
+            let parts: Vec<&str> = line.split("**").collect();
+            let mut is_bold = false;
 
-                job.append(
-                    "\n",
-                    0.0,
+            for (i, part) in parts.iter().enumerate() {
+                let format = if is_bold {
+                    let mut format = TextFormat {
+                        font_id: FontId::monospace(self.font_size),
+                        color: self.theme.strong,
+                        ..Default::default()
+                    };
+                    self.theme.apply_style(&mut format, self.theme.strong_style);
+                    format
+                } else {
                     TextFormat {
-                        font_id: FontId::monospace(14.0),
+                        font_id: FontId::monospace(self.font_size),
                         color: self.theme.foreground,
                         ..Default::default()
-                    },
-                );
-
-                pos += line_len + 1;
-                continue;
-            }
+                    }
+                };
 
-            // Lists (simple implementation)
-            if line.trim().starts_with("- ") || line.trim().starts_with("* ") {
-                let indent_len = line.len() - line.trim_start().len();
-                let marker_len = 2; // "- " or "* "
+                job.append(part, 0.0, format);
 
-                // Add any indentation
-                if indent_len > 0 {
+                // Add the opening/closing delimiter except for the last part
+                if i < parts.len() - 1 {
                     job.append(
-                        &line[0..indent_len],
+                        "**",
                         0.0,
                         TextFormat {
-                            font_id: FontId::monospace(14.0),
-                            color: self.theme.foreground,
+                            font_id: FontId::monospace(self.font_size),
+                            color: self.theme.operator,
                             ..Default::default()
                         },
                     );
+                    is_bold = !is_bold;
                 }
+            }
 
-                // Add the list marker
-                job.append(
-                    &line[indent_len..(indent_len + marker_len)],
-                    0.0,
-                    TextFormat {
-                        font_id: FontId::monospace(14.0),
-                        color: self.theme.list,
-                        ..Default::default()
-                    },
-                );
+            return;
+        }
 
-                // Add the list text
-                job.append(
-                    &line[(indent_len + marker_len)..],
-                    0.0,
-                    TextFormat {
-                        font_id: FontId::monospace(14.0),
-                        color: self.theme.foreground,
-                        ..Default::default()
-                    },
-                );
+        // Lists (simple implementation)
+        if line.trim().starts_with("- ") || line.trim().starts_with("* ") {
+            let indent_len = line.len() - line.trim_start().len();
+            let marker_len = 2; // "- " or "* "
 
+            // Add any indentation
+            if indent_len > 0 {
                 job.append(
-                    "\n",
+                    &line[0..indent_len],
                     0.0,
                     TextFormat {
-                        font_id: FontId::monospace(14.0),
+                        font_id: FontId::monospace(self.font_size),
                         color: self.theme.foreground,
                         ..Default::default()
                     },
                 );
-
-                pos += line_len + 1;
-                continue;
             }
 
-            // Default formatting for other lines
+            // Add the list marker
             job.append(
-                line,
+                &line[indent_len..(indent_len + marker_len)],
                 0.0,
                 TextFormat {
-                    font_id: FontId::monospace(14.0),
-                    color: self.theme.foreground,
+                    font_id: FontId::monospace(self.font_size),
+                    color: self.theme.list,
                     ..Default::default()
                 },
             );
 
+            // Add the list text
             job.append(
-                "\n",
+                &line[(indent_len + marker_len)..],
                 0.0,
                 TextFormat {
-                    font_id: FontId::monospace(14.0),
+                    font_id: FontId::monospace(self.font_size),
                     color: self.theme.foreground,
                     ..Default::default()
                 },
             );
 
-            pos += line_len + 1;
+            return;
         }
 
-        job
+        // Default formatting for other lines
+        job.append(
+            line,
+            0.0,
+            TextFormat {
+                font_id: FontId::monospace(self.font_size),
+                color: self.theme.foreground,
+                ..Default::default()
+            },
+        );
     }
 
-    fn highlight_code_block(&self, text: &str, language: Option<&str>) -> LayoutJob {
-        let mut job = LayoutJob::default();
+    fn highlight_code_block(&self, ctx: &Context, text: &str, language: Option<&str>) -> LayoutJob {
+        // Use a registered language highlighter when there is one for this fence
+        if let Some(highlighter) = language
+            .map(|language| self.resolve_language(language))
+            .and_then(|language| self.language_highlighters.get(&language))
+        {
+            return highlighter.highlight(ctx, text);
+        }
 
-        // Simple code highlighting for now
-        // In a real implementation, you'd invoke the appropriate language highlighter
+        let mut job = LayoutJob::default();
         job.append(
             text,
             0.0,
             TextFormat {
-                font_id: FontId::monospace(14.0),
+                font_id: FontId::monospace(self.font_size),
                 color: self.theme.code_block,
                 background: self.theme.background,
                 ..Default::default()
@@ -319,73 +372,62 @@ impl SyntaxHighlighter for MarkdownHighlighter {
         let mut job = LayoutJob::default();
 
         // Parse content blocks (markdown and code)
-        let blocks = self.parse_blocks(text);
+        let blocks = Self::parse_blocks(text);
 
         for block in blocks {
             let block_text = &text[block.start..block.end];
 
             if block.is_code_block {
-                // Handle code block
-                let fence_line_end = block_text.find('\n').unwrap_or(block_text.len());
-                let fence_line = &block_text[0..fence_line_end];
+                // The block runs from the opening fence to the closing fence,
+                // or to the end of the text when the fence is never closed
+                let opening = block_text.split_inclusive('\n').next().unwrap_or_default();
+                let rest = &block_text[opening.len()..];
+                let last_line = rest.split_inclusive('\n').next_back().unwrap_or_default();
+                let content_end = if last_line.trim().starts_with("```") {
+                    rest.len() - last_line.len()
+                } else {
+                    rest.len()
+                };
+                let (code_content, closing) = rest.split_at(content_end);
 
                 // Add the opening fence with language
+                let (fence_line, ending) = split_line_ending(opening);
                 job.append(
                     fence_line,
                     0.0,
                     TextFormat {
-                        font_id: FontId::monospace(14.0),
+                        font_id: FontId::monospace(self.font_size),
                         color: self.theme.operator,
                         ..Default::default()
                     },
                 );
-
-                job.append(
-                    "\n",
-                    0.0,
-                    TextFormat {
-                        font_id: FontId::monospace(14.0),
-                        color: self.theme.foreground,
-                        ..Default::default()
-                    },
-                );
+                self.append_line_ending(&mut job, ending);
 
                 // Handle the code block content
-                let content_start = fence_line_end + 1;
-                let content_end = block_text.rfind("```").unwrap_or(block_text.len());
-                if content_start < content_end {
-                    let code_content = &block_text[content_start..content_end];
-
-                    // For now, just use a simple highlighting
-                    // In a real implementation, you'd use the appropriate language highlighter
-                    job.append(
-                        code_content,
-                        0.0,
-                        TextFormat {
-                            font_id: FontId::monospace(14.0),
-                            color: self.theme.foreground,
-                            background: self.theme.background.linear_multiply(0.5),
-                            ..Default::default()
-                        },
-                    );
+                if !code_content.is_empty() {
+                    let code_job =
+                        self.highlight_code_block(ctx, code_content, block.language.as_deref());
+                    append_job(&mut job, code_job);
                 }
 
                 // Add the closing fence
-                if content_end < block_text.len() {
+                if !closing.is_empty() {
+                    let (fence_line, ending) = split_line_ending(closing);
                     job.append(
-                        &block_text[content_end..],
+                        fence_line,
                         0.0,
                         TextFormat {
-                            font_id: FontId::monospace(14.0),
+                            font_id: FontId::monospace(self.font_size),
                             color: self.theme.operator,
                             ..Default::default()
                         },
                     );
+                    self.append_line_ending(&mut job, ending);
                 }
             } else {
                 // Handle markdown content
                 let markdown_job = self.highlight_markdown(block_text);
-                job.append_job(markdown_job);
+                append_job(&mut job, markdown_job);
             }
         }
 
@@ -399,4 +441,493 @@ impl SyntaxHighlighter for MarkdownHighlighter {
     fn theme(&self) -> &HighlightTheme {
         &self.theme
     }
+
+    fn set_font_size(&mut self, size: f32) {
+        self.font_size = size;
+        for highlighter in self.language_highlighters.values_mut() {
+            highlighter.set_font_size(size);
+        }
+    }
+
+    fn highlight_line(&self, ctx: &Context, line: &str, state: &mut LineState) -> LayoutJob {
+        let content = line.strip_suffix('\n').unwrap_or(line);
+        let mut job = LayoutJob::default();
+
+        if content.trim().starts_with("```") {
+            job.append(
+                content,
+                0.0,
+                TextFormat {
+                    font_id: FontId::monospace(self.font_size),
+                    color: self.theme.operator,
+                    ..Default::default()
+                },
+            );
+
+            if state.in_code_block {
+                state.in_code_block = false;
+                state.code_block_language = None;
+            } else {
+                let fence_content = content.trim().strip_prefix("```").unwrap_or("");
+                state.in_code_block = true;
+                state.code_block_language =
+                    (!fence_content.is_empty()).then(|| fence_content.to_string());
+            }
+        } else if state.in_code_block {
+            let code_job =
+                self.highlight_code_block(ctx, content, state.code_block_language.as_deref());
+            append_job(&mut job, code_job);
+        } else {
+            self.append_markdown_line(&mut job, content);
+        }
+
+        if content.len() < line.len() {
+            job.append(
+                "\n",
+                0.0,
+                TextFormat {
+                    font_id: FontId::monospace(self.font_size),
+                    color: self.theme.foreground,
+                    ..Default::default()
+                },
+            );
+        }
+
+        job
+    }
+}
+
+/// Split a line from `split_inclusive('\n')` into its content and its ending
+fn split_line_ending(line: &str) -> (&str, &str) {
+    let content = line
+        .strip_suffix("\r\n")
+        .or_else(|| line.strip_suffix('\n'))
+        .unwrap_or(line);
+    line.split_at(content.len())
+}
+
+/// A run of inline text in a markdown preview
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct MarkdownSpan {
+    pub text: String,
+    /// Inside `**...**`
+    pub strong: bool,
+    /// Inside `*...*` or `_..._`
+    pub emphasis: bool,
+    /// Inside `` `...` ``
+    pub code: bool,
+    /// Target of a `[text](url)` link
+    pub link: Option<String>,
+}
+
+/// A block of a markdown document as the preview lays it out
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MarkdownBlock {
+    /// `#` through `######`
+    Heading {
+        level: usize,
+        spans: Vec<MarkdownSpan>,
+    },
+    /// Consecutive text lines, joined with spaces
+    Paragraph(Vec<MarkdownSpan>),
+    /// A `- `, `* `, or `1. ` item; `depth` counts two-space indents
+    ListItem {
+        depth: usize,
+        /// The item number of an ordered list, `None` for a bullet
+        number: Option<usize>,
+        spans: Vec<MarkdownSpan>,
+    },
+    /// A fenced code block, without its fences
+    CodeBlock {
+        language: Option<String>,
+        code: String,
+    },
+}
+
+/// Parse markdown into the blocks a preview shows
+///
+/// Covers headings, emphasis, inline code, links, lists, and fenced code
+/// blocks; anything else is shown as paragraph text.
+pub fn parse_markdown(text: &str) -> Vec<MarkdownBlock> {
+    let mut blocks = Vec::new();
+
+    for block in MarkdownHighlighter::parse_blocks(text) {
+        let block_text = &text[block.start..block.end];
+
+        if block.is_code_block {
+            let mut lines = block_text.split_inclusive('\n').skip(1).collect::<Vec<_>>();
+            if lines
+                .last()
+                .is_some_and(|line| line.trim().starts_with("```"))
+            {
+                lines.pop();
+            }
+            let language = block.language.filter(|_| {
+                block_text
+                    .lines()
+                    .next()
+                    .is_some_and(|fence| fence.trim() != "```")
+            });
+            blocks.push(MarkdownBlock::CodeBlock {
+                language,
+                code: lines.concat(),
+            });
+            continue;
+        }
+
+        let mut paragraph = String::new();
+        for line in block_text.lines() {
+            let trimmed = line.trim_start();
+            let indent = line.len() - trimmed.len();
+            let item = if let Some(rest) = trimmed
+                .strip_prefix("- ")
+                .or_else(|| trimmed.strip_prefix("* "))
+            {
+                Some((None, rest))
+            } else {
+                ordered_item(trimmed).map(|(number, rest)| (Some(number), rest))
+            };
+
+            if trimmed.is_empty() || heading_level(line).is_some() || item.is_some() {
+                flush_paragraph(&mut blocks, &mut paragraph);
+            }
+            if let Some(level) = heading_level(line) {
+                blocks.push(MarkdownBlock::Heading {
+                    level,
+                    spans: parse_inline(line[level..].trim()),
+                });
+            } else if let Some((number, rest)) = item {
+                blocks.push(MarkdownBlock::ListItem {
+                    depth: indent / 2,
+                    number,
+                    spans: parse_inline(rest.trim()),
+                });
+            } else if !trimmed.is_empty() {
+                if !paragraph.is_empty() {
+                    paragraph.push(' ');
+                }
+                paragraph.push_str(line.trim());
+            }
+        }
+        flush_paragraph(&mut blocks, &mut paragraph);
+    }
+
+    blocks
+}
+
+/// The number and text of an ordered list item like `12. text`
+fn ordered_item(line: &str) -> Option<(usize, &str)> {
+    let digits = line.bytes().take_while(u8::is_ascii_digit).count();
+    let rest = line[digits..].strip_prefix(". ")?;
+    Some((line[..digits].parse().ok()?, rest))
+}
+
+fn flush_paragraph(blocks: &mut Vec<MarkdownBlock>, paragraph: &mut String) {
+    if !paragraph.is_empty() {
+        blocks.push(MarkdownBlock::Paragraph(parse_inline(paragraph)));
+        paragraph.clear();
+    }
+}
+
+/// Split a line into spans of plain, strong, emphasized, code, and link text
+///
+/// Unmatched markers are kept as literal text.
+fn parse_inline(text: &str) -> Vec<MarkdownSpan> {
+    let mut spans = Vec::new();
+    let mut current = MarkdownSpan::default();
+    let mut rest = text;
+
+    while let Some(c) = rest.chars().next() {
+        // An underscore inside a word (snake_case) doesn't start emphasis
+        let in_word = text[..text.len() - rest.len()]
+            .chars()
+            .next_back()
+            .is_some_and(char::is_alphanumeric);
+        let marker = if rest.starts_with("**") {
+            "**"
+        } else {
+            &rest[..c.len_utf8()]
+        };
+        let closes = |marker: &str| rest[marker.len()..].contains(marker);
+
+        match marker {
+            "`" if closes("`") => {
+                let end = rest[1..].find('`').map_or(rest.len(), |end| end + 1);
+                push_span(&mut spans, &mut current);
+                spans.push(MarkdownSpan {
+                    text: rest[1..end].to_string(),
+                    code: true,
+                    ..MarkdownSpan::default()
+                });
+                rest = &rest[end + 1..];
+            }
+            "[" => {
+                if let Some((label, url, len)) = parse_link(rest) {
+                    push_span(&mut spans, &mut current);
+                    for mut span in parse_inline(label) {
+                        span.strong |= current.strong;
+                        span.emphasis |= current.emphasis;
+                        span.link = Some(url.to_string());
+                        spans.push(span);
+                    }
+                    rest = &rest[len..];
+                } else {
+                    current.text.push('[');
+                    rest = &rest[1..];
+                }
+            }
+            "**" if current.strong || closes("**") => {
+                push_span(&mut spans, &mut current);
+                current.strong = !current.strong;
+                rest = &rest[2..];
+            }
+            "*" | "_" if current.emphasis || (closes(marker) && !(marker == "_" && in_word)) => {
+                push_span(&mut spans, &mut current);
+                current.emphasis = !current.emphasis;
+                rest = &rest[1..];
+            }
+            _ => {
+                current.text.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    push_span(&mut spans, &mut current);
+
+    spans
+}
+
+/// Start a new span with the same style, keeping `current` if it has text
+fn push_span(spans: &mut Vec<MarkdownSpan>, current: &mut MarkdownSpan) {
+    if !current.text.is_empty() {
+        let style = MarkdownSpan {
+            text: String::new(),
+            ..current.clone()
+        };
+        spans.push(std::mem::replace(current, style));
+    }
+}
+
+/// The label, target, and byte length of a `[label](url)` link at the start of `text`
+fn parse_link(text: &str) -> Option<(&str, &str, usize)> {
+    let label_end = text.find("](")?;
+    let url_end = label_end + 2 + text[label_end + 2..].find(')')?;
+    Some((
+        &text[1..label_end],
+        &text[label_end + 2..url_end],
+        url_end + 1,
+    ))
+}
+
+/// Draw a rendered view of markdown text with egui widgets
+///
+/// Headings, emphasis, lists, links, and fenced code blocks are shown as
+/// described by [`parse_markdown`], colored from `theme`.
+pub fn render_markdown_preview(ui: &mut Ui, text: &str, theme: &HighlightTheme, font_size: f32) {
+    for block in parse_markdown(text) {
+        match block {
+            MarkdownBlock::Heading { level, spans } => {
+                ui.add_space(font_size * 0.5);
+                show_spans(ui, &spans, theme, |text| {
+                    text.size(heading_font_size(font_size, level))
+                        .strong()
+                        .color(theme.heading[level - 1])
+                });
+            }
+            MarkdownBlock::Paragraph(spans) => {
+                show_spans(ui, &spans, theme, |text| text.size(font_size));
+                ui.add_space(font_size * 0.5);
+            }
+            MarkdownBlock::ListItem {
+                depth,
+                number,
+                spans,
+            } => {
+                ui.horizontal_wrapped(|ui| {
+                    ui.add_space(font_size * 1.5 * depth as f32);
+                    let marker = number.map_or_else(|| "•".to_string(), |n| format!("{n}."));
+                    ui.label(RichText::new(marker).size(font_size).color(theme.list));
+                    show_spans(ui, &spans, theme, |text| text.size(font_size));
+                });
+            }
+            MarkdownBlock::CodeBlock { code, .. } => {
+                Frame::new()
+                    .fill(theme.background)
+                    .inner_margin(font_size * 0.5)
+                    .show(ui, |ui| {
+                        ui.set_width(ui.available_width());
+                        ui.label(
+                            RichText::new(code.trim_end_matches('\n'))
+                                .monospace()
+                                .size(font_size)
+                                .color(theme.code_block),
+                        );
+                    });
+                ui.add_space(font_size * 0.5);
+            }
+        }
+    }
+}
+
+/// Lay out spans in a wrapped row, styling each one's text with `base`
+fn show_spans(
+    ui: &mut Ui,
+    spans: &[MarkdownSpan],
+    theme: &HighlightTheme,
+    base: impl Fn(RichText) -> RichText,
+) {
+    ui.horizontal_wrapped(|ui| {
+        ui.spacing_mut().item_spacing.x = 0.0;
+        for span in spans {
+            let mut text = base(RichText::new(&span.text).color(theme.foreground));
+            if span.strong {
+                text = text.strong().color(theme.strong);
+            }
+            if span.emphasis {
+                text = text.italics();
+            }
+            if span.code {
+                text = text.code();
+            }
+            match &span.link {
+                Some(url) => {
+                    ui.hyperlink_to(text, url);
+                }
+                None => {
+                    ui.label(text);
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syntax::languages::{LanguageHighlighter, RustTokenizer};
+    use egui::Color32;
+
+    fn plain(text: &str) -> MarkdownSpan {
+        MarkdownSpan {
+            text: text.to_string(),
+            ..MarkdownSpan::default()
+        }
+    }
+
+    #[test]
+    fn parse_markdown_splits_headings_paragraphs_lists_and_code() {
+        let text = "# Title\n\nSome **bold** and *soft*\ntext with `code`.\n\n\
+                    - one\n  - [link](https://example.com)\n2. two\n\n\
+                    ```rust\nfn main() {}\n```\n";
+
+        assert_eq!(
+            parse_markdown(text),
+            vec![
+                MarkdownBlock::Heading {
+                    level: 1,
+                    spans: vec![plain("Title")],
+                },
+                MarkdownBlock::Paragraph(vec![
+                    plain("Some "),
+                    MarkdownSpan {
+                        strong: true,
+                        ..plain("bold")
+                    },
+                    plain(" and "),
+                    MarkdownSpan {
+                        emphasis: true,
+                        ..plain("soft")
+                    },
+                    plain(" text with "),
+                    MarkdownSpan {
+                        code: true,
+                        ..plain("code")
+                    },
+                    plain("."),
+                ]),
+                MarkdownBlock::ListItem {
+                    depth: 0,
+                    number: None,
+                    spans: vec![plain("one")],
+                },
+                MarkdownBlock::ListItem {
+                    depth: 1,
+                    number: None,
+                    spans: vec![MarkdownSpan {
+                        link: Some("https://example.com".to_string()),
+                        ..plain("link")
+                    }],
+                },
+                MarkdownBlock::ListItem {
+                    depth: 0,
+                    number: Some(2),
+                    spans: vec![plain("two")],
+                },
+                MarkdownBlock::CodeBlock {
+                    language: Some("rust".to_string()),
+                    code: "fn main() {}\n".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn snake_case_and_unmatched_markers_stay_literal() {
+        assert_eq!(
+            parse_markdown("call snake_case_name * 2"),
+            vec![MarkdownBlock::Paragraph(vec![plain(
+                "call snake_case_name * 2"
+            )])]
+        );
+    }
+
+    #[test]
+    fn fence_tags_resolve_through_aliases_attributes_and_the_resolver() {
+        let markdown = MarkdownHighlighter::new()
+            .with_language_alias("cc", "cpp")
+            .with_language_resolver(|tag| (tag == "golang").then(|| "Go".to_string()));
+
+        assert_eq!(markdown.resolve_language("rs"), "rust");
+        assert_eq!(markdown.resolve_language(" RS,ignore "), "rust");
+        assert_eq!(markdown.resolve_language("python {.numberLines}"), "python");
+        assert_eq!(markdown.resolve_language("cc"), "cpp");
+        assert_eq!(markdown.resolve_language("golang"), "go");
+        assert_eq!(markdown.resolve_language("zig"), "zig");
+    }
+
+    #[test]
+    fn an_rs_fence_is_highlighted_by_the_rust_highlighter() {
+        let mut rust = LanguageHighlighter::new("rust", RustTokenizer::default());
+        rust.set_theme(HighlightTheme::default().with_keyword(Color32::RED));
+        let markdown = MarkdownHighlighter::new().with_language_highlighter("rust", rust);
+
+        let job = markdown.highlight(&Context::default(), "Intro\n```rs\nlet x = 1;\n```\n");
+        let start = job.text.find("let").expect("code in the job");
+        let section = job
+            .sections
+            .iter()
+            .find(|section| section.byte_range.contains(&start))
+            .expect("section over the code");
+        assert_eq!(section.format.color, Color32::RED);
+    }
+
+    #[test]
+    fn highlighted_text_matches_the_buffer_exactly() {
+        let markdown = MarkdownHighlighter::new();
+        for text in [
+            "# hi",
+            "a\r\nb\n",
+            "x\n```rust",
+            "```\ncode",
+            "```rust\nfn main() {}\n```",
+            "```\r\ncode\r\n```\r\nafter",
+        ] {
+            let job = markdown.highlight(&Context::default(), text);
+            assert_eq!(job.text, text);
+            assert_eq!(
+                job.sections.last().map(|s| s.byte_range.end),
+                Some(text.len())
+            );
+        }
+    }
 }
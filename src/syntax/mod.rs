@@ -1,4 +1,394 @@
-use egui::{text::LayoutJob, Color32, FontId, TextFormat};
+pub mod ansi;
+pub mod comments;
+pub mod diff;
+pub mod languages;
+pub mod markdown;
+#[cfg(feature = "regex-highlight")]
+pub mod regex;
+pub mod registry;
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+use egui::{text::LayoutJob, Color32, Context, FontFamily, FontId, Stroke, TextFormat};
+
+/// Categories of tokens a highlighter can color
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TokenType {
+    Normal,
+    Keyword,
+    Function,
+    Type,
+    String,
+    Number,
+    Comment,
+    Operator,
+    Variable,
+}
+
+/// Font style flags applied on top of a token's color
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TokenStyle {
+    /// Drawn with the theme's `bold_font` family, when one is set
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+}
+
+impl TokenStyle {
+    pub const PLAIN: Self = Self {
+        bold: false,
+        italic: false,
+        underline: false,
+    };
+    pub const BOLD: Self = Self {
+        bold: true,
+        ..Self::PLAIN
+    };
+    pub const ITALIC: Self = Self {
+        italic: true,
+        ..Self::PLAIN
+    };
+    pub const UNDERLINE: Self = Self {
+        underline: true,
+        ..Self::PLAIN
+    };
+}
+
+/// Colors and font styles used by the syntax highlighters
+///
+/// Start from `HighlightTheme::default()` and override individual colors with
+/// the `with_*` setters, or set the public fields directly.
+///
+/// egui fonts have no weights, so bold tokens are only drawn bold once a bold
+/// font family has been registered with egui and set with `with_bold_font`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HighlightTheme {
+    pub foreground: Color32,
+    pub background: Color32,
+    pub keyword: Color32,
+    pub function: Color32,
+    pub type_name: Color32,
+    pub string: Color32,
+    pub number: Color32,
+    pub comment: Color32,
+    pub operator: Color32,
+    pub variable: Color32,
+    /// Heading colors for levels 1 through 6
+    pub heading: [Color32; 6],
+    pub strong: Color32,
+    pub list: Color32,
+    pub code_block: Color32,
+    /// Background behind search matches
+    pub search_match: Color32,
+    /// Background behind the search match at the cursor
+    pub current_match: Color32,
+    /// Background behind the bracket at the cursor and its partner
+    pub matching_bracket: Color32,
+    /// Style flags per token category; categories not listed are plain
+    pub styles: HashMap<TokenType, TokenStyle>,
+    /// Style for bold markdown text
+    pub strong_style: TokenStyle,
+    /// Font family used for bold tokens (e.g. a registered bold monospace font)
+    pub bold_font: Option<FontFamily>,
+}
+
+impl Default for HighlightTheme {
+    fn default() -> Self {
+        Self {
+            foreground: Color32::from_rgb(220, 223, 228),
+            background: Color32::from_rgb(40, 44, 52),
+            keyword: Color32::from_rgb(198, 120, 221),
+            function: Color32::from_rgb(97, 175, 239),
+            type_name: Color32::from_rgb(229, 192, 123),
+            string: Color32::from_rgb(152, 195, 121),
+            number: Color32::from_rgb(209, 154, 102),
+            comment: Color32::from_rgb(92, 99, 112),
+            operator: Color32::from_rgb(86, 182, 194),
+            variable: Color32::from_rgb(224, 108, 117),
+            heading: [
+                Color32::from_rgb(229, 192, 123),
+                Color32::from_rgb(224, 108, 117),
+                Color32::from_rgb(97, 175, 239),
+                Color32::from_rgb(152, 195, 121),
+                Color32::from_rgb(198, 120, 221),
+                Color32::from_rgb(86, 182, 194),
+            ],
+            strong: Color32::from_rgb(229, 192, 123),
+            list: Color32::from_rgb(224, 108, 117),
+            code_block: Color32::from_rgb(171, 178, 191),
+            search_match: Color32::from_rgba_unmultiplied(229, 192, 123, 70),
+            current_match: Color32::from_rgba_unmultiplied(229, 192, 123, 160),
+            matching_bracket: Color32::from_rgba_unmultiplied(171, 178, 191, 80),
+            styles: HashMap::from([
+                (TokenType::Keyword, TokenStyle::BOLD),
+                (TokenType::Comment, TokenStyle::ITALIC),
+            ]),
+            strong_style: TokenStyle::BOLD,
+            bold_font: None,
+        }
+    }
+}
+
+impl HighlightTheme {
+    /// Set the color for plain text
+    #[must_use]
+    pub const fn with_foreground(mut self, color: Color32) -> Self {
+        self.foreground = color;
+        self
+    }
+
+    /// Set the color for the editor background
+    #[must_use]
+    pub const fn with_background(mut self, color: Color32) -> Self {
+        self.background = color;
+        self
+    }
+
+    /// Set the color for keywords
+    #[must_use]
+    pub const fn with_keyword(mut self, color: Color32) -> Self {
+        self.keyword = color;
+        self
+    }
+
+    /// Set the color for function names
+    #[must_use]
+    pub const fn with_function(mut self, color: Color32) -> Self {
+        self.function = color;
+        self
+    }
+
+    /// Set the color for type names
+    #[must_use]
+    pub const fn with_type_name(mut self, color: Color32) -> Self {
+        self.type_name = color;
+        self
+    }
+
+    /// Set the color for string literals
+    #[must_use]
+    pub const fn with_string(mut self, color: Color32) -> Self {
+        self.string = color;
+        self
+    }
+
+    /// Set the color for number literals
+    #[must_use]
+    pub const fn with_number(mut self, color: Color32) -> Self {
+        self.number = color;
+        self
+    }
+
+    /// Set the color for comments
+    #[must_use]
+    pub const fn with_comment(mut self, color: Color32) -> Self {
+        self.comment = color;
+        self
+    }
+
+    /// Set the color for operators and punctuation
+    #[must_use]
+    pub const fn with_operator(mut self, color: Color32) -> Self {
+        self.operator = color;
+        self
+    }
+
+    /// Set the color for variables
+    #[must_use]
+    pub const fn with_variable(mut self, color: Color32) -> Self {
+        self.variable = color;
+        self
+    }
+
+    /// Set the color for bold markdown text
+    #[must_use]
+    pub const fn with_strong(mut self, color: Color32) -> Self {
+        self.strong = color;
+        self
+    }
+
+    /// Set the color for markdown list markers
+    #[must_use]
+    pub const fn with_list(mut self, color: Color32) -> Self {
+        self.list = color;
+        self
+    }
+
+    /// Set the color for markdown code blocks
+    #[must_use]
+    pub const fn with_code_block(mut self, color: Color32) -> Self {
+        self.code_block = color;
+        self
+    }
+
+    /// Set the background for search matches
+    #[must_use]
+    pub const fn with_search_match(mut self, color: Color32) -> Self {
+        self.search_match = color;
+        self
+    }
+
+    /// Set the background for the search match at the cursor
+    #[must_use]
+    pub const fn with_current_match(mut self, color: Color32) -> Self {
+        self.current_match = color;
+        self
+    }
+
+    /// Set the background for the bracket at the cursor and its partner
+    #[must_use]
+    pub const fn with_matching_bracket(mut self, color: Color32) -> Self {
+        self.matching_bracket = color;
+        self
+    }
+
+    /// Set the color for headings of `level` (1-6); other levels are ignored
+    #[must_use]
+    pub const fn with_heading(mut self, level: usize, color: Color32) -> Self {
+        if level >= 1 && level <= 6 {
+            self.heading[level - 1] = color;
+        }
+        self
+    }
+
+    /// The color used for a token category
+    pub const fn color_for(&self, token_type: TokenType) -> Color32 {
+        match token_type {
+            TokenType::Keyword => self.keyword,
+            TokenType::Function => self.function,
+            TokenType::Type => self.type_name,
+            TokenType::String => self.string,
+            TokenType::Number => self.number,
+            TokenType::Comment => self.comment,
+            TokenType::Operator => self.operator,
+            TokenType::Variable => self.variable,
+            TokenType::Normal => self.foreground,
+        }
+    }
+
+    /// Set the style flags for a token category
+    #[must_use]
+    pub fn with_style(mut self, token_type: TokenType, style: TokenStyle) -> Self {
+        self.styles.insert(token_type, style);
+        self
+    }
+
+    /// Set the style for bold markdown text
+    #[must_use]
+    pub const fn with_strong_style(mut self, style: TokenStyle) -> Self {
+        self.strong_style = style;
+        self
+    }
+
+    /// Draw bold tokens with `family`, which must already be registered with egui
+    #[must_use]
+    pub fn with_bold_font(mut self, family: FontFamily) -> Self {
+        self.bold_font = Some(family);
+        self
+    }
+
+    /// The style flags for a token category
+    pub fn style_for(&self, token_type: TokenType) -> TokenStyle {
+        self.styles.get(&token_type).copied().unwrap_or_default()
+    }
+
+    /// Apply `style` to `format`; underlines take the format's color
+    pub fn apply_style(&self, format: &mut TextFormat, style: TokenStyle) {
+        if style.bold {
+            if let Some(family) = &self.bold_font {
+                format.font_id.family = family.clone();
+            }
+        }
+        format.italics = style.italic;
+        if style.underline {
+            format.underline = Stroke::new(1.0, format.color);
+        }
+    }
+
+    /// A monospace format for a token category with its color and style applied
+    pub fn format_for(&self, token_type: TokenType, font_size: f32) -> TextFormat {
+        let mut format = TextFormat {
+            font_id: FontId::monospace(font_size),
+            color: self.color_for(token_type),
+            ..Default::default()
+        };
+        self.apply_style(&mut format, self.style_for(token_type));
+        format
+    }
+}
+
+/// A region of a document that is highlighted as a unit
+#[derive(Debug, Clone)]
+pub struct ContentBlock {
+    /// Byte offset where the block starts
+    pub start: usize,
+    /// Byte offset where the block ends (exclusive)
+    pub end: usize,
+    /// Language of the block, if known
+    pub language: Option<String>,
+    /// Whether the block is a fenced code block
+    pub is_code_block: bool,
+}
+
+/// A highlighter that turns text into a colored `LayoutJob`
+pub trait SyntaxHighlighter {
+    /// Highlight the whole document
+    fn highlight(&self, ctx: &Context, text: &str) -> LayoutJob;
+
+    /// Replace the theme used for highlighting
+    fn set_theme(&mut self, theme: HighlightTheme);
+
+    /// The theme currently used for highlighting
+    fn theme(&self) -> &HighlightTheme;
+
+    /// Tell the highlighter which lines are on screen
+    ///
+    /// Highlighters can use this to do expensive work for the viewport first.
+    /// The editor reuses its last highlighting until the text, theme, or font
+    /// size changes, so `highlight` must give the same result whatever lines
+    /// are visible. The default implementation ignores it.
+    fn set_visible_lines(&mut self, _lines: std::ops::Range<usize>) {}
+
+    /// Set the point size of the text in the jobs `highlight` returns
+    ///
+    /// The editor calls this with its own font size before each layout. The
+    /// default implementation ignores it.
+    fn set_font_size(&mut self, _size: f32) {}
+
+    /// Highlight a single line, carrying context to the next line in `state`
+    ///
+    /// `line` may include its trailing newline; the returned job covers exactly
+    /// `line`. Start with `LineState::default()` for the first line and pass the
+    /// same state to each following line in order. Caching the state at the
+    /// start of each line lets a host re-highlight only the lines that changed.
+    ///
+    /// The default implementation highlights the line on its own with
+    /// `highlight` and leaves `state` untouched, which is correct for
+    /// highlighters with no multi-line constructs.
+    fn highlight_line(&self, ctx: &Context, line: &str, _state: &mut LineState) -> LayoutJob {
+        self.highlight(ctx, line)
+    }
+}
+
+/// Context carried from one line to the next by `SyntaxHighlighter::highlight_line`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct LineState {
+    /// Inside a fenced code block
+    pub in_code_block: bool,
+    /// Language tag of the open code fence, if it had one
+    pub code_block_language: Option<String>,
+}
+
+/// Append the text and sections of `other` to the end of `job`
+pub(crate) fn append_job(job: &mut LayoutJob, other: LayoutJob) {
+    let offset = job.text.len();
+    job.text.push_str(&other.text);
+    for mut section in other.sections {
+        section.byte_range = (section.byte_range.start + offset)..(section.byte_range.end + offset);
+        job.sections.push(section);
+    }
+}
 
 /// Very basic syntax highlighting for the prototype
 pub struct HighlightOptions {
@@ -7,6 +397,21 @@ pub struct HighlightOptions {
     pub keyword_color: Color32,
     pub comment_color: Color32,
     pub heading_color: Color32,
+    pub string_color: Color32,
+}
+
+impl HighlightOptions {
+    /// Options that draw `basic_highlight` in a theme's colors
+    pub fn from_theme(theme: &HighlightTheme, font_size: f32) -> Self {
+        Self {
+            font_size,
+            text_color: theme.foreground,
+            keyword_color: theme.keyword,
+            comment_color: theme.comment,
+            heading_color: theme.heading[0],
+            string_color: theme.string,
+        }
+    }
 }
 
 impl Default for HighlightOptions {
@@ -17,54 +422,438 @@ impl Default for HighlightOptions {
             keyword_color: Color32::from_rgb(198, 120, 221),
             comment_color: Color32::from_rgb(92, 99, 112),
             heading_color: Color32::from_rgb(229, 192, 123),
+            string_color: Color32::from_rgb(152, 195, 121),
         }
     }
 }
 
+/// The markdown heading level of a line, if it is a heading
+///
+/// A heading is one to six `#` characters followed by a space, so `#tag` and
+/// `####### x` are plain text. This rule is shared by all highlighters.
+pub fn heading_level(line: &str) -> Option<usize> {
+    let level = line.bytes().take_while(|&b| b == b'#').count();
+    ((1..=6).contains(&level) && line[level..].starts_with(' ')).then_some(level)
+}
+
+/// Font size for a heading of the given level (1 is largest, 6 is the body size)
+pub fn heading_font_size(base: f32, level: usize) -> f32 {
+    base * (1.0 + 0.1 * 6usize.saturating_sub(level) as f32)
+}
+
 /// Basic highlighter function for prototype
+///
+/// Colors markdown headings, `"..."` string literals, and `//` comments
+/// (including trailing comments). The job text always matches `text` exactly.
 pub fn basic_highlight(text: &str, options: &HighlightOptions) -> LayoutJob {
     let mut job = LayoutJob::default();
 
-    // Just highlight a few basic things for the prototype
-    for line in text.lines() {
+    for line in text.split_inclusive('\n') {
+        let body = line.strip_suffix('\n').unwrap_or(line);
+
         // Highlight headings in markdown
-        if line.starts_with("# ") {
+        if let Some(level) = heading_level(body) {
             job.append(
-                line,
+                body,
                 0.0,
                 TextFormat {
-                    font_id: FontId::proportional(options.font_size * 1.5),
+                    font_id: FontId::proportional(heading_font_size(options.font_size, level)),
                     color: options.heading_color,
                     ..Default::default()
                 },
             );
+        } else {
+            append_code_line(&mut job, body, options);
         }
-        // Highlight comments
-        else if line.trim_start().starts_with("//") {
-            job.append(
-                line,
-                0.0,
-                TextFormat {
-                    font_id: FontId::monospace(options.font_size),
-                    color: options.comment_color,
-                    ..TextFormat::default()
-                },
-            );
-        }
-        // Default formatting
-        else {
-            job.append(
-                line,
-                0.0,
-                TextFormat {
-                    font_id: FontId::monospace(options.font_size),
-                    color: options.text_color,
-                    ..Default::default()
-                },
-            );
+
+        // Newlines use the body font so empty lines keep the editor's row height
+        if body.len() < line.len() {
+            append_span(&mut job, "\n", options.text_color, options);
         }
-        job.append("\n", 0.0, TextFormat::default());
     }
 
     job
 }
+
+/// Append one line of code, coloring string literals and `//` comments
+fn append_code_line(job: &mut LayoutJob, line: &str, options: &HighlightOptions) {
+    let mut segment_start = 0;
+    let mut chars = line.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        if c == '"' {
+            append_span(job, &line[segment_start..i], options.text_color, options);
+
+            // Find the closing quote, skipping escaped characters
+            let mut end = line.len();
+            let mut escaped = false;
+            for (j, c) in chars.by_ref() {
+                if escaped {
+                    escaped = false;
+                } else if c == '\\' {
+                    escaped = true;
+                } else if c == '"' {
+                    end = j + 1;
+                    break;
+                }
+            }
+
+            append_span(job, &line[i..end], options.string_color, options);
+            segment_start = end;
+        } else if c == '/' && chars.peek().map(|&(_, next)| next) == Some('/') {
+            // The rest of the line is a comment
+            append_span(job, &line[segment_start..i], options.text_color, options);
+            append_span(job, &line[i..], options.comment_color, options);
+            return;
+        }
+    }
+
+    append_span(job, &line[segment_start..], options.text_color, options);
+}
+
+/// Append a monospace span in the given color, skipping empty text
+fn append_span(job: &mut LayoutJob, text: &str, color: Color32, options: &HighlightOptions) {
+    if text.is_empty() {
+        return;
+    }
+    job.append(
+        text,
+        0.0,
+        TextFormat {
+            font_id: FontId::monospace(options.font_size),
+            color,
+            ..Default::default()
+        },
+    );
+}
+
+/// Apply per-line background colors to a layout job
+///
+/// Sections that span several lines are split at line breaks so each line
+/// can carry its own background. Lines not in `backgrounds` are left untouched.
+pub fn apply_line_backgrounds(job: &mut LayoutJob, backgrounds: &HashMap<usize, Color32>) {
+    if backgrounds.is_empty() {
+        return;
+    }
+
+    let mut sections = Vec::with_capacity(job.sections.len());
+    let mut line = 0;
+
+    for section in std::mem::take(&mut job.sections) {
+        let mut start = section.byte_range.start;
+        let end = section.byte_range.end;
+
+        while start < end {
+            let piece_end = job.text[start..end]
+                .find('\n')
+                .map_or(end, |offset| start + offset + 1);
+
+            let mut piece = section.clone();
+            if start != section.byte_range.start {
+                piece.leading_space = 0.0;
+            }
+            piece.byte_range = start..piece_end;
+            if let Some(color) = backgrounds.get(&line) {
+                piece.format.background = *color;
+            }
+            sections.push(piece);
+
+            if job.text[start..piece_end].ends_with('\n') {
+                line += 1;
+            }
+            start = piece_end;
+        }
+    }
+
+    job.sections = sections;
+}
+
+/// Give ranges of a layout job a background color, e.g. to show search matches
+///
+/// Ranges are char indices, which `apply_whitespace` keeps aligned with the
+/// buffer, and must be sorted and not overlap. Sections are split at range ends.
+pub fn apply_range_backgrounds(job: &mut LayoutJob, ranges: &[(Range<usize>, Color32)]) {
+    if ranges.is_empty() {
+        return;
+    }
+
+    // Byte offset in the job text of every char index, plus the end
+    let offsets: Vec<usize> = job
+        .text
+        .char_indices()
+        .map(|(offset, _)| offset)
+        .chain(std::iter::once(job.text.len()))
+        .collect();
+    let byte_ranges: Vec<(Range<usize>, Color32)> = ranges
+        .iter()
+        .filter_map(|(range, color)| {
+            let start = *offsets.get(range.start)?;
+            let end = offsets.get(range.end).copied().unwrap_or(job.text.len());
+            Some((start..end, *color))
+        })
+        .collect();
+
+    let mut sections = Vec::with_capacity(job.sections.len() + 2 * byte_ranges.len());
+    for section in std::mem::take(&mut job.sections) {
+        let Range { mut start, end } = section.byte_range.clone();
+        while start < end {
+            // The range covering `start`, or the next one to begin inside the section
+            let next = byte_ranges.partition_point(|(range, _)| range.end <= start);
+            let covering = byte_ranges.get(next).filter(|(range, _)| range.start < end);
+            let (piece_end, background) = match covering {
+                Some((range, color)) if range.start <= start => (range.end.min(end), Some(*color)),
+                Some((range, _)) => (range.start, None),
+                None => (end, None),
+            };
+
+            let mut piece = section.clone();
+            if start != section.byte_range.start {
+                piece.leading_space = 0.0;
+            }
+            piece.byte_range = start..piece_end;
+            if let Some(color) = background {
+                piece.format.background = color;
+            }
+            sections.push(piece);
+            start = piece_end;
+        }
+    }
+
+    job.sections = sections;
+}
+
+/// Marker drawn in place of a space when whitespace is shown
+pub const SPACE_MARKER: char = '\u{b7}';
+/// Marker drawn in place of a tab when whitespace is shown
+pub const TAB_MARKER: char = '\u{2192}';
+/// Marker drawn at the end of a line when whitespace is shown
+pub const NEWLINE_MARKER: char = '\u{ac}';
+
+/// Render tabs at `tab_width` columns and optionally show spaces and tabs as faint markers
+///
+/// Each space or tab is replaced by exactly one character, so char indices in
+/// the job still line up with the buffer. Tabs get extra letter spacing so they
+/// reach the next multiple of `tab_width` columns, each `space_width` points wide. Newlines are left alone;
+/// their markers are painted over the galley instead.
+pub fn apply_whitespace(
+    job: &mut LayoutJob,
+    show_markers: bool,
+    marker_color: Color32,
+    tab_width: usize,
+    space_width: f32,
+) {
+    if !show_markers && !job.text.contains('\t') {
+        return;
+    }
+
+    let tab_width = tab_width.max(1);
+    // Display column within the current line, in characters
+    let mut column = 0;
+    let mut text = String::with_capacity(job.text.len());
+    let mut sections = Vec::with_capacity(job.sections.len());
+
+    for section in &job.sections {
+        let mut run_start = text.len();
+        let mut first = true;
+        let mut push_run = |text: &str, run_start: &mut usize, format: TextFormat| {
+            if text.len() > *run_start {
+                let mut piece = section.clone();
+                if !first {
+                    piece.leading_space = 0.0;
+                }
+                first = false;
+                piece.byte_range = *run_start..text.len();
+                piece.format = format;
+                sections.push(piece);
+            }
+            *run_start = text.len();
+        };
+
+        for c in job.text[section.byte_range.clone()].chars() {
+            let columns = if c == '\t' {
+                tab_width - column % tab_width
+            } else {
+                1
+            };
+            column = if c == '\n' { 0 } else { column + columns };
+            let tab_spacing = space_width * (columns - 1) as f32;
+
+            let replacement = match c {
+                ' ' if show_markers => Some((SPACE_MARKER, 0.0)),
+                '\t' if show_markers => Some((TAB_MARKER, tab_spacing)),
+                '\t' => Some((' ', tab_spacing)),
+                _ => None,
+            };
+
+            if let Some((marker, spacing)) = replacement {
+                // Whitespace gets its own section so it can be styled independently
+                push_run(&text, &mut run_start, section.format.clone());
+                text.push(marker);
+                let mut format = section.format.clone();
+                if show_markers {
+                    format.color = marker_color;
+                }
+                format.extra_letter_spacing += spacing;
+                push_run(&text, &mut run_start, format);
+            } else {
+                text.push(c);
+            }
+        }
+        push_run(&text, &mut run_start, section.format.clone());
+    }
+
+    job.text = text;
+    job.sections = sections;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The text and background of each section
+    fn backgrounds(job: &LayoutJob) -> Vec<(&str, Color32)> {
+        job.sections
+            .iter()
+            .map(|section| {
+                (
+                    &job.text[section.byte_range.clone()],
+                    section.format.background,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn line_backgrounds_cover_only_their_line() {
+        let mut job = basic_highlight("one\nlet s = \"two\";\nthree", &HighlightOptions::default());
+        let text = job.text.clone();
+        apply_line_backgrounds(&mut job, &HashMap::from([(1, Color32::RED)]));
+
+        assert_eq!(job.text, text);
+        assert_eq!(
+            backgrounds(&job),
+            vec![
+                ("one", Color32::TRANSPARENT),
+                ("\n", Color32::TRANSPARENT),
+                ("let s = ", Color32::RED),
+                ("\"two\"", Color32::RED),
+                (";", Color32::RED),
+                ("\n", Color32::RED),
+                ("three", Color32::TRANSPARENT),
+            ]
+        );
+    }
+
+    #[test]
+    fn line_backgrounds_split_sections_spanning_lines() {
+        let mut job = LayoutJob::single_section("a\nb\nc".to_string(), TextFormat::default());
+        apply_line_backgrounds(&mut job, &HashMap::from([(1, Color32::BLUE)]));
+        assert_eq!(
+            backgrounds(&job),
+            vec![
+                ("a\n", Color32::TRANSPARENT),
+                ("b\n", Color32::BLUE),
+                ("c", Color32::TRANSPARENT),
+            ]
+        );
+    }
+
+    /// The text and color of each section
+    fn colors(job: &LayoutJob) -> Vec<(&str, Color32)> {
+        job.sections
+            .iter()
+            .map(|section| (&job.text[section.byte_range.clone()], section.format.color))
+            .collect()
+    }
+
+    #[test]
+    fn basic_highlight_colors_a_trailing_comment() {
+        let options = HighlightOptions::default();
+        let job = basic_highlight("let x = 1; // one", &options);
+        assert_eq!(
+            colors(&job),
+            vec![
+                ("let x = 1; ", options.text_color),
+                ("// one", options.comment_color),
+            ]
+        );
+    }
+
+    #[test]
+    fn basic_highlight_colors_an_embedded_string() {
+        let options = HighlightOptions::default();
+        let job = basic_highlight(r#"say("a \" // b") // c"#, &options);
+        assert_eq!(
+            colors(&job),
+            vec![
+                ("say(", options.text_color),
+                (r#""a \" // b""#, options.string_color),
+                (") ", options.text_color),
+                ("// c", options.comment_color),
+            ]
+        );
+    }
+
+    #[test]
+    fn headings_need_one_to_six_hashes_and_a_space() {
+        assert_eq!(heading_level("#notaheading"), None);
+        assert_eq!(heading_level("# h1"), Some(1));
+        assert_eq!(heading_level("###### h6"), Some(6));
+        assert_eq!(heading_level("####### h7"), None);
+        assert_eq!(heading_level("#"), None);
+
+        let options = HighlightOptions::default();
+        let job = basic_highlight("#notaheading\n# h1", &options);
+        assert_eq!(
+            colors(&job),
+            vec![
+                ("#notaheading", options.text_color),
+                ("\n", options.text_color),
+                ("# h1", options.heading_color),
+            ]
+        );
+    }
+
+    #[test]
+    fn whitespace_markers_replace_spaces_and_tabs_one_for_one() {
+        let text = "a b\tc\nab\tc\n\td";
+        let mut job = basic_highlight(text, &HighlightOptions::default());
+        apply_whitespace(&mut job, true, Color32::GRAY, 4, 8.0);
+
+        assert_eq!(
+            job.text,
+            format!("a{SPACE_MARKER}b{TAB_MARKER}c\nab{TAB_MARKER}c\n{TAB_MARKER}d")
+        );
+        assert_eq!(job.text.chars().count(), text.chars().count());
+        let markers: Vec<(&str, Color32, f32)> = job
+            .sections
+            .iter()
+            .map(|section| {
+                (
+                    &job.text[section.byte_range.clone()],
+                    section.format.color,
+                    section.format.extra_letter_spacing,
+                )
+            })
+            .filter(|(_, color, _)| *color == Color32::GRAY)
+            .collect();
+        assert_eq!(
+            markers,
+            vec![
+                (SPACE_MARKER.to_string().as_str(), Color32::GRAY, 0.0),
+                // Tabs pad to the next stop: column 3 to 4, 2 to 4 ("c" at
+                // column 4), and 0 to 4
+                (TAB_MARKER.to_string().as_str(), Color32::GRAY, 0.0),
+                (TAB_MARKER.to_string().as_str(), Color32::GRAY, 8.0),
+                (TAB_MARKER.to_string().as_str(), Color32::GRAY, 24.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn hidden_whitespace_only_widens_tabs() {
+        let mut job = basic_highlight("a b\tc", &HighlightOptions::default());
+        apply_whitespace(&mut job, false, Color32::GRAY, 4, 8.0);
+        assert_eq!(job.text, "a b c");
+    }
+}
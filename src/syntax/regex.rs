@@ -0,0 +1,176 @@
+use crate::syntax::{HighlightTheme, SyntaxHighlighter, TokenType};
+use egui::{text::LayoutJob, Context, FontId, TextFormat};
+use regex::Regex;
+
+/// Highlighter driven by an ordered list of regex rules
+///
+/// At each position the earliest match wins; when several rules match at the
+/// same offset, the rule listed first wins. Matches never overlap and text
+/// not covered by any rule is rendered as `TokenType::Normal`.
+pub struct RegexHighlighter {
+    rules: Vec<(Regex, TokenType)>,
+    theme: HighlightTheme,
+    font_size: f32,
+}
+
+impl RegexHighlighter {
+    pub fn new(rules: Vec<(Regex, TokenType)>) -> Self {
+        Self {
+            rules,
+            theme: HighlightTheme::default(),
+            font_size: 14.0,
+        }
+    }
+
+    #[must_use]
+    pub fn with_theme(mut self, theme: HighlightTheme) -> Self {
+        self.theme = theme;
+        self
+    }
+
+    /// Append `text` to `job` in the color for `token_type`
+    fn append(&self, job: &mut LayoutJob, text: &str, token_type: TokenType) {
+        if text.is_empty() {
+            return;
+        }
+        job.append(
+            text,
+            0.0,
+            TextFormat {
+                font_id: FontId::monospace(self.font_size),
+                color: self.theme.color_for(token_type),
+                ..Default::default()
+            },
+        );
+    }
+}
+
+impl SyntaxHighlighter for RegexHighlighter {
+    fn highlight(&self, _ctx: &Context, text: &str) -> LayoutJob {
+        let mut job = LayoutJob::default();
+        let mut pos = 0;
+
+        // Each rule's next non-empty match at or after `pos`. A rule is searched
+        // again only once `pos` has passed the start of its cached match, so the
+        // text is scanned about once per rule rather than once per token.
+        let mut next_matches: Vec<Option<regex::Match>> = self
+            .rules
+            .iter()
+            .map(|(regex, _)| find_non_empty(regex, text, 0))
+            .collect();
+
+        while pos < text.len() {
+            for ((regex, _), next_match) in self.rules.iter().zip(&mut next_matches) {
+                if next_match.is_some_and(|m| m.start() < pos) {
+                    *next_match = find_non_empty(regex, text, pos);
+                }
+            }
+
+            // Earliest match across all rules; ties go to the first rule
+            let next = next_matches
+                .iter()
+                .zip(&self.rules)
+                .filter_map(|(m, (_, token_type))| m.map(|m| (m, *token_type)))
+                .min_by_key(|(m, _)| m.start());
+
+            let Some((found, token_type)) = next else {
+                break;
+            };
+
+            self.append(&mut job, &text[pos..found.start()], TokenType::Normal);
+            self.append(&mut job, found.as_str(), token_type);
+            pos = found.end();
+        }
+
+        self.append(&mut job, &text[pos..], TokenType::Normal);
+        job
+    }
+
+    fn set_theme(&mut self, theme: HighlightTheme) {
+        self.theme = theme;
+    }
+
+    fn theme(&self) -> &HighlightTheme {
+        &self.theme
+    }
+
+    fn set_font_size(&mut self, size: f32) {
+        self.font_size = size;
+    }
+}
+
+/// The first non-empty match of `regex` in `text` starting at or after `start`
+fn find_non_empty<'t>(regex: &Regex, text: &'t str, start: usize) -> Option<regex::Match<'t>> {
+    let mut from = start;
+    while from <= text.len() {
+        let found = regex.find_at(text, from)?;
+        if !found.is_empty() {
+            return Some(found);
+        }
+        // Step past the empty match to the next character boundary
+        from = found.end() + text[found.end()..].chars().next().map_or(1, char::len_utf8);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keyword_and_number_rules_color_their_matches() {
+        let highlighter = RegexHighlighter::new(vec![
+            (Regex::new(r"\b(let|fn)\b").unwrap(), TokenType::Keyword),
+            (Regex::new(r"\b\d+\b").unwrap(), TokenType::Number),
+        ]);
+        let text = "let x = 42; fn let2()";
+        let job = highlighter.highlight(&Context::default(), text);
+        let theme = HighlightTheme::default();
+
+        assert_eq!(job.text, text);
+        let spans: Vec<(&str, egui::Color32)> = job
+            .sections
+            .iter()
+            .map(|section| (&job.text[section.byte_range.clone()], section.format.color))
+            .collect();
+        assert_eq!(
+            spans,
+            vec![
+                ("let", theme.keyword),
+                (" x = ", theme.foreground),
+                ("42", theme.number),
+                ("; ", theme.foreground),
+                ("fn", theme.keyword),
+                (" let2()", theme.foreground),
+            ]
+        );
+    }
+
+    #[test]
+    fn a_match_swallowed_by_an_earlier_token_is_searched_again() {
+        let highlighter = RegexHighlighter::new(vec![
+            (Regex::new(r#""[^"]*""#).unwrap(), TokenType::String),
+            (Regex::new(r"\blet\b").unwrap(), TokenType::Keyword),
+        ]);
+        let text = r#"x = "let"; let y"#;
+        let job = highlighter.highlight(&Context::default(), text);
+        let theme = HighlightTheme::default();
+
+        assert_eq!(job.text, text);
+        let spans: Vec<(&str, egui::Color32)> = job
+            .sections
+            .iter()
+            .map(|section| (&job.text[section.byte_range.clone()], section.format.color))
+            .collect();
+        assert_eq!(
+            spans,
+            vec![
+                ("x = ", theme.foreground),
+                ("\"let\"", theme.string),
+                ("; ", theme.foreground),
+                ("let", theme.keyword),
+                (" y", theme.foreground),
+            ]
+        );
+    }
+}
@@ -0,0 +1,122 @@
+use std::collections::HashMap;
+
+use crate::syntax::diff::DiffHighlighter;
+use crate::syntax::languages::{LanguageHighlighter, RustTokenizer};
+use crate::syntax::markdown::MarkdownHighlighter;
+use crate::syntax::SyntaxHighlighter;
+
+/// Constructor for a boxed highlighter
+pub type HighlighterFactory = Box<dyn Fn() -> Box<dyn SyntaxHighlighter>>;
+
+/// Registry mapping languages and file extensions to highlighter constructors
+///
+/// Languages are registered under a name (e.g. `"rust"`) together with the
+/// file extensions that select them (e.g. `"rs"`). Lookups are case-insensitive
+/// and ignore a leading `.` on extensions.
+pub struct HighlighterRegistry {
+    /// Highlighter constructors keyed by language name
+    factories: HashMap<String, HighlighterFactory>,
+    /// Language names keyed by file extension
+    extensions: HashMap<String, String>,
+}
+
+impl Default for HighlighterRegistry {
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry.register("rust", &["rs"], || {
+            Box::new(LanguageHighlighter::new("rust", RustTokenizer::default()))
+        });
+        registry.register("markdown", &["md", "markdown"], || {
+            Box::new(MarkdownHighlighter::new())
+        });
+        registry.register("diff", &["diff", "patch"], || {
+            Box::new(DiffHighlighter::new())
+        });
+        registry
+    }
+}
+
+impl HighlighterRegistry {
+    /// Create a registry pre-populated with the built-in highlighters
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a registry with no languages registered
+    pub fn empty() -> Self {
+        Self {
+            factories: HashMap::new(),
+            extensions: HashMap::new(),
+        }
+    }
+
+    /// Register (or replace) a language and the file extensions that select it
+    pub fn register(
+        &mut self,
+        language: &str,
+        extensions: &[&str],
+        factory: impl Fn() -> Box<dyn SyntaxHighlighter> + 'static,
+    ) {
+        let language = language.to_lowercase();
+        for extension in extensions {
+            self.extensions
+                .insert(normalize_extension(extension), language.clone());
+        }
+        self.factories.insert(language, Box::new(factory));
+    }
+
+    /// The language name registered for a file extension
+    pub fn language_for_extension(&self, extension: &str) -> Option<&str> {
+        self.extensions
+            .get(&normalize_extension(extension))
+            .map(String::as_str)
+    }
+
+    /// Create a highlighter for a language name
+    pub fn create(&self, language: &str) -> Option<Box<dyn SyntaxHighlighter>> {
+        self.factories
+            .get(&language.to_lowercase())
+            .map(|factory| factory())
+    }
+
+    /// Create a highlighter for a file extension
+    pub fn create_for_extension(&self, extension: &str) -> Option<Box<dyn SyntaxHighlighter>> {
+        self.language_for_extension(extension)
+            .and_then(|language| self.create(language))
+    }
+}
+
+/// Lowercase an extension and strip any leading dot
+fn normalize_extension(extension: &str) -> String {
+    extension.trim_start_matches('.').to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn built_in_languages_resolve_by_extension() {
+        let registry = HighlighterRegistry::new();
+        assert_eq!(registry.language_for_extension("rs"), Some("rust"));
+        assert_eq!(registry.language_for_extension(".MD"), Some("markdown"));
+        assert!(registry.create_for_extension("md").is_some());
+
+        // No Python highlighter ships, so `py` falls back to `basic_highlight`
+        assert_eq!(registry.language_for_extension("py"), None);
+        assert!(registry.create_for_extension("py").is_none());
+    }
+
+    #[test]
+    fn registered_languages_resolve_by_extension() {
+        let mut registry = HighlighterRegistry::empty();
+        assert!(registry.create_for_extension("rs").is_none());
+
+        registry.register("Python", &["py", ".pyi"], || {
+            Box::new(DiffHighlighter::new())
+        });
+        assert_eq!(registry.language_for_extension("py"), Some("python"));
+        assert_eq!(registry.language_for_extension("PYI"), Some("python"));
+        assert!(registry.create("python").is_some());
+    }
+}